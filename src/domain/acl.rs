@@ -0,0 +1,493 @@
+use quick_xml::escape::escape;
+use serde::{Deserialize, Serialize};
+use crate::domain::identity::{Identity, Requester};
+
+// Names under which the bucket ACL and ownership setting are stored with the bucket
+pub const CONFIG_NAME: &str = "acl.xml";
+pub const OWNERSHIP_CONFIG_NAME: &str = "ownership-controls.xml";
+
+pub const ALL_USERS_URI: &str = "http://acs.amazonaws.com/groups/global/AllUsers";
+pub const AUTHENTICATED_USERS_URI: &str = "http://acs.amazonaws.com/groups/global/AuthenticatedUsers";
+pub const LOG_DELIVERY_URI: &str = "http://acs.amazonaws.com/groups/s3/LogDelivery";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Permission {
+    #[serde(rename = "FULL_CONTROL")]
+    FullControl,
+    #[serde(rename = "READ")]
+    Read,
+    #[serde(rename = "WRITE")]
+    Write,
+    #[serde(rename = "READ_ACP")]
+    ReadAcp,
+    #[serde(rename = "WRITE_ACP")]
+    WriteAcp,
+}
+
+impl Permission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::FullControl => "FULL_CONTROL",
+            Permission::Read => "READ",
+            Permission::Write => "WRITE",
+            Permission::ReadAcp => "READ_ACP",
+            Permission::WriteAcp => "WRITE_ACP",
+        }
+    }
+
+    fn grants(&self, required: Permission) -> bool {
+        *self == Permission::FullControl || *self == required
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Owner {
+    #[serde(rename = "ID")]
+    pub id: String,
+    #[serde(rename = "DisplayName", default)]
+    pub display_name: String,
+}
+
+impl From<&Identity> for Owner {
+    fn from(identity: &Identity) -> Self {
+        Self {
+            id: identity.canonical_user_id.clone(),
+            display_name: identity.display_name.clone(),
+        }
+    }
+}
+
+/// Grantee of an ACL entry. The XML type attribute is inferred from the child
+/// elements, which is what identifies the grantee kind anyway.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Grantee {
+    #[serde(rename = "ID", default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(rename = "DisplayName", default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(rename = "URI", default, skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    #[serde(rename = "EmailAddress", default, skip_serializing_if = "Option::is_none")]
+    pub email_address: Option<String>,
+}
+
+impl Grantee {
+    pub fn canonical_user(id: &str, display_name: &str) -> Self {
+        Self {
+            id: Some(id.to_string()),
+            display_name: Some(display_name.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn group(uri: &str) -> Self {
+        Self {
+            uri: Some(uri.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn xsi_type(&self) -> &'static str {
+        if self.uri.is_some() {
+            "Group"
+        } else if self.email_address.is_some() {
+            "AmazonCustomerByEmail"
+        } else {
+            "CanonicalUser"
+        }
+    }
+
+    /// Whether the grantee is everyone, or every authenticated AWS user.
+    pub fn is_public(&self) -> bool {
+        matches!(self.uri.as_deref(), Some(ALL_USERS_URI | AUTHENTICATED_USERS_URI))
+    }
+
+    fn matches(&self, requester: &Requester) -> bool {
+        match (self.uri.as_deref(), requester.identity()) {
+            (Some(ALL_USERS_URI), _) => true,
+            (Some(AUTHENTICATED_USERS_URI), Some(_)) => true,
+            (Some(_), _) => false,
+            (None, Some(identity)) => self.id.as_deref() == Some(identity.canonical_user_id.as_str()),
+            (None, None) => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Grant {
+    #[serde(rename = "Grantee")]
+    pub grantee: Grantee,
+    #[serde(rename = "Permission")]
+    pub permission: Permission,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct AccessControlList {
+    #[serde(rename = "Grant", default)]
+    pub grants: Vec<Grant>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename = "AccessControlPolicy")]
+pub struct AccessControlPolicy {
+    #[serde(rename = "Owner")]
+    pub owner: Owner,
+    #[serde(rename = "AccessControlList", default)]
+    pub access_control_list: AccessControlList,
+}
+
+#[derive(Debug)]
+pub enum AclError {
+    InvalidCannedAcl(String),
+    InvalidGrantHeader(String),
+    UnresolvableEmail(String),
+    CannedAndGrantHeaders,
+}
+
+impl std::fmt::Display for AclError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AclError::InvalidCannedAcl(acl) => write!(f, "Invalid canned ACL: {}", acl),
+            AclError::InvalidGrantHeader(header) => write!(f, "Invalid grant header: {}", header),
+            AclError::UnresolvableEmail(email) => write!(f, "The e-mail address you provided does not match any account on record: {}", email),
+            AclError::CannedAndGrantHeaders => write!(f, "Specifying both Canned ACLs and Header Grants is not allowed"),
+        }
+    }
+}
+
+impl AccessControlPolicy {
+    /// ACL granting full control to the owner only, the default for new resources.
+    pub fn private(owner: Owner) -> Self {
+        Self::canned("private", owner, None).expect("private is a valid canned ACL")
+    }
+
+    /// Expands a canned ACL. `bucket_owner` is the owner of the bucket an
+    /// object lives in, used by the `bucket-owner-*` ACLs.
+    pub fn canned(name: &str, owner: Owner, bucket_owner: Option<&Owner>) -> Result<Self, AclError> {
+        let owner_grant = Grant {
+            grantee: Grantee::canonical_user(&owner.id, &owner.display_name),
+            permission: Permission::FullControl,
+        };
+        let group_grant = |uri: &str, permission| Grant {
+            grantee: Grantee::group(uri),
+            permission,
+        };
+        let bucket_owner_grant = |permission| {
+            bucket_owner
+                .filter(|bucket_owner| bucket_owner.id != owner.id)
+                .map(|bucket_owner| Grant {
+                    grantee: Grantee::canonical_user(&bucket_owner.id, &bucket_owner.display_name),
+                    permission,
+                })
+        };
+
+        let mut grants = vec![owner_grant];
+        match name {
+            "private" => {}
+            "public-read" => grants.push(group_grant(ALL_USERS_URI, Permission::Read)),
+            "public-read-write" => {
+                grants.push(group_grant(ALL_USERS_URI, Permission::Read));
+                grants.push(group_grant(ALL_USERS_URI, Permission::Write));
+            }
+            "authenticated-read" => grants.push(group_grant(AUTHENTICATED_USERS_URI, Permission::Read)),
+            "aws-exec-read" => {}
+            "bucket-owner-read" => grants.extend(bucket_owner_grant(Permission::Read)),
+            "bucket-owner-full-control" => grants.extend(bucket_owner_grant(Permission::FullControl)),
+            "log-delivery-write" => {
+                grants.push(group_grant(LOG_DELIVERY_URI, Permission::Write));
+                grants.push(group_grant(LOG_DELIVERY_URI, Permission::ReadAcp));
+            }
+            other => return Err(AclError::InvalidCannedAcl(other.to_string())),
+        }
+
+        Ok(Self {
+            owner,
+            access_control_list: AccessControlList { grants },
+        })
+    }
+
+    /// Builds an ACL from `x-amz-grant-*` header values, given as (permission, value) pairs.
+    pub fn from_grant_headers(owner: Owner, headers: &[(Permission, &str)]) -> Result<Self, AclError> {
+        let mut grants = Vec::new();
+        for (permission, value) in headers {
+            for grantee in parse_grantees(value)? {
+                grants.push(Grant {
+                    grantee,
+                    permission: *permission,
+                });
+            }
+        }
+        Ok(Self {
+            owner,
+            access_control_list: AccessControlList { grants },
+        })
+    }
+
+    pub fn from_xml(xml: &str) -> Result<Self, quick_xml::DeError> {
+        quick_xml::de::from_str(xml)
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from("<AccessControlPolicy xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">");
+        xml.push_str(&format!(
+            "<Owner><ID>{}</ID><DisplayName>{}</DisplayName></Owner><AccessControlList>",
+            escape(&self.owner.id), escape(&self.owner.display_name)
+        ));
+        for grant in &self.access_control_list.grants {
+            xml.push_str(&format!(
+                "<Grant><Grantee xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:type=\"{}\">",
+                grant.grantee.xsi_type()
+            ));
+            if let Some(id) = &grant.grantee.id {
+                xml.push_str(&format!("<ID>{}</ID>", escape(id)));
+            }
+            if let Some(display_name) = &grant.grantee.display_name {
+                xml.push_str(&format!("<DisplayName>{}</DisplayName>", escape(display_name)));
+            }
+            if let Some(uri) = &grant.grantee.uri {
+                xml.push_str(&format!("<URI>{}</URI>", escape(uri)));
+            }
+            if let Some(email) = &grant.grantee.email_address {
+                xml.push_str(&format!("<EmailAddress>{}</EmailAddress>", escape(email)));
+            }
+            xml.push_str(&format!("</Grantee><Permission>{}</Permission></Grant>", grant.permission.as_str()));
+        }
+        xml.push_str("</AccessControlList></AccessControlPolicy>");
+        xml
+    }
+
+    /// Email grantees cannot be resolved, there is no directory of account emails.
+    pub fn validate(&self) -> Result<(), AclError> {
+        for grant in &self.access_control_list.grants {
+            if let Some(email) = &grant.grantee.email_address {
+                return Err(AclError::UnresolvableEmail(email.clone()));
+            }
+            if grant.grantee.id.is_none() && grant.grantee.uri.is_none() {
+                return Err(AclError::InvalidGrantHeader("Grantee has no ID or URI".to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_owner(&self, requester: &Requester) -> bool {
+        requester
+            .identity()
+            .is_some_and(|identity| identity.canonical_user_id == self.owner.id)
+    }
+
+    /// Whether the ACL lets the requester perform an operation needing `required`.
+    pub fn allows(&self, requester: &Requester, required: Permission) -> bool {
+        self.is_owner(requester)
+            || self
+                .access_control_list
+                .grants
+                .iter()
+                .any(|grant| grant.permission.grants(required) && grant.grantee.matches(requester))
+    }
+
+    /// True when the ACL grants anything to the AllUsers or AuthenticatedUsers groups.
+    pub fn is_public(&self) -> bool {
+        self.access_control_list.grants.iter().any(|grant| grant.grantee.is_public())
+    }
+
+    /// The ACL with its public grants left out, as Block Public Access ignores them.
+    pub fn without_public_grants(mut self) -> Self {
+        self.access_control_list.grants.retain(|grant| !grant.grantee.is_public());
+        self
+    }
+
+    /// True when every grant goes to the owner, which is all ACLs may express
+    /// once ACLs are disabled on a bucket.
+    pub fn is_owner_only(&self) -> bool {
+        self.access_control_list
+            .grants
+            .iter()
+            .all(|grant| grant.grantee.id.as_deref() == Some(self.owner.id.as_str()) && grant.permission == Permission::FullControl)
+    }
+}
+
+// Grant headers hold comma separated grantees, as in `id="abc", uri="http://..."`
+fn parse_grantees(value: &str) -> Result<Vec<Grantee>, AclError> {
+    let mut grantees = Vec::new();
+    for part in value.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+        let (kind, raw) = part
+            .split_once('=')
+            .ok_or_else(|| AclError::InvalidGrantHeader(part.to_string()))?;
+        let raw = raw.trim().trim_matches('"').to_string();
+        let grantee = match kind.trim() {
+            "id" => Grantee { id: Some(raw), ..Default::default() },
+            "uri" => Grantee { uri: Some(raw), ..Default::default() },
+            "emailAddress" => return Err(AclError::UnresolvableEmail(raw)),
+            _ => return Err(AclError::InvalidGrantHeader(part.to_string())),
+        };
+        grantees.push(grantee);
+    }
+    Ok(grantees)
+}
+
+/// Who owns the objects written to a bucket, and whether ACLs are in effect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ObjectOwnership {
+    BucketOwnerEnforced,
+    BucketOwnerPreferred,
+    ObjectWriter,
+}
+
+impl ObjectOwnership {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "BucketOwnerEnforced" => Some(Self::BucketOwnerEnforced),
+            "BucketOwnerPreferred" => Some(Self::BucketOwnerPreferred),
+            "ObjectWriter" => Some(Self::ObjectWriter),
+            _ => None,
+        }
+    }
+
+    pub fn acls_enabled(&self) -> bool {
+        *self != Self::BucketOwnerEnforced
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OwnershipControlsRule {
+    #[serde(rename = "ObjectOwnership")]
+    pub object_ownership: ObjectOwnership,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename = "OwnershipControls")]
+pub struct OwnershipControls {
+    #[serde(rename = "Rule")]
+    pub rules: Vec<OwnershipControlsRule>,
+}
+
+impl OwnershipControls {
+    pub fn new(object_ownership: ObjectOwnership) -> Self {
+        Self {
+            rules: vec![OwnershipControlsRule { object_ownership }],
+        }
+    }
+
+    pub fn from_xml(xml: &str) -> Result<Self, quick_xml::DeError> {
+        quick_xml::de::from_str(xml)
+    }
+
+    pub fn to_xml(&self) -> Result<String, quick_xml::SeError> {
+        quick_xml::se::to_string(self)
+    }
+
+    pub fn object_ownership(&self) -> ObjectOwnership {
+        self.rules
+            .first()
+            .map(|rule| rule.object_ownership)
+            .unwrap_or(ObjectOwnership::ObjectWriter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(canonical_user_id: &str) -> Requester {
+        let mut identity = Identity::owner();
+        identity.canonical_user_id = canonical_user_id.to_string();
+        Requester::Authenticated(identity)
+    }
+
+    fn owner() -> Owner {
+        Owner::from(&Identity::owner())
+    }
+
+    #[test]
+    fn test_canned_acls() {
+        let private = AccessControlPolicy::private(owner());
+        assert!(private.allows(&Requester::Authenticated(Identity::owner()), Permission::WriteAcp));
+        assert!(!private.allows(&Requester::Anonymous, Permission::Read));
+        assert!(private.is_owner_only());
+
+        let public_read = AccessControlPolicy::canned("public-read", owner(), None).unwrap();
+        assert!(public_read.allows(&Requester::Anonymous, Permission::Read));
+        assert!(!public_read.allows(&Requester::Anonymous, Permission::Write));
+        assert!(!public_read.is_owner_only());
+
+        let authenticated = AccessControlPolicy::canned("authenticated-read", owner(), None).unwrap();
+        assert!(authenticated.allows(&identity("partner"), Permission::Read));
+        assert!(!authenticated.allows(&Requester::Anonymous, Permission::Read));
+
+        assert!(AccessControlPolicy::canned("everyone", owner(), None).is_err());
+    }
+
+    #[test]
+    fn test_public_grants() {
+        assert!(!AccessControlPolicy::private(owner()).is_public());
+        let authenticated = AccessControlPolicy::canned("authenticated-read", owner(), None).unwrap();
+        assert!(authenticated.is_public());
+
+        let ignored = authenticated.without_public_grants();
+        assert!(!ignored.is_public());
+        assert!(!ignored.allows(&identity("partner"), Permission::Read));
+        assert!(ignored.allows(&Requester::Authenticated(Identity::owner()), Permission::Read));
+    }
+
+    #[test]
+    fn test_bucket_owner_full_control() {
+        let writer = Owner { id: "partner".to_string(), display_name: "partner".to_string() };
+        let acl = AccessControlPolicy::canned("bucket-owner-full-control", writer, Some(&owner())).unwrap();
+        assert!(acl.allows(&Requester::Authenticated(Identity::owner()), Permission::FullControl));
+        assert!(acl.allows(&identity("partner"), Permission::Read));
+        assert!(!acl.allows(&identity("someone-else"), Permission::Read));
+    }
+
+    #[test]
+    fn test_grant_headers() {
+        let acl = AccessControlPolicy::from_grant_headers(
+            owner(),
+            &[
+                (Permission::Read, "id=\"partner\", uri=\"http://acs.amazonaws.com/groups/global/AuthenticatedUsers\""),
+                (Permission::WriteAcp, "id=partner"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(acl.access_control_list.grants.len(), 3);
+        assert!(acl.allows(&identity("partner"), Permission::WriteAcp));
+        assert!(acl.allows(&identity("other"), Permission::Read));
+        assert!(!acl.allows(&identity("other"), Permission::Write));
+
+        assert!(AccessControlPolicy::from_grant_headers(owner(), &[(Permission::Read, "emailAddress=\"a@b.c\"")]).is_err());
+        assert!(AccessControlPolicy::from_grant_headers(owner(), &[(Permission::Read, "nonsense")]).is_err());
+    }
+
+    #[test]
+    fn test_access_control_policy_xml_round_trip() {
+        let xml = r#"<AccessControlPolicy xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+            <Owner><ID>owner-id</ID><DisplayName>owner</DisplayName></Owner>
+            <AccessControlList>
+                <Grant>
+                    <Grantee xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:type="CanonicalUser"><ID>partner</ID></Grantee>
+                    <Permission>READ</Permission>
+                </Grant>
+                <Grant>
+                    <Grantee xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:type="Group"><URI>http://acs.amazonaws.com/groups/global/AllUsers</URI></Grantee>
+                    <Permission>READ_ACP</Permission>
+                </Grant>
+            </AccessControlList>
+        </AccessControlPolicy>"#;
+
+        let acl = AccessControlPolicy::from_xml(xml).unwrap();
+        assert_eq!(acl.owner.id, "owner-id");
+        assert_eq!(acl.access_control_list.grants.len(), 2);
+        assert_eq!(acl.access_control_list.grants[1].permission, Permission::ReadAcp);
+        assert!(acl.validate().is_ok());
+        assert_eq!(AccessControlPolicy::from_xml(&acl.to_xml()).unwrap(), acl);
+        assert!(acl.to_xml().contains("xsi:type=\"Group\""));
+    }
+
+    #[test]
+    fn test_ownership_controls_xml() {
+        let xml = "<OwnershipControls><Rule><ObjectOwnership>BucketOwnerEnforced</ObjectOwnership></Rule></OwnershipControls>";
+        let controls = OwnershipControls::from_xml(xml).unwrap();
+        assert_eq!(controls.object_ownership(), ObjectOwnership::BucketOwnerEnforced);
+        assert!(!controls.object_ownership().acls_enabled());
+        assert_eq!(OwnershipControls::from_xml(&controls.to_xml().unwrap()).unwrap(), controls);
+    }
+}
@@ -1,10 +1,16 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
+use crate::domain::region::DEFAULT_REGION;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Bucket {
     pub id: String,
     pub name: String,
+    #[serde(default = "Utc::now")]
+    pub creation_date: DateTime<Utc>,
+    #[serde(default = "default_region")]
+    pub region: String,
 }
 
 impl Bucket {
@@ -15,9 +21,16 @@ impl Bucket {
         Self {
             id: Uuid::new_v4().to_string(),
             name,
+            creation_date: Utc::now(),
+            region: DEFAULT_REGION.to_string(),
         }
     }
 
+    pub fn with_region(mut self, region: String) -> Self {
+        self.region = region;
+        self
+    }
+
     pub fn validate_name(name: &str) -> bool {
         // S3 bucket name rules:
         // 1. Must be between 3 and 63 characters long
@@ -64,6 +77,10 @@ impl Bucket {
     }
 }
 
+fn default_region() -> String {
+    DEFAULT_REGION.to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -86,6 +103,12 @@ mod tests {
 
         assert_eq!(bucket.name, deserialized.name);
         assert_eq!(bucket.id, deserialized.id);
+
+        // Buckets stored before regions existed are in the default region
+        let legacy: Bucket = serde_json::from_str(r#"{"id":"1","name":"old-bucket"}"#).unwrap();
+        assert_eq!(legacy.region, DEFAULT_REGION);
+        let moved = Bucket::new("eu-bucket".to_string()).with_region("eu-west-1".to_string());
+        assert_eq!(moved.region, "eu-west-1");
     }
 
     #[test]
@@ -152,6 +175,8 @@ mod tests {
         let invalid_bucket = Bucket {
             id: Uuid::new_v4().to_string(),
             name: "-invalid-bucket".to_string(),
+            creation_date: Utc::now(),
+            region: DEFAULT_REGION.to_string(),
         };
         assert!(!invalid_bucket.is_valid());
     }
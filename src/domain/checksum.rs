@@ -0,0 +1,136 @@
+use base64::Engine;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+/// Additional checksum algorithms clients can attach to uploads with `x-amz-checksum-*`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    Crc32,
+    Crc32c,
+    Crc64Nvme,
+    Sha1,
+    Sha256,
+}
+
+const ALGORITHMS: [ChecksumAlgorithm; 5] = [
+    ChecksumAlgorithm::Crc32,
+    ChecksumAlgorithm::Crc32c,
+    ChecksumAlgorithm::Crc64Nvme,
+    ChecksumAlgorithm::Sha1,
+    ChecksumAlgorithm::Sha256,
+];
+
+// Reflected polynomials of CRC-32C (Castagnoli) and CRC-64/NVME
+const CRC32C_POLY: u32 = 0x82F6_3B78;
+const CRC64_NVME_POLY: u64 = 0x9A6C_9329_AC4B_C9B5;
+const CRC32C_TABLE: [u32; 256] = crc32_table(CRC32C_POLY);
+const CRC64_NVME_TABLE: [u64; 256] = crc64_table(CRC64_NVME_POLY);
+
+impl ChecksumAlgorithm {
+    /// Algorithm checked by a header such as `x-amz-checksum-crc32`, case-insensitively.
+    pub fn from_header_name(name: &str) -> Option<Self> {
+        ALGORITHMS
+            .into_iter()
+            .find(|algorithm| algorithm.header_name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn header_name(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Crc32 => "x-amz-checksum-crc32",
+            ChecksumAlgorithm::Crc32c => "x-amz-checksum-crc32c",
+            ChecksumAlgorithm::Crc64Nvme => "x-amz-checksum-crc64nvme",
+            ChecksumAlgorithm::Sha1 => "x-amz-checksum-sha1",
+            ChecksumAlgorithm::Sha256 => "x-amz-checksum-sha256",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Crc32 => "CRC32",
+            ChecksumAlgorithm::Crc32c => "CRC32C",
+            ChecksumAlgorithm::Crc64Nvme => "CRC64NVME",
+            ChecksumAlgorithm::Sha1 => "SHA1",
+            ChecksumAlgorithm::Sha256 => "SHA256",
+        }
+    }
+
+    /// Base64 encoded checksum of `data`, as it appears in the header.
+    pub fn compute(&self, data: &[u8]) -> String {
+        let digest = match self {
+            ChecksumAlgorithm::Crc32 => crc32fast::hash(data).to_be_bytes().to_vec(),
+            ChecksumAlgorithm::Crc32c => crc32c(data).to_be_bytes().to_vec(),
+            ChecksumAlgorithm::Crc64Nvme => crc64_nvme(data).to_be_bytes().to_vec(),
+            ChecksumAlgorithm::Sha1 => Sha1::digest(data).to_vec(),
+            ChecksumAlgorithm::Sha256 => Sha256::digest(data).to_vec(),
+        };
+        base64::engine::general_purpose::STANDARD.encode(digest)
+    }
+}
+
+fn crc32c(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, byte| {
+        CRC32C_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+fn crc64_nvme(data: &[u8]) -> u64 {
+    !data.iter().fold(!0u64, |crc, byte| {
+        CRC64_NVME_TABLE[((crc ^ *byte as u64) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+const fn crc32_table(poly: u32) -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ poly } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+const fn crc64_table(poly: u64) -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u64;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ poly } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_values() {
+        // The standard "123456789" check values of each CRC
+        assert_eq!(crc32c(b"123456789"), 0xE306_9283);
+        assert_eq!(crc64_nvme(b"123456789"), 0xAE8B_1486_0A79_9888);
+        assert_eq!(crc32fast::hash(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_compute() {
+        assert_eq!(ChecksumAlgorithm::Crc32.compute(b"Hello world"), "i9aeUg==");
+        assert_eq!(ChecksumAlgorithm::Sha256.compute(b""), "47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=");
+        assert_eq!(
+            ChecksumAlgorithm::from_header_name("X-Amz-Checksum-CRC32C"),
+            Some(ChecksumAlgorithm::Crc32c)
+        );
+        assert_eq!(ChecksumAlgorithm::from_header_name("x-amz-checksum-md5"), None);
+    }
+}
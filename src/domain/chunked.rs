@@ -0,0 +1,133 @@
+use std::ops::Range;
+
+/// Chunk of an `aws-chunked` body, pointing into the decoded payload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub range: Range<usize>,
+    pub signature: Option<String>,
+}
+
+/// An `aws-chunked` body with its framing removed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChunkedBody {
+    pub payload: Vec<u8>,
+    // Every chunk, including the final empty one
+    pub chunks: Vec<Chunk>,
+    pub trailers: Vec<(String, String)>,
+    pub trailer_signature: Option<String>,
+}
+
+impl ChunkedBody {
+    /// Decodes `<hex-size>[;chunk-signature=<sig>]\r\n<data>\r\n` chunks up to the
+    /// zero-sized one, followed by optional `name:value` trailing headers.
+    pub fn decode(body: &[u8]) -> Result<Self, String> {
+        let mut decoded = ChunkedBody::default();
+        let mut position = 0;
+        loop {
+            let line = read_line(body, &mut position)?.ok_or("Missing final chunk")?;
+            let mut extensions = line.split(';');
+            let size = extensions.next().unwrap_or_default().trim();
+            let size = usize::from_str_radix(size, 16).map_err(|_| format!("Invalid chunk size \"{}\"", size))?;
+            let signature = extensions
+                .filter_map(|extension| extension.trim().strip_prefix("chunk-signature="))
+                .map(str::to_string)
+                .next();
+
+            if size == 0 {
+                let end = decoded.payload.len();
+                decoded.chunks.push(Chunk { range: end..end, signature });
+                break;
+            }
+            // Sizes come from the client, up to usize::MAX
+            let data = position
+                .checked_add(size)
+                .and_then(|end| body.get(position..end))
+                .ok_or_else(|| format!("Chunk of {} bytes is cut short", size))?;
+            let start = decoded.payload.len();
+            decoded.payload.extend_from_slice(data);
+            decoded.chunks.push(Chunk { range: start..decoded.payload.len(), signature });
+            position += size;
+            if body.get(position..position + 2) != Some(b"\r\n") {
+                return Err("Chunk data is not followed by CRLF".to_string());
+            }
+            position += 2;
+        }
+
+        // Trailing headers end with an empty line, or with the body itself
+        while let Some(line) = read_line(body, &mut position)? {
+            if line.is_empty() {
+                break;
+            }
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| format!("Invalid trailing header \"{}\"", line))?;
+            let (name, value) = (name.trim().to_lowercase(), value.trim().to_string());
+            if name == "x-amz-trailer-signature" {
+                decoded.trailer_signature = Some(value);
+            } else {
+                decoded.trailers.push((name, value));
+            }
+        }
+        Ok(decoded)
+    }
+
+    pub fn data(&self, chunk: &Chunk) -> &[u8] {
+        &self.payload[chunk.range.clone()]
+    }
+
+    pub fn trailer(&self, name: &str) -> Option<&str> {
+        self.trailers
+            .iter()
+            .find(|(trailer, _)| trailer.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn read_line(body: &[u8], position: &mut usize) -> Result<Option<String>, String> {
+    if *position >= body.len() {
+        return Ok(None);
+    }
+    let rest = &body[*position..];
+    let end = rest
+        .windows(2)
+        .position(|window| window == b"\r\n")
+        .ok_or("Chunk framing line is not terminated by CRLF")?;
+    *position += end + 2;
+    String::from_utf8(rest[..end].to_vec())
+        .map(Some)
+        .map_err(|_| "Chunk framing is not valid UTF-8".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_signed_chunks() {
+        let body = b"5;chunk-signature=aaa\r\nhello\r\n6;chunk-signature=bbb\r\n world\r\n0;chunk-signature=ccc\r\n\r\n";
+        let decoded = ChunkedBody::decode(body).unwrap();
+        assert_eq!(decoded.payload, b"hello world");
+        assert_eq!(decoded.chunks.len(), 3);
+        assert_eq!(decoded.data(&decoded.chunks[1]), b" world");
+        assert_eq!(decoded.chunks[2].signature.as_deref(), Some("ccc"));
+        assert!(decoded.trailers.is_empty());
+    }
+
+    #[test]
+    fn test_decode_trailers() {
+        let body = b"b\r\nhello world\r\n0\r\nx-amz-checksum-crc32:DUoRhQ==\r\nx-amz-trailer-signature:ddd\r\n\r\n";
+        let decoded = ChunkedBody::decode(body).unwrap();
+        assert_eq!(decoded.payload, b"hello world");
+        assert_eq!(decoded.trailer("X-Amz-Checksum-Crc32"), Some("DUoRhQ=="));
+        assert_eq!(decoded.trailer_signature.as_deref(), Some("ddd"));
+        assert_eq!(decoded.chunks[0].signature, None);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert!(ChunkedBody::decode(b"zz\r\nhello\r\n0\r\n\r\n").is_err());
+        assert!(ChunkedBody::decode(b"10\r\nhello\r\n0\r\n\r\n").is_err());
+        assert!(ChunkedBody::decode(b"5\r\nhello\r\n").is_err());
+        assert!(ChunkedBody::decode(b"ffffffffffffffff\r\nhello\r\n0\r\n\r\n").is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+use crate::domain::policy::wildcard_match;
+
+// Name under which the configuration is stored with the bucket
+pub const CONFIG_NAME: &str = "cors.xml";
+
+const MAX_RULES: usize = 100;
+const MAX_ID_LENGTH: usize = 255;
+const ALLOWED_METHODS: [&str; 5] = ["GET", "PUT", "HEAD", "POST", "DELETE"];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "CORSConfiguration")]
+pub struct CorsConfiguration {
+    #[serde(rename = "CORSRule", default)]
+    pub rules: Vec<CorsRule>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CorsRule {
+    #[serde(rename = "ID", default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(rename = "AllowedHeader", default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_headers: Vec<String>,
+    #[serde(rename = "AllowedMethod", default)]
+    pub allowed_methods: Vec<String>,
+    #[serde(rename = "AllowedOrigin", default)]
+    pub allowed_origins: Vec<String>,
+    #[serde(rename = "ExposeHeader", default, skip_serializing_if = "Vec::is_empty")]
+    pub expose_headers: Vec<String>,
+    #[serde(rename = "MaxAgeSeconds", default, skip_serializing_if = "Option::is_none")]
+    pub max_age_seconds: Option<u32>,
+}
+
+impl CorsConfiguration {
+    pub fn from_xml(xml: &str) -> Result<Self, quick_xml::DeError> {
+        quick_xml::de::from_str(xml)
+    }
+
+    pub fn to_xml(&self) -> Result<String, quick_xml::SeError> {
+        quick_xml::se::to_string(self)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.rules.is_empty() {
+            return Err("At least one CORS rule is required".to_string());
+        }
+        if self.rules.len() > MAX_RULES {
+            return Err(format!("A CORS configuration can have up to {} rules", MAX_RULES));
+        }
+        self.rules.iter().try_for_each(CorsRule::validate)
+    }
+
+    /// First rule allowing a request from `origin` with `method`, and with all of
+    /// `request_headers` for preflight requests. Rules are evaluated in order.
+    pub fn find_rule(&self, origin: &str, method: &str, request_headers: &[String]) -> Option<&CorsRule> {
+        self.rules.iter().find(|rule| {
+            rule.allows_origin(origin)
+                && rule.allowed_methods.iter().any(|allowed| allowed == method)
+                && request_headers.iter().all(|name| rule.allows_header(name))
+        })
+    }
+}
+
+impl CorsRule {
+    fn validate(&self) -> Result<(), String> {
+        if self.id.as_ref().is_some_and(|id| id.len() > MAX_ID_LENGTH) {
+            return Err("ID length should not exceed allowed limit of 255".to_string());
+        }
+        if self.allowed_methods.is_empty() || self.allowed_origins.is_empty() {
+            return Err("Each CORS rule needs at least one AllowedMethod and one AllowedOrigin".to_string());
+        }
+        if let Some(method) = self.allowed_methods.iter().find(|m| !ALLOWED_METHODS.contains(&m.as_str())) {
+            return Err(format!("Found unsupported HTTP method in CORS config. Unsupported method is {}", method));
+        }
+        // S3 allows a single wildcard in each origin and header pattern
+        if let Some(origin) = self.allowed_origins.iter().find(|o| o.matches('*').count() > 1) {
+            return Err(format!("AllowedOrigin \"{}\" can not have more than one wildcard.", origin));
+        }
+        if let Some(name) = self.allowed_headers.iter().find(|h| h.matches('*').count() > 1) {
+            return Err(format!("AllowedHeader \"{}\" can not have more than one wildcard.", name));
+        }
+        Ok(())
+    }
+
+    fn allows_origin(&self, origin: &str) -> bool {
+        self.allowed_origins
+            .iter()
+            .any(|pattern| wildcard_match(pattern, origin, false))
+    }
+
+    fn allows_header(&self, name: &str) -> bool {
+        self.allowed_headers
+            .iter()
+            .any(|pattern| wildcard_match(pattern, name, true))
+    }
+
+    /// Whether the rule lets any origin in, in which case `*` is sent back instead of the origin.
+    pub fn allows_any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|origin| origin == "*")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"<CORSConfiguration>
+        <CORSRule>
+            <AllowedOrigin>http://*.example.com</AllowedOrigin>
+            <AllowedMethod>PUT</AllowedMethod>
+            <AllowedMethod>POST</AllowedMethod>
+            <AllowedHeader>x-amz-*</AllowedHeader>
+            <AllowedHeader>Content-Type</AllowedHeader>
+            <ExposeHeader>ETag</ExposeHeader>
+            <MaxAgeSeconds>3000</MaxAgeSeconds>
+        </CORSRule>
+        <CORSRule>
+            <AllowedOrigin>*</AllowedOrigin>
+            <AllowedMethod>GET</AllowedMethod>
+        </CORSRule>
+    </CORSConfiguration>"#;
+
+    #[test]
+    fn test_parse_and_validate() {
+        let config = CorsConfiguration::from_xml(CONFIG).unwrap();
+        assert_eq!(config.rules.len(), 2);
+        assert_eq!(config.rules[0].allowed_methods, vec!["PUT", "POST"]);
+        assert_eq!(config.rules[0].max_age_seconds, Some(3000));
+        assert!(config.validate().is_ok());
+        assert_eq!(CorsConfiguration::from_xml(&config.to_xml().unwrap()).unwrap(), config);
+
+        let mut invalid = config.clone();
+        invalid.rules[1].allowed_methods = vec!["PATCH".to_string()];
+        assert!(invalid.validate().is_err());
+        invalid.rules[1].allowed_methods = vec!["GET".to_string()];
+        invalid.rules[1].allowed_origins = vec!["http://*.*.com".to_string()];
+        assert!(invalid.validate().is_err());
+        assert!(CorsConfiguration::default().validate().is_err());
+    }
+
+    #[test]
+    fn test_find_rule() {
+        let config = CorsConfiguration::from_xml(CONFIG).unwrap();
+        let headers = vec!["X-Amz-Date".to_string(), "content-type".to_string()];
+
+        let rule = config.find_rule("http://app.example.com", "PUT", &headers).unwrap();
+        assert_eq!(rule.expose_headers, vec!["ETag"]);
+        assert!(!rule.allows_any_origin());
+
+        // Headers outside AllowedHeader rule the first rule out
+        assert!(config.find_rule("http://app.example.com", "PUT", &["authorization".to_string()]).is_none());
+        assert!(config.find_rule("http://example.org", "PUT", &[]).is_none());
+        assert!(config.find_rule("http://example.org", "GET", &[]).unwrap().allows_any_origin());
+        assert!(config.find_rule("http://example.org", "DELETE", &[]).is_none());
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::BTreeMap;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::domain::identity::{Identity, DEFAULT_ACCOUNT_ID, DEFAULT_CANONICAL_USER_ID, DEFAULT_DISPLAY_NAME};
+use crate::domain::sigv4::sha256_hex;
+
+// The credential store is persisted as a server-wide document under this name
+pub const CONFIG_NAME: &str = "credentials.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum KeyStatus {
+    #[default]
+    Active,
+    Inactive,
+}
+
+impl KeyStatus {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "Active" => Some(KeyStatus::Active),
+            "Inactive" => Some(KeyStatus::Inactive),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyStatus::Active => "Active",
+            KeyStatus::Inactive => "Inactive",
+        }
+    }
+}
+
+/// An access key pair, and the identity that requests signed with it act as.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccessKey {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub identity: Identity,
+    #[serde(default)]
+    pub status: KeyStatus,
+    #[serde(default = "Utc::now")]
+    pub created: DateTime<Utc>,
+    // Temporary credentials issued by STS must come with their session token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiration: Option<DateTime<Utc>>,
+}
+
+impl AccessKey {
+    pub fn new(access_key_id: String, secret_access_key: String, identity: Identity) -> Self {
+        Self {
+            access_key_id,
+            secret_access_key,
+            identity,
+            status: KeyStatus::Active,
+            created: Utc::now(),
+            session_token: None,
+            expiration: None,
+        }
+    }
+
+    /// A fresh key pair, shaped like the ones IAM hands out.
+    pub fn generate(identity: Identity) -> Self {
+        let access_key_id = format!("AKIA{}", &random_hex()[..16]).to_uppercase();
+        let secret_access_key = format!("{}{}", random_hex(), random_hex())[..40].to_string();
+        Self::new(access_key_id, secret_access_key, identity)
+    }
+
+    /// Temporary credentials, as STS issues them, valid until `expiration`.
+    pub fn temporary(identity: Identity, expiration: DateTime<Utc>) -> Self {
+        let mut key = Self::generate(identity);
+        key.access_key_id = key.access_key_id.replacen("AKIA", "ASIA", 1);
+        let token: String = (0..6).map(|_| random_hex()).collect();
+        key.session_token = Some(base64::engine::general_purpose::STANDARD.encode(token));
+        key.expiration = Some(expiration);
+        key
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.status == KeyStatus::Active
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expiration.is_some_and(|expiration| expiration <= now)
+    }
+}
+
+/// Access keys the server accepts signatures from.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CredentialStore {
+    keys: BTreeMap<String, AccessKey>,
+}
+
+impl CredentialStore {
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn get(&self, access_key_id: &str) -> Option<&AccessKey> {
+        self.keys.get(access_key_id)
+    }
+
+    /// Every key, ordered by access key ID.
+    pub fn keys(&self) -> impl Iterator<Item = &AccessKey> {
+        self.keys.values()
+    }
+
+    pub fn add(&mut self, key: AccessKey) -> Result<(), String> {
+        if self.keys.contains_key(&key.access_key_id) {
+            return Err(format!("Access key {} already exists.", key.access_key_id));
+        }
+        self.keys.insert(key.access_key_id.clone(), key);
+        Ok(())
+    }
+
+    pub fn set_status(&mut self, access_key_id: &str, status: KeyStatus) -> Option<&AccessKey> {
+        let key = self.keys.get_mut(access_key_id)?;
+        key.status = status;
+        Some(key)
+    }
+
+    pub fn remove(&mut self, access_key_id: &str) -> Option<AccessKey> {
+        self.keys.remove(access_key_id)
+    }
+
+    /// Drops temporary credentials that expired over an hour ago. Recently expired ones
+    /// are kept so that requests using them get `ExpiredToken` rather than an unknown key.
+    pub fn prune_expired(&mut self, now: DateTime<Utc>) {
+        self.keys.retain(|_, key| !key.is_expired(now - Duration::hours(1)));
+    }
+
+    /// The identity acting for an account: the owner, the user of a known key in that
+    /// account, or a stand-in for an account nobody has keys for.
+    pub fn account_identity(&self, account_id: &str) -> Identity {
+        if account_id == DEFAULT_ACCOUNT_ID {
+            return Identity::owner();
+        }
+        match self.keys().find(|key| key.session_token.is_none() && key.identity.account_id == account_id) {
+            Some(key) => key.identity.clone(),
+            None => Identity {
+                account_id: account_id.to_string(),
+                arn: format!("arn:aws:iam::{}:root", account_id),
+                canonical_user_id: sha256_hex(account_id.as_bytes()),
+                display_name: account_id.to_string(),
+                tags: BTreeMap::new(),
+            },
+        }
+    }
+
+    /// The identity new keys for a user are mapped to: the owner, the user's existing
+    /// identity, or a new one with its own canonical user ID.
+    pub fn identity_for(&self, display_name: &str, canonical_user_id: Option<&str>) -> Identity {
+        let owner = Identity::owner();
+        let existing = self.keys().map(|key| &key.identity).find(|identity| match canonical_user_id {
+            Some(id) => identity.canonical_user_id == id,
+            None => identity.display_name == display_name,
+        });
+        match (existing, canonical_user_id) {
+            (Some(identity), _) => identity.clone(),
+            (None, Some(DEFAULT_CANONICAL_USER_ID)) => owner,
+            (None, None) if display_name == DEFAULT_DISPLAY_NAME => owner,
+            (None, canonical_user_id) => Identity::user(
+                display_name,
+                &canonical_user_id.map(str::to_string).unwrap_or_else(|| random_hex() + &random_hex()),
+            ),
+        }
+    }
+}
+
+// 32 random lowercase hex digits
+fn random_hex() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manage_keys() {
+        let mut store = CredentialStore::default();
+        let owner = AccessKey::generate(store.identity_for("owner", None));
+        assert_eq!(owner.identity, Identity::owner());
+        assert_eq!(owner.access_key_id.len(), 20);
+        assert_eq!(owner.secret_access_key.len(), 40);
+        store.add(owner.clone()).unwrap();
+        assert!(store.add(owner.clone()).is_err());
+
+        let partner = store.identity_for("partner", None);
+        assert_eq!(partner.canonical_user_id.len(), 64);
+        assert_ne!(partner.account_id, owner.identity.account_id);
+        store.add(AccessKey::new("AKIDPARTNER".to_string(), "secret".to_string(), partner.clone())).unwrap();
+        // Further keys of the same user share its identity
+        assert_eq!(store.identity_for("partner", None), partner);
+        assert_eq!(store.identity_for("renamed", Some(&partner.canonical_user_id)), partner);
+
+        assert!(!store.set_status("AKIDPARTNER", KeyStatus::Inactive).unwrap().is_active());
+        let restored = CredentialStore::from_json(&store.to_json().unwrap()).unwrap();
+        assert_eq!(restored, store);
+        assert_eq!(restored.keys().count(), 2);
+
+        assert!(store.remove("AKIDPARTNER").is_some());
+        assert!(store.get("AKIDPARTNER").is_none());
+    }
+
+    #[test]
+    fn test_temporary_keys() {
+        let mut store = CredentialStore::default();
+        let now = Utc::now();
+        let session = AccessKey::temporary(store.account_identity(DEFAULT_ACCOUNT_ID), now + Duration::hours(1));
+        assert!(session.access_key_id.starts_with("ASIA"));
+        assert!(session.session_token.is_some());
+        assert!(!session.is_expired(now));
+        assert!(session.is_expired(now + Duration::hours(1)));
+        store.add(session.clone()).unwrap();
+
+        store.prune_expired(now + Duration::minutes(90));
+        assert!(store.get(&session.access_key_id).is_some());
+        store.prune_expired(now + Duration::hours(3));
+        assert!(store.get(&session.access_key_id).is_none());
+
+        let stranger = store.account_identity("111122223333");
+        assert_eq!(stranger.arn, "arn:aws:iam::111122223333:root");
+        assert_ne!(stranger.canonical_user_id, DEFAULT_CANONICAL_USER_ID);
+    }
+}
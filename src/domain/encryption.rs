@@ -0,0 +1,326 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use md5::{Digest, Md5};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// Name under which the bucket default encryption is stored with the bucket
+pub const CONFIG_NAME: &str = "encryption.xml";
+// The server-managed keys are persisted as a server-wide document under this name
+pub const KEYS_CONFIG_NAME: &str = "encryption-keys.json";
+
+pub const AES256: &str = "AES256";
+
+// Encrypted files start with this, then the length of the key ID, the key ID and the nonce
+const MAGIC: &[u8] = b"S3MOCKER-AES256-GCM\0";
+const NONCE_LEN: usize = 12;
+
+/// Bucket default encryption, as set with `PUT /{bucket}?encryption`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "ServerSideEncryptionConfiguration")]
+pub struct ServerSideEncryptionConfiguration {
+    #[serde(rename = "Rule", default)]
+    pub rules: Vec<ServerSideEncryptionRule>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ServerSideEncryptionRule {
+    #[serde(rename = "ApplyServerSideEncryptionByDefault", default, skip_serializing_if = "Option::is_none")]
+    pub apply_server_side_encryption_by_default: Option<EncryptionByDefault>,
+    #[serde(rename = "BucketKeyEnabled", default, skip_serializing_if = "Option::is_none")]
+    pub bucket_key_enabled: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EncryptionByDefault {
+    #[serde(rename = "SSEAlgorithm")]
+    pub sse_algorithm: String,
+    #[serde(rename = "KMSMasterKeyID", default, skip_serializing_if = "Option::is_none")]
+    pub kms_master_key_id: Option<String>,
+}
+
+impl ServerSideEncryptionConfiguration {
+    /// SSE-S3, which buckets without a configuration of their own use, as they do in S3.
+    pub fn sse_s3() -> Self {
+        Self {
+            rules: vec![ServerSideEncryptionRule {
+                apply_server_side_encryption_by_default: Some(EncryptionByDefault {
+                    sse_algorithm: AES256.to_string(),
+                    kms_master_key_id: None,
+                }),
+                bucket_key_enabled: Some(false),
+            }],
+        }
+    }
+
+    pub fn from_xml(xml: &str) -> Result<Self, quick_xml::DeError> {
+        quick_xml::de::from_str(xml)
+    }
+
+    pub fn to_xml(&self) -> Result<String, quick_xml::SeError> {
+        quick_xml::se::to_string(self)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let [rule] = self.rules.as_slice() else {
+            return Err("The server side encryption configuration must have exactly one rule".to_string());
+        };
+        if rule.apply_server_side_encryption_by_default.is_none() {
+            return Err("The rule must specify ApplyServerSideEncryptionByDefault".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn sse_algorithm(&self) -> Option<&str> {
+        let rule = self.rules.first()?;
+        rule.apply_server_side_encryption_by_default
+            .as_ref()
+            .map(|by_default| by_default.sse_algorithm.as_str())
+    }
+
+    pub fn bucket_key_enabled(&self) -> bool {
+        self.rules.first().and_then(|rule| rule.bucket_key_enabled).unwrap_or(false)
+    }
+}
+
+/// How an object is encrypted, as its responses report it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObjectEncryption {
+    pub algorithm: String,
+    #[serde(default)]
+    pub bucket_key_enabled: bool,
+    // Base64 MD5 of the key of SSE-C objects, whose content is stored encrypted with it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub customer_key_md5: Option<String>,
+}
+
+impl Default for ObjectEncryption {
+    fn default() -> Self {
+        Self { algorithm: AES256.to_string(), bucket_key_enabled: false, customer_key_md5: None }
+    }
+}
+
+impl ObjectEncryption {
+    pub fn customer(key: &CustomerKey) -> Self {
+        Self { customer_key_md5: Some(key.key_md5.clone()), ..Self::default() }
+    }
+}
+
+/// Key of an SSE-C request. Content is encrypted with it with AES-256-GCM, and the key
+/// itself is never stored.
+#[derive(Clone)]
+pub struct CustomerKey {
+    key: Vec<u8>,
+    pub key_md5: String,
+}
+
+impl std::fmt::Debug for CustomerKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CustomerKey").field("key_md5", &self.key_md5).finish_non_exhaustive()
+    }
+}
+
+impl CustomerKey {
+    /// Checks the base64 key of a request against its base64 MD5.
+    pub fn new(key: &str, key_md5: &str) -> Result<Self, &'static str> {
+        let key = base64::engine::general_purpose::STANDARD
+            .decode(key)
+            .ok()
+            .filter(|key| key.len() == 32)
+            .ok_or("The secret key was invalid for the specified algorithm.")?;
+        let calculated_md5 = base64::engine::general_purpose::STANDARD.encode(Md5::digest(&key));
+        if calculated_md5 != key_md5 {
+            return Err("The calculated MD5 hash of the key did not match the hash that was provided.");
+        }
+        Ok(Self { key, key_md5: calculated_md5 })
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.key))
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        // Only fails for content larger than GCM allows, about 64 GiB
+        let ciphertext = self.cipher().encrypt(&nonce, plaintext).unwrap_or_default();
+        [nonce.as_slice(), &ciphertext].concat()
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Option<Vec<u8>> {
+        if data.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        self.cipher().decrypt(Nonce::from_slice(nonce), ciphertext).ok()
+    }
+}
+
+/// Server-managed keys object content is encrypted with at rest, with AES-256-GCM. New
+/// content is encrypted with the active key; older keys are kept to decrypt what they
+/// encrypted until it is re-encrypted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncryptionKeys {
+    pub active_key_id: String,
+    pub keys: Vec<EncryptionKey>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncryptionKey {
+    pub id: String,
+    // Base64 of the 256-bit key
+    pub key: String,
+    pub created: DateTime<Utc>,
+}
+
+impl EncryptionKey {
+    fn generate() -> Self {
+        let key = Aes256Gcm::generate_key(OsRng);
+        Self {
+            id: Uuid::new_v4().simple().to_string(),
+            key: base64::engine::general_purpose::STANDARD.encode(key),
+            created: Utc::now(),
+        }
+    }
+
+    fn cipher(&self) -> Result<Aes256Gcm, String> {
+        let key = base64::engine::general_purpose::STANDARD
+            .decode(&self.key)
+            .map_err(|e| format!("invalid encryption key {}: {}", self.id, e))?;
+        if key.len() != 32 {
+            return Err(format!("invalid encryption key {}: not 256 bits", self.id));
+        }
+        Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+    }
+}
+
+impl EncryptionKeys {
+    pub fn generate() -> Self {
+        let key = EncryptionKey::generate();
+        Self { active_key_id: key.id.clone(), keys: vec![key] }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Adds a key that encrypts from now on. Returns its ID.
+    pub fn rotate(&mut self) -> &str {
+        let key = EncryptionKey::generate();
+        self.active_key_id = key.id.clone();
+        self.keys.push(key);
+        &self.active_key_id
+    }
+
+    fn key(&self, id: &str) -> Option<&EncryptionKey> {
+        self.keys.iter().find(|key| key.id == id)
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        let key = self
+            .key(&self.active_key_id)
+            .ok_or_else(|| format!("no active encryption key {}", self.active_key_id))?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = key.cipher()?.encrypt(&nonce, plaintext).map_err(|e| e.to_string())?;
+
+        let mut data = Vec::with_capacity(MAGIC.len() + 1 + key.id.len() + NONCE_LEN + ciphertext.len());
+        data.extend_from_slice(MAGIC);
+        data.push(key.id.len() as u8);
+        data.extend_from_slice(key.id.as_bytes());
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&ciphertext);
+        Ok(data)
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        let (key_id, rest) = split_header(data).ok_or("not encrypted at rest")?;
+        let key = self.key(key_id).ok_or_else(|| format!("unknown encryption key {}", key_id))?;
+        if rest.len() < NONCE_LEN {
+            return Err("truncated encrypted content".to_string());
+        }
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        key.cipher()?
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| format!("content doesn't decrypt with key {}", key_id))
+    }
+}
+
+/// ID of the key content was encrypted with at rest, or `None` when it is stored in plain,
+/// as it was before encryption at rest.
+pub fn key_id(data: &[u8]) -> Option<&str> {
+    split_header(data).map(|(key_id, _)| key_id)
+}
+
+fn split_header(data: &[u8]) -> Option<(&str, &[u8])> {
+    let rest = data.strip_prefix(MAGIC)?;
+    let (&len, rest) = rest.split_first()?;
+    if rest.len() < len as usize {
+        return None;
+    }
+    let (key_id, rest) = rest.split_at(len as usize);
+    Some((std::str::from_utf8(key_id).ok()?, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_and_rotate() {
+        let mut keys = EncryptionKeys::generate();
+        let first_key = keys.active_key_id.clone();
+        let data = keys.encrypt(b"secret content").unwrap();
+        assert!(!data.windows(6).any(|window| window == b"secret"));
+        assert_eq!(key_id(&data), Some(first_key.as_str()));
+        assert_eq!(key_id(b"plain content"), None);
+
+        let second_key = keys.rotate().to_string();
+        assert_ne!(first_key, second_key);
+        let keys = EncryptionKeys::from_json(&keys.to_json().unwrap()).unwrap();
+        assert_eq!(keys.decrypt(&data).unwrap(), b"secret content");
+        let rotated = keys.encrypt(b"secret content").unwrap();
+        assert_eq!(key_id(&rotated), Some(second_key.as_str()));
+        assert_eq!(keys.decrypt(&rotated).unwrap(), b"secret content");
+
+        let mut tampered = rotated.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(keys.decrypt(&tampered).is_err());
+        assert!(EncryptionKeys::generate().decrypt(&data).unwrap_err().starts_with("unknown encryption key"));
+    }
+
+    #[test]
+    fn test_bucket_configuration() {
+        let config = ServerSideEncryptionConfiguration::from_xml(
+            "<ServerSideEncryptionConfiguration xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\"><Rule>\
+             <ApplyServerSideEncryptionByDefault><SSEAlgorithm>AES256</SSEAlgorithm></ApplyServerSideEncryptionByDefault>\
+             <BucketKeyEnabled>true</BucketKeyEnabled></Rule></ServerSideEncryptionConfiguration>",
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.sse_algorithm(), Some(AES256));
+        assert!(config.bucket_key_enabled());
+        assert!(!ServerSideEncryptionConfiguration::sse_s3().bucket_key_enabled());
+        assert!(ServerSideEncryptionConfiguration::default().validate().is_err());
+    }
+
+    #[test]
+    fn test_customer_key() {
+        let key = base64::engine::general_purpose::STANDARD.encode([7u8; 32]);
+        let key_md5 = base64::engine::general_purpose::STANDARD.encode(Md5::digest([7u8; 32]));
+        let customer_key = CustomerKey::new(&key, &key_md5).unwrap();
+        let data = customer_key.encrypt(b"partner data");
+        assert_eq!(customer_key.decrypt(&data).unwrap(), b"partner data");
+        assert_eq!(ObjectEncryption::customer(&customer_key).customer_key_md5, Some(key_md5.clone()));
+
+        let other = base64::engine::general_purpose::STANDARD.encode([8u8; 32]);
+        let other_md5 = base64::engine::general_purpose::STANDARD.encode(Md5::digest([8u8; 32]));
+        assert_eq!(CustomerKey::new(&other, &other_md5).unwrap().decrypt(&data), None);
+        assert!(CustomerKey::new(&other, &key_md5).unwrap_err().starts_with("The calculated MD5"));
+        assert!(CustomerKey::new("c2hvcnQ=", &key_md5).unwrap_err().starts_with("The secret key was invalid"));
+    }
+}
@@ -0,0 +1,110 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use crate::domain::notification::ObjectEvent;
+
+/// A change to a bucket or object, as sent to event stream subscribers. Object creations
+/// and removals are named like their notification events ("ObjectCreated:Put"), other
+/// changes after their operation ("PutBucketPolicy").
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ChangeEvent {
+    // Assigned in order of recording, starting at 1
+    pub sequence: u64,
+    pub time: DateTime<Utc>,
+    pub event_type: String,
+    pub bucket: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<usize>,
+    #[serde(rename = "ETag", skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    pub principal: String,
+}
+
+impl From<&ObjectEvent> for ChangeEvent {
+    fn from(event: &ObjectEvent) -> Self {
+        Self {
+            sequence: 0,
+            time: event.time,
+            event_type: event.event_name.clone(),
+            bucket: event.bucket_name.clone(),
+            key: Some(event.key.clone()),
+            size: event.size,
+            etag: event.etag.clone(),
+            principal: event.principal_id.clone(),
+        }
+    }
+}
+
+/// What a subscriber wants to see. Empty criteria match everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventFilter {
+    pub bucket: Option<String>,
+    // Only events for keys under it, which leaves out bucket-level changes
+    pub prefix: Option<String>,
+    // Event types, with an optional "s3:" prefix and a trailing "*" wildcard
+    pub types: Vec<String>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &ChangeEvent) -> bool {
+        let bucket_matches = self.bucket.as_ref().is_none_or(|bucket| *bucket == event.bucket);
+        let prefix_matches = match (&self.prefix, &event.key) {
+            (None, _) => true,
+            (Some(prefix), Some(key)) => key.starts_with(prefix.as_str()),
+            (Some(_), None) => false,
+        };
+        let type_matches = self.types.is_empty()
+            || self.types.iter().any(|pattern| {
+                let pattern = pattern.strip_prefix("s3:").unwrap_or(pattern);
+                match pattern.strip_suffix('*') {
+                    Some(start) => event.event_type.starts_with(start),
+                    None => event.event_type == pattern,
+                }
+            });
+        bucket_matches && prefix_matches && type_matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event_type: &str, bucket: &str, key: Option<&str>) -> ChangeEvent {
+        ChangeEvent {
+            sequence: 1,
+            time: Utc::now(),
+            event_type: event_type.to_string(),
+            bucket: bucket.to_string(),
+            key: key.map(str::to_string),
+            size: None,
+            etag: None,
+            principal: "AWS:000000000000".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_filter() {
+        let put = event("ObjectCreated:Put", "photos", Some("2024/a.jpg"));
+        let policy = event("PutBucketPolicy", "photos", None);
+        assert!(EventFilter::default().matches(&put) && EventFilter::default().matches(&policy));
+
+        let filter = EventFilter {
+            bucket: Some("photos".to_string()),
+            prefix: Some("2024/".to_string()),
+            types: vec!["s3:ObjectCreated:*".to_string()],
+        };
+        assert!(filter.matches(&put));
+        assert!(!filter.matches(&policy));
+        assert!(!filter.matches(&event("ObjectCreated:Put", "other", Some("2024/a.jpg"))));
+        assert!(!filter.matches(&event("ObjectCreated:Put", "photos", Some("2023/a.jpg"))));
+        assert!(!filter.matches(&event("ObjectRemoved:Delete", "photos", Some("2024/a.jpg"))));
+
+        let by_type = EventFilter { types: vec!["PutBucketPolicy".to_string()], ..Default::default() };
+        assert!(by_type.matches(&policy) && !by_type.matches(&put));
+        let json = serde_json::to_value(&put).unwrap();
+        assert_eq!(json["EventType"], "ObjectCreated:Put");
+        assert!(json.get("ETag").is_none());
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::BTreeMap;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+// Hooks are persisted as a server-wide document under this name
+pub const CONFIG_NAME: &str = "hooks.json";
+
+const DEFAULT_TIMEOUT_SECONDS: u64 = 30;
+const MAX_TIMEOUT_SECONDS: u64 = 900;
+const DEFAULT_CONCURRENCY: u32 = 4;
+const MAX_CONCURRENCY: u32 = 100;
+const MAX_NAME_LENGTH: usize = 64;
+
+/// A local stand-in for a Lambda function: an executable getting the event JSON on stdin,
+/// or an HTTP function URL getting it POSTed. Bucket notifications invoke it through its
+/// function ARN.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Hook {
+    #[serde(default)]
+    pub name: String,
+    // Program and arguments, run without a shell
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub command: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default = "default_timeout")]
+    pub timeout_seconds: u64,
+    // Invocations beyond this many wait for a running one to finish
+    #[serde(default = "default_concurrency")]
+    pub concurrency: u32,
+    // Added to the environment of commands
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub environment: BTreeMap<String, String>,
+}
+
+fn default_timeout() -> u64 {
+    DEFAULT_TIMEOUT_SECONDS
+}
+
+fn default_concurrency() -> u32 {
+    DEFAULT_CONCURRENCY
+}
+
+impl Hook {
+    pub fn validate(&self) -> Result<(), String> {
+        validate_function_name(&self.name)?;
+        match (self.command.is_empty(), &self.url) {
+            (false, None) | (true, Some(_)) => {}
+            _ => return Err("A hook needs either a Command or a Url.".to_string()),
+        }
+        if self.command.first().is_some_and(|program| program.is_empty()) {
+            return Err("The Command program must not be empty.".to_string());
+        }
+        if !(1..=MAX_TIMEOUT_SECONDS).contains(&self.timeout_seconds) {
+            return Err(format!("TimeoutSeconds must be between 1 and {}.", MAX_TIMEOUT_SECONDS));
+        }
+        if !(1..=MAX_CONCURRENCY).contains(&self.concurrency) {
+            return Err(format!("Concurrency must be between 1 and {}.", MAX_CONCURRENCY));
+        }
+        Ok(())
+    }
+}
+
+/// The registered hooks, by name.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HookStore {
+    hooks: BTreeMap<String, Hook>,
+}
+
+impl HookStore {
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Hook> {
+        self.hooks.get(name)
+    }
+
+    pub fn hooks(&self) -> impl Iterator<Item = &Hook> {
+        self.hooks.values()
+    }
+
+    /// Adds the hook, replacing any of the same name.
+    pub fn put(&mut self, hook: Hook) {
+        self.hooks.insert(hook.name.clone(), hook);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Hook> {
+        self.hooks.remove(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum InvocationStatus {
+    Succeeded,
+    Failed,
+    TimedOut,
+}
+
+/// One run of a hook, with what it printed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Invocation {
+    pub request_id: String,
+    pub start_time: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub status: InvocationStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    // HTTP hooks report their response status here
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_code: Option<u16>,
+    pub stdout: String,
+    pub stderr: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Function names have up to 64 letters, digits, hyphens and underscores.
+pub fn validate_function_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Hook names have 1 to {} letters, digits, hyphens or underscores.",
+            MAX_NAME_LENGTH
+        ))
+    }
+}
+
+pub fn function_arn(region: &str, account_id: &str, name: &str) -> String {
+    format!("arn:aws:lambda:{}:{}:function:{}", region, account_id, name)
+}
+
+/// The function name of a Lambda ARN, as in "arn:aws:lambda:us-east-1:123456789012:function:resize",
+/// possibly followed by a version or alias.
+pub fn function_name_of_arn(arn: &str) -> Option<&str> {
+    let parts: Vec<&str> = arn.split(':').collect();
+    match parts.as_slice() {
+        ["arn", _, "lambda", _, _, "function", name, ..] if !name.is_empty() => Some(name),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_hooks() {
+        let hook: Hook = serde_json::from_str(r#"{"Name": "resize", "Command": ["/bin/cat"]}"#).unwrap();
+        assert!(hook.validate().is_ok());
+        assert_eq!((hook.timeout_seconds, hook.concurrency), (30, 4));
+
+        let both = Hook { url: Some("http://localhost:9000".to_string()), ..hook.clone() };
+        assert!(both.validate().is_err());
+        let neither = Hook { command: Vec::new(), ..hook.clone() };
+        assert!(neither.validate().is_err());
+        assert!(Hook { timeout_seconds: 0, ..hook.clone() }.validate().is_err());
+        assert!(Hook { concurrency: 101, ..hook.clone() }.validate().is_err());
+        assert!(Hook { name: "no spaces".to_string(), ..hook.clone() }.validate().is_err());
+
+        let mut store = HookStore::default();
+        store.put(hook.clone());
+        let round_trip = HookStore::from_json(&store.to_json().unwrap()).unwrap();
+        assert_eq!(round_trip.get("resize"), Some(&hook));
+    }
+
+    #[test]
+    fn test_function_arns() {
+        let arn = function_arn("us-east-1", "000000000000", "resize");
+        assert_eq!(arn, "arn:aws:lambda:us-east-1:000000000000:function:resize");
+        assert_eq!(function_name_of_arn(&arn), Some("resize"));
+        assert_eq!(function_name_of_arn(&format!("{}:live", arn)), Some("resize"));
+        assert_eq!(function_name_of_arn("arn:aws:sqs:us-east-1:000000000000:resize"), None);
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+
+// Account owning the buckets when requests are not attributed to anyone else
+pub const DEFAULT_ACCOUNT_ID: &str = "000000000000";
+pub const DEFAULT_CANONICAL_USER_ID: &str = "75aa57f09aa0c8caeab4f8c24e99d10f8e7faeebf76c078efc7c6caea54ba06a";
+pub const DEFAULT_DISPLAY_NAME: &str = "owner";
+
+/// An authenticated principal issuing requests.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Identity {
+    pub account_id: String,
+    pub arn: String,
+    pub canonical_user_id: String,
+    pub display_name: String,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+}
+
+impl Identity {
+    pub fn owner() -> Self {
+        Self {
+            account_id: DEFAULT_ACCOUNT_ID.to_string(),
+            arn: format!("arn:aws:iam::{}:root", DEFAULT_ACCOUNT_ID),
+            canonical_user_id: DEFAULT_CANONICAL_USER_ID.to_string(),
+            display_name: DEFAULT_DISPLAY_NAME.to_string(),
+            tags: BTreeMap::new(),
+        }
+    }
+
+    /// An IAM user of its own account, derived from the canonical user ID.
+    pub fn user(display_name: &str, canonical_user_id: &str) -> Self {
+        let digits = canonical_user_id
+            .get(..12)
+            .and_then(|prefix| u64::from_str_radix(prefix, 16).ok())
+            .unwrap_or_default();
+        let account_id = format!("{:012}", digits % 1_000_000_000_000);
+        Self {
+            arn: format!("arn:aws:iam::{}:user/{}", account_id, display_name),
+            account_id,
+            canonical_user_id: canonical_user_id.to_string(),
+            display_name: display_name.to_string(),
+            tags: BTreeMap::new(),
+        }
+    }
+}
+
+/// Whoever sent a request: an identity, or nobody for unsigned requests.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Requester {
+    Anonymous,
+    Authenticated(Identity),
+}
+
+impl Requester {
+    pub fn identity(&self) -> Option<&Identity> {
+        match self {
+            Requester::Anonymous => None,
+            Requester::Authenticated(identity) => Some(identity),
+        }
+    }
+}
@@ -0,0 +1,385 @@
+use chrono::{DateTime, Utc};
+use percent_encoding::utf8_percent_encode;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use crate::domain::object::Object;
+
+// Name under which the configurations are stored with the bucket
+pub const CONFIG_NAME: &str = "inventory.xml";
+
+const MAX_CONFIGURATIONS: usize = 1000;
+const BUCKET_ARN_PREFIX: &str = "arn:aws:s3:::";
+
+// Fields that can be added to the bucket name and key of every listed object
+const OPTIONAL_FIELDS: [&str; 16] = [
+    "Size",
+    "LastModifiedDate",
+    "StorageClass",
+    "ETag",
+    "IsMultipartUploaded",
+    "ReplicationStatus",
+    "EncryptionStatus",
+    "ObjectLockRetainUntilDate",
+    "ObjectLockMode",
+    "ObjectLockLegalHoldStatus",
+    "IntelligentTieringAccessTier",
+    "BucketKeyStatus",
+    "ChecksumAlgorithm",
+    "ObjectAccessControlList",
+    "ObjectOwner",
+    "LifecycleExpirationDate",
+];
+
+// Keys are listed URL-encoded in CSV files, as S3 does
+const KEY_ENCODE: &percent_encoding::AsciiSet = &percent_encoding::NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~')
+    .remove(b'/');
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "InventoryConfiguration")]
+pub struct InventoryConfiguration {
+    #[serde(rename = "Id")]
+    pub id: String,
+    #[serde(rename = "IsEnabled")]
+    pub is_enabled: bool,
+    #[serde(rename = "Filter", default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<InventoryFilter>,
+    #[serde(rename = "Destination")]
+    pub destination: InventoryDestination,
+    #[serde(rename = "Schedule")]
+    pub schedule: Schedule,
+    // "All" or "Current"; objects aren't versioned, so both list the same objects
+    #[serde(rename = "IncludedObjectVersions")]
+    pub included_object_versions: String,
+    #[serde(rename = "OptionalFields", default, skip_serializing_if = "Option::is_none")]
+    pub optional_fields: Option<OptionalFields>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InventoryFilter {
+    #[serde(rename = "Prefix", default)]
+    pub prefix: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InventoryDestination {
+    #[serde(rename = "S3BucketDestination")]
+    pub s3_bucket_destination: S3BucketDestination,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct S3BucketDestination {
+    #[serde(rename = "AccountId", default, skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<String>,
+    // Bucket ARN, as in "arn:aws:s3:::inventory-bucket"
+    #[serde(rename = "Bucket")]
+    pub bucket: String,
+    // "CSV" or "JSON", for JSON Lines; ORC and Parquet aren't generated
+    #[serde(rename = "Format")]
+    pub format: String,
+    #[serde(rename = "Prefix", default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Schedule {
+    // "Daily" or "Weekly"
+    #[serde(rename = "Frequency")]
+    pub frequency: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OptionalFields {
+    #[serde(rename = "Field", default)]
+    pub fields: Vec<String>,
+}
+
+/// The configurations of a bucket, as stored. Not an S3 document.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "InventoryConfigurations")]
+pub struct InventoryConfigurations {
+    #[serde(rename = "InventoryConfiguration", default)]
+    pub configurations: Vec<InventoryConfiguration>,
+}
+
+/// ListBucketInventoryConfigurations response. Every configuration fits in one page.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename = "ListInventoryConfigurationsResult")]
+pub struct ListInventoryConfigurationsResult {
+    #[serde(rename = "@xmlns")]
+    pub xmlns: &'static str,
+    #[serde(rename = "InventoryConfiguration")]
+    pub configurations: Vec<InventoryConfiguration>,
+    #[serde(rename = "IsTruncated")]
+    pub is_truncated: bool,
+}
+
+impl InventoryConfiguration {
+    pub fn from_xml(xml: &str) -> Result<Self, quick_xml::DeError> {
+        quick_xml::de::from_str(xml)
+    }
+
+    pub fn to_xml(&self) -> Result<String, quick_xml::SeError> {
+        quick_xml::se::to_string(self)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.id.is_empty() || self.id.len() > 64 {
+            return Err("The inventory configuration ID must be 1 to 64 characters long".to_string());
+        }
+        let destination = &self.destination.s3_bucket_destination;
+        if self.destination_bucket().is_none_or(str::is_empty) {
+            return Err(format!("Invalid bucket ARN: {}", destination.bucket));
+        }
+        match destination.format.as_str() {
+            "CSV" | "JSON" => {}
+            "ORC" | "Parquet" => return Err(format!("Inventory format {} is not supported", destination.format)),
+            format => return Err(format!("Invalid inventory format: {}", format)),
+        }
+        if !matches!(self.schedule.frequency.as_str(), "Daily" | "Weekly") {
+            return Err(format!("Invalid schedule frequency: {}", self.schedule.frequency));
+        }
+        if !matches!(self.included_object_versions.as_str(), "All" | "Current") {
+            return Err(format!("Invalid IncludedObjectVersions: {}", self.included_object_versions));
+        }
+        match self.fields().iter().find(|field| !OPTIONAL_FIELDS.contains(&field.as_str())) {
+            Some(field) => Err(format!("Invalid optional field: {}", field)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn fields(&self) -> &[String] {
+        self.optional_fields.as_ref().map(|fields| fields.fields.as_slice()).unwrap_or_default()
+    }
+
+    /// Name of the destination bucket, from its ARN.
+    pub fn destination_bucket(&self) -> Option<&str> {
+        self.destination.s3_bucket_destination.bucket.strip_prefix(BUCKET_ARN_PREFIX)
+    }
+
+    pub fn includes(&self, key: &str) -> bool {
+        self.filter.as_ref().is_none_or(|filter| key.starts_with(&filter.prefix))
+    }
+
+    /// Time between two reports.
+    pub fn period(&self) -> chrono::Duration {
+        match self.schedule.frequency.as_str() {
+            "Weekly" => chrono::Duration::weeks(1),
+            _ => chrono::Duration::days(1),
+        }
+    }
+
+    /// Prefix of every report of the configuration: "[DestinationPrefix/]SourceBucket/Id".
+    pub fn report_prefix(&self, source_bucket: &str) -> String {
+        match self.destination.s3_bucket_destination.prefix.as_deref().map(|p| p.trim_end_matches('/')) {
+            Some(prefix) if !prefix.is_empty() => format!("{}/{}/{}", prefix, source_bucket, self.id),
+            _ => format!("{}/{}", source_bucket, self.id),
+        }
+    }
+
+    /// Column names, as in the `fileSchema` of manifests.
+    pub fn schema(&self) -> Vec<&str> {
+        let mut schema = vec!["Bucket", "Key"];
+        if self.included_object_versions == "All" {
+            schema.extend(["VersionId", "IsLatest", "IsDeleteMarker"]);
+        }
+        schema.extend(self.fields().iter().map(String::as_str));
+        schema
+    }
+
+    /// The row of an object, with a value per schema column.
+    pub fn values(&self, bucket: &str, object: &Object) -> Vec<Value> {
+        let mut values = vec![Value::from(bucket), Value::from(object.key.as_str())];
+        if self.included_object_versions == "All" {
+            values.extend([Value::from(""), Value::from(true), Value::from(false)]);
+        }
+        values.extend(self.fields().iter().map(|field| field_value(field, object)));
+        values
+    }
+
+    pub fn csv_line(&self, bucket: &str, object: &Object) -> String {
+        self.values(bucket, object)
+            .into_iter()
+            .enumerate()
+            .map(|(column, value)| {
+                let text = match value {
+                    Value::String(text) if column == 1 => utf8_percent_encode(&text, KEY_ENCODE).to_string(),
+                    Value::String(text) => text,
+                    Value::Null => String::new(),
+                    other => other.to_string(),
+                };
+                format!("\"{}\"", text.replace('"', "\"\""))
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    pub fn json_line(&self, bucket: &str, object: &Object) -> String {
+        let row: Map<String, Value> = self
+            .schema()
+            .into_iter()
+            .map(str::to_string)
+            .zip(self.values(bucket, object))
+            .filter(|(_, value)| !value.is_null())
+            .collect();
+        Value::Object(row).to_string()
+    }
+}
+
+impl InventoryConfigurations {
+    pub fn from_xml(xml: &str) -> Result<Self, quick_xml::DeError> {
+        quick_xml::de::from_str(xml)
+    }
+
+    pub fn to_xml(&self) -> Result<String, quick_xml::SeError> {
+        quick_xml::se::to_string(self)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&InventoryConfiguration> {
+        self.configurations.iter().find(|config| config.id == id)
+    }
+
+    /// Adds `config`, replacing the configuration with the same ID.
+    pub fn put(&mut self, config: InventoryConfiguration) -> Result<(), String> {
+        if let Some(existing) = self.configurations.iter_mut().find(|existing| existing.id == config.id) {
+            *existing = config;
+            return Ok(());
+        }
+        if self.configurations.len() >= MAX_CONFIGURATIONS {
+            return Err(format!("A bucket can have up to {} inventory configurations", MAX_CONFIGURATIONS));
+        }
+        self.configurations.push(config);
+        Ok(())
+    }
+
+    /// Removes the configuration with `id`, returning whether there was one.
+    pub fn remove(&mut self, id: &str) -> bool {
+        let count = self.configurations.len();
+        self.configurations.retain(|config| config.id != id);
+        self.configurations.len() != count
+    }
+}
+
+// Manifest of a report, as in "2024-03-31T00-00Z/manifest.json"
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub source_bucket: String,
+    // Bucket ARN
+    pub destination_bucket: String,
+    pub version: &'static str,
+    // Milliseconds since the epoch, as a string
+    pub creation_timestamp: String,
+    pub file_format: String,
+    pub file_schema: String,
+    pub files: Vec<ManifestFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ManifestFile {
+    pub key: String,
+    pub size: usize,
+    #[serde(rename = "MD5checksum")]
+    pub md5_checksum: String,
+}
+
+impl Manifest {
+    pub fn new(config: &InventoryConfiguration, source_bucket: &str, created: DateTime<Utc>, files: Vec<ManifestFile>) -> Self {
+        Self {
+            source_bucket: source_bucket.to_string(),
+            destination_bucket: config.destination.s3_bucket_destination.bucket.clone(),
+            version: "2016-11-30",
+            creation_timestamp: created.timestamp_millis().to_string(),
+            file_format: config.destination.s3_bucket_destination.format.clone(),
+            file_schema: config.schema().join(", "),
+            files,
+        }
+    }
+}
+
+fn field_value(field: &str, object: &Object) -> Value {
+    match field {
+        "Size" => Value::from(object.size),
+        "LastModifiedDate" => Value::from(object.last_modified.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()),
+        "StorageClass" => Value::from("STANDARD"),
+        "ETag" => Value::from(object.etag.trim_matches('"')),
+        "IsMultipartUploaded" => Value::from(false),
+        "ReplicationStatus" => Value::from(object.replication_status.map(|status| status.as_str()).unwrap_or("")),
+        "EncryptionStatus" => Value::from(match &object.encryption {
+            Some(encryption) if encryption.customer_key_md5.is_some() => "SSE-C",
+            Some(_) => "SSE-S3",
+            None => "NOT-SSE",
+        }),
+        "BucketKeyStatus" => {
+            let enabled = object.encryption.as_ref().is_some_and(|encryption| encryption.bucket_key_enabled);
+            Value::from(if enabled { "ENABLED" } else { "DISABLED" })
+        }
+        "ObjectOwner" => object.acl.as_ref().map(|acl| Value::from(acl.owner.id.as_str())).unwrap_or(Value::Null),
+        // Object Lock, Intelligent-Tiering and checksums aren't tracked
+        _ => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XML: &str = r#"<InventoryConfiguration xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+        <Id>report1</Id>
+        <IsEnabled>true</IsEnabled>
+        <Filter><Prefix>docs/</Prefix></Filter>
+        <Destination><S3BucketDestination>
+            <Format>CSV</Format>
+            <AccountId>000000000000</AccountId>
+            <Bucket>arn:aws:s3:::inventory</Bucket>
+            <Prefix>reports</Prefix>
+        </S3BucketDestination></Destination>
+        <Schedule><Frequency>Daily</Frequency></Schedule>
+        <IncludedObjectVersions>Current</IncludedObjectVersions>
+        <OptionalFields><Field>Size</Field><Field>ETag</Field><Field>ObjectLockMode</Field></OptionalFields>
+    </InventoryConfiguration>"#;
+
+    #[test]
+    fn test_parse_inventory_configuration() {
+        let config = InventoryConfiguration::from_xml(XML).unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.destination_bucket(), Some("inventory"));
+        assert_eq!(config.report_prefix("site"), "reports/site/report1");
+        assert!(config.includes("docs/a.txt"));
+        assert!(!config.includes("b.txt"));
+
+        let mut configs = InventoryConfigurations::default();
+        configs.put(config.clone()).unwrap();
+        configs.put(config.clone()).unwrap();
+        let round_trip = InventoryConfigurations::from_xml(&configs.to_xml().unwrap()).unwrap();
+        assert_eq!(round_trip.configurations, vec![config.clone()]);
+        assert!(configs.remove("report1"));
+        assert!(!configs.remove("report1"));
+
+        let invalid = [("<Format>CSV</Format>", "<Format>ORC</Format>"), ("<Field>Size</Field>", "<Field>Color</Field>")];
+        for (from, to) in invalid {
+            assert!(InventoryConfiguration::from_xml(&XML.replace(from, to)).unwrap().validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_inventory_rows() {
+        let config = InventoryConfiguration::from_xml(XML).unwrap();
+        let object = Object::new("docs/a b.txt".to_string(), b"hello".to_vec(), "text/plain".to_string());
+        assert_eq!(config.schema(), ["Bucket", "Key", "Size", "ETag", "ObjectLockMode"]);
+        assert_eq!(
+            config.csv_line("site", &object),
+            "\"site\",\"docs/a%20b.txt\",\"5\",\"5d41402abc4b2a76b9719d911017c592\",\"\""
+        );
+        assert_eq!(
+            config.json_line("site", &object),
+            r#"{"Bucket":"site","ETag":"5d41402abc4b2a76b9719d911017c592","Key":"docs/a b.txt","Size":5}"#
+        );
+
+        let all = InventoryConfiguration { included_object_versions: "All".to_string(), ..config };
+        assert_eq!(all.schema()[2..5], ["VersionId", "IsLatest", "IsDeleteMarker"]);
+    }
+}
@@ -0,0 +1,468 @@
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::domain::object::Object;
+
+// Name under which the configuration is stored with the bucket
+pub const CONFIG_NAME: &str = "lifecycle.xml";
+
+const MAX_RULES: usize = 1000;
+const MAX_ID_LENGTH: usize = 255;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "LifecycleConfiguration")]
+pub struct LifecycleConfiguration {
+    #[serde(rename = "Rule", default)]
+    pub rules: Vec<LifecycleRule>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LifecycleRule {
+    #[serde(rename = "ID", default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    // Deprecated top-level prefix, still accepted by S3 instead of a Filter
+    #[serde(rename = "Prefix", default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    #[serde(rename = "Filter", default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<LifecycleFilter>,
+    #[serde(rename = "Status")]
+    pub status: String,
+    #[serde(rename = "Expiration", default, skip_serializing_if = "Option::is_none")]
+    pub expiration: Option<Expiration>,
+    #[serde(rename = "NoncurrentVersionExpiration", default, skip_serializing_if = "Option::is_none")]
+    pub noncurrent_version_expiration: Option<NoncurrentVersionExpiration>,
+    #[serde(rename = "AbortIncompleteMultipartUpload", default, skip_serializing_if = "Option::is_none")]
+    pub abort_incomplete_multipart_upload: Option<AbortIncompleteMultipartUpload>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LifecycleFilter {
+    #[serde(rename = "Prefix", default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    #[serde(rename = "Tag", default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<Tag>,
+    #[serde(rename = "ObjectSizeGreaterThan", default, skip_serializing_if = "Option::is_none")]
+    pub object_size_greater_than: Option<u64>,
+    #[serde(rename = "ObjectSizeLessThan", default, skip_serializing_if = "Option::is_none")]
+    pub object_size_less_than: Option<u64>,
+    #[serde(rename = "And", default, skip_serializing_if = "Option::is_none")]
+    pub and: Option<LifecycleAnd>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LifecycleAnd {
+    #[serde(rename = "Prefix", default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    #[serde(rename = "Tag", default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<Tag>,
+    #[serde(rename = "ObjectSizeGreaterThan", default, skip_serializing_if = "Option::is_none")]
+    pub object_size_greater_than: Option<u64>,
+    #[serde(rename = "ObjectSizeLessThan", default, skip_serializing_if = "Option::is_none")]
+    pub object_size_less_than: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Tag {
+    #[serde(rename = "Key")]
+    pub key: String,
+    #[serde(rename = "Value")]
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Expiration {
+    #[serde(rename = "Days", default, skip_serializing_if = "Option::is_none")]
+    pub days: Option<u32>,
+    #[serde(rename = "Date", default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<DateTime<Utc>>,
+    #[serde(rename = "ExpiredObjectDeleteMarker", default, skip_serializing_if = "Option::is_none")]
+    pub expired_object_delete_marker: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NoncurrentVersionExpiration {
+    #[serde(rename = "NoncurrentDays", default, skip_serializing_if = "Option::is_none")]
+    pub noncurrent_days: Option<u32>,
+    #[serde(rename = "NewerNoncurrentVersions", default, skip_serializing_if = "Option::is_none")]
+    pub newer_noncurrent_versions: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AbortIncompleteMultipartUpload {
+    #[serde(rename = "DaysAfterInitiation")]
+    pub days_after_initiation: u32,
+}
+
+impl LifecycleConfiguration {
+    pub fn from_xml(xml: &str) -> Result<Self, quick_xml::DeError> {
+        quick_xml::de::from_str(xml)
+    }
+
+    pub fn to_xml(&self) -> Result<String, quick_xml::SeError> {
+        quick_xml::se::to_string(self)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.rules.is_empty() {
+            return Err("At least one lifecycle rule is required".to_string());
+        }
+        if self.rules.len() > MAX_RULES {
+            return Err(format!("A lifecycle configuration can have up to {} rules", MAX_RULES));
+        }
+
+        let mut ids = std::collections::HashSet::new();
+        for rule in &self.rules {
+            if let Some(id) = &rule.id {
+                if id.len() > MAX_ID_LENGTH {
+                    return Err("ID length should not exceed allowed limit of 255".to_string());
+                }
+                if !ids.insert(id.as_str()) {
+                    return Err("Rule ID must be unique. Found same ID for more than one rule".to_string());
+                }
+            }
+            rule.validate()?;
+        }
+        Ok(())
+    }
+
+    // Objects are not versioned and multipart uploads are not tracked, so these
+    // actions would never act on anything
+    pub fn unsupported_action(&self) -> Option<&'static str> {
+        self.rules.iter().find_map(|rule| {
+            if rule.noncurrent_version_expiration.is_some() {
+                Some("NoncurrentVersionExpiration")
+            } else if rule.expiration.as_ref().is_some_and(|e| e.expired_object_delete_marker.is_some()) {
+                Some("ExpiredObjectDeleteMarker")
+            } else if rule.abort_incomplete_multipart_upload.is_some() {
+                Some("AbortIncompleteMultipartUpload")
+            } else {
+                None
+            }
+        })
+    }
+
+    // S3 assigns an ID to every rule that was submitted without one
+    pub fn with_generated_ids(mut self) -> Self {
+        for rule in &mut self.rules {
+            if rule.id.as_deref().unwrap_or("").is_empty() {
+                rule.id = Some(Uuid::new_v4().to_string());
+            }
+        }
+        self
+    }
+
+    /// Returns the earliest expiration that applies to the object together with
+    /// the ID of the rule that produced it.
+    pub fn expiration_for(&self, object: &Object) -> Option<(DateTime<Utc>, &str)> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.is_enabled() && rule.matches(object))
+            .filter_map(|rule| {
+                rule.expiration_date(object.last_modified)
+                    .map(|date| (date, rule.id.as_deref().unwrap_or("")))
+            })
+            .min_by_key(|(date, _)| *date)
+    }
+}
+
+impl LifecycleRule {
+    pub fn is_enabled(&self) -> bool {
+        self.status == "Enabled"
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.status != "Enabled" && self.status != "Disabled" {
+            return Err(format!("Invalid rule status: {}", self.status));
+        }
+        if self.prefix.is_some() && self.filter.is_some() {
+            return Err("Rule can have either a Prefix or a Filter, not both".to_string());
+        }
+        if self.expiration.is_none()
+            && self.noncurrent_version_expiration.is_none()
+            && self.abort_incomplete_multipart_upload.is_none()
+        {
+            return Err("At least one action needs to be specified in a rule".to_string());
+        }
+
+        if let Some(filter) = &self.filter {
+            let conditions = [
+                filter.prefix.is_some(),
+                filter.tag.is_some(),
+                filter.object_size_greater_than.is_some(),
+                filter.object_size_less_than.is_some(),
+                filter.and.is_some(),
+            ];
+            if conditions.iter().filter(|set| **set).count() > 1 {
+                return Err("Filter can only have one condition, use And to combine them".to_string());
+            }
+        }
+
+        if let Some(expiration) = &self.expiration {
+            let actions = [
+                expiration.days.is_some(),
+                expiration.date.is_some(),
+                expiration.expired_object_delete_marker.is_some(),
+            ];
+            if actions.iter().filter(|set| **set).count() != 1 {
+                return Err("Expiration must specify exactly one of Days, Date or ExpiredObjectDeleteMarker".to_string());
+            }
+            if expiration.days == Some(0) {
+                return Err("'Days' for Expiration action must be a positive integer".to_string());
+            }
+            if let Some(date) = expiration.date {
+                if date.time() != NaiveTime::MIN {
+                    return Err("'Date' must be at midnight GMT".to_string());
+                }
+            }
+            if expiration.expired_object_delete_marker.is_some() && self.has_tag_filter() {
+                return Err("ExpiredObjectDeleteMarker cannot be specified with tags".to_string());
+            }
+        }
+
+        if let Some(noncurrent) = &self.noncurrent_version_expiration {
+            if noncurrent.noncurrent_days.unwrap_or(0) == 0 {
+                return Err("'NoncurrentDays' for NoncurrentVersionExpiration action must be a positive integer".to_string());
+            }
+        }
+
+        if let Some(abort) = &self.abort_incomplete_multipart_upload {
+            if abort.days_after_initiation == 0 {
+                return Err("'DaysAfterInitiation' for AbortIncompleteMultipartUpload action must be a positive integer".to_string());
+            }
+            if self.has_tag_filter() {
+                return Err("AbortIncompleteMultipartUpload cannot be specified with tags".to_string());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn has_tag_filter(&self) -> bool {
+        match &self.filter {
+            Some(filter) => filter.tag.is_some() || filter.and.as_ref().is_some_and(|and| !and.tags.is_empty()),
+            None => false,
+        }
+    }
+
+    pub fn matches(&self, object: &Object) -> bool {
+        if let Some(prefix) = &self.prefix {
+            return object.key.starts_with(prefix.as_str());
+        }
+        let Some(filter) = &self.filter else {
+            return true;
+        };
+
+        if let Some(and) = &filter.and {
+            return matches_prefix(object, and.prefix.as_deref())
+                && and.tags.iter().all(|tag| matches_tag(object, tag))
+                && matches_size(object, and.object_size_greater_than, and.object_size_less_than);
+        }
+
+        matches_prefix(object, filter.prefix.as_deref())
+            && filter.tag.as_ref().is_none_or(|tag| matches_tag(object, tag))
+            && matches_size(object, filter.object_size_greater_than, filter.object_size_less_than)
+    }
+
+    /// Expiration date of a current object version according to this rule.
+    /// Day-based expirations are rounded up to the next midnight UTC, like S3 does.
+    pub fn expiration_date(&self, last_modified: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let expiration = self.expiration.as_ref()?;
+        if let Some(date) = expiration.date {
+            return Some(date);
+        }
+        let days = expiration.days?;
+        Some(round_up_to_midnight(last_modified + Duration::days(i64::from(days))))
+    }
+}
+
+fn matches_prefix(object: &Object, prefix: Option<&str>) -> bool {
+    prefix.is_none_or(|prefix| object.key.starts_with(prefix))
+}
+
+fn matches_tag(object: &Object, tag: &Tag) -> bool {
+    object.tags.get(&tag.key) == Some(&tag.value)
+}
+
+fn matches_size(object: &Object, greater_than: Option<u64>, less_than: Option<u64>) -> bool {
+    let size = object.size as u64;
+    greater_than.is_none_or(|min| size > min) && less_than.is_none_or(|max| size < max)
+}
+
+fn round_up_to_midnight(time: DateTime<Utc>) -> DateTime<Utc> {
+    let midnight = time.date_naive().and_time(NaiveTime::MIN).and_utc();
+    if time == midnight {
+        midnight
+    } else {
+        midnight + Duration::days(1)
+    }
+}
+
+/// Formats the value of the `x-amz-expiration` response header.
+pub fn expiration_header(date: DateTime<Utc>, rule_id: &str) -> String {
+    format!(
+        "expiry-date=\"{}\", rule-id=\"{}\"",
+        date.format("%a, %d %b %Y %H:%M:%S GMT"),
+        rule_id
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::collections::BTreeMap;
+
+    fn object(key: &str, size: usize, tags: &[(&str, &str)]) -> Object {
+        let tags: BTreeMap<String, String> = tags
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Object::new(key.to_string(), vec![0; size], "text/plain".to_string()).with_tags(tags)
+    }
+
+    #[test]
+    fn test_parse_lifecycle_configuration() {
+        let xml = r#"<LifecycleConfiguration xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+            <Rule>
+                <ID>logs</ID>
+                <Filter><And><Prefix>logs/</Prefix><Tag><Key>tier</Key><Value>cold</Value></Tag><ObjectSizeGreaterThan>10</ObjectSizeGreaterThan></And></Filter>
+                <Status>Enabled</Status>
+                <Expiration><Days>30</Days></Expiration>
+            </Rule>
+            <Rule>
+                <Filter><Prefix>tmp/</Prefix></Filter>
+                <Status>Disabled</Status>
+                <Expiration><Date>2030-01-01T00:00:00Z</Date></Expiration>
+                <AbortIncompleteMultipartUpload><DaysAfterInitiation>7</DaysAfterInitiation></AbortIncompleteMultipartUpload>
+            </Rule>
+        </LifecycleConfiguration>"#;
+
+        let config = LifecycleConfiguration::from_xml(xml).unwrap();
+        assert_eq!(config.rules.len(), 2);
+        let and = config.rules[0].filter.as_ref().unwrap().and.as_ref().unwrap();
+        assert_eq!(and.prefix.as_deref(), Some("logs/"));
+        assert_eq!(and.tags.len(), 1);
+        assert_eq!(and.object_size_greater_than, Some(10));
+        assert_eq!(config.rules[0].expiration.as_ref().unwrap().days, Some(30));
+        assert!(config.validate().is_ok());
+
+        let round_trip = LifecycleConfiguration::from_xml(&config.to_xml().unwrap()).unwrap();
+        assert_eq!(round_trip, config);
+        assert_eq!(config.unsupported_action(), Some("AbortIncompleteMultipartUpload"));
+    }
+
+    #[test]
+    fn test_unsupported_actions() {
+        let rule = |action: &str| {
+            format!("<LifecycleConfiguration><Rule><Status>Enabled</Status>{}</Rule></LifecycleConfiguration>", action)
+        };
+        let cases = [
+            ("<Expiration><Days>1</Days></Expiration>", None),
+            ("<NoncurrentVersionExpiration><NoncurrentDays>1</NoncurrentDays></NoncurrentVersionExpiration>", Some("NoncurrentVersionExpiration")),
+            ("<Expiration><ExpiredObjectDeleteMarker>true</ExpiredObjectDeleteMarker></Expiration>", Some("ExpiredObjectDeleteMarker")),
+        ];
+        for (action, expected) in cases {
+            let config = LifecycleConfiguration::from_xml(&rule(action)).unwrap();
+            assert!(config.validate().is_ok());
+            assert_eq!(config.unsupported_action(), expected, "{}", action);
+        }
+    }
+
+    #[test]
+    fn test_validation_rejects_invalid_rules() {
+        let no_action = r#"<LifecycleConfiguration><Rule><ID>a</ID><Status>Enabled</Status></Rule></LifecycleConfiguration>"#;
+        assert!(LifecycleConfiguration::from_xml(no_action).unwrap().validate().is_err());
+
+        let duplicate_ids = r#"<LifecycleConfiguration>
+            <Rule><ID>a</ID><Status>Enabled</Status><Expiration><Days>1</Days></Expiration></Rule>
+            <Rule><ID>a</ID><Status>Enabled</Status><Expiration><Days>2</Days></Expiration></Rule>
+        </LifecycleConfiguration>"#;
+        assert!(LifecycleConfiguration::from_xml(duplicate_ids).unwrap().validate().is_err());
+
+        let not_midnight = r#"<LifecycleConfiguration><Rule><Status>Enabled</Status><Expiration><Date>2030-01-01T10:00:00Z</Date></Expiration></Rule></LifecycleConfiguration>"#;
+        assert!(LifecycleConfiguration::from_xml(not_midnight).unwrap().validate().is_err());
+
+        let bad_status = r#"<LifecycleConfiguration><Rule><Status>On</Status><Expiration><Days>1</Days></Expiration></Rule></LifecycleConfiguration>"#;
+        assert!(LifecycleConfiguration::from_xml(bad_status).unwrap().validate().is_err());
+    }
+
+    #[test]
+    fn test_rule_filters() {
+        let rule = LifecycleRule {
+            status: "Enabled".to_string(),
+            filter: Some(LifecycleFilter {
+                and: Some(LifecycleAnd {
+                    prefix: Some("logs/".to_string()),
+                    tags: vec![Tag { key: "tier".to_string(), value: "cold".to_string() }],
+                    object_size_greater_than: Some(10),
+                    object_size_less_than: Some(100),
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        assert!(rule.matches(&object("logs/a.txt", 50, &[("tier", "cold")])));
+        assert!(!rule.matches(&object("data/a.txt", 50, &[("tier", "cold")])));
+        assert!(!rule.matches(&object("logs/a.txt", 50, &[("tier", "hot")])));
+        assert!(!rule.matches(&object("logs/a.txt", 5, &[("tier", "cold")])));
+        assert!(!rule.matches(&object("logs/a.txt", 500, &[("tier", "cold")])));
+
+        let legacy = LifecycleRule {
+            status: "Enabled".to_string(),
+            prefix: Some("tmp/".to_string()),
+            ..Default::default()
+        };
+        assert!(legacy.matches(&object("tmp/x", 1, &[])));
+        assert!(!legacy.matches(&object("x", 1, &[])));
+    }
+
+    #[test]
+    fn test_expiration_days_round_up_to_midnight() {
+        let rule = LifecycleRule {
+            id: Some("days".to_string()),
+            status: "Enabled".to_string(),
+            expiration: Some(Expiration { days: Some(3), ..Default::default() }),
+            ..Default::default()
+        };
+        let created = Utc.with_ymd_and_hms(2014, 1, 15, 10, 30, 0).unwrap();
+        assert_eq!(
+            rule.expiration_date(created),
+            Some(Utc.with_ymd_and_hms(2014, 1, 19, 0, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_expiration_for_picks_earliest_enabled_rule() {
+        let config = LifecycleConfiguration {
+            rules: vec![
+                LifecycleRule {
+                    id: Some("late".to_string()),
+                    status: "Enabled".to_string(),
+                    expiration: Some(Expiration { days: Some(30), ..Default::default() }),
+                    ..Default::default()
+                },
+                LifecycleRule {
+                    id: Some("early".to_string()),
+                    status: "Enabled".to_string(),
+                    expiration: Some(Expiration { days: Some(1), ..Default::default() }),
+                    ..Default::default()
+                },
+                LifecycleRule {
+                    id: Some("disabled".to_string()),
+                    status: "Disabled".to_string(),
+                    expiration: Some(Expiration {
+                        date: Some(Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap()),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            ],
+        };
+
+        let (date, rule_id) = config.expiration_for(&object("a", 1, &[])).unwrap();
+        assert_eq!(rule_id, "early");
+        assert!(date > Utc::now());
+        assert!(expiration_header(date, rule_id).ends_with("GMT\", rule-id=\"early\""));
+    }
+}
@@ -0,0 +1,252 @@
+use chrono::{DateTime, Utc};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+
+// Name under which the configuration is stored with the bucket
+pub const CONFIG_NAME: &str = "logging.xml";
+
+// Keys are logged URL-encoded, keeping their slashes
+const KEY_ENCODE: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~').remove(b'/');
+
+/// `?logging` document. Logging is off when it has no `LoggingEnabled`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "BucketLoggingStatus")]
+pub struct BucketLoggingStatus {
+    #[serde(rename = "LoggingEnabled", default, skip_serializing_if = "Option::is_none")]
+    pub logging_enabled: Option<LoggingEnabled>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LoggingEnabled {
+    #[serde(rename = "TargetBucket")]
+    pub target_bucket: String,
+    #[serde(rename = "TargetPrefix", default)]
+    pub target_prefix: String,
+    #[serde(rename = "TargetObjectKeyFormat", default, skip_serializing_if = "Option::is_none")]
+    pub target_object_key_format: Option<TargetObjectKeyFormat>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TargetObjectKeyFormat {
+    #[serde(rename = "SimplePrefix", default, skip_serializing_if = "Option::is_none")]
+    pub simple_prefix: Option<SimplePrefix>,
+    #[serde(rename = "PartitionedPrefix", default, skip_serializing_if = "Option::is_none")]
+    pub partitioned_prefix: Option<PartitionedPrefix>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SimplePrefix {}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PartitionedPrefix {
+    // "EventTime" or "DeliveryTime", the date the partition is named after
+    #[serde(rename = "PartitionDateSource", default, skip_serializing_if = "Option::is_none")]
+    pub partition_date_source: Option<String>,
+}
+
+impl BucketLoggingStatus {
+    pub fn from_xml(xml: &str) -> Result<Self, quick_xml::DeError> {
+        quick_xml::de::from_str(xml)
+    }
+
+    pub fn to_xml(&self) -> Result<String, quick_xml::SeError> {
+        quick_xml::se::to_string(self)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let Some(enabled) = &self.logging_enabled else {
+            return Ok(());
+        };
+        if enabled.target_bucket.is_empty() {
+            return Err("The target bucket for logging must be specified".to_string());
+        }
+        if let Some(format) = &enabled.target_object_key_format {
+            if format.simple_prefix.is_some() == format.partitioned_prefix.is_some() {
+                return Err("TargetObjectKeyFormat needs either SimplePrefix or PartitionedPrefix".to_string());
+            }
+            let source = format.partitioned_prefix.as_ref().and_then(|p| p.partition_date_source.as_deref());
+            if !matches!(source, None | Some("EventTime") | Some("DeliveryTime")) {
+                return Err(format!("Invalid PartitionDateSource: {}", source.unwrap_or_default()));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl LoggingEnabled {
+    /// Key of a log object delivered at `delivered` with records starting at `first_event`:
+    /// "[TargetPrefix]YYYY-mm-DD-HH-MM-SS-UniqueString" with a simple prefix, or
+    /// "[TargetPrefix][SourceAccountId]/[SourceRegion]/[SourceBucket]/YYYY/mm/DD/..."
+    /// partitioned by the event or delivery date.
+    pub fn log_object_key(
+        &self,
+        source_account: &str,
+        source_region: &str,
+        source_bucket: &str,
+        first_event: DateTime<Utc>,
+        delivered: DateTime<Utc>,
+        unique: &str,
+    ) -> String {
+        let name = format!("{}-{}", delivered.format("%Y-%m-%d-%H-%M-%S"), unique);
+        let partitioned = self.target_object_key_format.as_ref().and_then(|format| format.partitioned_prefix.as_ref());
+        match partitioned {
+            Some(partitioned) => {
+                let date = match partitioned.partition_date_source.as_deref() {
+                    Some("DeliveryTime") => delivered,
+                    _ => first_event,
+                };
+                format!(
+                    "{}{}/{}/{}/{}/{}",
+                    self.target_prefix,
+                    source_account,
+                    source_region,
+                    source_bucket,
+                    date.format("%Y/%m/%d"),
+                    name
+                )
+            }
+            None => format!("{}{}", self.target_prefix, name),
+        }
+    }
+}
+
+/// One request, as a line of the S3 server access log format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessLogRecord {
+    pub bucket_owner: String,
+    pub bucket: String,
+    pub time: DateTime<Utc>,
+    pub remote_ip: String,
+    // Canonical user ID or IAM ARN of the requester, "-" when anonymous
+    pub requester: String,
+    pub request_id: String,
+    // As in "REST.GET.OBJECT"
+    pub operation: String,
+    pub key: Option<String>,
+    // As in "GET /bucket/key HTTP/1.1"
+    pub request_uri: String,
+    pub http_status: u16,
+    pub error_code: Option<String>,
+    pub bytes_sent: Option<u64>,
+    pub object_size: Option<u64>,
+    pub total_time_ms: u64,
+    pub turn_around_time_ms: Option<u64>,
+    pub referer: Option<String>,
+    pub user_agent: Option<String>,
+    // "SigV4", "SigV2" or none for anonymous requests
+    pub signature_version: Option<String>,
+    // "AuthHeader" or "QueryString"
+    pub authentication_type: Option<String>,
+    pub host_header: Option<String>,
+}
+
+impl AccessLogRecord {
+    pub fn to_line(&self) -> String {
+        let field = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+        let quoted = |value: Option<&str>| format!("\"{}\"", value.unwrap_or("-").replace('"', "\\\""));
+        let fields = [
+            self.bucket_owner.clone(),
+            self.bucket.clone(),
+            self.time.format("[%d/%b/%Y:%H:%M:%S +0000]").to_string(),
+            self.remote_ip.clone(),
+            self.requester.clone(),
+            self.request_id.clone(),
+            self.operation.clone(),
+            field(self.key.as_ref().map(|key| utf8_percent_encode(key, KEY_ENCODE).to_string())),
+            quoted(Some(&self.request_uri)),
+            self.http_status.to_string(),
+            field(self.error_code.clone()),
+            field(self.bytes_sent.filter(|bytes| *bytes > 0).map(|bytes| bytes.to_string())),
+            field(self.object_size.map(|size| size.to_string())),
+            self.total_time_ms.to_string(),
+            field(self.turn_around_time_ms.map(|time| time.to_string())),
+            quoted(self.referer.as_deref()),
+            quoted(self.user_agent.as_deref()),
+            // Version ID and host ID
+            "-".to_string(),
+            "-".to_string(),
+            field(self.signature_version.clone()),
+            // Cipher suite, as requests are plain HTTP
+            "-".to_string(),
+            field(self.authentication_type.clone()),
+            field(self.host_header.clone()),
+            // TLS version, access point ARN and whether an ACL was required
+            "-".to_string(),
+            "-".to_string(),
+            "-".to_string(),
+        ];
+        fields.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse_logging_status() {
+        let xml = r#"<BucketLoggingStatus xmlns="http://doc.s3.amazonaws.com/2006-03-01">
+            <LoggingEnabled>
+                <TargetBucket>logs</TargetBucket>
+                <TargetPrefix>site/</TargetPrefix>
+                <TargetObjectKeyFormat><PartitionedPrefix><PartitionDateSource>EventTime</PartitionDateSource></PartitionedPrefix></TargetObjectKeyFormat>
+            </LoggingEnabled>
+        </BucketLoggingStatus>"#;
+        let status = BucketLoggingStatus::from_xml(xml).unwrap();
+        assert!(status.validate().is_ok());
+        let round_trip = BucketLoggingStatus::from_xml(&status.to_xml().unwrap()).unwrap();
+        assert_eq!(round_trip, status);
+
+        let enabled = status.logging_enabled.unwrap();
+        let event = Utc.with_ymd_and_hms(2024, 3, 31, 23, 59, 0).unwrap();
+        let delivered = Utc.with_ymd_and_hms(2024, 4, 1, 0, 1, 2).unwrap();
+        assert_eq!(
+            enabled.log_object_key("000000000000", "us-east-1", "site", event, delivered, "ABCDEF"),
+            "site/000000000000/us-east-1/site/2024/03/31/2024-04-01-00-01-02-ABCDEF"
+        );
+        let simple = LoggingEnabled {
+            target_object_key_format: Some(TargetObjectKeyFormat { simple_prefix: Some(SimplePrefix {}), ..Default::default() }),
+            ..enabled
+        };
+        assert_eq!(
+            simple.log_object_key("000000000000", "us-east-1", "site", event, delivered, "ABCDEF"),
+            "site/2024-04-01-00-01-02-ABCDEF"
+        );
+
+        let disabled = BucketLoggingStatus::from_xml("<BucketLoggingStatus/>").unwrap();
+        assert!(disabled.logging_enabled.is_none());
+        let both = r#"<BucketLoggingStatus><LoggingEnabled><TargetBucket>logs</TargetBucket><TargetPrefix></TargetPrefix><TargetObjectKeyFormat><SimplePrefix/><PartitionedPrefix/></TargetObjectKeyFormat></LoggingEnabled></BucketLoggingStatus>"#;
+        assert!(BucketLoggingStatus::from_xml(both).unwrap().validate().is_err());
+    }
+
+    #[test]
+    fn test_access_log_line() {
+        let record = AccessLogRecord {
+            bucket_owner: "owner-id".to_string(),
+            bucket: "site".to_string(),
+            time: Utc.with_ymd_and_hms(2019, 2, 6, 0, 0, 38).unwrap(),
+            remote_ip: "192.0.2.3".to_string(),
+            requester: "owner-id".to_string(),
+            request_id: "3E57427F3EXAMPLE".to_string(),
+            operation: "REST.GET.OBJECT".to_string(),
+            key: Some("docs/a b.txt".to_string()),
+            request_uri: "GET /site/docs/a.txt HTTP/1.1".to_string(),
+            http_status: 200,
+            error_code: None,
+            bytes_sent: Some(113),
+            object_size: Some(113),
+            total_time_ms: 7,
+            turn_around_time_ms: Some(6),
+            referer: None,
+            user_agent: Some("curl/8.0".to_string()),
+            signature_version: Some("SigV4".to_string()),
+            authentication_type: Some("AuthHeader".to_string()),
+            host_header: Some("localhost:3000".to_string()),
+        };
+        assert_eq!(
+            record.to_line(),
+            "owner-id site [06/Feb/2019:00:00:38 +0000] 192.0.2.3 owner-id 3E57427F3EXAMPLE REST.GET.OBJECT docs/a%20b.txt \"GET /site/docs/a.txt HTTP/1.1\" 200 - 113 113 7 6 \"-\" \"curl/8.0\" - - SigV4 - AuthHeader localhost:3000 - - -"
+        );
+    }
+}
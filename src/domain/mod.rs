@@ -1,2 +1,27 @@
+pub mod acl;
+pub mod audit;
 pub mod bucket;
+pub mod checksum;
+pub mod chunked;
+pub mod cors;
+pub mod credentials;
+pub mod encryption;
+pub mod event;
+pub mod hook;
+pub mod identity;
+pub mod inventory;
+pub mod lifecycle;
+pub mod logging;
+pub mod notification;
 pub mod object;
+pub mod policy;
+pub mod post_policy;
+pub mod public_access;
+pub mod queue;
+pub mod region;
+pub mod replication;
+pub mod select;
+pub mod sigv4;
+pub mod sql;
+pub mod sts;
+pub mod website;
@@ -0,0 +1,315 @@
+use chrono::{DateTime, Utc};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+// Name under which the configuration is stored with the bucket
+pub const CONFIG_NAME: &str = "notification.xml";
+
+// Event types a configuration may subscribe to. Only object creation, removal and
+// lifecycle expiration ever happen in this mock.
+const EVENT_TYPES: [&str; 16] = [
+    "s3:ObjectCreated:*",
+    "s3:ObjectCreated:Put",
+    "s3:ObjectCreated:Post",
+    "s3:ObjectCreated:Copy",
+    "s3:ObjectCreated:CompleteMultipartUpload",
+    "s3:ObjectRemoved:*",
+    "s3:ObjectRemoved:Delete",
+    "s3:ObjectRemoved:DeleteMarkerCreated",
+    "s3:LifecycleExpiration:*",
+    "s3:LifecycleExpiration:Delete",
+    "s3:LifecycleExpiration:DeleteMarkerCreated",
+    "s3:ObjectRestore:*",
+    "s3:ObjectTagging:*",
+    "s3:ObjectAcl:Put",
+    "s3:Replication:*",
+    "s3:ReducedRedundancyLostObject",
+];
+
+// Keys in event records are form-encoded: spaces become "+", the rest is percent-encoded
+const KEY_ENCODE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~')
+    .remove(b'/')
+    .remove(b' ');
+
+/// Where a bucket sends event notifications. Topic, queue and Lambda destinations share
+/// this shape; they only differ in the element naming the destination ARN.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "NotificationConfiguration")]
+pub struct NotificationConfiguration {
+    #[serde(rename = "TopicConfiguration", default)]
+    pub topic_configurations: Vec<DestinationConfiguration>,
+    #[serde(rename = "QueueConfiguration", default)]
+    pub queue_configurations: Vec<DestinationConfiguration>,
+    #[serde(rename = "CloudFunctionConfiguration", default)]
+    pub lambda_configurations: Vec<DestinationConfiguration>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DestinationConfiguration {
+    #[serde(rename = "Id", default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(rename = "Topic", default, skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+    #[serde(rename = "Queue", default, skip_serializing_if = "Option::is_none")]
+    pub queue: Option<String>,
+    #[serde(rename = "CloudFunction", default, skip_serializing_if = "Option::is_none")]
+    pub cloud_function: Option<String>,
+    #[serde(rename = "Event", default)]
+    pub events: Vec<String>,
+    #[serde(rename = "Filter", default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<NotificationFilter>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NotificationFilter {
+    #[serde(rename = "S3Key")]
+    pub key: KeyFilter,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct KeyFilter {
+    #[serde(rename = "FilterRule", default)]
+    pub rules: Vec<FilterRule>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FilterRule {
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Value")]
+    pub value: String,
+}
+
+impl NotificationConfiguration {
+    pub fn from_xml(xml: &str) -> Result<Self, quick_xml::DeError> {
+        quick_xml::de::from_str(xml)
+    }
+
+    pub fn to_xml(&self) -> Result<String, quick_xml::SeError> {
+        quick_xml::se::to_string(self)
+    }
+
+    /// Every topic, queue and Lambda configuration.
+    pub fn destinations(&self) -> impl Iterator<Item = &DestinationConfiguration> {
+        self.topic_configurations
+            .iter()
+            .chain(&self.queue_configurations)
+            .chain(&self.lambda_configurations)
+    }
+
+    pub fn destinations_mut(&mut self) -> impl Iterator<Item = &mut DestinationConfiguration> {
+        self.topic_configurations
+            .iter_mut()
+            .chain(&mut self.queue_configurations)
+            .chain(&mut self.lambda_configurations)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for config in self.destinations() {
+            if config.destination().is_none() {
+                return Err("A notification configuration must name exactly one destination".to_string());
+            }
+            if config.events.is_empty() {
+                return Err("A notification configuration must specify at least one event".to_string());
+            }
+            if let Some(event) = config.events.iter().find(|event| !EVENT_TYPES.contains(&event.as_str())) {
+                return Err(format!("The event is not supported for notifications: {}", event));
+            }
+            let rules = config.filter.as_ref().map(|filter| filter.key.rules.as_slice()).unwrap_or_default();
+            for name in ["prefix", "suffix"] {
+                if rules.iter().filter(|rule| rule.name.eq_ignore_ascii_case(name)).count() > 1 {
+                    return Err(format!("Cannot specify more than one {} rule in a filter.", name));
+                }
+            }
+            if let Some(rule) = rules.iter().find(|rule| !["prefix", "suffix"].contains(&rule.name.to_lowercase().as_str())) {
+                return Err(format!("filter rule name must be either prefix or suffix, not {}", rule.name));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DestinationConfiguration {
+    /// ARN of the topic, queue or Lambda function, when exactly one is given.
+    pub fn destination(&self) -> Option<&str> {
+        match (&self.topic, &self.queue, &self.cloud_function) {
+            (Some(arn), None, None) | (None, Some(arn), None) | (None, None, Some(arn)) => Some(arn),
+            _ => None,
+        }
+    }
+
+    /// Whether an event named like "ObjectCreated:Put" on `key` is subscribed to.
+    pub fn matches(&self, event_name: &str, key: &str) -> bool {
+        let subscribed = self.events.iter().any(|event| {
+            let event = event.strip_prefix("s3:").unwrap_or(event);
+            match event.strip_suffix('*') {
+                Some(prefix) => event_name.starts_with(prefix),
+                None => event == event_name,
+            }
+        });
+        let rules = self.filter.as_ref().map(|filter| filter.key.rules.as_slice()).unwrap_or_default();
+        subscribed
+            && rules.iter().all(|rule| match rule.name.to_lowercase().as_str() {
+                "prefix" => key.starts_with(&rule.value),
+                "suffix" => key.ends_with(&rule.value),
+                _ => false,
+            })
+    }
+}
+
+/// Something that happened to an object, as reported in S3 event records.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectEvent {
+    // As in "ObjectCreated:Put", without the "s3:" prefix
+    pub event_name: String,
+    pub bucket_name: String,
+    pub key: String,
+    // Only known for created objects
+    pub size: Option<usize>,
+    pub etag: Option<String>,
+    pub time: DateTime<Utc>,
+    pub region: String,
+    pub principal_id: String,
+    pub bucket_owner_id: String,
+    pub source_ip: String,
+    pub request_id: String,
+}
+
+impl ObjectEvent {
+    /// Orders events for the same key, as a fixed-width hex timestamp.
+    pub fn sequencer(&self) -> String {
+        format!("{:016X}", self.time.timestamp_nanos_opt().unwrap_or_default())
+    }
+
+    /// An entry of the `Records` array, shaped like the ones S3 sends to SQS, SNS and Lambda.
+    pub fn record(&self, configuration_id: &str) -> Value {
+        let mut object = json!({
+            "key": utf8_percent_encode(&self.key, KEY_ENCODE).to_string().replace(' ', "+"),
+            "sequencer": self.sequencer(),
+        });
+        if let Some(size) = self.size {
+            object["size"] = json!(size);
+        }
+        if let Some(etag) = &self.etag {
+            object["eTag"] = json!(etag.trim_matches('"'));
+        }
+        json!({
+            "eventVersion": "2.1",
+            "eventSource": "aws:s3",
+            "awsRegion": self.region,
+            "eventTime": self.time.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
+            "eventName": self.event_name,
+            "userIdentity": { "principalId": self.principal_id },
+            "requestParameters": { "sourceIPAddress": self.source_ip },
+            "responseElements": {
+                "x-amz-request-id": self.request_id,
+                "x-amz-id-2": format!("{}/mock", self.request_id),
+            },
+            "s3": {
+                "s3SchemaVersion": "1.0",
+                "configurationId": configuration_id,
+                "bucket": {
+                    "name": self.bucket_name,
+                    "ownerIdentity": { "principalId": self.bucket_owner_id },
+                    "arn": format!("arn:aws:s3:::{}", self.bucket_name),
+                },
+                "object": object,
+            },
+        })
+    }
+
+    /// The message body delivered for this event: a `Records` array with a single record.
+    pub fn message(&self, configuration_id: &str) -> Value {
+        json!({ "Records": [self.record(configuration_id)] })
+    }
+}
+
+/// The `s3:TestEvent` message S3 sends to destinations when a configuration is stored.
+pub fn test_event(bucket_name: &str, time: DateTime<Utc>, request_id: &str) -> Value {
+    json!({
+        "Service": "Amazon S3",
+        "Event": "s3:TestEvent",
+        "Time": time.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
+        "Bucket": bucket_name,
+        "RequestId": request_id,
+        "HostId": format!("{}/mock", request_id),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_configuration_xml_and_matching() {
+        let xml = r#"<NotificationConfiguration xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+            <QueueConfiguration>
+                <Id>images</Id>
+                <Queue>arn:aws:sqs:us-east-1:123456789012:images</Queue>
+                <Event>s3:ObjectCreated:*</Event>
+                <Filter><S3Key>
+                    <FilterRule><Name>prefix</Name><Value>images/</Value></FilterRule>
+                    <FilterRule><Name>Suffix</Name><Value>.jpg</Value></FilterRule>
+                </S3Key></Filter>
+            </QueueConfiguration>
+            <CloudFunctionConfiguration>
+                <CloudFunction>arn:aws:lambda:us-east-1:123456789012:function:cleanup</CloudFunction>
+                <Event>s3:ObjectRemoved:Delete</Event>
+            </CloudFunctionConfiguration>
+        </NotificationConfiguration>"#;
+        let config = NotificationConfiguration::from_xml(xml).unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.destinations().count(), 2);
+        assert_eq!(NotificationConfiguration::from_xml(&config.to_xml().unwrap()).unwrap(), config);
+
+        let images = &config.queue_configurations[0];
+        assert!(images.matches("ObjectCreated:Put", "images/cat.jpg"));
+        assert!(images.matches("ObjectCreated:Copy", "images/cat.jpg"));
+        assert!(!images.matches("ObjectCreated:Put", "images/cat.png"));
+        assert!(!images.matches("ObjectRemoved:Delete", "images/cat.jpg"));
+        let cleanup = &config.lambda_configurations[0];
+        assert!(cleanup.matches("ObjectRemoved:Delete", "any/key"));
+        assert!(!cleanup.matches("ObjectRemoved:DeleteMarkerCreated", "any/key"));
+    }
+
+    #[test]
+    fn test_validation() {
+        let unknown_event = "<NotificationConfiguration><TopicConfiguration><Topic>arn:aws:sns:us-east-1:123456789012:t</Topic><Event>s3:ObjectRead:*</Event></TopicConfiguration></NotificationConfiguration>";
+        assert!(NotificationConfiguration::from_xml(unknown_event).unwrap().validate().is_err());
+        let two_prefixes = "<NotificationConfiguration><TopicConfiguration><Topic>arn:aws:sns:us-east-1:123456789012:t</Topic><Event>s3:ObjectCreated:*</Event><Filter><S3Key><FilterRule><Name>prefix</Name><Value>a</Value></FilterRule><FilterRule><Name>prefix</Name><Value>b</Value></FilterRule></S3Key></Filter></TopicConfiguration></NotificationConfiguration>";
+        assert!(NotificationConfiguration::from_xml(two_prefixes).unwrap().validate().is_err());
+        assert!(NotificationConfiguration::from_xml("<NotificationConfiguration/>").unwrap().validate().is_ok());
+    }
+
+    #[test]
+    fn test_event_record() {
+        let event = ObjectEvent {
+            event_name: "ObjectCreated:Put".to_string(),
+            bucket_name: "photos".to_string(),
+            key: "summer trip/day 1+2.jpg".to_string(),
+            size: Some(1024),
+            etag: Some("\"d41d8cd98f00b204e9800998ecf8427e\"".to_string()),
+            time: DateTime::parse_from_rfc3339("2024-05-01T12:00:00Z").unwrap().with_timezone(&Utc),
+            region: "us-east-1".to_string(),
+            principal_id: "AWS:123456789012".to_string(),
+            bucket_owner_id: "owner".to_string(),
+            source_ip: "127.0.0.1".to_string(),
+            request_id: "C3D13FE58DE4C810".to_string(),
+        };
+        let message = event.message("images");
+        let record = &message["Records"][0];
+        assert_eq!(record["eventName"], "ObjectCreated:Put");
+        assert_eq!(record["eventTime"], "2024-05-01T12:00:00.000Z");
+        assert_eq!(record["s3"]["configurationId"], "images");
+        assert_eq!(record["s3"]["bucket"]["arn"], "arn:aws:s3:::photos");
+        assert_eq!(record["s3"]["object"]["key"], "summer+trip/day+1%2B2.jpg");
+        assert_eq!(record["s3"]["object"]["eTag"], "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(record["s3"]["object"]["size"], 1024);
+        assert_eq!(record["s3"]["object"]["sequencer"].as_str().unwrap().len(), 16);
+    }
+}
@@ -1,24 +1,101 @@
+use std::collections::BTreeMap;
+use chrono::{DateTime, Utc};
+use md5::{Digest, Md5};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
+use crate::domain::acl::AccessControlPolicy;
+use crate::domain::encryption::ObjectEncryption;
+use crate::domain::replication::ReplicationStatus;
+
+// Longest key S3 accepts, in bytes of UTF-8
+pub const MAX_KEY_LENGTH: usize = 1024;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Object {
     pub id: String,
     pub key: String,
+    // Content lives next to the metadata, never inside it
+    #[serde(skip)]
     pub content: Vec<u8>,
     pub content_type: String,
     pub size: usize,
+    // Quoted hex MD5 of the content, as S3 reports it for single-part uploads
+    #[serde(default)]
+    pub etag: String,
+    #[serde(default = "Utc::now")]
+    pub last_modified: DateTime<Utc>,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    #[serde(default)]
+    pub acl: Option<AccessControlPolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replication_status: Option<ReplicationStatus>,
+    // Where website endpoints redirect requests for the object
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub website_redirect_location: Option<String>,
+    // Objects stored before content was encrypted at rest have none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<ObjectEncryption>,
 }
 
 impl Object {
     pub fn new(key: String, content: Vec<u8>, content_type: String) -> Self {
         let size = content.len();
+        let etag = format!("\"{}\"", hex::encode(Md5::digest(&content)));
         Self {
             id: Uuid::new_v4().to_string(),
             key,
             content,
             content_type,
             size,
+            etag,
+            last_modified: Utc::now(),
+            tags: BTreeMap::new(),
+            acl: None,
+            replication_status: None,
+            website_redirect_location: None,
+            encryption: Some(ObjectEncryption::default()),
+        }
+    }
+
+    pub fn with_tags(mut self, tags: BTreeMap<String, String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    pub fn with_website_redirect_location(mut self, location: Option<String>) -> Self {
+        self.website_redirect_location = location;
+        self
+    }
+
+    pub fn with_encryption(mut self, encryption: ObjectEncryption) -> Self {
+        self.encryption = Some(encryption);
+        self
+    }
+
+    pub fn with_acl(mut self, acl: AccessControlPolicy) -> Self {
+        self.acl = Some(acl);
+        self
+    }
+
+    // Any UTF-8 key of 1 to 1024 bytes, as in S3. Path-like segments have no special meaning.
+    pub fn validate_key(key: &str) -> bool {
+        !key.is_empty() && key.len() <= MAX_KEY_LENGTH
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_key() {
+        for key in ["a.txt", "photos/2024/a.jpg", "dir/", ".hidden", "..", "../other/s.txt", "/etc/passwd", ".meta"] {
+            assert!(Object::validate_key(key), "{}", key);
+        }
+        assert!(Object::validate_key(&"é".repeat(512)));
+        for key in [String::new(), "a".repeat(1025)] {
+            assert!(!Object::validate_key(&key), "{}", key);
         }
     }
-}
\ No newline at end of file
+}
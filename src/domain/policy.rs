@@ -0,0 +1,734 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::domain::identity::Requester;
+
+// Name under which the policy is stored with the bucket
+pub const CONFIG_NAME: &str = "policy.json";
+
+const MAX_POLICY_SIZE: usize = 20 * 1024;
+const SUPPORTED_VERSIONS: [&str; 2] = ["2012-10-17", "2008-10-17"];
+
+// Condition keys that, pinned to fixed values, keep a wildcard principal from making a
+// statement public
+const RESTRICTING_CONDITION_KEYS: [&str; 10] = [
+    "aws:SourceIp",
+    "aws:SourceVpc",
+    "aws:SourceVpce",
+    "aws:SourceArn",
+    "aws:SourceAccount",
+    "aws:SourceOwner",
+    "aws:PrincipalAccount",
+    "aws:PrincipalArn",
+    "aws:PrincipalOrgID",
+    "aws:userid",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum OneOrMany<T> {
+    // Tried first, a single JSON value would happily swallow an array too
+    Many(Vec<T>),
+    One(T),
+}
+
+impl<T> OneOrMany<T> {
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        match self {
+            OneOrMany::One(value) => std::slice::from_ref(value).iter(),
+            OneOrMany::Many(values) => values.iter(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PrincipalSpec {
+    // Only "*" is valid here
+    Wildcard(String),
+    Typed(BTreeMap<String, OneOrMany<String>>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Effect {
+    Allow,
+    Deny,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BucketPolicy {
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub statement: OneOrMany<Statement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Statement {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+    pub effect: Effect,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub principal: Option<PrincipalSpec>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_principal: Option<PrincipalSpec>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<OneOrMany<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_action: Option<OneOrMany<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource: Option<OneOrMany<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_resource: Option<OneOrMany<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<BTreeMap<String, BTreeMap<String, OneOrMany<Value>>>>,
+}
+
+/// Outcome of evaluating a policy against a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// A statement explicitly allows the request and none denies it
+    Allow,
+    /// A statement explicitly denies the request, which always wins
+    Deny,
+    /// No statement applies to the request
+    Implicit,
+}
+
+/// Everything a policy can look at about a request.
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    pub requester: Requester,
+    pub action: String,
+    pub resource: String,
+    // Condition keys are case-insensitive, they are stored lowercased
+    conditions: HashMap<String, Vec<String>>,
+}
+
+impl RequestContext {
+    pub fn new(requester: Requester, action: impl Into<String>, resource: impl Into<String>) -> Self {
+        let mut context = Self {
+            requester,
+            action: action.into(),
+            resource: resource.into(),
+            conditions: HashMap::new(),
+        };
+        let now = Utc::now();
+        context.set_condition("aws:CurrentTime", now.to_rfc3339());
+        context.set_condition("aws:EpochTime", now.timestamp().to_string());
+        if let Some(identity) = context.requester.identity().cloned() {
+            context.set_condition("aws:PrincipalAccount", identity.account_id.clone());
+            context.set_condition("aws:PrincipalArn", identity.arn.clone());
+            for (key, value) in &identity.tags {
+                context.set_condition(&format!("aws:PrincipalTag/{}", key), value.clone());
+            }
+        }
+        context
+    }
+
+    pub fn with_condition(mut self, key: &str, value: impl Into<String>) -> Self {
+        self.set_condition(key, value);
+        self
+    }
+
+    pub fn set_condition(&mut self, key: &str, value: impl Into<String>) {
+        self.conditions.insert(key.to_lowercase(), vec![value.into()]);
+    }
+
+    pub fn condition(&self, key: &str) -> Option<&[String]> {
+        self.conditions.get(&key.to_lowercase()).map(Vec::as_slice)
+    }
+}
+
+impl BucketPolicy {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        if json.len() > MAX_POLICY_SIZE {
+            return Err("Policies must be no more than 20 KB".to_string());
+        }
+        serde_json::from_str(json).map_err(|e| format!("Policies must be valid JSON: {}", e))
+    }
+
+    /// Checks the policy the way PutBucketPolicy does before storing it.
+    pub fn validate(&self, bucket_name: &str) -> Result<(), String> {
+        if let Some(version) = &self.version {
+            if !SUPPORTED_VERSIONS.contains(&version.as_str()) {
+                return Err("Policy has an invalid version".to_string());
+            }
+        }
+        if self.statement.iter().next().is_none() {
+            return Err("Could not parse the policy: Statement is empty!".to_string());
+        }
+
+        for statement in self.statement.iter() {
+            if statement.principal.is_none() && statement.not_principal.is_none() {
+                return Err("Missing required field Principal".to_string());
+            }
+            for principal in [&statement.principal, &statement.not_principal].into_iter().flatten() {
+                if let PrincipalSpec::Wildcard(value) = principal {
+                    if value != "*" {
+                        return Err("Invalid principal in policy".to_string());
+                    }
+                }
+            }
+            if statement.action.is_none() && statement.not_action.is_none() {
+                return Err("Missing required field Action".to_string());
+            }
+            for action in statement.action.iter().chain(statement.not_action.iter()).flat_map(OneOrMany::iter) {
+                if action != "*" && !action.to_lowercase().starts_with("s3:") {
+                    return Err("Policy has invalid action".to_string());
+                }
+            }
+            let resources: Vec<&String> = statement
+                .resource
+                .iter()
+                .chain(statement.not_resource.iter())
+                .flat_map(OneOrMany::iter)
+                .collect();
+            if resources.is_empty() {
+                return Err("Missing required field Resource".to_string());
+            }
+            for resource in resources {
+                if !resource_targets_bucket(resource, bucket_name) {
+                    return Err("Policy has invalid resource".to_string());
+                }
+            }
+            if let Some(conditions) = &statement.condition {
+                for operator in conditions.keys() {
+                    if ConditionOperator::parse(operator).is_none() {
+                        return Err(format!("Policy has an invalid condition operator: {}", operator));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Explicit denies win over allows, which win over the implicit default.
+    pub fn evaluate(&self, context: &RequestContext) -> Decision {
+        let mut decision = Decision::Implicit;
+        for statement in self.statement.iter() {
+            if !statement.applies_to(context) {
+                continue;
+            }
+            match statement.effect {
+                Effect::Deny => return Decision::Deny,
+                Effect::Allow => decision = Decision::Allow,
+            }
+        }
+        decision
+    }
+
+    /// Whether the policy is public the way Block Public Access judges it: some statement
+    /// allows a wildcard principal without pinning it to fixed sources or principals.
+    pub fn is_public(&self) -> bool {
+        self.statement.iter().any(Statement::is_public)
+    }
+
+    /// The policy with its public statements left out.
+    pub fn without_public_statements(&self) -> Self {
+        let statements = self.statement.iter().filter(|statement| !statement.is_public()).cloned().collect();
+        Self {
+            statement: OneOrMany::Many(statements),
+            ..self.clone()
+        }
+    }
+}
+
+fn resource_targets_bucket(resource: &str, bucket_name: &str) -> bool {
+    if resource == "*" {
+        return true;
+    }
+    let Some(path) = resource.strip_prefix("arn:aws:s3:::") else {
+        return false;
+    };
+    let bucket_pattern = path.split('/').next().unwrap_or("");
+    wildcard_match(bucket_pattern, bucket_name, false)
+}
+
+impl Statement {
+    pub fn applies_to(&self, context: &RequestContext) -> bool {
+        let principal_matches = match (&self.principal, &self.not_principal) {
+            (Some(principal), _) => principal_matches(principal, &context.requester),
+            (None, Some(not_principal)) => !principal_matches(not_principal, &context.requester),
+            (None, None) => false,
+        };
+        if !principal_matches {
+            return false;
+        }
+
+        let action_matches = match (&self.action, &self.not_action) {
+            (Some(actions), _) => actions.iter().any(|pattern| wildcard_match(pattern, &context.action, true)),
+            (None, Some(actions)) => !actions.iter().any(|pattern| wildcard_match(pattern, &context.action, true)),
+            (None, None) => false,
+        };
+        if !action_matches {
+            return false;
+        }
+
+        let resource_matches = match (&self.resource, &self.not_resource) {
+            (Some(resources), _) => resources.iter().any(|pattern| wildcard_match(pattern, &context.resource, false)),
+            (None, Some(resources)) => !resources.iter().any(|pattern| wildcard_match(pattern, &context.resource, false)),
+            (None, None) => false,
+        };
+        if !resource_matches {
+            return false;
+        }
+
+        match &self.condition {
+            Some(conditions) => conditions.iter().all(|(operator, keys)| {
+                let Some(operator) = ConditionOperator::parse(operator) else {
+                    return false;
+                };
+                keys.iter().all(|(key, values)| {
+                    let expected: Vec<String> = values.iter().map(value_to_string).collect();
+                    operator.evaluate(context.condition(key), &expected)
+                })
+            }),
+            None => true,
+        }
+    }
+
+    fn is_public(&self) -> bool {
+        let wildcard = match (&self.principal, &self.not_principal) {
+            (Some(principal), _) => principal_is_wildcard(principal),
+            // Everyone but a few principals
+            (None, Some(_)) => true,
+            (None, None) => false,
+        };
+        self.effect == Effect::Allow && wildcard && !self.is_restricted()
+    }
+
+    fn is_restricted(&self) -> bool {
+        let Some(conditions) = &self.condition else {
+            return false;
+        };
+        conditions.iter().any(|(operator, keys)| {
+            let restricting_operator = ConditionOperator::parse(operator)
+                .is_some_and(|operator| !operator.negated && operator.comparison != Comparison::Null);
+            restricting_operator
+                && keys.iter().any(|(key, values)| {
+                    RESTRICTING_CONDITION_KEYS.iter().any(|restricting| restricting.eq_ignore_ascii_case(key))
+                        && values.iter().map(value_to_string).all(|value| is_fixed_value(key, &value))
+                })
+        })
+    }
+}
+
+fn principal_is_wildcard(principal: &PrincipalSpec) -> bool {
+    match principal {
+        PrincipalSpec::Wildcard(value) => value == "*",
+        PrincipalSpec::Typed(principals) => principals
+            .get("AWS")
+            .is_some_and(|values| values.iter().any(|value| value == "*")),
+    }
+}
+
+// Wildcards and wide IP ranges don't narrow anything down
+fn is_fixed_value(key: &str, value: &str) -> bool {
+    if value.contains(['*', '?']) {
+        return false;
+    }
+    if !key.eq_ignore_ascii_case("aws:SourceIp") {
+        return true;
+    }
+    match value.split_once('/') {
+        Some((ip, prefix)) => match (ip.parse::<IpAddr>(), prefix.parse::<u8>()) {
+            (Ok(IpAddr::V4(_)), Ok(prefix)) => prefix >= 8,
+            (Ok(IpAddr::V6(_)), Ok(prefix)) => prefix >= 32,
+            _ => false,
+        },
+        None => value.parse::<IpAddr>().is_ok(),
+    }
+}
+
+fn principal_matches(principal: &PrincipalSpec, requester: &Requester) -> bool {
+    match principal {
+        PrincipalSpec::Wildcard(value) => value == "*",
+        PrincipalSpec::Typed(principals) => principals.iter().any(|(kind, values)| {
+            values.iter().any(|value| match (kind.as_str(), requester.identity()) {
+                (_, _) if value == "*" && kind == "AWS" => true,
+                ("AWS", Some(identity)) => {
+                    value == &identity.account_id
+                        || value == &identity.arn
+                        || *value == format!("arn:aws:iam::{}:root", identity.account_id)
+                }
+                ("CanonicalUser", Some(identity)) => value == &identity.canonical_user_id,
+                _ => false,
+            })
+        }),
+    }
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    StringEquals,
+    StringEqualsIgnoreCase,
+    StringLike,
+    NumericEquals,
+    NumericLessThan,
+    NumericLessThanEquals,
+    NumericGreaterThan,
+    NumericGreaterThanEquals,
+    DateEquals,
+    DateLessThan,
+    DateLessThanEquals,
+    DateGreaterThan,
+    DateGreaterThanEquals,
+    Bool,
+    IpAddress,
+    Null,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SetQualifier {
+    None,
+    ForAnyValue,
+    ForAllValues,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ConditionOperator {
+    comparison: Comparison,
+    negated: bool,
+    if_exists: bool,
+    qualifier: SetQualifier,
+}
+
+impl ConditionOperator {
+    fn parse(operator: &str) -> Option<Self> {
+        let (qualifier, operator) = if let Some(rest) = operator.strip_prefix("ForAnyValue:") {
+            (SetQualifier::ForAnyValue, rest)
+        } else if let Some(rest) = operator.strip_prefix("ForAllValues:") {
+            (SetQualifier::ForAllValues, rest)
+        } else {
+            (SetQualifier::None, operator)
+        };
+        let (operator, if_exists) = match operator.strip_suffix("IfExists") {
+            Some(rest) => (rest, true),
+            None => (operator, false),
+        };
+
+        let (comparison, negated) = match operator {
+            "StringEquals" => (Comparison::StringEquals, false),
+            "StringNotEquals" => (Comparison::StringEquals, true),
+            "StringEqualsIgnoreCase" => (Comparison::StringEqualsIgnoreCase, false),
+            "StringNotEqualsIgnoreCase" => (Comparison::StringEqualsIgnoreCase, true),
+            "StringLike" | "ArnLike" => (Comparison::StringLike, false),
+            "StringNotLike" | "ArnNotLike" => (Comparison::StringLike, true),
+            "ArnEquals" => (Comparison::StringEquals, false),
+            "ArnNotEquals" => (Comparison::StringEquals, true),
+            "NumericEquals" => (Comparison::NumericEquals, false),
+            "NumericNotEquals" => (Comparison::NumericEquals, true),
+            "NumericLessThan" => (Comparison::NumericLessThan, false),
+            "NumericLessThanEquals" => (Comparison::NumericLessThanEquals, false),
+            "NumericGreaterThan" => (Comparison::NumericGreaterThan, false),
+            "NumericGreaterThanEquals" => (Comparison::NumericGreaterThanEquals, false),
+            "DateEquals" => (Comparison::DateEquals, false),
+            "DateNotEquals" => (Comparison::DateEquals, true),
+            "DateLessThan" => (Comparison::DateLessThan, false),
+            "DateLessThanEquals" => (Comparison::DateLessThanEquals, false),
+            "DateGreaterThan" => (Comparison::DateGreaterThan, false),
+            "DateGreaterThanEquals" => (Comparison::DateGreaterThanEquals, false),
+            "Bool" => (Comparison::Bool, false),
+            "IpAddress" => (Comparison::IpAddress, false),
+            "NotIpAddress" => (Comparison::IpAddress, true),
+            "Null" if !if_exists => (Comparison::Null, false),
+            _ => return None,
+        };
+        Some(Self { comparison, negated, if_exists, qualifier })
+    }
+
+    fn evaluate(&self, actual: Option<&[String]>, expected: &[String]) -> bool {
+        if self.comparison == Comparison::Null {
+            let want_missing = expected.iter().any(|value| value.eq_ignore_ascii_case("true"));
+            return actual.is_none() == want_missing;
+        }
+
+        let Some(actual) = actual else {
+            // Missing keys only satisfy IfExists, negated operators and ForAllValues
+            return self.if_exists || self.negated || self.qualifier == SetQualifier::ForAllValues;
+        };
+
+        let value_matches = |value: &String| expected.iter().any(|pattern| self.compare(value, pattern));
+        let matched = match self.qualifier {
+            SetQualifier::ForAllValues => actual.iter().all(value_matches),
+            SetQualifier::ForAnyValue | SetQualifier::None => actual.iter().any(value_matches),
+        };
+        matched != self.negated
+    }
+
+    fn compare(&self, actual: &str, expected: &str) -> bool {
+        match self.comparison {
+            Comparison::StringEquals => actual == expected,
+            Comparison::StringEqualsIgnoreCase => actual.eq_ignore_ascii_case(expected),
+            Comparison::StringLike => wildcard_match(expected, actual, false),
+            Comparison::Bool => actual.eq_ignore_ascii_case(expected),
+            Comparison::IpAddress => ip_in_cidr(actual, expected),
+            Comparison::NumericEquals
+            | Comparison::NumericLessThan
+            | Comparison::NumericLessThanEquals
+            | Comparison::NumericGreaterThan
+            | Comparison::NumericGreaterThanEquals => {
+                match (actual.parse::<f64>(), expected.parse::<f64>()) {
+                    (Ok(actual), Ok(expected)) => compare_ordered(self.comparison, actual.partial_cmp(&expected)),
+                    _ => false,
+                }
+            }
+            Comparison::DateEquals
+            | Comparison::DateLessThan
+            | Comparison::DateLessThanEquals
+            | Comparison::DateGreaterThan
+            | Comparison::DateGreaterThanEquals => match (parse_date(actual), parse_date(expected)) {
+                (Some(actual), Some(expected)) => compare_ordered(self.comparison, Some(actual.cmp(&expected))),
+                _ => false,
+            },
+            Comparison::Null => false,
+        }
+    }
+}
+
+fn compare_ordered(comparison: Comparison, ordering: Option<std::cmp::Ordering>) -> bool {
+    use std::cmp::Ordering::*;
+    let Some(ordering) = ordering else {
+        return false;
+    };
+    match comparison {
+        Comparison::NumericEquals | Comparison::DateEquals => ordering == Equal,
+        Comparison::NumericLessThan | Comparison::DateLessThan => ordering == Less,
+        Comparison::NumericLessThanEquals | Comparison::DateLessThanEquals => ordering != Greater,
+        Comparison::NumericGreaterThan | Comparison::DateGreaterThan => ordering == Greater,
+        Comparison::NumericGreaterThanEquals | Comparison::DateGreaterThanEquals => ordering != Less,
+        _ => false,
+    }
+}
+
+fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(epoch) = value.parse::<i64>() {
+        return DateTime::from_timestamp(epoch, 0);
+    }
+    DateTime::parse_from_rfc3339(value).ok().map(|date| date.with_timezone(&Utc))
+}
+
+fn ip_in_cidr(ip: &str, cidr: &str) -> bool {
+    let (network, prefix) = match cidr.split_once('/') {
+        Some((network, prefix)) => (network, prefix.parse::<u32>().ok()),
+        None => (cidr, None),
+    };
+    let (Ok(ip), Ok(network)) = (ip.parse::<IpAddr>(), network.parse::<IpAddr>()) else {
+        return false;
+    };
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let prefix = prefix.unwrap_or(32).min(32);
+            let mask = if prefix == 0 { 0 } else { u32::MAX << (32 - prefix) };
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let prefix = prefix.unwrap_or(128).min(128);
+            let mask = if prefix == 0 { 0 } else { u128::MAX << (128 - prefix) };
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+/// Matches `value` against a pattern where `*` matches any run of characters
+/// and `?` matches a single character.
+pub fn wildcard_match(pattern: &str, value: &str, ignore_case: bool) -> bool {
+    let (pattern, value) = if ignore_case {
+        (pattern.to_lowercase(), value.to_lowercase())
+    } else {
+        (pattern.to_string(), value.to_string())
+    };
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+
+    let (mut p, mut v) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while v < value.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == value[v]) {
+            p += 1;
+            v += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, v));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            v = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::identity::Identity;
+
+    fn partner() -> Requester {
+        let mut identity = Identity::owner();
+        identity.account_id = "111122223333".to_string();
+        identity.arn = "arn:aws:iam::111122223333:user/partner".to_string();
+        identity.tags.insert("team".to_string(), "analytics".to_string());
+        Requester::Authenticated(identity)
+    }
+
+    fn context(requester: Requester, action: &str, resource: &str) -> RequestContext {
+        RequestContext::new(requester, action, resource)
+            .with_condition("aws:SecureTransport", "false")
+            .with_condition("aws:SourceIp", "10.1.2.3")
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("s3:Get*", "s3:GetObject", true));
+        assert!(wildcard_match("s3:get*", "s3:GetObject", true));
+        assert!(!wildcard_match("s3:Put*", "s3:GetObject", true));
+        assert!(wildcard_match("arn:aws:s3:::bucket/*", "arn:aws:s3:::bucket/a/b.txt", false));
+        assert!(!wildcard_match("arn:aws:s3:::bucket/*", "arn:aws:s3:::bucket", false));
+        assert!(wildcard_match("arn:aws:s3:::bucket/??.txt", "arn:aws:s3:::bucket/ab.txt", false));
+        assert!(!wildcard_match("arn:aws:s3:::Bucket/*", "arn:aws:s3:::bucket/a", false));
+    }
+
+    #[test]
+    fn test_explicit_deny_wins_over_allow() {
+        let policy = BucketPolicy::from_json(r#"{
+            "Version": "2012-10-17",
+            "Statement": [
+                {"Effect": "Allow", "Principal": "*", "Action": "s3:*", "Resource": "arn:aws:s3:::bucket/*"},
+                {"Effect": "Deny", "Principal": "*", "Action": "s3:DeleteObject", "Resource": "arn:aws:s3:::bucket/*"}
+            ]
+        }"#).unwrap();
+        assert!(policy.validate("bucket").is_ok());
+
+        assert_eq!(policy.evaluate(&context(partner(), "s3:GetObject", "arn:aws:s3:::bucket/a")), Decision::Allow);
+        assert_eq!(policy.evaluate(&context(partner(), "s3:DeleteObject", "arn:aws:s3:::bucket/a")), Decision::Deny);
+        assert_eq!(policy.evaluate(&context(partner(), "s3:ListBucket", "arn:aws:s3:::bucket")), Decision::Implicit);
+    }
+
+    #[test]
+    fn test_principal_matching() {
+        let policy = BucketPolicy::from_json(r#"{
+            "Statement": {"Effect": "Allow", "Principal": {"AWS": ["111122223333"]}, "Action": "s3:GetObject", "Resource": "arn:aws:s3:::bucket/*"}
+        }"#).unwrap();
+
+        assert_eq!(policy.evaluate(&context(partner(), "s3:GetObject", "arn:aws:s3:::bucket/a")), Decision::Allow);
+        assert_eq!(
+            policy.evaluate(&context(Requester::Authenticated(Identity::owner()), "s3:GetObject", "arn:aws:s3:::bucket/a")),
+            Decision::Implicit
+        );
+        assert_eq!(policy.evaluate(&context(Requester::Anonymous, "s3:GetObject", "arn:aws:s3:::bucket/a")), Decision::Implicit);
+    }
+
+    #[test]
+    fn test_secure_transport_and_source_ip_conditions() {
+        let policy = BucketPolicy::from_json(r#"{
+            "Version": "2012-10-17",
+            "Statement": [
+                {"Effect": "Deny", "Principal": "*", "Action": "s3:*", "Resource": ["arn:aws:s3:::bucket", "arn:aws:s3:::bucket/*"],
+                 "Condition": {"Bool": {"aws:SecureTransport": false}}},
+                {"Effect": "Allow", "Principal": "*", "Action": "s3:GetObject", "Resource": "arn:aws:s3:::bucket/*",
+                 "Condition": {"IpAddress": {"aws:SourceIp": "10.0.0.0/8"}}}
+            ]
+        }"#).unwrap();
+
+        assert_eq!(policy.evaluate(&context(partner(), "s3:GetObject", "arn:aws:s3:::bucket/a")), Decision::Deny);
+
+        let secure = context(partner(), "s3:GetObject", "arn:aws:s3:::bucket/a").with_condition("aws:SecureTransport", "true");
+        assert_eq!(policy.evaluate(&secure), Decision::Allow);
+        let outside = secure.with_condition("aws:SourceIp", "192.168.0.1");
+        assert_eq!(policy.evaluate(&outside), Decision::Implicit);
+    }
+
+    #[test]
+    fn test_request_and_principal_tag_conditions() {
+        let policy = BucketPolicy::from_json(r#"{
+            "Version": "2012-10-17",
+            "Statement": [
+                {"Effect": "Allow", "Principal": "*", "Action": "s3:ListBucket", "Resource": "arn:aws:s3:::bucket",
+                 "Condition": {"StringLike": {"s3:prefix": ["home/*", ""]}, "StringEquals": {"aws:PrincipalTag/team": "analytics"}}},
+                {"Effect": "Deny", "Principal": "*", "Action": "s3:PutObject", "Resource": "arn:aws:s3:::bucket/*",
+                 "Condition": {"StringNotEquals": {"s3:x-amz-acl": "bucket-owner-full-control"}}}
+            ]
+        }"#).unwrap();
+
+        let list = context(partner(), "s3:ListBucket", "arn:aws:s3:::bucket").with_condition("s3:prefix", "home/docs");
+        assert_eq!(policy.evaluate(&list), Decision::Allow);
+        let other_prefix = context(partner(), "s3:ListBucket", "arn:aws:s3:::bucket").with_condition("s3:prefix", "private/");
+        assert_eq!(policy.evaluate(&other_prefix), Decision::Implicit);
+        let untagged = context(Requester::Authenticated(Identity::owner()), "s3:ListBucket", "arn:aws:s3:::bucket")
+            .with_condition("s3:prefix", "home/docs");
+        assert_eq!(policy.evaluate(&untagged), Decision::Implicit);
+
+        let put = context(partner(), "s3:PutObject", "arn:aws:s3:::bucket/a");
+        assert_eq!(policy.evaluate(&put), Decision::Deny);
+        let put_with_acl = put.with_condition("s3:x-amz-acl", "bucket-owner-full-control");
+        assert_eq!(policy.evaluate(&put_with_acl), Decision::Implicit);
+    }
+
+    #[test]
+    fn test_public_policies() {
+        let policy = BucketPolicy::from_json(r#"{
+            "Version": "2012-10-17",
+            "Statement": [
+                {"Effect": "Allow", "Principal": {"AWS": "111122223333"}, "Action": "s3:GetObject", "Resource": "arn:aws:s3:::bucket/*"},
+                {"Effect": "Allow", "Principal": "*", "Action": "s3:GetObject", "Resource": "arn:aws:s3:::bucket/*",
+                 "Condition": {"IpAddress": {"aws:SourceIp": "10.0.0.0/8"}}},
+                {"Effect": "Deny", "Principal": "*", "Action": "s3:DeleteObject", "Resource": "arn:aws:s3:::bucket/*"}
+            ]
+        }"#).unwrap();
+        assert!(!policy.is_public());
+
+        let public = BucketPolicy::from_json(r#"{
+            "Version": "2012-10-17",
+            "Statement": [
+                {"Effect": "Allow", "Principal": {"AWS": "111122223333"}, "Action": "s3:PutObject", "Resource": "arn:aws:s3:::bucket/*"},
+                {"Effect": "Allow", "Principal": {"AWS": "*"}, "Action": "s3:GetObject", "Resource": "arn:aws:s3:::bucket/*",
+                 "Condition": {"IpAddress": {"aws:SourceIp": "0.0.0.0/1"}, "StringLike": {"aws:PrincipalArn": "arn:aws:iam::*"}}}
+            ]
+        }"#).unwrap();
+        assert!(public.is_public());
+        let restricted = public.without_public_statements();
+        assert!(!restricted.is_public());
+        assert_eq!(restricted.evaluate(&context(partner(), "s3:GetObject", "arn:aws:s3:::bucket/a")), Decision::Implicit);
+        assert_eq!(restricted.evaluate(&context(partner(), "s3:PutObject", "arn:aws:s3:::bucket/a")), Decision::Allow);
+    }
+
+    #[test]
+    fn test_validation() {
+        let foreign_resource = BucketPolicy::from_json(r#"{
+            "Version": "2012-10-17",
+            "Statement": {"Effect": "Allow", "Principal": "*", "Action": "s3:GetObject", "Resource": "arn:aws:s3:::other/*"}
+        }"#).unwrap();
+        assert!(foreign_resource.validate("bucket").is_err());
+
+        let missing_principal = BucketPolicy::from_json(r#"{
+            "Version": "2012-10-17",
+            "Statement": {"Effect": "Allow", "Action": "s3:GetObject", "Resource": "arn:aws:s3:::bucket/*"}
+        }"#).unwrap();
+        assert!(missing_principal.validate("bucket").is_err());
+
+        let bad_operator = BucketPolicy::from_json(r#"{
+            "Version": "2012-10-17",
+            "Statement": {"Effect": "Allow", "Principal": "*", "Action": "s3:GetObject", "Resource": "arn:aws:s3:::bucket/*",
+                          "Condition": {"StringSortOf": {"s3:prefix": "a"}}}
+        }"#).unwrap();
+        assert!(bad_operator.validate("bucket").is_err());
+
+        assert!(BucketPolicy::from_json("{not json").is_err());
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+/// Policy document of a browser-based POST upload, restricting the form fields it accepts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostPolicy {
+    pub expiration: DateTime<Utc>,
+    pub conditions: Vec<Condition>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    // {"acl": "public-read"} and ["eq", "$acl", "public-read"]
+    Eq(String, String),
+    StartsWith(String, String),
+    ContentLengthRange(u64, u64),
+}
+
+/// Why a form was rejected by its policy.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PolicyViolation {
+    Expired,
+    ConditionFailed(String),
+    ExtraField(String),
+    TooLarge,
+    TooSmall,
+}
+
+impl std::fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PolicyViolation::Expired => write!(f, "Invalid according to Policy: Policy expired."),
+            PolicyViolation::ConditionFailed(condition) => {
+                write!(f, "Invalid according to Policy: Policy Condition failed: {}", condition)
+            }
+            PolicyViolation::ExtraField(name) => write!(f, "Invalid according to Policy: Extra input fields: {}", name),
+            PolicyViolation::TooLarge => write!(f, "Your proposed upload exceeds the maximum allowed size"),
+            PolicyViolation::TooSmall => write!(f, "Your proposed upload is smaller than the minimum allowed size"),
+        }
+    }
+}
+
+// Form fields that never need a matching condition
+const UNCHECKED_FIELDS: [&str; 5] = ["policy", "x-amz-signature", "file", "awsaccesskeyid", "signature"];
+
+impl PostPolicy {
+    /// Decodes the base64 `policy` form field.
+    pub fn from_base64(encoded: &str) -> Result<Self, String> {
+        let json = base64::engine::general_purpose::STANDARD
+            .decode(encoded.trim())
+            .map_err(|_| "Invalid Policy: Invalid Base64 Encoding.".to_string())?;
+        let document: Value =
+            serde_json::from_slice(&json).map_err(|_| "Invalid Policy: Invalid JSON.".to_string())?;
+
+        let expiration = document
+            .get("expiration")
+            .and_then(Value::as_str)
+            .ok_or_else(|| "Invalid Policy: Policy missing expiration.".to_string())?;
+        let expiration = DateTime::parse_from_rfc3339(expiration)
+            .map_err(|_| format!("Invalid Policy: Invalid 'expiration' value: '{}'", expiration))?
+            .with_timezone(&Utc);
+        let conditions = document
+            .get("conditions")
+            .and_then(Value::as_array)
+            .ok_or_else(|| "Invalid Policy: Policy missing conditions.".to_string())?
+            .iter()
+            .map(Condition::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { expiration, conditions })
+    }
+
+    /// Checks the form against the policy. Field names must be lowercased, and hold
+    /// the bucket and the final key.
+    pub fn check(
+        &self,
+        fields: &HashMap<String, String>,
+        content_length: u64,
+        now: DateTime<Utc>,
+    ) -> Result<(), PolicyViolation> {
+        if now > self.expiration {
+            return Err(PolicyViolation::Expired);
+        }
+        for condition in &self.conditions {
+            condition.check(fields, content_length)?;
+        }
+
+        let mut names: Vec<&String> = fields.keys().collect();
+        names.sort();
+        for name in names {
+            let covered = name == "bucket"
+                || UNCHECKED_FIELDS.contains(&name.as_str())
+                || name.starts_with("x-ignore-")
+                || self.conditions.iter().any(|condition| condition.field() == Some(name.as_str()));
+            if !covered {
+                return Err(PolicyViolation::ExtraField(name.clone()));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Condition {
+    fn parse(value: &Value) -> Result<Self, String> {
+        let invalid = || format!("Invalid Policy: Invalid Condition: {}", value);
+        if let Some(object) = value.as_object() {
+            let (name, expected) = object.iter().next().filter(|_| object.len() == 1).ok_or_else(invalid)?;
+            let expected = expected.as_str().ok_or_else(invalid)?;
+            return Ok(Condition::Eq(name.to_lowercase(), expected.to_string()));
+        }
+
+        let items = value.as_array().ok_or_else(invalid)?;
+        let operator = items.first().and_then(Value::as_str).ok_or_else(invalid)?;
+        match (operator.to_lowercase().as_str(), items.len()) {
+            ("content-length-range", 3) => {
+                let bound = |value: &Value| {
+                    value
+                        .as_u64()
+                        .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+                        .ok_or_else(invalid)
+                };
+                Ok(Condition::ContentLengthRange(bound(&items[1])?, bound(&items[2])?))
+            }
+            (operator @ ("eq" | "starts-with"), 3) => {
+                let field = items[1]
+                    .as_str()
+                    .and_then(|field| field.strip_prefix('$'))
+                    .ok_or_else(invalid)?
+                    .to_lowercase();
+                let expected = items[2].as_str().ok_or_else(invalid)?.to_string();
+                Ok(if operator == "eq" {
+                    Condition::Eq(field, expected)
+                } else {
+                    Condition::StartsWith(field, expected)
+                })
+            }
+            _ => Err(invalid()),
+        }
+    }
+
+    fn field(&self) -> Option<&str> {
+        match self {
+            Condition::Eq(field, _) | Condition::StartsWith(field, _) => Some(field),
+            Condition::ContentLengthRange(..) => None,
+        }
+    }
+
+    fn check(&self, fields: &HashMap<String, String>, content_length: u64) -> Result<(), PolicyViolation> {
+        let actual = |field: &str| fields.get(field).map(String::as_str).unwrap_or_default();
+        let failed = |operator: &str, field: &str, expected: &str| {
+            PolicyViolation::ConditionFailed(format!("[\"{}\", \"${}\", \"{}\"]", operator, field, expected))
+        };
+        match self {
+            Condition::Eq(field, expected) if actual(field) != expected => Err(failed("eq", field, expected)),
+            Condition::StartsWith(field, prefix) if !actual(field).starts_with(prefix.as_str()) => {
+                Err(failed("starts-with", field, prefix))
+            }
+            Condition::ContentLengthRange(_, max) if content_length > *max => Err(PolicyViolation::TooLarge),
+            Condition::ContentLengthRange(min, _) if content_length < *min => Err(PolicyViolation::TooSmall),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(json: &str) -> String {
+        base64::engine::general_purpose::STANDARD.encode(json)
+    }
+
+    fn fields(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn test_parse_and_check() {
+        let policy = PostPolicy::from_base64(&encode(
+            r#"{"expiration": "2030-01-01T00:00:00.000Z",
+                "conditions": [
+                    {"bucket": "uploads"},
+                    ["starts-with", "$key", "user/"],
+                    {"acl": "public-read"},
+                    ["content-length-range", 1, 1024],
+                    ["eq", "$success_action_status", "201"]
+                ]}"#,
+        ))
+        .unwrap();
+        assert_eq!(policy.conditions.len(), 5);
+
+        let now = DateTime::parse_from_rfc3339("2025-01-01T00:00:00Z").unwrap().with_timezone(&Utc);
+        let form = fields(&[
+            ("bucket", "uploads"),
+            ("key", "user/photo.jpg"),
+            ("acl", "public-read"),
+            ("success_action_status", "201"),
+            ("policy", "..."),
+            ("x-ignore-tracking", "1"),
+        ]);
+        assert_eq!(policy.check(&form, 10, now), Ok(()));
+        assert_eq!(policy.check(&form, 2048, now), Err(PolicyViolation::TooLarge));
+        assert_eq!(policy.check(&form, 0, now), Err(PolicyViolation::TooSmall));
+
+        let mut other_key = form.clone();
+        other_key.insert("key".to_string(), "admin/photo.jpg".to_string());
+        assert_eq!(
+            policy.check(&other_key, 10, now).unwrap_err().to_string(),
+            "Invalid according to Policy: Policy Condition failed: [\"starts-with\", \"$key\", \"user/\"]"
+        );
+
+        let mut extra = form.clone();
+        extra.insert("content-type".to_string(), "image/jpeg".to_string());
+        assert_eq!(policy.check(&extra, 10, now), Err(PolicyViolation::ExtraField("content-type".to_string())));
+
+        let later = DateTime::parse_from_rfc3339("2031-01-01T00:00:00Z").unwrap().with_timezone(&Utc);
+        assert_eq!(policy.check(&form, 10, later), Err(PolicyViolation::Expired));
+    }
+
+    #[test]
+    fn test_invalid_policies() {
+        assert!(PostPolicy::from_base64("not base64!").is_err());
+        assert!(PostPolicy::from_base64(&encode("{")).is_err());
+        assert!(PostPolicy::from_base64(&encode(r#"{"conditions": []}"#)).is_err());
+        assert!(PostPolicy::from_base64(&encode(
+            r#"{"expiration": "2030-01-01T00:00:00Z", "conditions": [["matches", "$key", "a"]]}"#
+        ))
+        .is_err());
+    }
+}
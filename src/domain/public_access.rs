@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+// Name under which the configuration is stored with the bucket
+pub const CONFIG_NAME: &str = "public-access-block.xml";
+
+/// Block Public Access settings of a bucket. Buckets without a configuration block nothing.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename = "PublicAccessBlockConfiguration")]
+pub struct PublicAccessBlockConfiguration {
+    // Reject requests that would set a public ACL
+    #[serde(rename = "BlockPublicAcls", default)]
+    pub block_public_acls: bool,
+    // Treat existing public ACL grants as absent
+    #[serde(rename = "IgnorePublicAcls", default)]
+    pub ignore_public_acls: bool,
+    // Reject bucket policies that grant public access
+    #[serde(rename = "BlockPublicPolicy", default)]
+    pub block_public_policy: bool,
+    // Only let the bucket owner's account through a public bucket policy
+    #[serde(rename = "RestrictPublicBuckets", default)]
+    pub restrict_public_buckets: bool,
+}
+
+impl PublicAccessBlockConfiguration {
+    pub fn from_xml(xml: &str) -> Result<Self, quick_xml::DeError> {
+        quick_xml::de::from_str(xml)
+    }
+
+    pub fn to_xml(&self) -> Result<String, quick_xml::SeError> {
+        quick_xml::se::to_string(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_configuration_xml() {
+        let xml = r#"<PublicAccessBlockConfiguration xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+            <BlockPublicAcls>true</BlockPublicAcls>
+            <RestrictPublicBuckets>true</RestrictPublicBuckets>
+        </PublicAccessBlockConfiguration>"#;
+        let config = PublicAccessBlockConfiguration::from_xml(xml).unwrap();
+        assert!(config.block_public_acls && config.restrict_public_buckets);
+        assert!(!config.ignore_public_acls && !config.block_public_policy);
+        let round_trip = PublicAccessBlockConfiguration::from_xml(&config.to_xml().unwrap()).unwrap();
+        assert_eq!(round_trip, config);
+        assert!(PublicAccessBlockConfiguration::from_xml("<PublicAccessBlockConfiguration><BlockPublicAcls>maybe</BlockPublicAcls></PublicAccessBlockConfiguration>").is_err());
+    }
+}
@@ -0,0 +1,392 @@
+use std::collections::{BTreeMap, VecDeque};
+use chrono::{DateTime, Duration, Utc};
+use md5::{Digest, Md5};
+use uuid::Uuid;
+
+pub const MAX_WAIT_SECONDS: u32 = 20;
+pub const MAX_MESSAGES_PER_RECEIVE: u32 = 10;
+const MAX_VISIBILITY_TIMEOUT: u32 = 12 * 60 * 60;
+const MAX_QUEUE_NAME_LENGTH: usize = 80;
+
+// Attributes kept as given, without any effect on the queue
+const PASSTHROUGH_ATTRIBUTES: [&str; 10] = [
+    "Policy",
+    "RedrivePolicy",
+    "RedriveAllowPolicy",
+    "FifoQueue",
+    "ContentBasedDeduplication",
+    "DeduplicationScope",
+    "FifoThroughputLimit",
+    "KmsMasterKeyId",
+    "KmsDataKeyReusePeriodSeconds",
+    "SqsManagedSseEnabled",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueueError {
+    NonExistentQueue,
+    QueueNameExists(String),
+    InvalidAttributeName(String),
+    InvalidAttributeValue(String),
+    InvalidParameterValue(String),
+    ReceiptHandleIsInvalid(String),
+    MessageNotInflight,
+}
+
+/// Settings of a queue, set through the `Attributes` of CreateQueue.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueueAttributes {
+    pub delay_seconds: u32,
+    pub maximum_message_size: u32,
+    pub message_retention_period: u32,
+    pub receive_message_wait_time_seconds: u32,
+    pub visibility_timeout: u32,
+    pub other: BTreeMap<String, String>,
+}
+
+impl Default for QueueAttributes {
+    fn default() -> Self {
+        Self {
+            delay_seconds: 0,
+            maximum_message_size: 256 * 1024,
+            message_retention_period: 4 * 24 * 60 * 60,
+            receive_message_wait_time_seconds: 0,
+            visibility_timeout: 30,
+            other: BTreeMap::new(),
+        }
+    }
+}
+
+impl QueueAttributes {
+    pub fn from_map(attributes: &BTreeMap<String, String>) -> Result<Self, QueueError> {
+        let mut result = Self::default();
+        for (name, value) in attributes {
+            let number = |min: u32, max: u32| {
+                value
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|number| (min..=max).contains(number))
+                    .ok_or_else(|| QueueError::InvalidAttributeValue(format!("Invalid value for the parameter {}.", name)))
+            };
+            match name.as_str() {
+                "DelaySeconds" => result.delay_seconds = number(0, 900)?,
+                "MaximumMessageSize" => result.maximum_message_size = number(1024, 256 * 1024)?,
+                "MessageRetentionPeriod" => result.message_retention_period = number(60, 14 * 24 * 60 * 60)?,
+                "ReceiveMessageWaitTimeSeconds" => result.receive_message_wait_time_seconds = number(0, MAX_WAIT_SECONDS)?,
+                "VisibilityTimeout" => result.visibility_timeout = number(0, MAX_VISIBILITY_TIMEOUT)?,
+                _ if PASSTHROUGH_ATTRIBUTES.contains(&name.as_str()) => {
+                    result.other.insert(name.clone(), value.clone());
+                }
+                _ => return Err(QueueError::InvalidAttributeName(format!("Unknown Attribute {}.", name))),
+            }
+        }
+        Ok(result)
+    }
+
+    pub fn to_map(&self) -> BTreeMap<String, String> {
+        let mut attributes = self.other.clone();
+        attributes.insert("DelaySeconds".to_string(), self.delay_seconds.to_string());
+        attributes.insert("MaximumMessageSize".to_string(), self.maximum_message_size.to_string());
+        attributes.insert("MessageRetentionPeriod".to_string(), self.message_retention_period.to_string());
+        attributes.insert(
+            "ReceiveMessageWaitTimeSeconds".to_string(),
+            self.receive_message_wait_time_seconds.to_string(),
+        );
+        attributes.insert("VisibilityTimeout".to_string(), self.visibility_timeout.to_string());
+        attributes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub message_id: String,
+    pub body: String,
+    pub md5_of_body: String,
+    pub sender_id: String,
+    pub sent_at: DateTime<Utc>,
+    // Delayed and received messages are hidden until then
+    pub visible_at: DateTime<Utc>,
+    pub receive_count: u32,
+    pub first_received_at: Option<DateTime<Utc>>,
+    // Handle of the latest receive, which deletes and visibility changes must present
+    pub receipt_handle: Option<String>,
+}
+
+impl Message {
+    /// System attributes reported by ReceiveMessage, as named in `AttributeNames`.
+    pub fn attributes(&self) -> BTreeMap<String, String> {
+        let millis = |time: DateTime<Utc>| time.timestamp_millis().to_string();
+        let mut attributes = BTreeMap::new();
+        attributes.insert("SenderId".to_string(), self.sender_id.clone());
+        attributes.insert("SentTimestamp".to_string(), millis(self.sent_at));
+        attributes.insert("ApproximateReceiveCount".to_string(), self.receive_count.to_string());
+        if let Some(first_received_at) = self.first_received_at {
+            attributes.insert("ApproximateFirstReceiveTimestamp".to_string(), millis(first_received_at));
+        }
+        attributes
+    }
+}
+
+/// A standard queue. Messages are handed out in the order they were sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Queue {
+    pub name: String,
+    pub attributes: QueueAttributes,
+    pub created_at: DateTime<Utc>,
+    messages: VecDeque<Message>,
+}
+
+impl Queue {
+    pub fn new(name: &str, attributes: QueueAttributes, now: DateTime<Utc>) -> Result<Self, QueueError> {
+        validate_queue_name(name)?;
+        Ok(Self {
+            name: name.to_string(),
+            attributes,
+            created_at: now,
+            messages: VecDeque::new(),
+        })
+    }
+
+    pub fn send(&mut self, body: &str, delay_seconds: Option<u32>, sender_id: &str, now: DateTime<Utc>) -> Result<Message, QueueError> {
+        if body.is_empty() {
+            return Err(QueueError::InvalidParameterValue(
+                "The request must contain the parameter MessageBody.".to_string(),
+            ));
+        }
+        if body.len() > self.attributes.maximum_message_size as usize {
+            return Err(QueueError::InvalidParameterValue(format!(
+                "One or more parameters are invalid. Reason: Message must be shorter than {} bytes.",
+                self.attributes.maximum_message_size
+            )));
+        }
+        let delay = delay_seconds.unwrap_or(self.attributes.delay_seconds);
+        if delay > 900 {
+            return Err(QueueError::InvalidParameterValue(format!(
+                "Value {} for parameter DelaySeconds is invalid. Reason: must be between 0 and 900, if provided.",
+                delay
+            )));
+        }
+        let message = Message {
+            message_id: Uuid::new_v4().to_string(),
+            body: body.to_string(),
+            md5_of_body: hex::encode(Md5::digest(body.as_bytes())),
+            sender_id: sender_id.to_string(),
+            sent_at: now,
+            visible_at: now + Duration::seconds(delay.into()),
+            receive_count: 0,
+            first_received_at: None,
+            receipt_handle: None,
+        };
+        self.messages.push_back(message.clone());
+        Ok(message)
+    }
+
+    /// Hands out up to `max` visible messages, hiding them for `visibility_timeout` seconds
+    /// or the queue's default.
+    pub fn receive(&mut self, max: u32, visibility_timeout: Option<u32>, now: DateTime<Utc>) -> Result<Vec<Message>, QueueError> {
+        if !(1..=MAX_MESSAGES_PER_RECEIVE).contains(&max) {
+            return Err(QueueError::InvalidParameterValue(format!(
+                "Value {} for parameter MaxNumberOfMessages is invalid. Reason: Must be between 1 and 10, if provided.",
+                max
+            )));
+        }
+        let timeout = visibility_timeout.unwrap_or(self.attributes.visibility_timeout);
+        check_visibility_timeout(timeout)?;
+        self.expire(now);
+        let mut received = Vec::new();
+        for message in self.messages.iter_mut().filter(|message| message.visible_at <= now) {
+            if received.len() == max as usize {
+                break;
+            }
+            message.visible_at = now + Duration::seconds(timeout.into());
+            message.receive_count += 1;
+            message.first_received_at.get_or_insert(now);
+            message.receipt_handle = Some(new_receipt_handle(&message.message_id));
+            received.push(message.clone());
+        }
+        Ok(received)
+    }
+
+    /// Deletes the message last received with `receipt_handle`. Deleting it twice is fine.
+    pub fn delete(&mut self, receipt_handle: &str) -> Result<(), QueueError> {
+        check_receipt_handle(receipt_handle)?;
+        self.messages
+            .retain(|message| message.receipt_handle.as_deref() != Some(receipt_handle));
+        Ok(())
+    }
+
+    pub fn change_visibility(&mut self, receipt_handle: &str, timeout: u32, now: DateTime<Utc>) -> Result<(), QueueError> {
+        check_receipt_handle(receipt_handle)?;
+        check_visibility_timeout(timeout)?;
+        let message = self
+            .messages
+            .iter_mut()
+            .find(|message| message.receipt_handle.as_deref() == Some(receipt_handle))
+            .ok_or_else(|| QueueError::ReceiptHandleIsInvalid(format!("The receipt handle \"{}\" is not valid.", receipt_handle)))?;
+        if message.visible_at <= now {
+            return Err(QueueError::MessageNotInflight);
+        }
+        message.visible_at = now + Duration::seconds(timeout.into());
+        Ok(())
+    }
+
+    pub fn purge(&mut self) {
+        self.messages.clear();
+    }
+
+    /// Queue attributes along with the approximate message counts, as in GetQueueAttributes.
+    pub fn attributes(&mut self, arn: &str, now: DateTime<Utc>) -> BTreeMap<String, String> {
+        self.expire(now);
+        let visible = self.messages.iter().filter(|message| message.visible_at <= now).count();
+        let in_flight = self.messages.iter().filter(|message| message.visible_at > now && message.receive_count > 0).count();
+        let delayed = self.messages.len() - visible - in_flight;
+        let mut attributes = self.attributes.to_map();
+        attributes.insert("QueueArn".to_string(), arn.to_string());
+        attributes.insert("ApproximateNumberOfMessages".to_string(), visible.to_string());
+        attributes.insert("ApproximateNumberOfMessagesNotVisible".to_string(), in_flight.to_string());
+        attributes.insert("ApproximateNumberOfMessagesDelayed".to_string(), delayed.to_string());
+        attributes.insert("CreatedTimestamp".to_string(), self.created_at.timestamp().to_string());
+        attributes.insert("LastModifiedTimestamp".to_string(), self.created_at.timestamp().to_string());
+        attributes
+    }
+
+    // Drops messages older than the retention period
+    fn expire(&mut self, now: DateTime<Utc>) {
+        let retention = Duration::seconds(self.attributes.message_retention_period.into());
+        self.messages.retain(|message| message.sent_at + retention > now);
+    }
+}
+
+/// Queue names have up to 80 alphanumeric characters, hyphens and underscores, FIFO
+/// queues end with `.fifo`.
+pub fn validate_queue_name(name: &str) -> Result<(), QueueError> {
+    let base = name.strip_suffix(".fifo").unwrap_or(name);
+    let valid = !base.is_empty()
+        && name.len() <= MAX_QUEUE_NAME_LENGTH
+        && base.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(QueueError::InvalidParameterValue(
+            "Can only include alphanumeric characters, hyphens, or underscores. 1 to 80 in length".to_string(),
+        ))
+    }
+}
+
+/// The queue name of an SQS ARN, as in "arn:aws:sqs:us-east-1:123456789012:jobs".
+pub fn queue_name_of_arn(arn: &str) -> Option<&str> {
+    let parts: Vec<&str> = arn.splitn(6, ':').collect();
+    match parts.as_slice() {
+        ["arn", _, "sqs", _, _, name] if !name.is_empty() => Some(name),
+        _ => None,
+    }
+}
+
+fn new_receipt_handle(message_id: &str) -> String {
+    format!("{}#{}", message_id, Uuid::new_v4().simple())
+}
+
+fn check_receipt_handle(receipt_handle: &str) -> Result<(), QueueError> {
+    let well_formed = receipt_handle
+        .split_once('#')
+        .is_some_and(|(message_id, nonce)| Uuid::parse_str(message_id).is_ok() && Uuid::parse_str(nonce).is_ok());
+    if well_formed {
+        Ok(())
+    } else {
+        Err(QueueError::ReceiptHandleIsInvalid(format!(
+            "The input receipt handle \"{}\" is not a valid receipt handle.",
+            receipt_handle
+        )))
+    }
+}
+
+fn check_visibility_timeout(timeout: u32) -> Result<(), QueueError> {
+    if timeout > MAX_VISIBILITY_TIMEOUT {
+        return Err(QueueError::InvalidParameterValue(format!(
+            "Value {} for parameter VisibilityTimeout is invalid. Reason: Must be between 0 and 43200.",
+            timeout
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue() -> Queue {
+        Queue::new("jobs", QueueAttributes::default(), Utc::now()).unwrap()
+    }
+
+    #[test]
+    fn test_visibility() {
+        let now = Utc::now();
+        let mut queue = queue();
+        let sent = queue.send("hello", None, "000000000000", now).unwrap();
+        assert_eq!(sent.md5_of_body, "5d41402abc4b2a76b9719d911017c592");
+
+        let received = queue.receive(10, Some(30), now).unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].receive_count, 1);
+        // Hidden until the visibility timeout runs out
+        assert!(queue.receive(10, None, now + Duration::seconds(29)).unwrap().is_empty());
+        let handle = received[0].receipt_handle.clone().unwrap();
+        queue.change_visibility(&handle, 0, now + Duration::seconds(10)).unwrap();
+        assert_eq!(
+            queue.change_visibility(&handle, 10, now + Duration::seconds(10)),
+            Err(QueueError::MessageNotInflight)
+        );
+
+        let again = queue.receive(10, None, now + Duration::seconds(10)).unwrap();
+        assert_eq!(again[0].receive_count, 2);
+        // Only the latest receipt handle deletes the message
+        queue.delete(&handle).unwrap();
+        assert_eq!(queue.attributes("arn", now + Duration::seconds(10))["ApproximateNumberOfMessagesNotVisible"], "1");
+        queue.delete(again[0].receipt_handle.as_deref().unwrap()).unwrap();
+        assert!(queue.receive(10, None, now + Duration::days(1)).unwrap().is_empty());
+        assert!(matches!(queue.delete("garbage"), Err(QueueError::ReceiptHandleIsInvalid(_))));
+    }
+
+    #[test]
+    fn test_delays_and_limits() {
+        let now = Utc::now();
+        let mut queue = queue();
+        queue.send("later", Some(60), "000000000000", now).unwrap();
+        for i in 0..12 {
+            queue.send(&format!("message {}", i), None, "000000000000", now).unwrap();
+        }
+        let received = queue.receive(10, None, now).unwrap();
+        assert_eq!(received.len(), 10);
+        assert_eq!(received[0].body, "message 0");
+        assert!(queue.receive(11, None, now).is_err());
+        assert_eq!(queue.receive(10, None, now).unwrap().len(), 2);
+        assert_eq!(queue.attributes("arn", now)["ApproximateNumberOfMessagesDelayed"], "1");
+        assert!(queue.receive(10, None, now + Duration::seconds(29)).unwrap().is_empty());
+        assert_eq!(queue.receive(1, None, now + Duration::seconds(60)).unwrap()[0].body, "later");
+        // Retention drops old messages
+        assert!(queue.receive(10, None, now + Duration::days(5)).unwrap().is_empty());
+        assert!(queue.send("", None, "000000000000", now).is_err());
+    }
+
+    #[test]
+    fn test_attributes_and_names() {
+        let mut attributes = BTreeMap::new();
+        attributes.insert("VisibilityTimeout".to_string(), "60".to_string());
+        attributes.insert("Policy".to_string(), "{}".to_string());
+        let parsed = QueueAttributes::from_map(&attributes).unwrap();
+        assert_eq!(parsed.visibility_timeout, 60);
+        assert_eq!(parsed.to_map()["Policy"], "{}");
+        attributes.insert("VisibilityTimeout".to_string(), "50000".to_string());
+        assert!(matches!(QueueAttributes::from_map(&attributes), Err(QueueError::InvalidAttributeValue(_))));
+        attributes.clear();
+        attributes.insert("Colour".to_string(), "blue".to_string());
+        assert!(matches!(QueueAttributes::from_map(&attributes), Err(QueueError::InvalidAttributeName(_))));
+
+        assert!(validate_queue_name("jobs_1-a.fifo").is_ok());
+        assert!(validate_queue_name("").is_err());
+        assert!(validate_queue_name("no spaces").is_err());
+        assert!(validate_queue_name(&"a".repeat(81)).is_err());
+        assert_eq!(queue_name_of_arn("arn:aws:sqs:us-east-1:000000000000:jobs"), Some("jobs"));
+        assert_eq!(queue_name_of_arn("arn:aws:sns:us-east-1:000000000000:jobs"), None);
+    }
+}
@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+
+// Region of buckets created without a location constraint, and of unsigned requests
+pub const DEFAULT_REGION: &str = "us-east-1";
+
+/// Body of a CreateBucket request.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CreateBucketConfiguration {
+    #[serde(rename = "LocationConstraint", default, skip_serializing_if = "Option::is_none")]
+    pub location_constraint: Option<String>,
+}
+
+impl CreateBucketConfiguration {
+    pub fn from_xml(xml: &str) -> Result<Self, quick_xml::DeError> {
+        quick_xml::de::from_str(xml)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LocationError {
+    // Not a region name at all
+    Invalid,
+    // A region other than the one of the endpoint the request was sent to
+    Illegal(String),
+}
+
+/// The region a new bucket is created in, from the region the request was sent to and
+/// its `LocationConstraint`. As in S3, the us-east-1 endpoint creates buckets in any
+/// region, while the others only take their own region.
+pub fn bucket_region(endpoint_region: &str, location_constraint: Option<&str>) -> Result<String, LocationError> {
+    let constraint = location_constraint.map(str::trim).filter(|constraint| !constraint.is_empty());
+    match constraint {
+        // us-east-1 is implied by leaving the constraint out, naming it is an error
+        Some(DEFAULT_REGION) => Err(LocationError::Invalid),
+        Some(region) if !is_region_name(region) => Err(LocationError::Invalid),
+        Some(region) if endpoint_region == DEFAULT_REGION || endpoint_region == region => Ok(region.to_string()),
+        Some(region) => Err(LocationError::Illegal(format!(
+            "The {} location constraint is incompatible for the region specific endpoint this request was sent to.",
+            region
+        ))),
+        None if endpoint_region == DEFAULT_REGION => Ok(DEFAULT_REGION.to_string()),
+        None => Err(LocationError::Illegal(
+            "The unspecified location constraint is incompatible for the region specific endpoint this request was sent to."
+                .to_string(),
+        )),
+    }
+}
+
+/// Whether `name` is shaped like a region, as in "eu-west-1" or "us-gov-east-1".
+pub fn is_region_name(name: &str) -> bool {
+    let parts: Vec<&str> = name.split('-').collect();
+    let Some((number, words)) = parts.split_last() else {
+        return false;
+    };
+    words.len() >= 2
+        && words.iter().all(|word| !word.is_empty() && word.chars().all(|c| c.is_ascii_lowercase()))
+        && !number.is_empty()
+        && number.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Host that requests for a bucket in `region` should be sent to.
+pub fn endpoint(bucket_name: &str, region: &str) -> String {
+    if region == DEFAULT_REGION {
+        format!("{}.s3.amazonaws.com", bucket_name)
+    } else {
+        format!("{}.s3.{}.amazonaws.com", bucket_name, region)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_region() {
+        assert_eq!(bucket_region("us-east-1", None), Ok("us-east-1".to_string()));
+        assert_eq!(bucket_region("us-east-1", Some("eu-west-1")), Ok("eu-west-1".to_string()));
+        assert_eq!(bucket_region("eu-west-1", Some("eu-west-1")), Ok("eu-west-1".to_string()));
+        assert_eq!(bucket_region("us-east-1", Some("us-east-1")), Err(LocationError::Invalid));
+        assert_eq!(bucket_region("us-east-1", Some("Mars")), Err(LocationError::Invalid));
+        assert!(matches!(bucket_region("eu-west-1", Some("ap-south-1")), Err(LocationError::Illegal(_))));
+        assert!(matches!(bucket_region("eu-west-1", None), Err(LocationError::Illegal(_))));
+
+        assert!(is_region_name("us-gov-east-1"));
+        assert!(!is_region_name("eu-west"));
+        assert_eq!(endpoint("photos", "eu-west-1"), "photos.s3.eu-west-1.amazonaws.com");
+    }
+
+    #[test]
+    fn test_parse_configuration() {
+        let xml = r#"<CreateBucketConfiguration xmlns="http://s3.amazonaws.com/doc/2006-03-01/"><LocationConstraint>eu-west-1</LocationConstraint></CreateBucketConfiguration>"#;
+        let config = CreateBucketConfiguration::from_xml(xml).unwrap();
+        assert_eq!(config.location_constraint.as_deref(), Some("eu-west-1"));
+        assert_eq!(CreateBucketConfiguration::from_xml("<CreateBucketConfiguration/>").unwrap(), CreateBucketConfiguration::default());
+    }
+}
@@ -0,0 +1,322 @@
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::domain::lifecycle::Tag;
+use crate::domain::object::Object;
+
+// Name under which the configuration is stored with the bucket
+pub const CONFIG_NAME: &str = "replication.xml";
+
+const MAX_RULES: usize = 1000;
+const MAX_ID_LENGTH: usize = 255;
+const BUCKET_ARN_PREFIX: &str = "arn:aws:s3:::";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "ReplicationConfiguration")]
+pub struct ReplicationConfiguration {
+    // IAM role S3 would assume, only stored here
+    #[serde(rename = "Role", default)]
+    pub role: String,
+    #[serde(rename = "Rule", default)]
+    pub rules: Vec<ReplicationRule>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReplicationRule {
+    #[serde(rename = "ID", default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    // Of two matching rules, the one with the higher priority applies
+    #[serde(rename = "Priority", default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u32>,
+    // Deprecated top-level prefix of the first schema version, instead of a Filter
+    #[serde(rename = "Prefix", default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    #[serde(rename = "Filter", default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<ReplicationFilter>,
+    #[serde(rename = "Status")]
+    pub status: String,
+    #[serde(rename = "Destination")]
+    pub destination: Destination,
+    #[serde(rename = "DeleteMarkerReplication", default, skip_serializing_if = "Option::is_none")]
+    pub delete_marker_replication: Option<DeleteMarkerReplication>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReplicationFilter {
+    #[serde(rename = "Prefix", default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    #[serde(rename = "Tag", default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<Tag>,
+    #[serde(rename = "And", default, skip_serializing_if = "Option::is_none")]
+    pub and: Option<ReplicationAnd>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReplicationAnd {
+    #[serde(rename = "Prefix", default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    #[serde(rename = "Tag", default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<Tag>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Destination {
+    // Bucket ARN, as in "arn:aws:s3:::replica-bucket"
+    #[serde(rename = "Bucket")]
+    pub bucket: String,
+    #[serde(rename = "Account", default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    #[serde(rename = "StorageClass", default, skip_serializing_if = "Option::is_none")]
+    pub storage_class: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeleteMarkerReplication {
+    #[serde(rename = "Status")]
+    pub status: String,
+}
+
+/// Replication state of an object, as reported in `x-amz-replication-status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ReplicationStatus {
+    Pending,
+    Completed,
+    Failed,
+    // Set on the copies in destination buckets
+    Replica,
+}
+
+impl ReplicationStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "PENDING",
+            Self::Completed => "COMPLETED",
+            Self::Failed => "FAILED",
+            Self::Replica => "REPLICA",
+        }
+    }
+}
+
+impl ReplicationConfiguration {
+    pub fn from_xml(xml: &str) -> Result<Self, quick_xml::DeError> {
+        quick_xml::de::from_str(xml)
+    }
+
+    pub fn to_xml(&self) -> Result<String, quick_xml::SeError> {
+        quick_xml::se::to_string(self)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.rules.is_empty() {
+            return Err("At least one replication rule is required".to_string());
+        }
+        if self.rules.len() > MAX_RULES {
+            return Err(format!("A replication configuration can have up to {} rules", MAX_RULES));
+        }
+
+        let mut ids = HashSet::new();
+        let mut priorities = HashSet::new();
+        for rule in &self.rules {
+            if let Some(id) = &rule.id {
+                if id.len() > MAX_ID_LENGTH {
+                    return Err("ID length should not exceed allowed limit of 255".to_string());
+                }
+                if !ids.insert(id.as_str()) {
+                    return Err("Rule Id must be unique".to_string());
+                }
+            }
+            if let Some(priority) = rule.priority {
+                if !priorities.insert(priority) {
+                    return Err("Found duplicate priority. Priority must be unique among rules".to_string());
+                }
+            }
+            rule.validate()?;
+        }
+        Ok(())
+    }
+
+    // S3 assigns an ID to every rule that was submitted without one
+    pub fn with_generated_ids(mut self) -> Self {
+        for rule in &mut self.rules {
+            if rule.id.as_deref().unwrap_or("").is_empty() {
+                rule.id = Some(Uuid::new_v4().to_string());
+            }
+        }
+        self
+    }
+
+    /// The enabled rule replicating the object: the matching one of highest priority, or the
+    /// first matching one among equals.
+    pub fn rule_for(&self, object: &Object) -> Option<&ReplicationRule> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.is_enabled() && rule.matches(object))
+            .fold(None, |best: Option<&ReplicationRule>, rule| match best {
+                Some(best) if best.priority.unwrap_or(0) >= rule.priority.unwrap_or(0) => Some(best),
+                _ => Some(rule),
+            })
+    }
+
+    /// The enabled rule replicating the removal of `key` to its destination. Rules with tag
+    /// filters never do, as the removed object's tags are unknown.
+    pub fn delete_rule_for(&self, key: &str) -> Option<&ReplicationRule> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.is_enabled() && rule.replicates_delete_markers() && !rule.has_tag_filter())
+            .filter(|rule| key.starts_with(rule.key_prefix()))
+            .fold(None, |best: Option<&ReplicationRule>, rule| match best {
+                Some(best) if best.priority.unwrap_or(0) >= rule.priority.unwrap_or(0) => Some(best),
+                _ => Some(rule),
+            })
+    }
+}
+
+impl ReplicationRule {
+    pub fn is_enabled(&self) -> bool {
+        self.status == "Enabled"
+    }
+
+    /// Name of the destination bucket, from its ARN.
+    pub fn destination_bucket(&self) -> Option<&str> {
+        self.destination
+            .bucket
+            .strip_prefix(BUCKET_ARN_PREFIX)
+            .filter(|name| !name.is_empty() && !name.contains('/'))
+    }
+
+    // Rules of the first schema version, with a top-level Prefix, always replicate delete
+    // markers. Filtered rules do when DeleteMarkerReplication is enabled.
+    pub fn replicates_delete_markers(&self) -> bool {
+        match &self.delete_marker_replication {
+            Some(replication) => replication.status == "Enabled",
+            None => self.filter.is_none(),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.status != "Enabled" && self.status != "Disabled" {
+            return Err(format!("Invalid rule status: {}", self.status));
+        }
+        if self.prefix.is_some() && self.filter.is_some() {
+            return Err("Rule can have either a Prefix or a Filter, not both".to_string());
+        }
+        if self.destination_bucket().is_none() {
+            return Err(format!("Invalid destination bucket ARN: {}", self.destination.bucket));
+        }
+        if let Some(replication) = &self.delete_marker_replication {
+            if replication.status != "Enabled" && replication.status != "Disabled" {
+                return Err(format!("Invalid DeleteMarkerReplication status: {}", replication.status));
+            }
+        }
+
+        if let Some(filter) = &self.filter {
+            let conditions = [filter.prefix.is_some(), filter.tag.is_some(), filter.and.is_some()];
+            if conditions.iter().filter(|set| **set).count() > 1 {
+                return Err("Filter can only have one condition, use And to combine them".to_string());
+            }
+            if self.delete_marker_replication.is_none() {
+                return Err("DeleteMarkerReplication must be specified for this version of Cross Region Replication configuration schema.".to_string());
+            }
+            if self.has_tag_filter() && self.replicates_delete_markers() {
+                return Err("Delete marker replication is not supported if any Tag filter is specified.".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    fn has_tag_filter(&self) -> bool {
+        match &self.filter {
+            Some(filter) => filter.tag.is_some() || filter.and.as_ref().is_some_and(|and| !and.tags.is_empty()),
+            None => false,
+        }
+    }
+
+    fn key_prefix(&self) -> &str {
+        let filter_prefix = self.filter.as_ref().and_then(|filter| {
+            filter.prefix.as_deref().or(filter.and.as_ref().and_then(|and| and.prefix.as_deref()))
+        });
+        self.prefix.as_deref().or(filter_prefix).unwrap_or("")
+    }
+
+    pub fn matches(&self, object: &Object) -> bool {
+        if !object.key.starts_with(self.key_prefix()) {
+            return false;
+        }
+        let Some(filter) = &self.filter else {
+            return true;
+        };
+        let mut tags = filter.tag.iter().chain(filter.and.iter().flat_map(|and| and.tags.iter()));
+        tags.all(|tag| object.tags.get(&tag.key) == Some(&tag.value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(key: &str, tags: &[(&str, &str)]) -> Object {
+        let tags = tags.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        Object::new(key.to_string(), b"data".to_vec(), "text/plain".to_string()).with_tags(tags)
+    }
+
+    #[test]
+    fn test_parse_replication_configuration() {
+        let xml = r#"<ReplicationConfiguration xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+            <Role>arn:aws:iam::000000000000:role/replication</Role>
+            <Rule>
+                <ID>logs</ID>
+                <Priority>2</Priority>
+                <Filter><And><Prefix>logs/</Prefix><Tag><Key>dr</Key><Value>yes</Value></Tag></And></Filter>
+                <Status>Enabled</Status>
+                <Destination><Bucket>arn:aws:s3:::logs-replica</Bucket><StorageClass>STANDARD_IA</StorageClass></Destination>
+                <DeleteMarkerReplication><Status>Disabled</Status></DeleteMarkerReplication>
+            </Rule>
+            <Rule>
+                <Priority>1</Priority>
+                <Filter><Prefix></Prefix></Filter>
+                <Status>Enabled</Status>
+                <Destination><Bucket>arn:aws:s3:::dr</Bucket></Destination>
+                <DeleteMarkerReplication><Status>Enabled</Status></DeleteMarkerReplication>
+            </Rule>
+        </ReplicationConfiguration>"#;
+
+        let config = ReplicationConfiguration::from_xml(xml).unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.rules[0].destination_bucket(), Some("logs-replica"));
+        let round_trip = ReplicationConfiguration::from_xml(&config.to_xml().unwrap()).unwrap();
+        assert_eq!(round_trip, config);
+
+        let rule_id = |object: &Object| config.rule_for(object).and_then(|rule| rule.id.as_deref());
+        assert_eq!(rule_id(&object("logs/a.txt", &[("dr", "yes")])), Some("logs"));
+        assert_eq!(config.rule_for(&object("logs/a.txt", &[])).unwrap().destination_bucket(), Some("dr"));
+        assert_eq!(config.delete_rule_for("logs/a.txt").unwrap().destination_bucket(), Some("dr"));
+
+        let legacy = r#"<ReplicationConfiguration><Role></Role><Rule><Prefix>tmp/</Prefix><Status>Enabled</Status><Destination><Bucket>arn:aws:s3:::dr</Bucket></Destination></Rule></ReplicationConfiguration>"#;
+        let legacy = ReplicationConfiguration::from_xml(legacy).unwrap();
+        assert!(legacy.validate().is_ok());
+        assert!(legacy.delete_rule_for("tmp/a").is_some());
+        assert!(legacy.rule_for(&object("a", &[])).is_none());
+    }
+
+    #[test]
+    fn test_validation_rejects_invalid_rules() {
+        let rule = |body: &str| format!("<ReplicationConfiguration><Role></Role><Rule>{}</Rule></ReplicationConfiguration>", body);
+        let invalid = [
+            "<Status>Enabled</Status><Destination><Bucket>dr</Bucket></Destination>",
+            "<Status>On</Status><Destination><Bucket>arn:aws:s3:::dr</Bucket></Destination>",
+            "<Filter><Prefix>a</Prefix></Filter><Status>Enabled</Status><Destination><Bucket>arn:aws:s3:::dr</Bucket></Destination>",
+            "<Filter><Tag><Key>a</Key><Value>b</Value></Tag></Filter><Status>Enabled</Status><Destination><Bucket>arn:aws:s3:::dr</Bucket></Destination><DeleteMarkerReplication><Status>Enabled</Status></DeleteMarkerReplication>",
+        ];
+        for body in invalid {
+            let config = ReplicationConfiguration::from_xml(&rule(body)).unwrap();
+            assert!(config.validate().is_err(), "{}", body);
+        }
+
+        let duplicate_priorities = r#"<ReplicationConfiguration><Role></Role>
+            <Rule><Priority>1</Priority><Filter></Filter><Status>Enabled</Status><Destination><Bucket>arn:aws:s3:::a</Bucket></Destination><DeleteMarkerReplication><Status>Disabled</Status></DeleteMarkerReplication></Rule>
+            <Rule><Priority>1</Priority><Filter></Filter><Status>Enabled</Status><Destination><Bucket>arn:aws:s3:::b</Bucket></Destination><DeleteMarkerReplication><Status>Disabled</Status></DeleteMarkerReplication></Rule>
+        </ReplicationConfiguration>"#;
+        assert!(ReplicationConfiguration::from_xml(duplicate_priorities).unwrap().validate().is_err());
+    }
+}
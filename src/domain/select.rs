@@ -0,0 +1,512 @@
+use std::io::Read;
+use std::sync::Arc;
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use serde_json::Value;
+use crate::domain::sql::{self, Query, Record, SqlError};
+
+// Records are sent in messages of about this many bytes
+const RECORDS_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Body of a SelectObjectContent request.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename = "SelectObjectContentRequest")]
+pub struct SelectRequest {
+    #[serde(rename = "Expression")]
+    pub expression: String,
+    // Only "SQL"
+    #[serde(rename = "ExpressionType")]
+    pub expression_type: String,
+    #[serde(rename = "RequestProgress", default)]
+    pub request_progress: Option<RequestProgress>,
+    #[serde(rename = "InputSerialization")]
+    pub input_serialization: InputSerialization,
+    #[serde(rename = "OutputSerialization")]
+    pub output_serialization: OutputSerialization,
+    #[serde(rename = "ScanRange", default)]
+    pub scan_range: Option<ScanRange>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct RequestProgress {
+    #[serde(rename = "Enabled", default)]
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct InputSerialization {
+    // "NONE", "GZIP" or "BZIP2"
+    #[serde(rename = "CompressionType", default)]
+    pub compression_type: Option<String>,
+    #[serde(rename = "CSV", default)]
+    pub csv: Option<CsvInput>,
+    #[serde(rename = "JSON", default)]
+    pub json: Option<JsonInput>,
+    #[serde(rename = "Parquet", default)]
+    pub parquet: Option<ParquetInput>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct CsvInput {
+    // "USE", "IGNORE" or "NONE"
+    #[serde(rename = "FileHeaderInfo", default)]
+    pub file_header_info: Option<String>,
+    #[serde(rename = "Comments", default)]
+    pub comments: Option<String>,
+    #[serde(rename = "QuoteEscapeCharacter", default)]
+    pub quote_escape_character: Option<String>,
+    #[serde(rename = "RecordDelimiter", default)]
+    pub record_delimiter: Option<String>,
+    #[serde(rename = "FieldDelimiter", default)]
+    pub field_delimiter: Option<String>,
+    #[serde(rename = "QuoteCharacter", default)]
+    pub quote_character: Option<String>,
+    // Quoted fields may always hold record delimiters here
+    #[serde(rename = "AllowQuotedRecordDelimiter", default)]
+    pub allow_quoted_record_delimiter: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct JsonInput {
+    // "DOCUMENT" or "LINES"; both are read as a sequence of JSON values
+    #[serde(rename = "Type", default)]
+    pub json_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct ParquetInput {}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct OutputSerialization {
+    #[serde(rename = "CSV", default)]
+    pub csv: Option<CsvOutput>,
+    #[serde(rename = "JSON", default)]
+    pub json: Option<JsonOutput>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct CsvOutput {
+    // "ALWAYS" or "ASNEEDED"
+    #[serde(rename = "QuoteFields", default)]
+    pub quote_fields: Option<String>,
+    #[serde(rename = "QuoteEscapeCharacter", default)]
+    pub quote_escape_character: Option<String>,
+    #[serde(rename = "RecordDelimiter", default)]
+    pub record_delimiter: Option<String>,
+    #[serde(rename = "FieldDelimiter", default)]
+    pub field_delimiter: Option<String>,
+    #[serde(rename = "QuoteCharacter", default)]
+    pub quote_character: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct JsonOutput {
+    #[serde(rename = "RecordDelimiter", default)]
+    pub record_delimiter: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct ScanRange {
+    #[serde(rename = "Start", default)]
+    pub start: Option<u64>,
+    #[serde(rename = "End", default)]
+    pub end: Option<u64>,
+}
+
+/// Output of a query: the serialized records and what the Stats message reports.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Selection {
+    pub records: Vec<u8>,
+    // Size of the object once decompressed
+    pub bytes_processed: usize,
+}
+
+impl SelectRequest {
+    pub fn from_xml(xml: &str) -> Result<Self, quick_xml::DeError> {
+        quick_xml::de::from_str(xml)
+    }
+
+    /// Checks what the request asks for can be done, before the object is read.
+    pub fn validate(&self) -> Result<(), SqlError> {
+        if !self.expression_type.eq_ignore_ascii_case("SQL") {
+            return Err(error("InvalidExpressionType", "The ExpressionType is invalid. Only SQL expressions are supported."));
+        }
+        if self.scan_range.is_some() {
+            return Err(error("NotImplemented", "ScanRange is not implemented"));
+        }
+        let input = &self.input_serialization;
+        match input.compression_type.as_deref().map(str::to_uppercase).as_deref() {
+            None | Some("NONE") | Some("GZIP") => {}
+            Some("BZIP2") => return Err(error("NotImplemented", "BZIP2 compression is not implemented")),
+            Some(_) => return Err(error("InvalidCompressionFormat", "The file is not in a supported compression format.")),
+        }
+        if input.parquet.is_some() {
+            return Err(error("NotImplemented", "Parquet input is not implemented"));
+        }
+        if input.csv.is_some() == input.json.is_some() {
+            return Err(error("InvalidDataSource", "Exactly one of CSV, JSON or Parquet input must be specified."));
+        }
+        if let Some(csv) = &input.csv {
+            if !matches!(csv.file_header_info.as_deref().map(str::to_uppercase).as_deref(), None | Some("USE" | "IGNORE" | "NONE")) {
+                return Err(error("InvalidFileHeaderInfo", "The FileHeaderInfo is invalid. Only NONE, USE, and IGNORE are supported."));
+            }
+        }
+        if let Some(json) = &input.json {
+            if !matches!(json.json_type.as_deref().map(str::to_uppercase).as_deref(), None | Some("DOCUMENT" | "LINES")) {
+                return Err(error("InvalidJsonType", "The JsonType is invalid. Only DOCUMENT and LINES are supported."));
+            }
+        }
+        let output = &self.output_serialization;
+        if output.csv.is_some() == output.json.is_some() {
+            return Err(error("InvalidRequestParameter", "Exactly one of CSV or JSON output must be specified."));
+        }
+        Ok(())
+    }
+
+    fn is_progress_enabled(&self) -> bool {
+        self.request_progress.as_ref().is_some_and(|progress| progress.enabled)
+    }
+
+    /// Runs the query over the content of an object.
+    pub fn select(&self, query: &Query, content: &[u8]) -> Result<Selection, SqlError> {
+        let is_gzip = self
+            .input_serialization
+            .compression_type
+            .as_deref()
+            .is_some_and(|compression| compression.eq_ignore_ascii_case("GZIP"));
+        let mut decompressed = Vec::new();
+        let content = if is_gzip {
+            GzDecoder::new(content)
+                .read_to_end(&mut decompressed)
+                .map_err(|_| error("InvalidCompressionFormat", "The file is not in a supported compression format."))?;
+            decompressed.as_slice()
+        } else {
+            content
+        };
+        let text = std::str::from_utf8(content).map_err(|_| error("InvalidTextEncoding", "Invalid encoding type. Only UTF-8 encoding is supported."))?;
+
+        let records = match (&self.input_serialization.csv, &self.input_serialization.json) {
+            (Some(csv), _) => csv_records(text, csv)?,
+            _ => json_records(text, query)?,
+        };
+        let mut writer = Writer::new(&self.output_serialization);
+        if query.is_aggregate() {
+            let mut aggregator = query.aggregator();
+            for record in &records {
+                if query.matches(record)? {
+                    aggregator.add(record)?;
+                }
+            }
+            writer.write(&aggregator.finish());
+        } else {
+            let mut returned = 0;
+            for record in &records {
+                if query.limit.is_some_and(|limit| returned >= limit) {
+                    break;
+                }
+                if query.matches(record)? {
+                    writer.write(&query.project(record)?);
+                    returned += 1;
+                }
+            }
+        }
+        Ok(Selection { records: writer.output, bytes_processed: content.len() })
+    }
+}
+
+impl Selection {
+    /// The response body: Records messages, then Progress when requested, Stats and End.
+    pub fn to_event_stream(&self, request: &SelectRequest, bytes_scanned: usize) -> Vec<u8> {
+        let mut stream = Vec::new();
+        let mut start = 0;
+        while start < self.records.len() {
+            let end = record_boundary(&self.records, start + RECORDS_CHUNK_SIZE);
+            stream.extend(event("Records", Some("application/octet-stream"), &self.records[start..end]));
+            start = end;
+        }
+        let counts = format!(
+            "<BytesScanned>{}</BytesScanned><BytesProcessed>{}</BytesProcessed><BytesReturned>{}</BytesReturned>",
+            bytes_scanned,
+            self.bytes_processed,
+            self.records.len()
+        );
+        if request.is_progress_enabled() {
+            let progress = format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?><Progress>{}</Progress>", counts);
+            stream.extend(event("Progress", Some("text/xml"), progress.as_bytes()));
+        }
+        let stats = format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?><Stats>{}</Stats>", counts);
+        stream.extend(event("Stats", Some("text/xml"), stats.as_bytes()));
+        stream.extend(event("End", None, &[]));
+        stream
+    }
+}
+
+fn error(code: &'static str, message: &str) -> SqlError {
+    SqlError { code, message: message.to_string() }
+}
+
+// The end of the record that reaches past `at`, so that records aren't split across messages
+fn record_boundary(records: &[u8], at: usize) -> usize {
+    if at >= records.len() {
+        return records.len();
+    }
+    records[at..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map(|i| at + i + 1)
+        .unwrap_or(records.len())
+}
+
+/// Encodes a message of the `application/vnd.amazon.eventstream` format: the total and
+/// headers lengths and their CRC32, string headers, the payload, and the message CRC32.
+pub fn event(event_type: &str, content_type: Option<&str>, payload: &[u8]) -> Vec<u8> {
+    let mut headers = Vec::new();
+    let mut header = |name: &str, value: &str| {
+        headers.push(name.len() as u8);
+        headers.extend_from_slice(name.as_bytes());
+        // Value type 7, a string
+        headers.push(7);
+        headers.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        headers.extend_from_slice(value.as_bytes());
+    };
+    header(":event-type", event_type);
+    if let Some(content_type) = content_type {
+        header(":content-type", content_type);
+    }
+    header(":message-type", "event");
+
+    let total_length = 12 + headers.len() + payload.len() + 4;
+    let mut message = Vec::with_capacity(total_length);
+    message.extend_from_slice(&(total_length as u32).to_be_bytes());
+    message.extend_from_slice(&(headers.len() as u32).to_be_bytes());
+    message.extend_from_slice(&crc32fast::hash(&message).to_be_bytes());
+    message.extend_from_slice(&headers);
+    message.extend_from_slice(payload);
+    message.extend_from_slice(&crc32fast::hash(&message).to_be_bytes());
+    message
+}
+
+// The first character of a setting, or its default
+fn character(setting: &Option<String>, default: char) -> char {
+    setting.as_deref().and_then(|s| s.chars().next()).unwrap_or(default)
+}
+
+fn csv_records(text: &str, input: &CsvInput) -> Result<Vec<Record>, SqlError> {
+    let mut rows = parse_csv(text, input)?.into_iter();
+    let header = match input.file_header_info.as_deref().map(str::to_uppercase).as_deref() {
+        Some("USE") => rows.next().map(Arc::new),
+        Some("IGNORE") => {
+            rows.next();
+            None
+        }
+        _ => None,
+    };
+    Ok(rows.map(|fields| Record::Csv { header: header.clone(), fields }).collect())
+}
+
+/// Splits CSV text into the fields of its records, skipping comment lines.
+pub fn parse_csv(text: &str, input: &CsvInput) -> Result<Vec<Vec<String>>, SqlError> {
+    let field_delimiter = character(&input.field_delimiter, ',');
+    let record_delimiter = character(&input.record_delimiter, '\n');
+    let quote = character(&input.quote_character, '"');
+    let escape = character(&input.quote_escape_character, '"');
+    let comments = input.comments.as_deref().and_then(|c| c.chars().next());
+
+    let mut rows = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut at_record_start = true;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if at_record_start && Some(c) == comments {
+            // Skip to the next record
+            for c in chars.by_ref() {
+                if c == record_delimiter {
+                    break;
+                }
+            }
+            continue;
+        }
+        at_record_start = false;
+        if in_quotes {
+            // A doubled quote, or one after the escape character, is part of the field
+            if (c == escape || c == quote) && chars.peek() == Some(&quote) {
+                field.push(quote);
+                chars.next();
+            } else if c == quote {
+                in_quotes = false;
+            } else {
+                field.push(c);
+            }
+        } else if c == quote && field.is_empty() {
+            in_quotes = true;
+        } else if c == field_delimiter {
+            fields.push(std::mem::take(&mut field));
+        } else if c == record_delimiter {
+            // "\r\n" ends records too when they are delimited by "\n"
+            if record_delimiter == '\n' && field.ends_with('\r') {
+                field.pop();
+            }
+            fields.push(std::mem::take(&mut field));
+            rows.push(std::mem::take(&mut fields));
+            at_record_start = true;
+        } else {
+            field.push(c);
+        }
+    }
+    if in_quotes {
+        return Err(error("CSVParsingError", "Encountered an error parsing the CSV file: unterminated quoted field"));
+    }
+    if !at_record_start {
+        if record_delimiter == '\n' && field.ends_with('\r') {
+            field.pop();
+        }
+        fields.push(field);
+        rows.push(fields);
+    }
+    Ok(rows)
+}
+
+fn json_records(text: &str, query: &Query) -> Result<Vec<Record>, SqlError> {
+    let mut records = Vec::new();
+    for value in serde_json::Deserializer::from_str(text).into_iter::<Value>() {
+        let value = value.map_err(|e| error("JSONParsingError", &format!("Encountered an error parsing the JSON file: {}", e)))?;
+        records.extend(query.source_records(value).into_iter().map(Record::Json));
+    }
+    Ok(records)
+}
+
+// Serializes the selected records as CSV or JSON
+struct Writer {
+    output: Vec<u8>,
+    csv: Option<CsvOutput>,
+    record_delimiter: String,
+}
+
+impl Writer {
+    fn new(serialization: &OutputSerialization) -> Self {
+        let record_delimiter = match (&serialization.csv, &serialization.json) {
+            (Some(csv), _) => csv.record_delimiter.clone(),
+            (_, Some(json)) => json.record_delimiter.clone(),
+            _ => None,
+        };
+        Self {
+            output: Vec::new(),
+            csv: serialization.csv.clone(),
+            record_delimiter: record_delimiter.unwrap_or_else(|| "\n".to_string()),
+        }
+    }
+
+    fn write(&mut self, entries: &[(String, Value)]) {
+        let line = match &self.csv {
+            Some(csv) => {
+                let delimiter = character(&csv.field_delimiter, ',');
+                let quote = character(&csv.quote_character, '"');
+                let escape = character(&csv.quote_escape_character, '"');
+                let always = csv.quote_fields.as_deref().is_some_and(|q| q.eq_ignore_ascii_case("ALWAYS"));
+                entries
+                    .iter()
+                    .map(|(_, value)| {
+                        let text = sql::text(value);
+                        let needs_quotes = always
+                            || text.contains(delimiter)
+                            || text.contains(quote)
+                            || text.contains(['\n', '\r'])
+                            || text.contains(self.record_delimiter.as_str());
+                        if needs_quotes {
+                            let escaped = text.replace(quote, &format!("{}{}", escape, quote));
+                            format!("{}{}{}", quote, escaped, quote)
+                        } else {
+                            text
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join(&delimiter.to_string())
+            }
+            // Written by hand to keep the selected order of the names
+            None => {
+                let members: Vec<String> = entries
+                    .iter()
+                    .map(|(name, value)| format!("{}:{}", Value::from(name.as_str()), value))
+                    .collect();
+                format!("{{{}}}", members.join(","))
+            }
+        };
+        self.output.extend_from_slice(line.as_bytes());
+        self.output.extend_from_slice(self.record_delimiter.as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    fn select_request(input: &str, output: &str, expression: &str) -> SelectRequest {
+        let xml = format!(
+            "<SelectObjectContentRequest><Expression>{}</Expression><ExpressionType>SQL</ExpressionType>\
+             <InputSerialization>{}</InputSerialization><OutputSerialization>{}</OutputSerialization>\
+             </SelectObjectContentRequest>",
+            expression, input, output
+        );
+        let request = SelectRequest::from_xml(&xml).unwrap();
+        request.validate().unwrap();
+        request
+    }
+
+    #[test]
+    fn test_select_csv_and_json() {
+        let csv = "# people\nname;age;city\nAlice;31;\"Berlin; DE\"\r\nBob;25;Rome\nCarol;40;Paris\n";
+        let request = select_request(
+            "<CSV><FileHeaderInfo>USE</FileHeaderInfo><FieldDelimiter>;</FieldDelimiter><Comments>#</Comments></CSV>",
+            "<CSV/>",
+            "SELECT s.name, s.city FROM S3Object s WHERE CAST(s.age AS INT) &gt; 30",
+        );
+        let query = Query::parse(&request.expression).unwrap();
+        let selection = request.select(&query, csv.as_bytes()).unwrap();
+        assert_eq!(String::from_utf8(selection.records).unwrap(), "Alice,Berlin; DE\nCarol,Paris\n");
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"{\"id\":2,\"tags\":[\"x\"]}\n{\"id\":1}\n{\"id\":3,\"tags\":[]}\n").unwrap();
+        let content = encoder.finish().unwrap();
+        let request = select_request(
+            "<CompressionType>GZIP</CompressionType><JSON><Type>LINES</Type></JSON>",
+            "<JSON/>",
+            "SELECT s.id, s.tags FROM S3Object s WHERE s.id &lt;&gt; 1 LIMIT 1",
+        );
+        let query = Query::parse(&request.expression).unwrap();
+        let selection = request.select(&query, &content).unwrap();
+        assert_eq!(String::from_utf8(selection.records).unwrap(), "{\"id\":2,\"tags\":[\"x\"]}\n");
+        assert_eq!(selection.bytes_processed, 50);
+
+        let request = select_request("<JSON><Type>DOCUMENT</Type></JSON>", "<CSV/>", "SELECT COUNT(*), AVG(s.id) FROM S3Object[*] s");
+        let query = Query::parse(&request.expression).unwrap();
+        let selection = request.select(&query, b"[{\"id\": 1}, {\"id\": 2}]").unwrap();
+        assert_eq!(String::from_utf8(selection.records).unwrap(), "2,1.5\n");
+    }
+
+    #[test]
+    fn test_event_stream() {
+        let message = event("Records", Some("application/octet-stream"), b"a,b\n");
+        let total = u32::from_be_bytes(message[0..4].try_into().unwrap()) as usize;
+        let headers = u32::from_be_bytes(message[4..8].try_into().unwrap()) as usize;
+        assert_eq!(total, message.len());
+        assert_eq!(u32::from_be_bytes(message[8..12].try_into().unwrap()), crc32fast::hash(&message[0..8]));
+        assert_eq!(&message[12 + headers..total - 4], b"a,b\n");
+        assert_eq!(
+            u32::from_be_bytes(message[total - 4..].try_into().unwrap()),
+            crc32fast::hash(&message[..total - 4])
+        );
+        assert_eq!(message[12] as usize, ":event-type".len());
+
+        let request = SelectRequest { request_progress: Some(RequestProgress { enabled: true }), ..Default::default() };
+        let selection = Selection { records: b"1\n".to_vec(), bytes_processed: 10 };
+        let stream = selection.to_event_stream(&request, 4);
+        let text = String::from_utf8_lossy(&stream);
+        assert!(text.contains("<Progress><BytesScanned>4</BytesScanned><BytesProcessed>10</BytesProcessed><BytesReturned>2</BytesReturned></Progress>"));
+        assert!(text.contains(":event-type\u{7}\u{0}\u{3}End"));
+    }
+}
@@ -0,0 +1,1144 @@
+use std::cmp::Ordering;
+use std::sync::Arc;
+use serde_json::{Map, Number, Value};
+
+// Symbols of the language, longest first so that "<=" isn't read as "<"
+const SYMBOLS: [&str; 19] = [
+    "<=", ">=", "<>", "!=", "=", "<", ">", "*", ",", "(", ")", ".", "[", "]", "+", "-", "/", "%", ";",
+];
+
+// Words that end an expression, so they can't be taken for an alias
+const RESERVED: [&str; 13] = [
+    "SELECT", "FROM", "WHERE", "LIMIT", "AS", "AND", "OR", "NOT", "LIKE", "IS", "IN", "BETWEEN", "ESCAPE",
+];
+
+/// Error of a query, with the S3 error code it is reported with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SqlError {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl SqlError {
+    fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+
+    fn unexpected(token: Option<&Token>) -> Self {
+        match token {
+            Some(token) => Self::new("ParseUnexpectedToken", format!("Unexpected token found {}", token)),
+            None => Self::new("ParseUnexpectedToken", "Unexpected end of the expression"),
+        }
+    }
+}
+
+/// A record of the input: the fields of a CSV line, with the header line's names when it
+/// has one, or a JSON value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Record {
+    Csv { header: Option<Arc<Vec<String>>>, fields: Vec<String> },
+    Json(Value),
+}
+
+impl Record {
+    /// The record as name and value pairs, as `SELECT *` returns it.
+    pub fn entries(&self) -> Vec<(String, Value)> {
+        match self {
+            Record::Csv { header, fields } => fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| {
+                    let name = header.as_ref().and_then(|names| names.get(i).cloned()).unwrap_or_else(|| format!("_{}", i + 1));
+                    (name, Value::from(field.as_str()))
+                })
+                .collect(),
+            Record::Json(Value::Object(object)) => object.iter().map(|(name, value)| (name.clone(), value.clone())).collect(),
+            Record::Json(value) => vec![("_1".to_string(), value.clone())],
+        }
+    }
+
+    fn field(&self, name: &str, quoted: bool) -> Value {
+        match self {
+            Record::Csv { header, fields } => {
+                let position = match name.strip_prefix('_').and_then(|n| n.parse::<usize>().ok()) {
+                    Some(position) if !quoted || header.is_none() => position.checked_sub(1),
+                    _ => header.as_ref().and_then(|names| {
+                        names
+                            .iter()
+                            .position(|n| if quoted { n == name } else { n.eq_ignore_ascii_case(name) })
+                    }),
+                };
+                position
+                    .and_then(|i| fields.get(i))
+                    .map(|field| Value::from(field.as_str()))
+                    .unwrap_or(Value::Null)
+            }
+            Record::Json(value) => member(value, name, quoted),
+        }
+    }
+}
+
+fn member(value: &Value, name: &str, quoted: bool) -> Value {
+    let Value::Object(object) = value else {
+        return Value::Null;
+    };
+    if let Some(value) = object.get(name) {
+        return value.clone();
+    }
+    if quoted {
+        return Value::Null;
+    }
+    object
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.clone())
+        .unwrap_or(Value::Null)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Quoted(String),
+    Str(String),
+    Num(String),
+    Sym(&'static str),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Ident(word) | Token::Num(word) => write!(f, "{}", word),
+            Token::Quoted(word) => write!(f, "\"{}\"", word),
+            Token::Str(text) => write!(f, "'{}'", text),
+            Token::Sym(symbol) => write!(f, "{}", symbol),
+        }
+    }
+}
+
+fn tokenize(sql: &str) -> Result<Vec<Token>, SqlError> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '\'' || c == '"' {
+            // Quotes are escaped by doubling them
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(SqlError::new("ParseExpectedTokenType", "Unterminated quoted text")),
+                    Some(&q) if q == c && chars.get(i + 1) == Some(&c) => {
+                        text.push(c);
+                        i += 2;
+                    }
+                    Some(&q) if q == c => {
+                        i += 1;
+                        break;
+                    }
+                    Some(&other) => {
+                        text.push(other);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push(if c == '\'' { Token::Str(text) } else { Token::Quoted(text) });
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                i += 1;
+                if i < chars.len() && (chars[i] == '+' || chars[i] == '-') {
+                    i += 1;
+                }
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+            tokens.push(Token::Num(chars[start..i].iter().collect()));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| rest.starts_with(**symbol))
+                .ok_or_else(|| SqlError::new("ParseInvalidTypeParam", format!("Invalid character {}", c)))?;
+            tokens.push(Token::Sym(symbol));
+            i += symbol.len();
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathStep {
+    // Name, and whether it was quoted and so is matched case-sensitively
+    Field(String, bool),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    And,
+    Or,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aggregate {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Literal(Value),
+    Column(Vec<PathStep>),
+    Negate(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Like { expr: Box<Expr>, pattern: Box<Expr>, escape: Option<char>, negated: bool },
+    IsNull { expr: Box<Expr>, negated: bool },
+    Between { expr: Box<Expr>, low: Box<Expr>, high: Box<Expr>, negated: bool },
+    In { expr: Box<Expr>, list: Vec<Expr>, negated: bool },
+    Cast(Box<Expr>, String),
+    Function(String, Vec<Expr>),
+    // `None` counts every record, as COUNT(*) does
+    Aggregate(Aggregate, Option<Box<Expr>>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceStep {
+    // `[*]`, the elements of an array
+    Wildcard,
+    Field(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SelectItem {
+    expr: Expr,
+    name: Option<String>,
+}
+
+/// A parsed `SELECT ... FROM S3Object [alias] [WHERE ...] [LIMIT n]` query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    // `None` for `SELECT *`
+    items: Option<Vec<SelectItem>>,
+    source: Vec<SourceStep>,
+    alias: Option<String>,
+    condition: Option<Expr>,
+    pub limit: Option<usize>,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), SqlError> {
+        if self.keyword(keyword) {
+            return Ok(());
+        }
+        Err(SqlError::new(
+            "ParseExpectedKeyword",
+            format!("Expected keyword {} but found {}", keyword, self.found()),
+        ))
+    }
+
+    fn symbol(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Sym(s)) if *s == symbol);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), SqlError> {
+        if self.symbol(symbol) {
+            return Ok(());
+        }
+        Err(SqlError::new("ParseExpectedToken", format!("Expected {} but found {}", symbol, self.found())))
+    }
+
+    fn found(&self) -> String {
+        self.peek().map(Token::to_string).unwrap_or_else(|| "the end of the expression".to_string())
+    }
+
+    // An identifier that isn't a reserved word, as the name of a column or alias
+    fn name(&mut self) -> Option<(String, bool)> {
+        match self.peek()? {
+            Token::Ident(word) if !RESERVED.iter().any(|reserved| word.eq_ignore_ascii_case(reserved)) => {
+                let word = word.clone();
+                self.pos += 1;
+                Some((word, false))
+            }
+            Token::Quoted(word) => {
+                let word = word.clone();
+                self.pos += 1;
+                Some((word, true))
+            }
+            _ => None,
+        }
+    }
+
+    fn query(&mut self) -> Result<Query, SqlError> {
+        self.expect_keyword("SELECT")?;
+        let items = if self.symbol("*") {
+            None
+        } else {
+            let mut items = vec![self.select_item()?];
+            while self.symbol(",") {
+                items.push(self.select_item()?);
+            }
+            Some(items)
+        };
+
+        self.expect_keyword("FROM")?;
+        match self.next() {
+            Some(Token::Ident(word)) if word.eq_ignore_ascii_case("S3Object") => {}
+            token => return Err(SqlError::unexpected(token.as_ref())),
+        }
+        let mut source = Vec::new();
+        loop {
+            if self.symbol("[") {
+                self.expect_symbol("*")?;
+                self.expect_symbol("]")?;
+                source.push(SourceStep::Wildcard);
+            } else if self.symbol(".") {
+                let (name, _) = self.name().ok_or_else(|| SqlError::unexpected(self.peek()))?;
+                source.push(SourceStep::Field(name));
+            } else {
+                break;
+            }
+        }
+        self.keyword("AS");
+        let alias = self.name().map(|(alias, _)| alias);
+
+        let condition = if self.keyword("WHERE") { Some(self.expr()?) } else { None };
+        let limit = if self.keyword("LIMIT") {
+            match self.next() {
+                Some(Token::Num(number)) => Some(number.parse().map_err(|_| {
+                    SqlError::new("InvalidLimit", format!("Invalid LIMIT {}", number))
+                })?),
+                token => return Err(SqlError::unexpected(token.as_ref())),
+            }
+        } else {
+            None
+        };
+        self.symbol(";");
+        if self.peek().is_some() {
+            return Err(SqlError::unexpected(self.peek()));
+        }
+        Ok(Query { items, source, alias, condition, limit })
+    }
+
+    fn select_item(&mut self) -> Result<SelectItem, SqlError> {
+        let expr = self.expr()?;
+        let name = if self.keyword("AS") {
+            Some(self.name().ok_or_else(|| SqlError::unexpected(self.peek()))?.0)
+        } else {
+            self.name().map(|(name, _)| name)
+        };
+        Ok(SelectItem { expr, name })
+    }
+
+    fn expr(&mut self) -> Result<Expr, SqlError> {
+        let mut left = self.and()?;
+        while self.keyword("OR") {
+            left = Expr::Binary(BinaryOp::Or, Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, SqlError> {
+        let mut left = self.not()?;
+        while self.keyword("AND") {
+            left = Expr::Binary(BinaryOp::And, Box::new(left), Box::new(self.not()?));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Expr, SqlError> {
+        if self.keyword("NOT") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, SqlError> {
+        let left = self.additive()?;
+        if self.keyword("IS") {
+            let negated = self.keyword("NOT");
+            self.expect_keyword("NULL")?;
+            return Ok(Expr::IsNull { expr: Box::new(left), negated });
+        }
+        let negated = self.keyword("NOT");
+        if self.keyword("LIKE") {
+            let pattern = self.additive()?;
+            let escape = if self.keyword("ESCAPE") {
+                match self.next() {
+                    Some(Token::Str(escape)) if escape.chars().count() == 1 => escape.chars().next(),
+                    token => return Err(SqlError::unexpected(token.as_ref())),
+                }
+            } else {
+                None
+            };
+            return Ok(Expr::Like { expr: Box::new(left), pattern: Box::new(pattern), escape, negated });
+        }
+        if self.keyword("BETWEEN") {
+            let low = self.additive()?;
+            self.expect_keyword("AND")?;
+            let high = self.additive()?;
+            return Ok(Expr::Between { expr: Box::new(left), low: Box::new(low), high: Box::new(high), negated });
+        }
+        if self.keyword("IN") {
+            self.expect_symbol("(")?;
+            let mut list = vec![self.expr()?];
+            while self.symbol(",") {
+                list.push(self.expr()?);
+            }
+            self.expect_symbol(")")?;
+            return Ok(Expr::In { expr: Box::new(left), list, negated });
+        }
+        if negated {
+            return Err(SqlError::unexpected(self.peek()));
+        }
+
+        let operators = [
+            ("=", BinaryOp::Eq),
+            ("!=", BinaryOp::Ne),
+            ("<>", BinaryOp::Ne),
+            ("<=", BinaryOp::Le),
+            (">=", BinaryOp::Ge),
+            ("<", BinaryOp::Lt),
+            (">", BinaryOp::Gt),
+        ];
+        for (symbol, op) in operators {
+            if self.symbol(symbol) {
+                return Ok(Expr::Binary(op, Box::new(left), Box::new(self.additive()?)));
+            }
+        }
+        Ok(left)
+    }
+
+    fn additive(&mut self) -> Result<Expr, SqlError> {
+        let mut left = self.multiplicative()?;
+        loop {
+            let op = if self.symbol("+") {
+                BinaryOp::Add
+            } else if self.symbol("-") {
+                BinaryOp::Sub
+            } else {
+                return Ok(left);
+            };
+            left = Expr::Binary(op, Box::new(left), Box::new(self.multiplicative()?));
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, SqlError> {
+        let mut left = self.unary()?;
+        loop {
+            let op = if self.symbol("*") {
+                BinaryOp::Mul
+            } else if self.symbol("/") {
+                BinaryOp::Div
+            } else if self.symbol("%") {
+                BinaryOp::Mod
+            } else {
+                return Ok(left);
+            };
+            left = Expr::Binary(op, Box::new(left), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, SqlError> {
+        if self.symbol("-") {
+            return Ok(Expr::Negate(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, SqlError> {
+        if self.symbol("(") {
+            let expr = self.expr()?;
+            self.expect_symbol(")")?;
+            return Ok(expr);
+        }
+        match self.peek().cloned() {
+            Some(Token::Str(text)) => {
+                self.pos += 1;
+                Ok(Expr::Literal(Value::from(text)))
+            }
+            Some(Token::Num(number)) => {
+                self.pos += 1;
+                parse_number(&number)
+                    .map(Expr::Literal)
+                    .ok_or_else(|| SqlError::new("ParseInvalidTypeParam", format!("Invalid number {}", number)))
+            }
+            Some(Token::Ident(word)) if self.tokens.get(self.pos + 1) == Some(&Token::Sym("(")) => {
+                self.pos += 2;
+                self.function(&word.to_uppercase())
+            }
+            Some(Token::Ident(word)) if word.eq_ignore_ascii_case("TRUE") || word.eq_ignore_ascii_case("FALSE") => {
+                self.pos += 1;
+                Ok(Expr::Literal(Value::from(word.eq_ignore_ascii_case("TRUE"))))
+            }
+            Some(Token::Ident(word)) if word.eq_ignore_ascii_case("NULL") || word.eq_ignore_ascii_case("MISSING") => {
+                self.pos += 1;
+                Ok(Expr::Literal(Value::Null))
+            }
+            Some(Token::Ident(_)) | Some(Token::Quoted(_)) => self.path(),
+            token => Err(SqlError::unexpected(token.as_ref())),
+        }
+    }
+
+    fn path(&mut self) -> Result<Expr, SqlError> {
+        let (name, quoted) = self.name().ok_or_else(|| SqlError::unexpected(self.peek()))?;
+        let mut steps = vec![PathStep::Field(name, quoted)];
+        loop {
+            if self.symbol(".") {
+                let (name, quoted) = self.name().ok_or_else(|| SqlError::unexpected(self.peek()))?;
+                steps.push(PathStep::Field(name, quoted));
+            } else if self.symbol("[") {
+                match self.next() {
+                    Some(Token::Num(index)) => {
+                        let index = index.parse().map_err(|_| SqlError::new("InvalidColumnIndex", format!("Invalid index {}", index)))?;
+                        steps.push(PathStep::Index(index));
+                    }
+                    token => return Err(SqlError::unexpected(token.as_ref())),
+                }
+                self.expect_symbol("]")?;
+            } else {
+                return Ok(Expr::Column(steps));
+            }
+        }
+    }
+
+    // After the opening parenthesis
+    fn function(&mut self, name: &str) -> Result<Expr, SqlError> {
+        let aggregate = match name {
+            "COUNT" => Some(Aggregate::Count),
+            "SUM" => Some(Aggregate::Sum),
+            "AVG" => Some(Aggregate::Avg),
+            "MIN" => Some(Aggregate::Min),
+            "MAX" => Some(Aggregate::Max),
+            _ => None,
+        };
+        if let Some(aggregate) = aggregate {
+            let argument = if aggregate == Aggregate::Count && self.symbol("*") {
+                None
+            } else {
+                Some(Box::new(self.expr()?))
+            };
+            self.expect_symbol(")")?;
+            return Ok(Expr::Aggregate(aggregate, argument));
+        }
+        if name == "CAST" {
+            let expr = self.expr()?;
+            self.expect_keyword("AS")?;
+            let data_type = match self.next() {
+                Some(Token::Ident(data_type)) => data_type.to_uppercase(),
+                token => return Err(SqlError::unexpected(token.as_ref())),
+            };
+            if cast(&Value::Null, &data_type).is_err() {
+                return Err(SqlError::new("ParseUnsupportedSyntax", format!("Unsupported type {}", data_type)));
+            }
+            self.expect_symbol(")")?;
+            return Ok(Expr::Cast(Box::new(expr), data_type));
+        }
+        if !matches!(name, "LOWER" | "UPPER" | "CHAR_LENGTH" | "CHARACTER_LENGTH" | "TRIM" | "COALESCE") {
+            return Err(SqlError::new("UnsupportedFunction", format!("Function {} is not supported", name)));
+        }
+        let mut arguments = Vec::new();
+        if !self.symbol(")") {
+            arguments.push(self.expr()?);
+            while self.symbol(",") {
+                arguments.push(self.expr()?);
+            }
+            self.expect_symbol(")")?;
+        }
+        if name != "COALESCE" && arguments.len() != 1 {
+            return Err(SqlError::new("IncorrectSqlFunctionArgumentType", format!("{} takes one argument", name)));
+        }
+        Ok(Expr::Function(name.to_string(), arguments))
+    }
+}
+
+impl Query {
+    pub fn parse(sql: &str) -> Result<Self, SqlError> {
+        let mut parser = Parser { tokens: tokenize(sql)?, pos: 0 };
+        let query = parser.query()?;
+        if let Some(condition) = &query.condition {
+            if condition.has_aggregate() {
+                return Err(SqlError::new("InvalidAggregation", "Aggregate functions are not allowed in WHERE"));
+            }
+        }
+        if let Some(items) = &query.items {
+            let aggregates = items.iter().filter(|item| matches!(item.expr, Expr::Aggregate(..))).count();
+            if items.iter().any(|item| !matches!(item.expr, Expr::Aggregate(..)) && item.expr.has_aggregate())
+                || (aggregates > 0 && aggregates < items.len())
+            {
+                return Err(SqlError::new(
+                    "UnsupportedSqlStructure",
+                    "Aggregate functions can't be combined with other expressions",
+                ));
+            }
+        }
+        Ok(query)
+    }
+
+    pub fn is_aggregate(&self) -> bool {
+        self.items.as_ref().is_some_and(|items| items.iter().any(|item| item.expr.has_aggregate()))
+    }
+
+    /// The records a JSON value of the input holds, through the `FROM S3Object[*].path` steps.
+    pub fn source_records(&self, value: Value) -> Vec<Value> {
+        let mut values = vec![value];
+        for step in &self.source {
+            values = values
+                .into_iter()
+                .flat_map(|value| match (step, value) {
+                    (SourceStep::Wildcard, Value::Array(elements)) => elements,
+                    (SourceStep::Wildcard, _) => Vec::new(),
+                    (SourceStep::Field(name), value) => vec![member(&value, name, false)],
+                })
+                .collect();
+        }
+        values
+    }
+
+    pub fn matches(&self, record: &Record) -> Result<bool, SqlError> {
+        match &self.condition {
+            Some(condition) => Ok(condition.eval(record, self.alias.as_deref())? == Value::Bool(true)),
+            None => Ok(true),
+        }
+    }
+
+    /// The selected names and values of a record.
+    pub fn project(&self, record: &Record) -> Result<Vec<(String, Value)>, SqlError> {
+        let Some(items) = &self.items else {
+            return Ok(record.entries());
+        };
+        items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| Ok((self.item_name(i, item), item.expr.eval(record, self.alias.as_deref())?)))
+            .collect()
+    }
+
+    pub fn aggregator(&self) -> Aggregator<'_> {
+        let items = self.items.as_deref().unwrap_or_default();
+        Aggregator { query: self, accumulators: items.iter().map(|_| Accumulator::default()).collect() }
+    }
+
+    // Columns are named after their alias, their last path step, or their position
+    fn item_name(&self, i: usize, item: &SelectItem) -> String {
+        if let Some(name) = &item.name {
+            return name.clone();
+        }
+        match &item.expr {
+            Expr::Column(steps) => match steps.last() {
+                Some(PathStep::Field(name, _)) if steps.len() > 1 || Some(name.as_str()) != self.alias.as_deref() => name.clone(),
+                _ => format!("_{}", i + 1),
+            },
+            _ => format!("_{}", i + 1),
+        }
+    }
+}
+
+/// Running aggregates of the records of an aggregate query.
+pub struct Aggregator<'a> {
+    query: &'a Query,
+    accumulators: Vec<Accumulator>,
+}
+
+#[derive(Default)]
+struct Accumulator {
+    count: u64,
+    int_sum: Option<i64>,
+    sum: f64,
+    all_ints: bool,
+    extreme: Option<Value>,
+}
+
+impl Aggregator<'_> {
+    pub fn add(&mut self, record: &Record) -> Result<(), SqlError> {
+        let items = self.query.items.as_deref().unwrap_or_default();
+        for (item, accumulator) in items.iter().zip(self.accumulators.iter_mut()) {
+            let Expr::Aggregate(aggregate, argument) = &item.expr else {
+                continue;
+            };
+            let value = match argument {
+                Some(argument) => argument.eval(record, self.query.alias.as_deref())?,
+                None => Value::Bool(true),
+            };
+            if value.is_null() {
+                continue;
+            }
+            match aggregate {
+                Aggregate::Count => {}
+                Aggregate::Sum | Aggregate::Avg => {
+                    let number = to_number(&value).ok_or_else(|| {
+                        SqlError::new("EvaluatorInvalidArguments", format!("Cannot sum the value {}", value))
+                    })?;
+                    if accumulator.count == 0 {
+                        accumulator.all_ints = true;
+                        accumulator.int_sum = Some(0);
+                    }
+                    accumulator.sum += number.as_f64();
+                    match number {
+                        Num::Int(int) if accumulator.all_ints => {
+                            accumulator.int_sum = accumulator.int_sum.and_then(|sum| sum.checked_add(int));
+                        }
+                        _ => accumulator.all_ints = false,
+                    }
+                }
+                Aggregate::Min | Aggregate::Max => {
+                    let replace = match &accumulator.extreme {
+                        None => true,
+                        Some(current) => {
+                            let ordering = compare(&value, current);
+                            if *aggregate == Aggregate::Min {
+                                ordering == Some(Ordering::Less)
+                            } else {
+                                ordering == Some(Ordering::Greater)
+                            }
+                        }
+                    };
+                    if replace {
+                        accumulator.extreme = Some(value);
+                    }
+                }
+            }
+            accumulator.count += 1;
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Vec<(String, Value)> {
+        let items = self.query.items.as_deref().unwrap_or_default();
+        items
+            .iter()
+            .zip(self.accumulators)
+            .enumerate()
+            .map(|(i, (item, accumulator))| {
+                let value = match &item.expr {
+                    Expr::Aggregate(Aggregate::Count, _) => Value::from(accumulator.count),
+                    _ if accumulator.count == 0 => Value::Null,
+                    Expr::Aggregate(Aggregate::Sum, _) => match accumulator.int_sum.filter(|_| accumulator.all_ints) {
+                        Some(sum) => Value::from(sum),
+                        None => float(accumulator.sum),
+                    },
+                    Expr::Aggregate(Aggregate::Avg, _) => float(accumulator.sum / accumulator.count as f64),
+                    _ => accumulator.extreme.unwrap_or(Value::Null),
+                };
+                (self.query.item_name(i, item), value)
+            })
+            .collect()
+    }
+}
+
+impl Expr {
+    fn has_aggregate(&self) -> bool {
+        match self {
+            Expr::Aggregate(..) => true,
+            Expr::Literal(_) | Expr::Column(_) => false,
+            Expr::Negate(expr) | Expr::Not(expr) | Expr::Cast(expr, _) | Expr::IsNull { expr, .. } => expr.has_aggregate(),
+            Expr::Binary(_, left, right) => left.has_aggregate() || right.has_aggregate(),
+            Expr::Like { expr, pattern, .. } => expr.has_aggregate() || pattern.has_aggregate(),
+            Expr::Between { expr, low, high, .. } => expr.has_aggregate() || low.has_aggregate() || high.has_aggregate(),
+            Expr::In { expr, list, .. } => expr.has_aggregate() || list.iter().any(Expr::has_aggregate),
+            Expr::Function(_, arguments) => arguments.iter().any(Expr::has_aggregate),
+        }
+    }
+
+    fn eval(&self, record: &Record, alias: Option<&str>) -> Result<Value, SqlError> {
+        let eval = |expr: &Expr| expr.eval(record, alias);
+        Ok(match self {
+            Expr::Literal(value) => value.clone(),
+            Expr::Column(steps) => column(record, steps, alias),
+            Expr::Negate(expr) => match to_number(&eval(expr)?) {
+                Some(Num::Int(int)) => Value::from(-int),
+                Some(Num::Float(value)) => float(-value),
+                None => Value::Null,
+            },
+            Expr::Not(expr) => match eval(expr)? {
+                Value::Bool(value) => Value::Bool(!value),
+                _ => Value::Null,
+            },
+            Expr::Binary(BinaryOp::And, left, right) => match (eval(left)?, eval(right)?) {
+                (Value::Bool(false), _) | (_, Value::Bool(false)) => Value::Bool(false),
+                (Value::Bool(true), Value::Bool(true)) => Value::Bool(true),
+                _ => Value::Null,
+            },
+            Expr::Binary(BinaryOp::Or, left, right) => match (eval(left)?, eval(right)?) {
+                (Value::Bool(true), _) | (_, Value::Bool(true)) => Value::Bool(true),
+                (Value::Bool(false), Value::Bool(false)) => Value::Bool(false),
+                _ => Value::Null,
+            },
+            Expr::Binary(op, left, right) => binary(*op, &eval(left)?, &eval(right)?)?,
+            Expr::Like { expr, pattern, escape, negated } => match (eval(expr)?, eval(pattern)?) {
+                (Value::Null, _) | (_, Value::Null) => Value::Null,
+                (value, pattern) => {
+                    let pattern = like_pattern(&text(&pattern), *escape);
+                    let chars: Vec<char> = text(&value).chars().collect();
+                    Value::Bool(like(&chars, &pattern) != *negated)
+                }
+            },
+            Expr::IsNull { expr, negated } => Value::Bool(eval(expr)?.is_null() != *negated),
+            Expr::Between { expr, low, high, negated } => {
+                let value = eval(expr)?;
+                match (compare(&value, &eval(low)?), compare(&value, &eval(high)?)) {
+                    (Some(low), Some(high)) => Value::Bool((low != Ordering::Less && high != Ordering::Greater) != *negated),
+                    _ => Value::Null,
+                }
+            }
+            Expr::In { expr, list, negated } => {
+                let value = eval(expr)?;
+                if value.is_null() {
+                    return Ok(Value::Null);
+                }
+                let mut found = false;
+                for candidate in list {
+                    found |= compare(&value, &eval(candidate)?) == Some(Ordering::Equal);
+                }
+                Value::Bool(found != *negated)
+            }
+            Expr::Cast(expr, data_type) => cast(&eval(expr)?, data_type)?,
+            Expr::Function(name, arguments) => {
+                let values = arguments.iter().map(eval).collect::<Result<Vec<_>, _>>()?;
+                function(name, values)
+            }
+            Expr::Aggregate(..) => {
+                return Err(SqlError::new("InvalidAggregation", "Aggregate functions can only be selected"));
+            }
+        })
+    }
+}
+
+// The value at a path, which may start with the alias of S3Object
+fn column(record: &Record, steps: &[PathStep], alias: Option<&str>) -> Value {
+    let is_alias = |step: &PathStep| {
+        matches!(step, PathStep::Field(name, _) if alias.is_some_and(|alias| alias.eq_ignore_ascii_case(name)))
+    };
+    let steps = match steps {
+        [first] if is_alias(first) => return Value::Object(record.entries().into_iter().collect::<Map<_, _>>()),
+        [first, rest @ ..] if is_alias(first) => rest,
+        steps => steps,
+    };
+    let mut value = match steps.first() {
+        Some(PathStep::Field(name, quoted)) => record.field(name, *quoted),
+        _ => return Value::Null,
+    };
+    for step in &steps[1..] {
+        value = match (step, &value) {
+            (PathStep::Field(name, quoted), _) => member(&value, name, *quoted),
+            (PathStep::Index(index), Value::Array(elements)) => elements.get(*index).cloned().unwrap_or(Value::Null),
+            _ => Value::Null,
+        };
+    }
+    value
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Num {
+    Int(i64),
+    Float(f64),
+}
+
+impl Num {
+    fn as_f64(self) -> f64 {
+        match self {
+            Num::Int(int) => int as f64,
+            Num::Float(value) => value,
+        }
+    }
+}
+
+// Numbers, and text holding one, as CSV fields always are
+fn to_number(value: &Value) -> Option<Num> {
+    match value {
+        Value::Number(number) => number.as_i64().map(Num::Int).or_else(|| number.as_f64().map(Num::Float)),
+        Value::String(text) => {
+            let text = text.trim();
+            text.parse().map(Num::Int).ok().or_else(|| text.parse().ok().map(Num::Float))
+        }
+        _ => None,
+    }
+}
+
+fn parse_number(text: &str) -> Option<Value> {
+    match text.parse::<i64>() {
+        Ok(int) => Some(Value::from(int)),
+        Err(_) => text.parse::<f64>().ok().map(float),
+    }
+}
+
+fn float(value: f64) -> Value {
+    Number::from_f64(value).map(Value::Number).unwrap_or(Value::Null)
+}
+
+/// Orders two values: numerically when either is a number, as text when both are text.
+fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Null, _) | (_, Value::Null) => None,
+        (Value::Bool(left), Value::Bool(right)) => Some(left.cmp(right)),
+        (Value::String(left), Value::String(right)) => Some(left.cmp(right)),
+        (Value::Number(_), _) | (_, Value::Number(_)) => match (to_number(left)?, to_number(right)?) {
+            (Num::Int(left), Num::Int(right)) => Some(left.cmp(&right)),
+            (left, right) => left.as_f64().partial_cmp(&right.as_f64()),
+        },
+        (left, right) => (left == right).then_some(Ordering::Equal),
+    }
+}
+
+fn binary(op: BinaryOp, left: &Value, right: &Value) -> Result<Value, SqlError> {
+    let comparison = |accept: fn(Ordering) -> bool| match compare(left, right) {
+        Some(ordering) => Value::Bool(accept(ordering)),
+        None => Value::Null,
+    };
+    Ok(match op {
+        BinaryOp::Eq => comparison(|o| o == Ordering::Equal),
+        BinaryOp::Ne => comparison(|o| o != Ordering::Equal),
+        BinaryOp::Lt => comparison(|o| o == Ordering::Less),
+        BinaryOp::Le => comparison(|o| o != Ordering::Greater),
+        BinaryOp::Gt => comparison(|o| o == Ordering::Greater),
+        BinaryOp::Ge => comparison(|o| o != Ordering::Less),
+        BinaryOp::And | BinaryOp::Or => Value::Null,
+        _ => {
+            let (Some(a), Some(b)) = (to_number(left), to_number(right)) else {
+                return Ok(Value::Null);
+            };
+            if let (Num::Int(a), Num::Int(b)) = (a, b) {
+                let result = match op {
+                    BinaryOp::Add => a.checked_add(b),
+                    BinaryOp::Sub => a.checked_sub(b),
+                    BinaryOp::Mul => a.checked_mul(b),
+                    BinaryOp::Div | BinaryOp::Mod if b == 0 => {
+                        return Err(SqlError::new("DivisionByZero", "Division by zero"));
+                    }
+                    BinaryOp::Div => a.checked_div(b),
+                    _ => a.checked_rem(b),
+                };
+                return Ok(result.map(Value::from).unwrap_or(Value::Null));
+            }
+            let (a, b) = (a.as_f64(), b.as_f64());
+            float(match op {
+                BinaryOp::Add => a + b,
+                BinaryOp::Sub => a - b,
+                BinaryOp::Mul => a * b,
+                BinaryOp::Div => a / b,
+                _ => a % b,
+            })
+        }
+    })
+}
+
+fn cast(value: &Value, data_type: &str) -> Result<Value, SqlError> {
+    let failed = || SqlError::new("CastFailed", format!("Attempt to convert from one data type to another using CAST failed: {} to {}", value, data_type));
+    let supported = matches!(
+        data_type,
+        "INT" | "INTEGER" | "FLOAT" | "REAL" | "DOUBLE" | "DECIMAL" | "NUMERIC" | "STRING" | "VARCHAR" | "CHAR" | "BOOL" | "BOOLEAN"
+    );
+    if !supported {
+        return Err(failed());
+    }
+    if value.is_null() {
+        return Ok(Value::Null);
+    }
+    Ok(match data_type {
+        "INT" | "INTEGER" => match to_number(value).ok_or_else(failed)? {
+            Num::Int(int) => Value::from(int),
+            Num::Float(value) => Value::from(value.trunc() as i64),
+        },
+        "FLOAT" | "REAL" | "DOUBLE" | "DECIMAL" | "NUMERIC" => match to_number(value).ok_or_else(failed)? {
+            // Decimal integers stay integers
+            Num::Int(int) if matches!(data_type, "DECIMAL" | "NUMERIC") => Value::from(int),
+            number => float(number.as_f64()),
+        },
+        "STRING" | "VARCHAR" | "CHAR" => Value::from(text(value)),
+        _ => match value {
+            Value::Bool(value) => Value::Bool(*value),
+            Value::String(text) if text.eq_ignore_ascii_case("true") => Value::Bool(true),
+            Value::String(text) if text.eq_ignore_ascii_case("false") => Value::Bool(false),
+            _ => return Err(failed()),
+        },
+    })
+}
+
+fn function(name: &str, mut values: Vec<Value>) -> Value {
+    if name == "COALESCE" {
+        return values.into_iter().find(|value| !value.is_null()).unwrap_or(Value::Null);
+    }
+    let value = values.remove(0);
+    if value.is_null() {
+        return Value::Null;
+    }
+    let text = text(&value);
+    match name {
+        "LOWER" => Value::from(text.to_lowercase()),
+        "UPPER" => Value::from(text.to_uppercase()),
+        "TRIM" => Value::from(text.trim()),
+        _ => Value::from(text.chars().count()),
+    }
+}
+
+/// A value as text, as CSV output writes it.
+pub fn text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum LikeToken {
+    Char(char),
+    // `_`
+    One,
+    // `%`
+    Any,
+}
+
+fn like_pattern(pattern: &str, escape: Option<char>) -> Vec<LikeToken> {
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            c if Some(c) == escape => LikeToken::Char(chars.next().unwrap_or(c)),
+            '%' => LikeToken::Any,
+            '_' => LikeToken::One,
+            c => LikeToken::Char(c),
+        });
+    }
+    tokens
+}
+
+fn like(text: &[char], pattern: &[LikeToken]) -> bool {
+    // matched[j]: whether the text so far matches the first j tokens
+    let mut matched = vec![false; pattern.len() + 1];
+    matched[0] = true;
+    for j in 1..=pattern.len() {
+        matched[j] = matched[j - 1] && pattern[j - 1] == LikeToken::Any;
+    }
+    for c in text {
+        let mut next = vec![false; pattern.len() + 1];
+        for j in 1..=pattern.len() {
+            next[j] = match &pattern[j - 1] {
+                LikeToken::Any => next[j - 1] || matched[j],
+                LikeToken::One => matched[j - 1],
+                LikeToken::Char(expected) => matched[j - 1] && expected == c,
+            };
+        }
+        matched = next;
+    }
+    matched[pattern.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn csv(fields: &[&str]) -> Record {
+        let header = Arc::new(vec!["name".to_string(), "age".to_string(), "city".to_string()]);
+        Record::Csv { header: Some(header), fields: fields.iter().map(|f| f.to_string()).collect() }
+    }
+
+    #[test]
+    fn test_filter_and_project() {
+        let query = Query::parse(
+            "SELECT s.name, CAST(s.age AS INT) + 1 AS next_age FROM S3Object s WHERE s.age >= 30 AND (s.city LIKE 'Ber%' OR s.name = 'Bob') LIMIT 5",
+        )
+        .unwrap();
+        assert_eq!(query.limit, Some(5));
+        let alice = csv(&["Alice", "31", "Berlin"]);
+        assert!(query.matches(&alice).unwrap());
+        assert_eq!(
+            query.project(&alice).unwrap(),
+            vec![("name".to_string(), json!("Alice")), ("next_age".to_string(), json!(32))]
+        );
+        assert!(!query.matches(&csv(&["Carol", "31", "Paris"])).unwrap());
+        assert!(query.matches(&csv(&["Bob", "45", "Paris"])).unwrap());
+        assert!(!query.matches(&csv(&["Dan", "29", "Berlin"])).unwrap());
+
+        let positional = Query::parse("select _1, _3 from s3object where _2 between 30 and 40").unwrap();
+        assert_eq!(positional.project(&alice).unwrap()[1], ("_3".to_string(), json!("Berlin")));
+
+        let json = Record::Json(json!({"user": {"Name": "Eve", "tags": ["a", "b"]}, "score": 7.5}));
+        let query = Query::parse("SELECT s.user.name, s.user.tags[1] FROM S3Object[*] s WHERE s.score > 7 AND s.missing IS NULL").unwrap();
+        assert!(query.matches(&json).unwrap());
+        assert_eq!(query.project(&json).unwrap(), vec![("name".to_string(), json!("Eve")), ("_2".to_string(), json!("b"))]);
+        assert_eq!(query.source_records(json!([1, 2])), vec![json!(1), json!(2)]);
+    }
+
+    #[test]
+    fn test_aggregates_and_errors() {
+        let query = Query::parse("SELECT COUNT(*), SUM(s.age), AVG(s.age), MAX(s.city) FROM S3Object s WHERE s.name <> 'Bob'").unwrap();
+        assert!(query.is_aggregate());
+        let mut aggregator = query.aggregator();
+        for record in [csv(&["Alice", "30", "Berlin"]), csv(&["Bob", "40", "Rome"]), csv(&["Carol", "35", "Paris"])] {
+            if query.matches(&record).unwrap() {
+                aggregator.add(&record).unwrap();
+            }
+        }
+        let values: Vec<Value> = aggregator.finish().into_iter().map(|(_, value)| value).collect();
+        assert_eq!(values, vec![json!(2), json!(65), json!(32.5), json!("Paris")]);
+
+        assert_eq!(Query::parse("SELECT * FROM table").unwrap_err().code, "ParseUnexpectedToken");
+        assert_eq!(Query::parse("SELECT s.a FROM S3Object s WHERE").unwrap_err().code, "ParseUnexpectedToken");
+        assert_eq!(Query::parse("SELECT SHA(s.a) FROM S3Object s").unwrap_err().code, "UnsupportedFunction");
+        assert_eq!(Query::parse("SELECT s.a, COUNT(*) FROM S3Object s").unwrap_err().code, "UnsupportedSqlStructure");
+        let cast = Query::parse("SELECT CAST(s.name AS INT) FROM S3Object s").unwrap();
+        assert_eq!(cast.project(&csv(&["Alice", "30", "Berlin"])).unwrap_err().code, "CastFailed");
+        assert!(like(&"a_c%".chars().collect::<Vec<_>>(), &like_pattern("a!_c!%", Some('!'))));
+        assert!(!like(&"abc".chars().collect::<Vec<_>>(), &like_pattern("a_", None)));
+    }
+}
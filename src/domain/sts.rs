@@ -0,0 +1,150 @@
+use base64::Engine;
+use chrono::Duration;
+use serde_json::Value;
+use crate::domain::identity::Identity;
+use crate::domain::sigv4::sha256_hex;
+
+// Session lengths allowed by STS, in seconds
+pub const ASSUME_ROLE_DURATION: DurationRange = DurationRange { default: 3600, min: 900, max: 43200 };
+pub const SESSION_TOKEN_DURATION: DurationRange = DurationRange { default: 43200, min: 900, max: 129600 };
+
+pub struct DurationRange {
+    pub default: i64,
+    pub min: i64,
+    pub max: i64,
+}
+
+impl DurationRange {
+    /// The `DurationSeconds` parameter, or the default when it is left out.
+    pub fn parse(&self, value: Option<&str>) -> Result<Duration, String> {
+        let Some(value) = value else {
+            return Ok(Duration::seconds(self.default));
+        };
+        let seconds: i64 = value
+            .parse()
+            .map_err(|_| format!("Value '{}' at 'durationSeconds' failed to satisfy constraint: Member must be a number", value))?;
+        if seconds < self.min {
+            return Err(validation_message(value, "durationSeconds", &format!("Member must have value greater than or equal to {}", self.min)));
+        }
+        if seconds > self.max {
+            return Err(validation_message(value, "durationSeconds", &format!("Member must have value less than or equal to {}", self.max)));
+        }
+        Ok(Duration::seconds(seconds))
+    }
+}
+
+/// A role ARN such as "arn:aws:iam::123456789012:role/path/name".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoleArn {
+    pub arn: String,
+    pub account_id: String,
+    pub role_name: String,
+}
+
+impl RoleArn {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let invalid = || format!("{} is invalid", value);
+        let rest = value.strip_prefix("arn:aws:iam::").ok_or_else(invalid)?;
+        let (account_id, resource) = rest.split_once(':').ok_or_else(invalid)?;
+        let path = resource.strip_prefix("role/").ok_or_else(invalid)?;
+        let role_name = path.rsplit('/').next().unwrap_or_default();
+        if account_id.len() != 12 || !account_id.chars().all(|c| c.is_ascii_digit()) || role_name.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            arn: value.to_string(),
+            account_id: account_id.to_string(),
+            role_name: role_name.to_string(),
+        })
+    }
+
+    /// Stable role ID, shaped like the "AROA..." IDs of IAM.
+    pub fn role_id(&self) -> String {
+        format!("AROA{}", &sha256_hex(self.arn.as_bytes())[..17]).to_uppercase()
+    }
+
+    /// Identity of a session of this role. It acts within the account, so it gets the
+    /// canonical user ID of `account` and the ARN of the assumed role.
+    pub fn session_identity(&self, account: &Identity, session_name: &str) -> Identity {
+        Identity {
+            arn: format!("arn:aws:sts::{}:assumed-role/{}/{}", self.account_id, self.role_name, session_name),
+            ..account.clone()
+        }
+    }
+}
+
+/// Checks `RoleSessionName`: 2 to 64 characters out of `[\w+=,.@-]`.
+pub fn validate_session_name(value: &str) -> Result<(), String> {
+    let valid_chars = value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "_+=,.@-".contains(c));
+    if !(2..=64).contains(&value.len()) || !valid_chars {
+        return Err(validation_message(
+            value,
+            "roleSessionName",
+            "Member must satisfy regular expression pattern: [\\w+=,.@-]*",
+        ));
+    }
+    Ok(())
+}
+
+/// The `sub` and `iss` claims of a web identity token. The signature isn't checked:
+/// any well-formed JWT is trusted.
+pub fn web_identity_claims(token: &str) -> Result<(String, String), String> {
+    let invalid = || "Token is not a valid JWT".to_string();
+    let payload = token.split('.').nth(1).ok_or_else(invalid)?;
+    let json = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .map_err(|_| invalid())?;
+    let claims: Value = serde_json::from_slice(&json).map_err(|_| invalid())?;
+    let claim = |name: &str| claims.get(name).and_then(Value::as_str).unwrap_or_default().to_string();
+    Ok((claim("sub"), claim("iss")))
+}
+
+fn validation_message(value: &str, member: &str, constraint: &str) -> String {
+    format!(
+        "1 validation error detected: Value '{}' at '{}' failed to satisfy constraint: {}",
+        value, member, constraint
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_arn() {
+        let role = RoleArn::parse("arn:aws:iam::123456789012:role/service/uploader").unwrap();
+        assert_eq!(role.account_id, "123456789012");
+        assert_eq!(role.role_name, "uploader");
+        assert_eq!(role.role_id().len(), 21);
+        assert!(RoleArn::parse("arn:aws:iam::123456789012:user/uploader").is_err());
+        assert!(RoleArn::parse("arn:aws:iam::1234:role/uploader").is_err());
+
+        let identity = role.session_identity(&Identity::owner(), "build-42");
+        assert_eq!(identity.arn, "arn:aws:sts::123456789012:assumed-role/uploader/build-42");
+        assert_eq!(identity.canonical_user_id, Identity::owner().canonical_user_id);
+    }
+
+    #[test]
+    fn test_durations_and_names() {
+        assert_eq!(ASSUME_ROLE_DURATION.parse(None), Ok(Duration::hours(1)));
+        assert_eq!(SESSION_TOKEN_DURATION.parse(Some("900")), Ok(Duration::minutes(15)));
+        assert!(ASSUME_ROLE_DURATION.parse(Some("899")).is_err());
+        assert!(ASSUME_ROLE_DURATION.parse(Some("43201")).is_err());
+        assert!(validate_session_name("ci-job@example.com").is_ok());
+        assert!(validate_session_name("a").is_err());
+        assert!(validate_session_name("has space").is_err());
+    }
+
+    #[test]
+    fn test_web_identity_claims() {
+        let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(r#"{"sub":"user-1","iss":"https://issuer.example.com"}"#);
+        let token = format!("eyJhbGciOiJub25lIn0.{}.sig", payload);
+        assert_eq!(
+            web_identity_claims(&token),
+            Ok(("user-1".to_string(), "https://issuer.example.com".to_string()))
+        );
+        assert!(web_identity_claims("not-a-jwt").is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use serde::{Deserialize, Serialize};
+
+// Name under which the configuration is stored with the bucket
+pub const CONFIG_NAME: &str = "website.xml";
+
+const MAX_ROUTING_RULES: usize = 50;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "WebsiteConfiguration")]
+pub struct WebsiteConfiguration {
+    #[serde(rename = "IndexDocument", default, skip_serializing_if = "Option::is_none")]
+    pub index_document: Option<IndexDocument>,
+    #[serde(rename = "ErrorDocument", default, skip_serializing_if = "Option::is_none")]
+    pub error_document: Option<ErrorDocument>,
+    #[serde(rename = "RedirectAllRequestsTo", default, skip_serializing_if = "Option::is_none")]
+    pub redirect_all_requests_to: Option<RedirectAllRequestsTo>,
+    #[serde(rename = "RoutingRules", default, skip_serializing_if = "Option::is_none")]
+    pub routing_rules: Option<RoutingRules>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IndexDocument {
+    // Appended to keys ending in a slash, as in "index.html"
+    #[serde(rename = "Suffix")]
+    pub suffix: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ErrorDocument {
+    #[serde(rename = "Key")]
+    pub key: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RedirectAllRequestsTo {
+    #[serde(rename = "HostName")]
+    pub host_name: String,
+    #[serde(rename = "Protocol", default, skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoutingRules {
+    #[serde(rename = "RoutingRule", default)]
+    pub rules: Vec<RoutingRule>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoutingRule {
+    #[serde(rename = "Condition", default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<Condition>,
+    #[serde(rename = "Redirect")]
+    pub redirect: Redirect,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Condition {
+    #[serde(rename = "KeyPrefixEquals", default, skip_serializing_if = "Option::is_none")]
+    pub key_prefix_equals: Option<String>,
+    #[serde(rename = "HttpErrorCodeReturnedEquals", default, skip_serializing_if = "Option::is_none")]
+    pub http_error_code_returned_equals: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Redirect {
+    #[serde(rename = "HostName", default, skip_serializing_if = "Option::is_none")]
+    pub host_name: Option<String>,
+    #[serde(rename = "HttpRedirectCode", default, skip_serializing_if = "Option::is_none")]
+    pub http_redirect_code: Option<String>,
+    #[serde(rename = "Protocol", default, skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    #[serde(rename = "ReplaceKeyPrefixWith", default, skip_serializing_if = "Option::is_none")]
+    pub replace_key_prefix_with: Option<String>,
+    #[serde(rename = "ReplaceKeyWith", default, skip_serializing_if = "Option::is_none")]
+    pub replace_key_with: Option<String>,
+}
+
+impl WebsiteConfiguration {
+    pub fn from_xml(xml: &str) -> Result<Self, quick_xml::DeError> {
+        quick_xml::de::from_str(xml)
+    }
+
+    pub fn to_xml(&self) -> Result<String, quick_xml::SeError> {
+        quick_xml::se::to_string(self)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(redirect) = &self.redirect_all_requests_to {
+            if self.index_document.is_some() || self.error_document.is_some() || self.routing_rules.is_some() {
+                return Err("RedirectAllRequestsTo cannot be provided in conjunction with other Routing Rules.".to_string());
+            }
+            if redirect.host_name.is_empty() {
+                return Err("A host name must be provided.".to_string());
+            }
+            return validate_protocol(redirect.protocol.as_deref());
+        }
+
+        let Some(index_document) = &self.index_document else {
+            return Err("A value for IndexDocument Suffix must be provided if RedirectAllRequestsTo is empty".to_string());
+        };
+        if index_document.suffix.is_empty() || index_document.suffix.contains('/') {
+            return Err("The IndexDocument Suffix is not well formed".to_string());
+        }
+        if self.error_document.as_ref().is_some_and(|document| document.key.is_empty()) {
+            return Err("The ErrorDocument Key is not well formed".to_string());
+        }
+        let rules = self.rules();
+        if rules.len() > MAX_ROUTING_RULES {
+            return Err(format!("A website configuration can have up to {} routing rules", MAX_ROUTING_RULES));
+        }
+        rules.iter().try_for_each(RoutingRule::validate)
+    }
+
+    pub fn rules(&self) -> &[RoutingRule] {
+        self.routing_rules.as_ref().map(|rules| rules.rules.as_slice()).unwrap_or_default()
+    }
+
+    /// The first routing rule redirecting a request for `key`: before the key is looked up
+    /// when `error_code` is `None`, or after looking it up failed with `error_code`.
+    pub fn rule_for(&self, key: &str, error_code: Option<u16>) -> Option<&RoutingRule> {
+        self.rules().iter().find(|rule| rule.matches(key, error_code))
+    }
+
+    /// The key served for a request path: the index document of folders, as in
+    /// "docs/index.html" for "docs/", or the path itself.
+    pub fn resolve(&self, key: &str) -> String {
+        match &self.index_document {
+            Some(index) if key.is_empty() || key.ends_with('/') => format!("{}{}", key, index.suffix),
+            _ => key.to_string(),
+        }
+    }
+}
+
+impl RoutingRule {
+    fn validate(&self) -> Result<(), String> {
+        let redirect = &self.redirect;
+        if redirect.replace_key_with.is_some() && redirect.replace_key_prefix_with.is_some() {
+            return Err("You can only define ReplaceKeyPrefix or ReplaceKey but not both.".to_string());
+        }
+        if let Some(code) = &redirect.http_redirect_code {
+            if !matches!(code.parse::<u16>(), Ok(300..=399)) {
+                return Err(format!("The provided HTTP redirect code ({}) is not valid.", code));
+            }
+        }
+        if let Some(code) = self.condition.as_ref().and_then(|c| c.http_error_code_returned_equals.as_ref()) {
+            if !matches!(code.parse::<u16>(), Ok(400..=599)) {
+                return Err(format!("The provided HTTP error code ({}) is not valid.", code));
+            }
+        }
+        validate_protocol(redirect.protocol.as_deref())
+    }
+
+    fn matches(&self, key: &str, error_code: Option<u16>) -> bool {
+        let Some(condition) = &self.condition else {
+            return error_code.is_none();
+        };
+        let expected_code = condition.http_error_code_returned_equals.as_ref().and_then(|code| code.parse().ok());
+        expected_code == error_code
+            && condition.key_prefix_equals.as_deref().is_none_or(|prefix| key.starts_with(prefix))
+    }
+
+    /// Status code and `Location` of the redirect for `key`. Requests keep their own
+    /// host and protocol unless the rule names others.
+    pub fn location(&self, key: &str, host: &str, protocol: &str) -> (u16, String) {
+        let redirect = &self.redirect;
+        let key = match (&redirect.replace_key_with, &redirect.replace_key_prefix_with) {
+            (Some(replacement), _) => replacement.clone(),
+            (None, Some(replacement)) => {
+                let prefix = self.condition.as_ref().and_then(|c| c.key_prefix_equals.as_deref()).unwrap_or("");
+                format!("{}{}", replacement, key.strip_prefix(prefix).unwrap_or(key))
+            }
+            (None, None) => key.to_string(),
+        };
+        let code = redirect.http_redirect_code.as_ref().and_then(|code| code.parse().ok()).unwrap_or(301);
+        let location = format!(
+            "{}://{}/{}",
+            redirect.protocol.as_deref().unwrap_or(protocol),
+            redirect.host_name.as_deref().unwrap_or(host),
+            key
+        );
+        (code, location)
+    }
+}
+
+fn validate_protocol(protocol: Option<&str>) -> Result<(), String> {
+    match protocol {
+        None | Some("http") | Some("https") => Ok(()),
+        Some(protocol) => Err(format!("Invalid protocol, protocol can be http or https. Protocol: {}", protocol)),
+    }
+}
+
+/// Objects can redirect website requests to another object of the bucket or to any URL.
+pub fn validate_redirect_location(location: &str) -> Result<(), String> {
+    if location.starts_with('/') || location.starts_with("http://") || location.starts_with("https://") {
+        Ok(())
+    } else {
+        Err("The website redirect location must have a prefix of 'http://' or 'https://' or '/'.".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_website_configuration() {
+        let xml = r#"<WebsiteConfiguration xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+            <IndexDocument><Suffix>index.html</Suffix></IndexDocument>
+            <ErrorDocument><Key>404.html</Key></ErrorDocument>
+            <RoutingRules>
+                <RoutingRule>
+                    <Condition><KeyPrefixEquals>docs/</KeyPrefixEquals></Condition>
+                    <Redirect><ReplaceKeyPrefixWith>documents/</ReplaceKeyPrefixWith></Redirect>
+                </RoutingRule>
+                <RoutingRule>
+                    <Condition><HttpErrorCodeReturnedEquals>404</HttpErrorCodeReturnedEquals></Condition>
+                    <Redirect><HostName>fallback.example.com</HostName><Protocol>https</Protocol><HttpRedirectCode>302</HttpRedirectCode></Redirect>
+                </RoutingRule>
+            </RoutingRules>
+        </WebsiteConfiguration>"#;
+
+        let config = WebsiteConfiguration::from_xml(xml).unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.rules().len(), 2);
+        let round_trip = WebsiteConfiguration::from_xml(&config.to_xml().unwrap()).unwrap();
+        assert_eq!(round_trip, config);
+
+        assert_eq!(config.resolve(""), "index.html");
+        assert_eq!(config.resolve("blog/"), "blog/index.html");
+        assert_eq!(config.resolve("about.html"), "about.html");
+
+        let rule = config.rule_for("docs/intro.html", None).unwrap();
+        assert_eq!(
+            rule.location("docs/intro.html", "site.localhost:3000", "http"),
+            (301, "http://site.localhost:3000/documents/intro.html".to_string())
+        );
+        assert!(config.rule_for("missing.html", None).is_none());
+        let fallback = config.rule_for("missing.html", Some(404)).unwrap();
+        assert_eq!(
+            fallback.location("missing.html", "site.localhost", "http"),
+            (302, "https://fallback.example.com/missing.html".to_string())
+        );
+        assert!(config.rule_for("missing.html", Some(403)).is_none());
+    }
+
+    #[test]
+    fn test_validation_rejects_invalid_configurations() {
+        let invalid = [
+            "<WebsiteConfiguration></WebsiteConfiguration>",
+            "<WebsiteConfiguration><IndexDocument><Suffix>a/index.html</Suffix></IndexDocument></WebsiteConfiguration>",
+            "<WebsiteConfiguration><RedirectAllRequestsTo><HostName>example.com</HostName></RedirectAllRequestsTo><IndexDocument><Suffix>index.html</Suffix></IndexDocument></WebsiteConfiguration>",
+            "<WebsiteConfiguration><RedirectAllRequestsTo><HostName>example.com</HostName><Protocol>ftp</Protocol></RedirectAllRequestsTo></WebsiteConfiguration>",
+            "<WebsiteConfiguration><IndexDocument><Suffix>index.html</Suffix></IndexDocument><RoutingRules><RoutingRule><Redirect><HttpRedirectCode>200</HttpRedirectCode></Redirect></RoutingRule></RoutingRules></WebsiteConfiguration>",
+        ];
+        for xml in invalid {
+            assert!(WebsiteConfiguration::from_xml(xml).unwrap().validate().is_err(), "{}", xml);
+        }
+        assert!(validate_redirect_location("/other.html").is_ok());
+        assert!(validate_redirect_location("other.html").is_err());
+    }
+}
@@ -0,0 +1,151 @@
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+use crate::domain::identity::DEFAULT_ACCOUNT_ID;
+use crate::domain::logging::{AccessLogRecord, LoggingEnabled};
+use crate::domain::object::Object;
+use crate::infrastructure::storage::Storage;
+
+// A record waiting for delivery, with where its bucket logged to when it was made
+struct PendingRecord {
+    source_region: String,
+    target: LoggingEnabled,
+    record: AccessLogRecord,
+}
+
+/// Buffers server access log records and delivers them periodically as log objects to
+/// the target buckets, one object per source bucket and target.
+pub struct AccessLogger {
+    storage: Arc<dyn Storage>,
+    interval: Duration,
+    pending: Mutex<Vec<PendingRecord>>,
+}
+
+impl AccessLogger {
+    pub fn new(storage: Arc<dyn Storage>, interval: Duration) -> Self {
+        Self { storage, interval, pending: Mutex::new(Vec::new()) }
+    }
+
+    pub fn spawn(self: &Arc<Self>) -> JoinHandle<()> {
+        let logger = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(logger.interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = logger.flush(Utc::now()).await {
+                    eprintln!("Access log delivery failed: {}", e);
+                }
+            }
+        })
+    }
+
+    pub fn record(&self, source_region: &str, target: LoggingEnabled, record: AccessLogRecord) {
+        let mut pending = self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        pending.push(PendingRecord { source_region: source_region.to_string(), target, record });
+    }
+
+    /// Writes the buffered records as of `now`. Records for target buckets that are gone
+    /// are dropped. Returns the number of log objects written.
+    pub async fn flush(&self, now: DateTime<Utc>) -> io::Result<usize> {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+        let mut batches: Vec<(PendingRecord, Vec<String>)> = Vec::new();
+        for pending in pending {
+            let line = pending.record.to_line();
+            let batch = batches.iter_mut().find(|(first, _)| {
+                first.record.bucket == pending.record.bucket && first.target == pending.target
+            });
+            match batch {
+                Some((_, lines)) => lines.push(line),
+                None => batches.push((pending, vec![line])),
+            }
+        }
+
+        let mut delivered = 0;
+        for (first, lines) in batches {
+            let target_bucket = &first.target.target_bucket;
+            if self.storage.get_bucket(target_bucket).await?.is_none() {
+                eprintln!("Dropping access logs of bucket {}: no target bucket {}", first.record.bucket, target_bucket);
+                continue;
+            }
+            let unique = Uuid::new_v4().simple().to_string()[..16].to_uppercase();
+            let key = first.target.log_object_key(
+                DEFAULT_ACCOUNT_ID,
+                &first.source_region,
+                &first.record.bucket,
+                first.record.time,
+                now,
+                &unique,
+            );
+            let content = lines.iter().map(|line| format!("{}\n", line)).collect::<String>();
+            let object = Object::new(key, content.into_bytes(), "text/plain".to_string());
+            self.storage.put_object(target_bucket, &object).await?;
+            delivered += 1;
+        }
+        Ok(delivered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::bucket::Bucket;
+    use crate::infrastructure::storage::FileStorage;
+
+    fn record(bucket: &str, key: &str) -> AccessLogRecord {
+        AccessLogRecord {
+            bucket_owner: "owner".to_string(),
+            bucket: bucket.to_string(),
+            time: Utc::now(),
+            remote_ip: "127.0.0.1".to_string(),
+            requester: "-".to_string(),
+            request_id: "0123456789ABCDEF".to_string(),
+            operation: "REST.GET.OBJECT".to_string(),
+            key: Some(key.to_string()),
+            request_uri: format!("GET /{}/{} HTTP/1.1", bucket, key),
+            http_status: 200,
+            error_code: None,
+            bytes_sent: Some(4),
+            object_size: Some(4),
+            total_time_ms: 1,
+            turn_around_time_ms: Some(1),
+            referer: None,
+            user_agent: None,
+            signature_version: None,
+            authentication_type: None,
+            host_header: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flush_writes_log_objects() {
+        let base_path = std::env::temp_dir().join(format!("s3-mocker-access-logs-{}", Uuid::new_v4()));
+        let storage: Arc<dyn Storage> = Arc::new(FileStorage::new(base_path.clone()));
+        storage.create_bucket(&Bucket::new("log-target".to_string())).await.unwrap();
+        let logger = AccessLogger::new(storage.clone(), Duration::from_secs(60));
+        let target = LoggingEnabled { target_bucket: "log-target".to_string(), target_prefix: "logs/".to_string(), ..Default::default() };
+
+        logger.record("us-east-1", target.clone(), record("site", "a.txt"));
+        logger.record("us-east-1", target.clone(), record("docs", "b.txt"));
+        logger.record("us-east-1", target.clone(), record("site", "c.txt"));
+        let missing = LoggingEnabled { target_bucket: "missing".to_string(), ..target };
+        logger.record("us-east-1", missing, record("site", "d.txt"));
+        assert_eq!(logger.flush(Utc::now()).await.unwrap(), 2);
+        assert_eq!(logger.flush(Utc::now()).await.unwrap(), 0);
+
+        let keys = storage.list_objects("log-target").await.unwrap();
+        assert_eq!(keys.len(), 2);
+        let mut lines = Vec::new();
+        for key in &keys {
+            assert!(key.starts_with("logs/"));
+            let object = storage.get_object("log-target", key).await.unwrap();
+            lines.extend(String::from_utf8(object.content).unwrap().lines().map(str::to_string));
+        }
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().any(|line| line.contains(" REST.GET.OBJECT c.txt ")));
+
+        std::fs::remove_dir_all(base_path).unwrap();
+    }
+}
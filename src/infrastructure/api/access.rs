@@ -0,0 +1,153 @@
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use axum::extract::{ConnectInfo, FromRequestParts, Request, State};
+use axum::http::request::Parts;
+use axum::http::{Extensions, HeaderMap};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use crate::domain::acl::Permission;
+use crate::domain::identity::{Identity, Requester};
+use crate::domain::policy::Decision;
+use super::api::AppState;
+use super::error::S3Error;
+use super::operation::S3Operation;
+use super::public_access::public_access_block;
+use super::{acl, policy};
+
+const POLICY_ACTIONS: [&str; 3] = ["s3:GetBucketPolicy", "s3:PutBucketPolicy", "s3:DeleteBucketPolicy"];
+
+enum Target {
+    Bucket,
+    Object,
+}
+
+/// Requester attached to the request, the default owner when nothing identified it.
+pub fn requester_of(extensions: &Extensions) -> Requester {
+    extensions
+        .get::<Requester>()
+        .cloned()
+        .unwrap_or_else(|| Requester::Authenticated(Identity::owner()))
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Requester {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(requester_of(&parts.extensions))
+    }
+}
+
+/// Middleware deciding whether the requester may perform the operation, from
+/// the bucket policy and ACLs, before the request reaches storage.
+pub async fn authorize(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let operation = S3Operation::classify(request.method(), request.uri(), request.headers());
+    // Preflights are answered from the CORS configuration alone, and POST uploads
+    // are signed inside the form, so their handler checks access itself
+    if operation.name == "PreflightRequest" || operation.name == "PostObject" {
+        return next.run(request).await;
+    }
+    let requester = requester_of(request.extensions());
+    let source_ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+
+    let mut operations = vec![operation];
+    // Copies also read the source object
+    if operations[0].name == "CopyObject" {
+        operations.extend(S3Operation::copy_source(request.headers()));
+    }
+    for operation in &operations {
+        if let Err(e) = check_access(&state, operation, &requester, request.headers(), source_ip).await {
+            return e.into_response();
+        }
+    }
+    next.run(request).await
+}
+
+/// Decides whether `requester` may perform `operation`, from the bucket policy and ACLs.
+pub async fn check_access(
+    state: &AppState,
+    operation: &S3Operation,
+    requester: &Requester,
+    headers: &HeaderMap,
+    source_ip: Option<IpAddr>,
+) -> Result<(), S3Error> {
+    let Some(bucket_name) = &operation.bucket else {
+        return Ok(());
+    };
+    // Missing buckets are reported by the handlers, as are existing ones to CreateBucket
+    if operation.name == "CreateBucket" || state.storage.get_bucket(bucket_name).await?.is_none() {
+        return Ok(());
+    }
+
+    let public_access = public_access_block(state, bucket_name).await?;
+    let mut bucket_acl = acl::bucket_acl(state, bucket_name).await?;
+    let is_bucket_owner = bucket_acl.is_owner(requester);
+    // The bucket owner can always manage the policy, so a bad policy cannot lock them out
+    if is_bucket_owner && POLICY_ACTIONS.contains(&operation.action) {
+        return Ok(());
+    }
+    if public_access.ignore_public_acls {
+        bucket_acl = bucket_acl.without_public_grants();
+    }
+
+    let decision = match policy::bucket_policy(state, bucket_name).await? {
+        Some(mut bucket_policy) => {
+            // A public policy still lets the bucket owner's account in, but nobody else
+            if public_access.restrict_public_buckets && !is_bucket_owner {
+                bucket_policy = bucket_policy.without_public_statements();
+            }
+            bucket_policy.evaluate(&policy::request_context(state, requester.clone(), operation, headers, source_ip))
+        }
+        None => Decision::Implicit,
+    };
+    match decision {
+        Decision::Deny => return Err(policy::access_denied()),
+        Decision::Allow => return Ok(()),
+        Decision::Implicit => {}
+    }
+
+    let acls_enabled = acl::object_ownership(state, bucket_name).await?.acls_enabled();
+    let allowed = match required_permission(operation.name) {
+        Some((Target::Bucket, permission)) => {
+            is_bucket_owner || (acls_enabled && bucket_acl.allows(requester, permission))
+        }
+        Some((Target::Object, permission)) => {
+            let key = operation.key.as_deref().unwrap_or_default();
+            match state.storage.head_object(bucket_name, key).await {
+                Ok(object) if acls_enabled => match object.acl {
+                    Some(object_acl) if public_access.ignore_public_acls => {
+                        object_acl.without_public_grants().allows(requester, permission)
+                    }
+                    Some(object_acl) => object_acl.allows(requester, permission),
+                    None => is_bucket_owner,
+                },
+                // With ACLs disabled the bucket owner owns every object
+                Ok(_) => is_bucket_owner,
+                // Let the handler report the missing key to whoever may list the bucket
+                Err(_) => is_bucket_owner || (acls_enabled && bucket_acl.allows(requester, Permission::Read)),
+            }
+        }
+        None => is_bucket_owner,
+    };
+    if allowed {
+        Ok(())
+    } else {
+        Err(policy::access_denied())
+    }
+}
+
+// Operations that ACL grants can open up to others than the bucket owner
+fn required_permission(operation: &str) -> Option<(Target, Permission)> {
+    match operation {
+        "GetObject" | "HeadObject" => Some((Target::Object, Permission::Read)),
+        "GetObjectAcl" => Some((Target::Object, Permission::ReadAcp)),
+        "PutObjectAcl" => Some((Target::Object, Permission::WriteAcp)),
+        "PutObject" | "PostObject" | "CopyObject" | "DeleteObject" => Some((Target::Bucket, Permission::Write)),
+        "ListObjects" | "HeadBucket" => Some((Target::Bucket, Permission::Read)),
+        "GetBucketAcl" => Some((Target::Bucket, Permission::ReadAcp)),
+        "PutBucketAcl" => Some((Target::Bucket, Permission::WriteAcp)),
+        _ => None,
+    }
+}
@@ -0,0 +1,289 @@
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use crate::domain::acl::{self, AccessControlPolicy, AclError, ObjectOwnership, Owner, OwnershipControls, Permission};
+use crate::domain::identity::{Identity, Requester};
+use super::api::{require_bucket, AppState};
+use super::error::S3Error;
+use super::object::{header_str, object_error};
+use super::public_access::public_access_block;
+
+const GRANT_HEADERS: [(&str, Permission); 5] = [
+    ("x-amz-grant-read", Permission::Read),
+    ("x-amz-grant-write", Permission::Write),
+    ("x-amz-grant-read-acp", Permission::ReadAcp),
+    ("x-amz-grant-write-acp", Permission::WriteAcp),
+    ("x-amz-grant-full-control", Permission::FullControl),
+];
+
+/// ACL of the bucket, falling back to a private ACL for buckets created without one.
+pub async fn bucket_acl(state: &AppState, bucket_name: &str) -> Result<AccessControlPolicy, S3Error> {
+    match state.storage.get_bucket_config(bucket_name, acl::CONFIG_NAME).await? {
+        Some(xml) => AccessControlPolicy::from_xml(&xml).map_err(S3Error::internal),
+        None => Ok(AccessControlPolicy::private(Owner::from(&Identity::owner()))),
+    }
+}
+
+/// Object ownership of the bucket. Buckets that predate the setting behave as `ObjectWriter`.
+pub async fn object_ownership(state: &AppState, bucket_name: &str) -> Result<ObjectOwnership, S3Error> {
+    match state.storage.get_bucket_config(bucket_name, acl::OWNERSHIP_CONFIG_NAME).await? {
+        Some(xml) => OwnershipControls::from_xml(&xml)
+            .map(|controls| controls.object_ownership())
+            .map_err(S3Error::internal),
+        None => Ok(ObjectOwnership::ObjectWriter),
+    }
+}
+
+pub async fn get_bucket_acl(state: &AppState, bucket_name: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let acl = bucket_acl(state, bucket_name).await?;
+    Ok(xml_response(acl.to_xml()))
+}
+
+pub async fn put_bucket_acl(
+    state: &AppState,
+    bucket_name: &str,
+    headers: &HeaderMap,
+    body: &str,
+) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let owner = bucket_acl(state, bucket_name).await?.owner;
+    let acl = acl_from_request(headers, body, owner, None)?
+        .ok_or_else(|| S3Error::new(StatusCode::BAD_REQUEST, "MissingSecurityHeader", "Your request was missing a required header"))?;
+    if !object_ownership(state, bucket_name).await?.acls_enabled() && !acl.is_owner_only() {
+        return Err(acls_not_supported());
+    }
+    check_public_acl(state, bucket_name, &acl).await?;
+    state
+        .storage
+        .put_bucket_config(bucket_name, acl::CONFIG_NAME, &acl.to_xml())
+        .await?;
+    Ok(StatusCode::OK.into_response())
+}
+
+pub async fn get_object_acl(state: &AppState, bucket_name: &str, key: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let object = state
+        .storage
+        .head_object(bucket_name, key)
+        .await
+        .map_err(|e| object_error(e, key))?;
+    let acl = match object.acl {
+        Some(acl) if object_ownership(state, bucket_name).await?.acls_enabled() => acl,
+        // With ACLs disabled, or before objects carried an ACL, the bucket owner owns everything
+        _ => AccessControlPolicy::private(bucket_acl(state, bucket_name).await?.owner),
+    };
+    Ok(xml_response(acl.to_xml()))
+}
+
+pub async fn put_object_acl(
+    state: &AppState,
+    bucket_name: &str,
+    key: &str,
+    headers: &HeaderMap,
+    body: &str,
+) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let mut object = state
+        .storage
+        .head_object(bucket_name, key)
+        .await
+        .map_err(|e| object_error(e, key))?;
+    let bucket_owner = bucket_acl(state, bucket_name).await?.owner;
+    let owner = object.acl.as_ref().map(|acl| acl.owner.clone()).unwrap_or_else(|| bucket_owner.clone());
+
+    let acl = acl_from_request(headers, body, owner, Some(&bucket_owner))?
+        .ok_or_else(|| S3Error::new(StatusCode::BAD_REQUEST, "MissingSecurityHeader", "Your request was missing a required header"))?;
+    if !object_ownership(state, bucket_name).await?.acls_enabled() && !acl.is_owner_only() {
+        return Err(acls_not_supported());
+    }
+    check_public_acl(state, bucket_name, &acl).await?;
+    object.acl = Some(acl);
+    state.storage.put_object_metadata(bucket_name, &object).await?;
+    Ok(StatusCode::OK.into_response())
+}
+
+/// Ownership and ACL of a bucket about to be created, from the CreateBucket headers.
+pub fn new_bucket_acl(
+    headers: &HeaderMap,
+    requester: &Requester,
+) -> Result<(OwnershipControls, AccessControlPolicy), S3Error> {
+    let identity = requester.identity().ok_or_else(super::policy::access_denied)?;
+    let ownership = match header_str(headers, "x-amz-object-ownership") {
+        Some(value) => ObjectOwnership::parse(value)
+            .ok_or_else(|| S3Error::invalid_argument(format!("Invalid x-amz-object-ownership header: {}", value)))?,
+        // The S3 default for new buckets
+        None => ObjectOwnership::BucketOwnerEnforced,
+    };
+
+    let owner = Owner::from(identity);
+    let acl = acl_from_headers(headers, owner.clone(), None)?.unwrap_or_else(|| AccessControlPolicy::private(owner));
+    if !ownership.acls_enabled() && !acl.is_owner_only() {
+        return Err(acl_with_enforced_ownership());
+    }
+    Ok((OwnershipControls::new(ownership), acl))
+}
+
+/// ACL of an object being written by `requester`, honouring the bucket's object ownership.
+pub async fn new_object_acl(
+    state: &AppState,
+    bucket_name: &str,
+    headers: &HeaderMap,
+    requester: &Requester,
+) -> Result<AccessControlPolicy, S3Error> {
+    let bucket_owner = bucket_acl(state, bucket_name).await?.owner;
+    let ownership = object_ownership(state, bucket_name).await?;
+    let canned = header_str(headers, "x-amz-acl");
+
+    let owner = match (ownership, requester.identity()) {
+        (ObjectOwnership::BucketOwnerEnforced, _) => bucket_owner.clone(),
+        (ObjectOwnership::BucketOwnerPreferred, _) if canned == Some("bucket-owner-full-control") => bucket_owner.clone(),
+        (_, Some(identity)) => Owner::from(identity),
+        // Anonymous writes to public-write buckets belong to the bucket owner
+        (_, None) => bucket_owner.clone(),
+    };
+
+    let acl = acl_from_headers(headers, owner.clone(), Some(&bucket_owner))?;
+    if !ownership.acls_enabled() {
+        // Only ACLs equivalent to bucket-owner-full-control are still accepted
+        if acl.as_ref().is_some_and(|acl| !acl.is_owner_only()) {
+            return Err(acls_not_supported());
+        }
+        return Ok(AccessControlPolicy::private(bucket_owner));
+    }
+    let acl = acl.unwrap_or_else(|| AccessControlPolicy::private(owner));
+    check_public_acl(state, bucket_name, &acl).await?;
+    Ok(acl)
+}
+
+pub async fn get_bucket_ownership_controls(state: &AppState, bucket_name: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let xml = state
+        .storage
+        .get_bucket_config(bucket_name, acl::OWNERSHIP_CONFIG_NAME)
+        .await?
+        .ok_or_else(|| {
+            S3Error::new(
+                StatusCode::NOT_FOUND,
+                "OwnershipControlsNotFoundError",
+                "The bucket ownership controls were not found",
+            )
+            .with_detail("BucketName", bucket_name)
+        })?;
+    Ok(xml_response(xml))
+}
+
+pub async fn put_bucket_ownership_controls(state: &AppState, bucket_name: &str, body: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let controls = OwnershipControls::from_xml(body).map_err(|_| S3Error::malformed_xml())?;
+    if controls.rules.len() != 1 {
+        return Err(S3Error::malformed_xml());
+    }
+    // Disabling ACLs requires the bucket ACL to be back to the owner alone
+    if !controls.object_ownership().acls_enabled() && !bucket_acl(state, bucket_name).await?.is_owner_only() {
+        return Err(acl_with_enforced_ownership());
+    }
+    let xml = controls.to_xml().map_err(S3Error::internal)?;
+    state
+        .storage
+        .put_bucket_config(bucket_name, acl::OWNERSHIP_CONFIG_NAME, &xml)
+        .await?;
+    Ok(StatusCode::OK.into_response())
+}
+
+/// Without ownership controls the bucket is back to `ObjectWriter`.
+pub async fn delete_bucket_ownership_controls(state: &AppState, bucket_name: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    state
+        .storage
+        .delete_bucket_config(bucket_name, acl::OWNERSHIP_CONFIG_NAME)
+        .await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+// Block Public Access turns away public ACLs before they are stored
+async fn check_public_acl(state: &AppState, bucket_name: &str, acl: &AccessControlPolicy) -> Result<(), S3Error> {
+    if acl.is_public() && public_access_block(state, bucket_name).await?.block_public_acls {
+        return Err(super::policy::access_denied());
+    }
+    Ok(())
+}
+
+/// ACL sent to PutBucketAcl or PutObjectAcl, either as headers or as an AccessControlPolicy body.
+pub fn acl_from_request(
+    headers: &HeaderMap,
+    body: &str,
+    owner: Owner,
+    bucket_owner: Option<&Owner>,
+) -> Result<Option<AccessControlPolicy>, S3Error> {
+    if let Some(acl) = acl_from_headers(headers, owner.clone(), bucket_owner)? {
+        if !body.trim().is_empty() {
+            return Err(S3Error::new(
+                StatusCode::BAD_REQUEST,
+                "UnexpectedContent",
+                "This request does not support content",
+            ));
+        }
+        return Ok(Some(acl));
+    }
+    if body.trim().is_empty() {
+        return Ok(None);
+    }
+
+    let mut acl = AccessControlPolicy::from_xml(body).map_err(|_| {
+        S3Error::new(
+            StatusCode::BAD_REQUEST,
+            "MalformedACLError",
+            "The XML you provided was not well-formed or did not validate against our published schema",
+        )
+    })?;
+    acl.validate().map_err(acl_error)?;
+    // The owner of a resource cannot be changed through its ACL
+    acl.owner = owner;
+    Ok(Some(acl))
+}
+
+fn acl_from_headers(
+    headers: &HeaderMap,
+    owner: Owner,
+    bucket_owner: Option<&Owner>,
+) -> Result<Option<AccessControlPolicy>, S3Error> {
+    let grants: Vec<(Permission, &str)> = GRANT_HEADERS
+        .iter()
+        .filter_map(|(name, permission)| header_str(headers, name).map(|value| (*permission, value)))
+        .collect();
+
+    match header_str(headers, "x-amz-acl") {
+        Some(_) if !grants.is_empty() => Err(acl_error(AclError::CannedAndGrantHeaders)),
+        Some(canned) => AccessControlPolicy::canned(canned, owner, bucket_owner)
+            .map(Some)
+            .map_err(acl_error),
+        None if !grants.is_empty() => AccessControlPolicy::from_grant_headers(owner, &grants)
+            .map(Some)
+            .map_err(acl_error),
+        None => Ok(None),
+    }
+}
+
+fn acl_error(err: AclError) -> S3Error {
+    match err {
+        AclError::UnresolvableEmail(_) => S3Error::new(StatusCode::BAD_REQUEST, "UnresolvableGrantByEmailAddress", err.to_string()),
+        AclError::CannedAndGrantHeaders => S3Error::new(StatusCode::BAD_REQUEST, "InvalidRequest", err.to_string()),
+        AclError::InvalidCannedAcl(_) | AclError::InvalidGrantHeader(_) => S3Error::invalid_argument(err.to_string()),
+    }
+}
+
+fn acl_with_enforced_ownership() -> S3Error {
+    S3Error::new(
+        StatusCode::BAD_REQUEST,
+        "InvalidBucketAclWithObjectOwnership",
+        "Bucket cannot have ACLs set with ObjectOwnership's BucketOwnerEnforced setting",
+    )
+}
+
+pub fn acls_not_supported() -> S3Error {
+    S3Error::new(StatusCode::BAD_REQUEST, "AccessControlListNotSupported", "The bucket does not allow ACLs")
+}
+
+fn xml_response(xml: String) -> Response {
+    ([(header::CONTENT_TYPE, "application/xml")], xml).into_response()
+}
@@ -0,0 +1,168 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, patch, post};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::domain::credentials::{AccessKey, KeyStatus};
+use crate::domain::identity::{Requester, DEFAULT_CANONICAL_USER_ID};
+use super::api::AppState;
+use super::auth::{credential_store, save_credential_store};
+use super::error::S3Error;
+use super::{encryption, events, hooks, inventory, policy};
+
+/// Admin endpoints managing access keys, encryption keys and hooks, and streaming changes. Bucket names
+/// can't start with an underscore, so these never shadow a bucket.
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/_admin/access-keys", get(list_access_keys).post(create_access_key))
+        .route("/_admin/access-keys/{access_key_id}", patch(update_access_key).delete(delete_access_key))
+        .route("/_admin/encryption-keys", get(encryption::list_encryption_keys).post(encryption::rotate_encryption_key))
+        .route("/_admin/encryption-keys/reencrypt", post(encryption::reencrypt_objects))
+        .route("/_admin/events", get(events::stream_events))
+        .route("/_admin/hooks", get(hooks::list_hooks))
+        .route("/_admin/hooks/{name}", get(hooks::get_hook).put(hooks::put_hook).delete(hooks::delete_hook))
+        .route("/_admin/hooks/{name}/invocations", get(hooks::list_invocations))
+        .route("/_admin/inventory/{bucket}/{id}", post(inventory::generate_report))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CreateAccessKeyRequest {
+    user_name: String,
+    canonical_user_id: Option<String>,
+    // Fixed credentials, handy for test fixtures; generated when left out
+    access_key_id: Option<String>,
+    secret_access_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct UpdateAccessKeyRequest {
+    status: String,
+}
+
+// Mirrors the AccessKey shape of IAM. Secrets are only returned when a key is created.
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct AccessKeyView<'a> {
+    access_key_id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secret_access_key: Option<&'a str>,
+    status: &'static str,
+    user_name: &'a str,
+    canonical_user_id: &'a str,
+    arn: &'a str,
+    create_date: &'a DateTime<Utc>,
+}
+
+impl<'a> AccessKeyView<'a> {
+    fn new(key: &'a AccessKey, with_secret: bool) -> Self {
+        Self {
+            access_key_id: &key.access_key_id,
+            secret_access_key: with_secret.then_some(key.secret_access_key.as_str()),
+            status: key.status.as_str(),
+            user_name: &key.identity.display_name,
+            canonical_user_id: &key.identity.canonical_user_id,
+            arn: &key.identity.arn,
+            create_date: &key.created,
+        }
+    }
+}
+
+async fn list_access_keys(State(state): State<AppState>, requester: Requester) -> Result<Response, S3Error> {
+    require_owner(&state, &requester)?;
+    let store = credential_store(&state).await?;
+    // Temporary credentials from STS aren't managed here
+    let keys: Vec<AccessKeyView> = store
+        .keys()
+        .filter(|key| key.session_token.is_none())
+        .map(|key| AccessKeyView::new(key, false))
+        .collect();
+    Ok(Json(serde_json::json!({ "AccessKeys": keys })).into_response())
+}
+
+async fn create_access_key(
+    State(state): State<AppState>,
+    requester: Requester,
+    body: String,
+) -> Result<Response, S3Error> {
+    require_owner(&state, &requester)?;
+    let request: CreateAccessKeyRequest =
+        serde_json::from_str(&body).map_err(|e| validation_error(format!("Invalid request body: {}", e)))?;
+    if request.user_name.is_empty() {
+        return Err(validation_error("UserName must not be empty."));
+    }
+
+    let mut store = credential_store(&state).await?;
+    let identity = store.identity_for(&request.user_name, request.canonical_user_id.as_deref());
+    let mut key = AccessKey::generate(identity);
+    if let Some(access_key_id) = request.access_key_id {
+        key.access_key_id = access_key_id;
+    }
+    if let Some(secret_access_key) = request.secret_access_key {
+        key.secret_access_key = secret_access_key;
+    }
+    store
+        .add(key.clone())
+        .map_err(|message| S3Error::new(StatusCode::CONFLICT, "EntityAlreadyExists", message))?;
+    save_credential_store(&state, &store).await?;
+    Ok((StatusCode::CREATED, Json(serde_json::json!({ "AccessKey": AccessKeyView::new(&key, true) }))).into_response())
+}
+
+async fn update_access_key(
+    State(state): State<AppState>,
+    Path(access_key_id): Path<String>,
+    requester: Requester,
+    body: String,
+) -> Result<Response, S3Error> {
+    require_owner(&state, &requester)?;
+    let request: UpdateAccessKeyRequest =
+        serde_json::from_str(&body).map_err(|e| validation_error(format!("Invalid request body: {}", e)))?;
+    let status = KeyStatus::parse(&request.status)
+        .ok_or_else(|| validation_error("Status must be either Active or Inactive."))?;
+
+    let mut store = credential_store(&state).await?;
+    let key = store
+        .set_status(&access_key_id, status)
+        .ok_or_else(|| no_such_entity(&access_key_id))?
+        .clone();
+    save_credential_store(&state, &store).await?;
+    Ok(Json(AccessKeyView::new(&key, false)).into_response())
+}
+
+async fn delete_access_key(
+    State(state): State<AppState>,
+    Path(access_key_id): Path<String>,
+    requester: Requester,
+) -> Result<Response, S3Error> {
+    require_owner(&state, &requester)?;
+    let mut store = credential_store(&state).await?;
+    store.remove(&access_key_id).ok_or_else(|| no_such_entity(&access_key_id))?;
+    save_credential_store(&state, &store).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+// With authentication on, only the owner account manages keys
+pub(crate) fn require_owner(state: &AppState, requester: &Requester) -> Result<(), S3Error> {
+    let is_owner = requester
+        .identity()
+        .is_some_and(|identity| identity.canonical_user_id == DEFAULT_CANONICAL_USER_ID);
+    if state.auth_enabled && !is_owner {
+        return Err(policy::access_denied());
+    }
+    Ok(())
+}
+
+pub(crate) fn validation_error(message: impl Into<String>) -> S3Error {
+    S3Error::new(StatusCode::BAD_REQUEST, "ValidationError", message)
+}
+
+fn no_such_entity(access_key_id: &str) -> S3Error {
+    S3Error::new(
+        StatusCode::NOT_FOUND,
+        "NoSuchEntity",
+        format!("The Access Key with id {} cannot be found.", access_key_id),
+    )
+}
@@ -1,29 +1,399 @@
-use axum::{Router, routing::{get, put}, extract::{Path, State}, Json};
-use std::sync::{Arc, Mutex};
+use axum::{Router, routing::get, extract::{DefaultBodyLimit, Path, Query, State}, middleware};
+use axum::http::{header, HeaderMap, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use std::collections::HashMap;
+use std::sync::Arc;
+use crate::domain::acl as acl_config;
 use crate::domain::bucket::Bucket;
+use crate::domain::identity::Requester;
+use crate::infrastructure::access_logs::AccessLogger;
+use crate::infrastructure::audit::AuditTrail;
+use crate::infrastructure::events::EventLog;
+use crate::infrastructure::hooks::HookRunner;
+use crate::infrastructure::inventory::InventoryGenerator;
+use crate::infrastructure::notifications::Notifier;
+use crate::infrastructure::queues::QueueService;
+use crate::infrastructure::replication::Replicator;
+use crate::infrastructure::storage::Storage;
+use super::error::{escape_xml, S3Error};
+use super::{access, acl, admin, audit, auth, chunked, cors, encryption, events, inventory, lifecycle, list_objects, logging, notification, object, policy, post_object, public_access, region, replication, select, sqs, sts, virtual_host, website};
+
+// The largest object a single PutObject may upload to S3, instead of axum's 2 MB default
+const MAX_BODY_SIZE: usize = 5 * 1024 * 1024 * 1024;
 
 #[derive(Clone)]
 pub struct AppState {
-    pub buckets: Arc<Mutex<Vec<Bucket>>>,
+    pub storage: Arc<dyn Storage>,
+    // Request signatures are only checked when this is set
+    pub auth_enabled: bool,
+    // X-Forwarded-Proto is only trusted when a proxy in front of the server sets it
+    pub trust_proxy: bool,
+    // Hosts under these domains address a bucket, as in "bucket.s3.localhost"
+    pub domains: Vec<String>,
+    // Hosts under these domains are served as static websites
+    pub website_domains: Vec<String>,
+    // Region of requests that aren't signed for one
+    pub region: String,
+    pub notifier: Arc<Notifier>,
+    // Embedded SQS queues, answering on the same endpoint
+    pub queues: Arc<QueueService>,
+    // Recent changes, for the admin event stream
+    pub events: Arc<EventLog>,
+    // Local commands and function URLs standing in for Lambda functions
+    pub hooks: Arc<HookRunner>,
+    // Copies objects to the destination buckets of replication rules
+    pub replicator: Arc<Replicator>,
+    // Buffers server access log records until they are delivered to target buckets
+    pub access_logs: Arc<AccessLogger>,
+    // CloudTrail records of every S3 API call, when an audit trail is configured
+    pub audit: Option<Arc<AuditTrail>>,
+    // Writes inventory reports, on schedule or on demand
+    pub inventory: Arc<InventoryGenerator>,
 }
 
 pub fn create_router(state: AppState) -> Router {
+    let routes = Router::new()
+        .route("/", get(list_buckets).post(sts::handle))
+        .route(
+            "/{bucket}",
+            get(get_bucket)
+                .put(create_bucket)
+                .post(post_object::post_object)
+                .delete(delete_bucket)
+                .options(cors::preflight_bucket),
+        )
+        .route(
+            "/{bucket}/{*key}",
+            get(object::get_object)
+                .head(object::head_object)
+                .put(object::put_object)
+                .post(select::select_object_content)
+                .delete(object::delete_object)
+                .options(cors::preflight_object),
+        )
+        .layer(middleware::from_fn_with_state(state.clone(), access::authorize))
+        .layer(middleware::from_fn_with_state(state.clone(), events::record_changes))
+        .layer(middleware::from_fn_with_state(state.clone(), region::redirect_to_bucket_region))
+        .layer(middleware::from_fn(chunked::decode_aws_chunked))
+        .layer(middleware::from_fn_with_state(state.clone(), logging::log_access))
+        .layer(middleware::from_fn_with_state(state.clone(), audit::record_call))
+        // SQS requests are signed, but don't go through S3 routing and access control
+        .layer(middleware::from_fn_with_state(state.clone(), sqs::handle_sqs_requests))
+        // Signed like any other request, but outside of the bucket policy and ACLs
+        .merge(admin::router())
+        .layer(middleware::from_fn_with_state(state.clone(), auth::authenticate))
+        // Outermost, so that errors from authorization carry CORS headers too
+        .layer(middleware::from_fn_with_state(state.clone(), cors::add_cors_headers))
+        .layer(DefaultBodyLimit::max(MAX_BODY_SIZE))
+        .with_state(state.clone());
+    // Host-based addressing has to rewrite the path before any route is matched
     Router::new()
-        // .route("/", get(get_buckets))
-        .route("/{bucket}", get(get_bucket))
-        .route("/{bucket}", put(create_bucket))
-        // .route("/{bucket}/{object}", get(get_object))
-        // .route("/{bucket}/{object}", put(create_object))
-        .with_state(state)
+        .fallback_service(routes)
+        .layer(middleware::from_fn_with_state(state.clone(), virtual_host::route_virtual_host))
+        // Website endpoints answer for the bucket in their host, without the S3 API
+        .layer(middleware::from_fn_with_state(state, website::route_website))
+}
+
+/// Router of the separate website listener, serving every request as a website request.
+pub fn create_website_router(state: AppState) -> Router {
+    Router::new().fallback(website::serve_any_host).with_state(state)
+}
+
+pub(crate) async fn require_bucket(state: &AppState, bucket_name: &str) -> Result<Bucket, S3Error> {
+    state
+        .storage
+        .get_bucket(bucket_name)
+        .await?
+        .ok_or_else(|| S3Error::no_such_bucket(bucket_name))
+}
+
+// ListAllMyBucketsResult with the buckets the requester owns
+async fn list_buckets(State(state): State<AppState>, requester: Requester) -> Result<Response, S3Error> {
+    let owner = requester.identity().map(acl_config::Owner::from).ok_or_else(policy::access_denied)?;
+    let mut names = state.storage.list_buckets().await?;
+    names.sort();
+    let mut buckets = String::new();
+    for name in names {
+        let Some(bucket) = state.storage.get_bucket(&name).await? else {
+            continue;
+        };
+        if acl::bucket_acl(&state, &name).await?.is_owner(&requester) {
+            buckets.push_str(&format!(
+                "<Bucket><Name>{}</Name><CreationDate>{}</CreationDate></Bucket>",
+                escape_xml(&bucket.name),
+                bucket.creation_date.format("%Y-%m-%dT%H:%M:%S%.3fZ")
+            ));
+        }
+    }
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<ListAllMyBucketsResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\"><Owner><ID>{}</ID><DisplayName>{}</DisplayName></Owner><Buckets>{}</Buckets></ListAllMyBucketsResult>",
+        escape_xml(&owner.id),
+        escape_xml(&owner.display_name),
+        buckets
+    );
+    Ok(([(header::CONTENT_TYPE, "application/xml")], xml).into_response())
+}
+
+async fn get_bucket(
+    State(state): State<AppState>,
+    Path(bucket_name): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Response, S3Error> {
+    if params.contains_key("lifecycle") {
+        return lifecycle::get_bucket_lifecycle(&state, &bucket_name).await;
+    }
+    if params.contains_key("policy") {
+        return policy::get_bucket_policy(&state, &bucket_name).await;
+    }
+    if params.contains_key("acl") {
+        return acl::get_bucket_acl(&state, &bucket_name).await;
+    }
+    if params.contains_key("cors") {
+        return cors::get_bucket_cors(&state, &bucket_name).await;
+    }
+    if params.contains_key("location") {
+        return region::get_bucket_location(&state, &bucket_name).await;
+    }
+    if params.contains_key("publicAccessBlock") {
+        return public_access::get_public_access_block(&state, &bucket_name).await;
+    }
+    if params.contains_key("ownershipControls") {
+        return acl::get_bucket_ownership_controls(&state, &bucket_name).await;
+    }
+    if params.contains_key("notification") {
+        return notification::get_bucket_notification(&state, &bucket_name).await;
+    }
+    if params.contains_key("replication") {
+        return replication::get_bucket_replication(&state, &bucket_name).await;
+    }
+    if params.contains_key("website") {
+        return website::get_bucket_website(&state, &bucket_name).await;
+    }
+    if params.contains_key("logging") {
+        return logging::get_bucket_logging(&state, &bucket_name).await;
+    }
+    if params.contains_key("inventory") {
+        return inventory::get_bucket_inventory(&state, &bucket_name, &params).await;
+    }
+    if params.contains_key("encryption") {
+        return encryption::get_bucket_encryption(&state, &bucket_name).await;
+    }
+    list_objects::list_objects(&state, &bucket_name, &params).await
+}
+
+async fn create_bucket(
+    State(state): State<AppState>,
+    Path(bucket_name): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    requester: Requester,
+    uri: Uri,
+    headers: HeaderMap,
+    body: String,
+) -> Result<Response, S3Error> {
+    if params.contains_key("lifecycle") {
+        return lifecycle::put_bucket_lifecycle(&state, &bucket_name, &body).await;
+    }
+    if params.contains_key("policy") {
+        return policy::put_bucket_policy(&state, &bucket_name, &body).await;
+    }
+    if params.contains_key("acl") {
+        return acl::put_bucket_acl(&state, &bucket_name, &headers, &body).await;
+    }
+    if params.contains_key("cors") {
+        return cors::put_bucket_cors(&state, &bucket_name, &body).await;
+    }
+    if params.contains_key("publicAccessBlock") {
+        return public_access::put_public_access_block(&state, &bucket_name, &body).await;
+    }
+    if params.contains_key("ownershipControls") {
+        return acl::put_bucket_ownership_controls(&state, &bucket_name, &body).await;
+    }
+    if params.contains_key("notification") {
+        return notification::put_bucket_notification(&state, &bucket_name, &headers, &body).await;
+    }
+    if params.contains_key("replication") {
+        return replication::put_bucket_replication(&state, &bucket_name, &body).await;
+    }
+    if params.contains_key("website") {
+        return website::put_bucket_website(&state, &bucket_name, &body).await;
+    }
+    if params.contains_key("logging") {
+        return logging::put_bucket_logging(&state, &bucket_name, &body).await;
+    }
+    if params.contains_key("inventory") {
+        return inventory::put_bucket_inventory(&state, &bucket_name, &params, &body).await;
+    }
+    if params.contains_key("encryption") {
+        return encryption::put_bucket_encryption(&state, &bucket_name, &body).await;
+    }
+    if !Bucket::validate_name(&bucket_name) {
+        return Err(S3Error::new(StatusCode::BAD_REQUEST, "InvalidBucketName", "The specified bucket is not valid.")
+            .with_resource(&bucket_name));
+    }
+    require_new_bucket(&state, &bucket_name, &requester).await?;
+    let region = region::new_bucket_region(&state, &headers, &uri, &body)?;
+    let (controls, bucket_acl) = acl::new_bucket_acl(&headers, &requester)?;
+    let controls = controls.to_xml().map_err(S3Error::internal)?;
+    let bucket = Bucket::new(bucket_name).with_region(region);
+    state.storage.create_bucket(&bucket).await?;
+    state
+        .storage
+        .put_bucket_config(&bucket.name, acl_config::OWNERSHIP_CONFIG_NAME, &controls)
+        .await?;
+    state
+        .storage
+        .put_bucket_config(&bucket.name, acl_config::CONFIG_NAME, &bucket_acl.to_xml())
+        .await?;
+    let mut response = StatusCode::OK.into_response();
+    object::insert_header(&mut response, header::LOCATION.as_str(), &format!("/{}", bucket.name));
+    Ok(response)
 }
 
-async fn get_bucket(State(state): State<AppState>, Path(bucket_name): Path<String>) -> Json<Option<Bucket>> {
-    let buckets = state.buckets.lock().unwrap();
-    Json(buckets.iter().find(|b| b.name == bucket_name).cloned())
+// Creating a bucket that exists fails, so that its owner, ACL and region are never rewritten
+async fn require_new_bucket(state: &AppState, bucket_name: &str, requester: &Requester) -> Result<(), S3Error> {
+    if state.storage.get_bucket(bucket_name).await?.is_none() {
+        return Ok(());
+    }
+    let error = if acl::bucket_acl(state, bucket_name).await?.is_owner(requester) {
+        S3Error::new(
+            StatusCode::CONFLICT,
+            "BucketAlreadyOwnedByYou",
+            "Your previous request to create the named bucket succeeded and you already own it.",
+        )
+    } else {
+        S3Error::new(
+            StatusCode::CONFLICT,
+            "BucketAlreadyExists",
+            "The requested bucket name is not available. The bucket namespace is shared by all users of the system. Please select a different name and try again.",
+        )
+    };
+    Err(error.with_detail("BucketName", bucket_name))
 }
 
-async fn create_bucket(State(state): State<AppState>, Path(bucket_name): Path<String>) -> Json<Bucket> {
-    let bucket = Bucket::new(bucket_name);
-    state.buckets.lock().unwrap().push(bucket.clone());
-    Json(bucket)
-}
\ No newline at end of file
+async fn delete_bucket(
+    State(state): State<AppState>,
+    Path(bucket_name): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Response, S3Error> {
+    if params.contains_key("lifecycle") {
+        return lifecycle::delete_bucket_lifecycle(&state, &bucket_name).await;
+    }
+    if params.contains_key("policy") {
+        return policy::delete_bucket_policy(&state, &bucket_name).await;
+    }
+    if params.contains_key("cors") {
+        return cors::delete_bucket_cors(&state, &bucket_name).await;
+    }
+    if params.contains_key("publicAccessBlock") {
+        return public_access::delete_public_access_block(&state, &bucket_name).await;
+    }
+    if params.contains_key("ownershipControls") {
+        return acl::delete_bucket_ownership_controls(&state, &bucket_name).await;
+    }
+    if params.contains_key("replication") {
+        return replication::delete_bucket_replication(&state, &bucket_name).await;
+    }
+    if params.contains_key("website") {
+        return website::delete_bucket_website(&state, &bucket_name).await;
+    }
+    if params.contains_key("inventory") {
+        return inventory::delete_bucket_inventory(&state, &bucket_name, &params).await;
+    }
+    if params.contains_key("encryption") {
+        return encryption::delete_bucket_encryption(&state, &bucket_name).await;
+    }
+    require_bucket(&state, &bucket_name).await?;
+    state.storage.delete_bucket(&bucket_name).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+    use tower::ServiceExt;
+    use crate::domain::identity::{Identity, DEFAULT_ACCOUNT_ID};
+    use crate::infrastructure::storage::FileStorage;
+
+    // The data directory is removed when the returned TempDir is dropped
+    pub(crate) fn test_state() -> (TempDir, AppState) {
+        let data_dir = TempDir::new().unwrap();
+        let storage: Arc<dyn Storage> = Arc::new(FileStorage::new(data_dir.path().to_path_buf()));
+        let queues = Arc::new(QueueService::new(DEFAULT_ACCOUNT_ID, "us-east-1"));
+        let events = Arc::new(EventLog::default());
+        let hooks = Arc::new(HookRunner::new(storage.clone(), "us-east-1"));
+        let notifier = Arc::new(Notifier::new(storage.clone(), queues.clone(), hooks.clone(), events.clone(), Vec::new(), 0));
+        let (replicator, _) = Replicator::spawn(storage.clone(), Duration::ZERO);
+        let state = AppState {
+            storage: storage.clone(),
+            auth_enabled: true,
+            trust_proxy: false,
+            domains: Vec::new(),
+            website_domains: Vec::new(),
+            region: "us-east-1".to_string(),
+            notifier,
+            queues,
+            events,
+            hooks,
+            replicator,
+            access_logs: Arc::new(AccessLogger::new(storage.clone(), Duration::from_secs(60))),
+            audit: None,
+            inventory: Arc::new(InventoryGenerator::new(storage, Duration::from_secs(60))),
+        };
+        (data_dir, state)
+    }
+
+    async fn create(state: &AppState, bucket_name: &str, requester: Requester) -> Result<Response, S3Error> {
+        create_bucket(
+            State(state.clone()),
+            Path(bucket_name.to_string()),
+            Query(HashMap::new()),
+            requester,
+            Uri::from_static("/"),
+            HeaderMap::new(),
+            String::new(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_put_object_larger_than_default_body_limit() {
+        let (_data_dir, mut state) = test_state();
+        state.auth_enabled = false;
+        create(&state, "large", Requester::Authenticated(Identity::owner())).await.unwrap();
+
+        let content = vec![b'x'; 3 * 1024 * 1024];
+        let request = axum::http::Request::put("/large/object.bin")
+            .header(header::HOST, "localhost")
+            .body(axum::body::Body::from(content.clone()))
+            .unwrap();
+        let response = create_router(state.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(state.storage.get_object("large", "object.bin").await.unwrap().content, content);
+    }
+
+    #[tokio::test]
+    async fn test_create_existing_bucket() {
+        let (_data_dir, state) = test_state();
+        let owner = Requester::Authenticated(Identity::owner());
+        let other = Requester::Authenticated(Identity::user("other", &"b".repeat(64)));
+        create(&state, "owned", owner.clone()).await.unwrap();
+
+        let error = create(&state, "owned", other).await.unwrap_err();
+        assert_eq!((error.status, error.code), (StatusCode::CONFLICT, "BucketAlreadyExists"));
+        let error = create(&state, "owned", owner.clone()).await.unwrap_err();
+        assert_eq!(error.code, "BucketAlreadyOwnedByYou");
+        // The bucket and its ACL are left as they were
+        assert!(acl::bucket_acl(&state, "owned").await.unwrap().is_owner(&owner));
+    }
+
+    #[tokio::test]
+    async fn test_create_bucket_with_invalid_name() {
+        let (_data_dir, state) = test_state();
+        for name in ["BAD_NAME", "ab", "192.168.0.1"] {
+            let error = create(&state, name, Requester::Authenticated(Identity::owner())).await.unwrap_err();
+            assert_eq!((error.status, error.code), (StatusCode::BAD_REQUEST, "InvalidBucketName"));
+        }
+    }
+}
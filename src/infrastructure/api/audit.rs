@@ -0,0 +1,142 @@
+use std::net::SocketAddr;
+use axum::body::HttpBody;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{header, HeaderMap};
+use axum::middleware::Next;
+use axum::response::Response;
+use chrono::Utc;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+use crate::domain::audit::{AuditRecord, UserIdentity};
+use crate::domain::identity::{Requester, DEFAULT_ACCOUNT_ID};
+use crate::domain::sigv4::{Authorization, Credential};
+use super::access::requester_of;
+use super::api::AppState;
+use super::error::ResponseError;
+use super::logging::authentication;
+use super::object::header_str;
+use super::operation::S3Operation;
+use super::sts;
+
+/// Middleware recording every S3 API call in the audit trail, as a CloudTrail record.
+pub async fn record_call(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(trail) = state.audit.clone() else {
+        return next.run(request).await;
+    };
+    let operation = S3Operation::classify(request.method(), request.uri(), request.headers());
+    // STS calls share the endpoint, and preflights aren't API calls
+    if sts::is_sts_request(&request) || matches!(operation.name, "PreflightRequest" | "Unknown") {
+        return next.run(request).await;
+    }
+    let time = Utc::now();
+    let requester = requester_of(request.extensions());
+    let headers = request.headers().clone();
+    let query = request.uri().query().unwrap_or_default().to_string();
+    let source_ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string())
+        .unwrap_or_else(|| "127.0.0.1".to_string());
+
+    let response = next.run(request).await;
+
+    let region = match &operation.bucket {
+        Some(bucket) => match state.storage.get_bucket(bucket).await {
+            Ok(Some(bucket)) => bucket.region,
+            _ => state.region.clone(),
+        },
+        None => state.region.clone(),
+    };
+    let identity = UserIdentity::from_requester(&requester, access_key_id(&requester, &headers, &query));
+    let mut record = AuditRecord::new(operation.name, identity, &region, DEFAULT_ACCOUNT_ID);
+    if let Some(bucket) = &operation.bucket {
+        record = record.with_resources(bucket, operation.key.as_deref(), DEFAULT_ACCOUNT_ID);
+    }
+    record.event_time = time;
+    record.source_ip_address = source_ip;
+    record.user_agent = header_str(&headers, header::USER_AGENT.as_str()).unwrap_or_default().to_string();
+    if let Some(error) = response.extensions().get::<ResponseError>() {
+        record.error_code = Some(error.code.to_string());
+        record.error_message = Some(error.message.clone());
+    }
+    record.request_parameters = request_parameters(&operation, &headers);
+    let request_id = Uuid::new_v4().simple().to_string()[..16].to_uppercase();
+
+    let mut additional = Map::new();
+    let (signature_version, authentication_type) = authentication(&headers, &query);
+    if let Some(version) = signature_version {
+        additional.insert("SignatureVersion".to_string(), Value::from(version));
+    }
+    if let Some(method) = authentication_type {
+        additional.insert("AuthenticationMethod".to_string(), Value::from(method));
+    }
+    let bytes_in = header_str(&headers, header::CONTENT_LENGTH.as_str())
+        .and_then(|length| length.parse::<u64>().ok())
+        .unwrap_or_default();
+    let bytes_out = response.body().size_hint().exact().unwrap_or_default();
+    additional.insert("bytesTransferredIn".to_string(), Value::from(bytes_in));
+    additional.insert("bytesTransferredOut".to_string(), Value::from(bytes_out));
+    additional.insert("x-amz-id-2".to_string(), Value::from(request_id.clone()));
+    record.additional_event_data = additional;
+    record.request_id = request_id;
+    record.event_id = Uuid::new_v4().to_string();
+
+    trail.record(record);
+    response
+}
+
+// The bucket, key and host, and the query parameters, subresources included
+fn request_parameters(operation: &S3Operation, headers: &HeaderMap) -> Map<String, Value> {
+    let mut parameters: Map<String, Value> = operation
+        .query
+        .iter()
+        .map(|(name, value)| (name.clone(), Value::from(value.clone())))
+        .collect();
+    if let Some(bucket) = &operation.bucket {
+        parameters.insert("bucketName".to_string(), Value::from(bucket.clone()));
+    }
+    if let Some(key) = &operation.key {
+        parameters.insert("key".to_string(), Value::from(key.clone()));
+    }
+    if let Some(host) = header_str(headers, header::HOST.as_str()) {
+        parameters.insert("Host".to_string(), Value::from(host));
+    }
+    parameters
+}
+
+// Access key a signed request was signed with, from its credential scope
+fn access_key_id(requester: &Requester, headers: &HeaderMap, query: &str) -> Option<String> {
+    requester.identity()?;
+    if let Some(authorization) = header_str(headers, header::AUTHORIZATION.as_str()) {
+        return Authorization::parse(authorization).ok().map(|auth| auth.credential.access_key_id);
+    }
+    let (_, credential) = serde_urlencoded::from_str::<Vec<(String, String)>>(query)
+        .ok()?
+        .into_iter()
+        .find(|(name, _)| name == "X-Amz-Credential")?;
+    Credential::parse(&credential).ok().map(|credential| credential.access_key_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Method;
+    use crate::domain::identity::Identity;
+
+    #[test]
+    fn test_request_parameters_and_access_key() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, "localhost:3000".parse().unwrap());
+        let operation = S3Operation::classify(&Method::GET, &"/site/a%20b.txt?acl".parse().unwrap(), &headers);
+        let parameters = request_parameters(&operation, &headers);
+        assert_eq!(parameters["bucketName"], "site");
+        assert_eq!(parameters["key"], "a b.txt");
+        assert_eq!(parameters["Host"], "localhost:3000");
+        assert_eq!(parameters["acl"], "");
+
+        let owner = Requester::Authenticated(Identity::owner());
+        let query = "X-Amz-Algorithm=AWS4-HMAC-SHA256&X-Amz-Credential=AKIDEXAMPLE%2F20240101%2Fus-east-1%2Fs3%2Faws4_request";
+        assert_eq!(access_key_id(&owner, &headers, query).as_deref(), Some("AKIDEXAMPLE"));
+        assert_eq!(access_key_id(&Requester::Anonymous, &headers, query), None);
+    }
+}
@@ -0,0 +1,662 @@
+use std::collections::HashMap;
+use axum::body::Body;
+use axum::extract::{OriginalUri, Request, State};
+use axum::http::{header, HeaderMap, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Duration, Utc};
+use crate::domain::credentials::{self, AccessKey, CredentialStore};
+use crate::domain::identity::Requester;
+use crate::domain::sigv4::{self, Authorization, Credential, StreamingSigner};
+use super::api::AppState;
+use super::error::S3Error;
+use super::object::header_str;
+use super::{policy, sqs, sts};
+
+const MAX_SKEW_SECS: i64 = 15 * 60;
+const MAX_PRESIGNED_EXPIRES_SECS: i64 = 7 * 24 * 60 * 60;
+
+/// Middleware verifying request signatures when authentication is enabled, and
+/// attaching the signer as the request's `Requester`. Unsigned requests are anonymous.
+pub async fn authenticate(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if !state.auth_enabled {
+        return next.run(request).await;
+    }
+    let credentials = match credential_store(&state).await {
+        Ok(credentials) => credentials,
+        Err(e) => return e.into_response(),
+    };
+    let sqs_protocol = sqs::is_sqs_request(&request).then(|| sqs::Protocol::of(request.headers()));
+    let is_sts = sts::is_sts_request(&request);
+    match verify(&credentials, request).await {
+        Ok(request) => next.run(request).await,
+        Err(e) => match sqs_protocol {
+            Some(protocol) => sqs::error_response(e, protocol),
+            // STS clients only understand errors of the query protocol
+            None if is_sts => sts::error_response(e),
+            None => e.into_response(),
+        },
+    }
+}
+
+async fn verify(credentials: &CredentialStore, mut request: Request) -> Result<Request, S3Error> {
+    let authorization = header_str(request.headers(), header::AUTHORIZATION.as_str()).map(str::to_string);
+    let query: HashMap<String, String> = request
+        .uri()
+        .query()
+        .and_then(|query| serde_urlencoded::from_str(query).ok())
+        .unwrap_or_default();
+    let presigned = query.contains_key("X-Amz-Algorithm");
+    if query.contains_key("AWSAccessKeyId") && query.contains_key("Signature") {
+        return Err(unsupported_mechanism());
+    }
+
+    // Preflights never carry credentials
+    if request.method() == Method::OPTIONS || (authorization.is_none() && !presigned) {
+        request.extensions_mut().insert(Requester::Anonymous);
+        return Ok(request);
+    }
+    let requester = match authorization {
+        Some(_) if presigned => {
+            return Err(S3Error::invalid_argument(
+                "Only one auth mechanism allowed; only the X-Amz-Algorithm query parameter, Signature query string parameter or the Authorization header should be specified",
+            ))
+        }
+        Some(authorization) => {
+            let service = if sqs::is_sqs_request(&request) {
+                "sqs"
+            } else if sts::is_sts_request(&request) {
+                "sts"
+            } else {
+                "s3"
+            };
+            let mut body_hash = None;
+            if service != "s3" && !request.headers().contains_key("x-amz-content-sha256") {
+                let (buffered, hash) = hash_payload(request).await?;
+                (request, body_hash) = (buffered, Some(hash));
+            }
+            let (requester, payload_hash, signer) =
+                verify_header(credentials, &request, &authorization, service, body_hash)?;
+            request = check_payload(request, &payload_hash).await?;
+            // Signed chunks are verified once the aws-chunked body is decoded
+            if let Some(signer) = signer {
+                request.extensions_mut().insert(signer);
+            }
+            requester
+        }
+        None => verify_query(credentials, &request, &query, Utc::now())?,
+    };
+    request.extensions_mut().insert(requester);
+    Ok(request)
+}
+
+/// The persisted access keys, read on every request so that changes apply right away.
+pub async fn credential_store(state: &AppState) -> Result<CredentialStore, S3Error> {
+    match state.storage.get_server_config(credentials::CONFIG_NAME).await? {
+        Some(json) => CredentialStore::from_json(&json).map_err(S3Error::internal),
+        None => Ok(CredentialStore::default()),
+    }
+}
+
+pub async fn save_credential_store(state: &AppState, store: &CredentialStore) -> Result<(), S3Error> {
+    let json = store.to_json().map_err(S3Error::internal)?;
+    state.storage.put_server_config(credentials::CONFIG_NAME, &json).await?;
+    Ok(())
+}
+
+// Authorization: AWS4-HMAC-SHA256 Credential=..., SignedHeaders=..., Signature=...
+fn verify_header(
+    credentials: &CredentialStore,
+    request: &Request,
+    authorization: &str,
+    service: &str,
+    body_hash: Option<String>,
+) -> Result<(Requester, String, Option<StreamingSigner>), S3Error> {
+    if !authorization.starts_with(sigv4::ALGORITHM) {
+        return Err(unsupported_mechanism());
+    }
+    let authorization = Authorization::parse(authorization).map_err(malformed_authorization)?;
+    let credential = &authorization.credential;
+    let session_token = header_str(request.headers(), "x-amz-security-token");
+    let access_key = active_key(credentials, &credential.access_key_id, session_token, Utc::now())?;
+
+    let headers = request.headers();
+    let (amz_date, request_time) = request_time(headers)?;
+    check_credential_scope(credential, &amz_date, service).map_err(malformed_authorization)?;
+    check_skew(&amz_date, request_time, Utc::now())?;
+    check_signed_headers(headers, &authorization.signed_headers)?;
+
+    let payload_hash = header_str(headers, "x-amz-content-sha256")
+        .map(str::to_string)
+        .or(body_hash)
+        .ok_or_else(|| {
+            S3Error::new(
+                StatusCode::BAD_REQUEST,
+                "InvalidRequest",
+                "Missing required header for this request: x-amz-content-sha256",
+            )
+        })?;
+    let signing_key = check_signature(
+        access_key,
+        request,
+        request.uri().query().unwrap_or_default(),
+        &amz_date,
+        credential,
+        &authorization.signed_headers,
+        &payload_hash,
+        &authorization.signature,
+    )?;
+    let signer = (payload_hash == sigv4::STREAMING_SIGNED_PAYLOAD
+        || payload_hash == sigv4::STREAMING_SIGNED_PAYLOAD_TRAILER)
+        .then(|| StreamingSigner::new(signing_key, &amz_date, credential, &authorization.signature));
+    Ok((Requester::Authenticated(access_key.identity.clone()), payload_hash, signer))
+}
+
+// Presigned URLs carry the signature in X-Amz-* query parameters
+fn verify_query(
+    credentials: &CredentialStore,
+    request: &Request,
+    query: &HashMap<String, String>,
+    now: DateTime<Utc>,
+) -> Result<Requester, S3Error> {
+    let param = |name: &str| query.get(name).map(String::as_str);
+    if param("X-Amz-Algorithm") != Some(sigv4::ALGORITHM) {
+        return Err(query_parameters_error("X-Amz-Algorithm only supports \"AWS4-HMAC-SHA256\""));
+    }
+    let (Some(credential), Some(amz_date), Some(expires), Some(signed_headers), Some(signature)) = (
+        param("X-Amz-Credential"),
+        param("X-Amz-Date"),
+        param("X-Amz-Expires"),
+        param("X-Amz-SignedHeaders"),
+        param("X-Amz-Signature"),
+    ) else {
+        return Err(query_parameters_error(
+            "Query-string authentication version 4 requires the X-Amz-Algorithm, X-Amz-Credential, X-Amz-Signature, X-Amz-Date, X-Amz-SignedHeaders, and X-Amz-Expires parameters.",
+        ));
+    };
+
+    let credential_error = |reason: String| {
+        query_parameters_error(format!("Error parsing the X-Amz-Credential parameter; {}", reason))
+    };
+    let credential = Credential::parse(credential).map_err(credential_error)?;
+    let request_time = sigv4::parse_amz_date(amz_date).ok_or_else(|| {
+        query_parameters_error("X-Amz-Date must be in the ISO8601 Long Format \"yyyyMMdd'T'HHmmss'Z'\"")
+    })?;
+    let expires: i64 = expires
+        .parse()
+        .map_err(|_| query_parameters_error("X-Amz-Expires should be a number"))?;
+    if expires < 0 {
+        return Err(query_parameters_error("X-Amz-Expires must be non-negative"));
+    }
+    if expires > MAX_PRESIGNED_EXPIRES_SECS {
+        return Err(query_parameters_error(format!(
+            "X-Amz-Expires must be less than a week (in seconds) that is; {}",
+            MAX_PRESIGNED_EXPIRES_SECS
+        )));
+    }
+    check_credential_scope(&credential, amz_date, "s3").map_err(credential_error)?;
+    let session_token = query.get("X-Amz-Security-Token").map(String::as_str);
+    let access_key = active_key(credentials, &credential.access_key_id, session_token, now)?;
+
+    let server_time = now.format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    if request_time - now > Duration::seconds(MAX_SKEW_SECS) {
+        return Err(policy::access_denied_with("Request is not valid yet")
+            .with_detail("X-Amz-Date", amz_date)
+            .with_detail("ServerTime", server_time));
+    }
+    let expiration = request_time + Duration::seconds(expires);
+    if now > expiration {
+        return Err(policy::access_denied_with("Request has expired")
+            .with_detail("X-Amz-Expires", expires.to_string())
+            .with_detail("Expires", expiration.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+            .with_detail("ServerTime", server_time));
+    }
+
+    let signed_headers: Vec<String> = signed_headers.split(';').map(str::to_string).collect();
+    check_signed_headers(request.headers(), &signed_headers)?;
+    // Every query parameter but the signature itself is signed
+    let signed_query: Vec<&str> = request
+        .uri()
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.starts_with("X-Amz-Signature="))
+        .collect();
+    check_signature(
+        access_key,
+        request,
+        &signed_query.join("&"),
+        amz_date,
+        &credential,
+        &signed_headers,
+        sigv4::UNSIGNED_PAYLOAD,
+        signature,
+    )?;
+    Ok(Requester::Authenticated(access_key.identity.clone()))
+}
+
+/// Verifies the signature of a browser-based POST upload, which signs its base64 policy.
+/// `fields` holds the form fields with lowercased names.
+pub fn verify_post_signature(
+    credentials: &CredentialStore,
+    fields: &HashMap<String, String>,
+) -> Result<Requester, S3Error> {
+    if fields.contains_key("awsaccesskeyid") || fields.contains_key("signature") {
+        return Err(unsupported_mechanism());
+    }
+    let field = |name: &str| {
+        fields.get(name).map(String::as_str).ok_or_else(|| {
+            S3Error::invalid_argument(format!(
+                "Bucket POST must contain a field named '{}'.  If it is specified, please check the order of the fields.",
+                name
+            ))
+        })
+    };
+    if field("x-amz-algorithm")? != sigv4::ALGORITHM {
+        return Err(S3Error::invalid_argument("Only AWS4-HMAC-SHA256 is supported for x-amz-algorithm"));
+    }
+    let policy = field("policy")?;
+    let amz_date = field("x-amz-date")?;
+    let signature = field("x-amz-signature")?;
+    let credential = Credential::parse(field("x-amz-credential")?).map_err(S3Error::invalid_argument)?;
+    sigv4::parse_amz_date(amz_date)
+        .ok_or_else(|| S3Error::invalid_argument("X-Amz-Date must be in the ISO8601 Long Format \"yyyyMMdd'T'HHmmss'Z'\""))?;
+    check_credential_scope(&credential, amz_date, "s3").map_err(S3Error::invalid_argument)?;
+    let session_token = fields.get("x-amz-security-token").map(String::as_str);
+    let access_key = active_key(credentials, &credential.access_key_id, session_token, Utc::now())?;
+
+    let expected = sigv4::signature(&sigv4::signing_key(&access_key.secret_access_key, &credential), policy);
+    if !sigv4::signatures_match(&expected, signature) {
+        return Err(S3Error::new(
+            StatusCode::FORBIDDEN,
+            "SignatureDoesNotMatch",
+            "The request signature we calculated does not match the signature you provided. Check your key and signing method.",
+        )
+        .with_detail("AWSAccessKeyId", credential.access_key_id.as_str())
+        .with_detail("StringToSign", policy)
+        .with_detail("SignatureProvided", signature));
+    }
+    Ok(Requester::Authenticated(access_key.identity.clone()))
+}
+
+fn check_credential_scope(credential: &Credential, amz_date: &str, service: &str) -> Result<(), String> {
+    if credential.date != amz_date[..8] {
+        return Err(format!(
+            "Invalid credential date \"{}\". This date is not the same as X-Amz-Date: \"{}\".",
+            credential.date,
+            &amz_date[..8]
+        ));
+    }
+    if credential.service != service {
+        return Err(format!(
+            "the credential is for an incorrect service \"{}\". This endpoint belongs to \"{}\".",
+            credential.service, service
+        ));
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn check_signature(
+    access_key: &AccessKey,
+    request: &Request,
+    query: &str,
+    amz_date: &str,
+    credential: &Credential,
+    signed_headers: &[String],
+    payload_hash: &str,
+    provided: &str,
+) -> Result<Vec<u8>, S3Error> {
+    let header_pairs: Vec<(String, String)> = request
+        .headers()
+        .iter()
+        .map(|(name, value)| (name.as_str().to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+        .collect();
+    // Virtual-hosted-style requests were signed before their path got the bucket prefixed
+    let path = match request.extensions().get::<OriginalUri>() {
+        Some(OriginalUri(uri)) => uri.path(),
+        None => request.uri().path(),
+    };
+    let canonical_request = sigv4::canonical_request(
+        request.method().as_str(),
+        path,
+        query,
+        &header_pairs,
+        signed_headers,
+        payload_hash,
+    );
+    let string_to_sign = sigv4::string_to_sign(amz_date, credential, &canonical_request);
+    let signing_key = sigv4::signing_key(&access_key.secret_access_key, credential);
+    let expected = sigv4::signature(&signing_key, &string_to_sign);
+    if sigv4::signatures_match(&expected, provided) {
+        Ok(signing_key)
+    } else {
+        Err(signature_does_not_match(&credential.access_key_id, &string_to_sign, provided, &canonical_request))
+    }
+}
+
+// The signing time from x-amz-date, or from Date for clients that only send that
+fn request_time(headers: &HeaderMap) -> Result<(String, DateTime<Utc>), S3Error> {
+    let missing_date = || {
+        S3Error::new(
+            StatusCode::FORBIDDEN,
+            "AccessDenied",
+            "AWS authentication requires a valid Date or x-amz-date header",
+        )
+    };
+    if let Some(amz_date) = header_str(headers, "x-amz-date") {
+        let time = sigv4::parse_amz_date(amz_date).ok_or_else(missing_date)?;
+        return Ok((amz_date.to_string(), time));
+    }
+    let date = header_str(headers, header::DATE.as_str()).ok_or_else(missing_date)?;
+    let time = DateTime::parse_from_rfc2822(date)
+        .map_err(|_| missing_date())?
+        .with_timezone(&Utc);
+    Ok((time.format(sigv4::AMZ_DATE_FORMAT).to_string(), time))
+}
+
+fn check_skew(amz_date: &str, request_time: DateTime<Utc>, now: DateTime<Utc>) -> Result<(), S3Error> {
+    if (now - request_time).num_seconds().abs() > MAX_SKEW_SECS {
+        return Err(S3Error::new(
+            StatusCode::FORBIDDEN,
+            "RequestTimeTooSkewed",
+            "The difference between the request time and the current time is too large.",
+        )
+        .with_detail("RequestTime", amz_date)
+        .with_detail("ServerTime", now.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+        .with_detail("MaxAllowedSkewMilliseconds", (MAX_SKEW_SECS * 1000).to_string()));
+    }
+    Ok(())
+}
+
+// Host and every x-amz-* header sent have to be covered by the signature
+fn check_signed_headers(headers: &HeaderMap, signed_headers: &[String]) -> Result<(), S3Error> {
+    let mut unsigned: Vec<&str> = headers
+        .keys()
+        .map(|name| name.as_str())
+        .filter(|name| *name == "host" || name.starts_with("x-amz-"))
+        .filter(|name| !signed_headers.iter().any(|signed| signed == name))
+        .collect();
+    if !signed_headers.iter().any(|signed| signed == "host") && !unsigned.contains(&"host") {
+        unsigned.push("host");
+    }
+    if unsigned.is_empty() {
+        return Ok(());
+    }
+    unsigned.sort_unstable();
+    unsigned.dedup();
+    Err(S3Error::new(
+        StatusCode::FORBIDDEN,
+        "AccessDenied",
+        "There were headers present in the request which were not signed",
+    )
+    .with_detail("HeadersNotSigned", unsigned.join(", ")))
+}
+
+// Buffers the body to compare it with x-amz-content-sha256 when that holds a digest
+async fn check_payload(request: Request, payload_hash: &str) -> Result<Request, S3Error> {
+    if payload_hash == sigv4::UNSIGNED_PAYLOAD || payload_hash.starts_with("STREAMING-") {
+        return Ok(request);
+    }
+    if payload_hash.len() != 64 || !payload_hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(S3Error::invalid_argument(
+            "x-amz-content-sha256 must be UNSIGNED-PAYLOAD, STREAMING-AWS4-HMAC-SHA256-PAYLOAD, or a valid sha256 value.",
+        ));
+    }
+
+    let (parts, body) = request.into_parts();
+    let body = axum::body::to_bytes(body, usize::MAX)
+        .await
+        .map_err(S3Error::internal)?;
+    let computed = sigv4::sha256_hex(&body);
+    if !computed.eq_ignore_ascii_case(payload_hash) {
+        return Err(S3Error::new(
+            StatusCode::BAD_REQUEST,
+            "XAmzContentSHA256Mismatch",
+            "The provided 'x-amz-content-sha256' header does not match what was computed.",
+        )
+        .with_detail("ClientComputedContentSHA256", payload_hash)
+        .with_detail("S3ComputedContentSHA256", computed));
+    }
+    Ok(Request::from_parts(parts, Body::from(body)))
+}
+
+// Only S3 requires x-amz-content-sha256, other services sign the digest of the body
+async fn hash_payload(request: Request) -> Result<(Request, String), S3Error> {
+    let (parts, body) = request.into_parts();
+    let body = axum::body::to_bytes(body, usize::MAX)
+        .await
+        .map_err(S3Error::internal)?;
+    let payload_hash = sigv4::sha256_hex(&body);
+    Ok((Request::from_parts(parts, Body::from(body)), payload_hash))
+}
+
+// Signature Version 2, in the header or the query string
+fn unsupported_mechanism() -> S3Error {
+    S3Error::new(
+        StatusCode::BAD_REQUEST,
+        "InvalidRequest",
+        "The authorization mechanism you have provided is not supported. Please use AWS4-HMAC-SHA256.",
+    )
+}
+
+fn query_parameters_error(reason: impl Into<String>) -> S3Error {
+    S3Error::new(StatusCode::BAD_REQUEST, "AuthorizationQueryParametersError", reason)
+}
+
+pub fn malformed_authorization(reason: impl std::fmt::Display) -> S3Error {
+    S3Error::new(
+        StatusCode::BAD_REQUEST,
+        "AuthorizationHeaderMalformed",
+        format!("The authorization header is malformed; {}", reason),
+    )
+}
+
+// Disabled keys are as good as unknown. Temporary credentials must come with their
+// session token, and stop working once they expire.
+fn active_key<'a>(
+    credentials: &'a CredentialStore,
+    access_key_id: &str,
+    session_token: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<&'a AccessKey, S3Error> {
+    let key = credentials
+        .get(access_key_id)
+        .filter(|key| key.is_active())
+        .ok_or_else(|| invalid_access_key_id(access_key_id))?;
+    match (&key.session_token, session_token) {
+        (Some(_), None) => return Err(invalid_access_key_id(access_key_id)),
+        (Some(expected), Some(provided)) if expected == provided => {}
+        (None, None) => {}
+        _ => {
+            return Err(S3Error::new(
+                StatusCode::BAD_REQUEST,
+                "InvalidToken",
+                "The provided token is malformed or otherwise invalid.",
+            ))
+        }
+    }
+    if key.is_expired(now) {
+        return Err(S3Error::new(StatusCode::BAD_REQUEST, "ExpiredToken", "The provided token has expired.")
+            .with_detail("Token-0", session_token.unwrap_or_default()));
+    }
+    Ok(key)
+}
+
+pub fn invalid_access_key_id(access_key_id: &str) -> S3Error {
+    S3Error::new(
+        StatusCode::FORBIDDEN,
+        "InvalidAccessKeyId",
+        "The AWS Access Key Id you provided does not exist in our records.",
+    )
+    .with_detail("AWSAccessKeyId", access_key_id)
+}
+
+pub fn signature_does_not_match(
+    access_key_id: &str,
+    string_to_sign: &str,
+    signature: &str,
+    canonical_request: &str,
+) -> S3Error {
+    let bytes = |value: &str| value.bytes().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ");
+    S3Error::new(
+        StatusCode::FORBIDDEN,
+        "SignatureDoesNotMatch",
+        "The request signature we calculated does not match the signature you provided. Check your key and signing method.",
+    )
+    .with_detail("AWSAccessKeyId", access_key_id)
+    .with_detail("StringToSign", string_to_sign)
+    .with_detail("SignatureProvided", signature)
+    .with_detail("StringToSignBytes", bytes(string_to_sign))
+    .with_detail("CanonicalRequest", canonical_request)
+    .with_detail("CanonicalRequestBytes", bytes(canonical_request))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::credentials::KeyStatus;
+    use crate::domain::identity::Identity;
+
+    const SECRET: &str = "secret";
+
+    fn store() -> CredentialStore {
+        let mut store = CredentialStore::default();
+        store.add(AccessKey::new("AKID".to_string(), SECRET.to_string(), Identity::owner())).unwrap();
+        store
+    }
+
+    // A presigned GET URL for /bucket/key, signed at `amz_date`
+    fn presigned_request(amz_date: &str, expires: u32) -> Request {
+        let query = format!(
+            "X-Amz-Algorithm=AWS4-HMAC-SHA256&X-Amz-Credential=AKID%2F{}%2Fus-east-1%2Fs3%2Faws4_request&X-Amz-Date={}&X-Amz-Expires={}&X-Amz-SignedHeaders=host",
+            &amz_date[..8],
+            amz_date,
+            expires
+        );
+        let credential = Credential::parse(&format!("AKID/{}/us-east-1/s3/aws4_request", &amz_date[..8])).unwrap();
+        let headers = vec![("host".to_string(), "localhost".to_string())];
+        let canonical = sigv4::canonical_request("GET", "/bucket/key", &query, &headers, &["host".to_string()], sigv4::UNSIGNED_PAYLOAD);
+        let signature = sigv4::signature(
+            &sigv4::signing_key(SECRET, &credential),
+            &sigv4::string_to_sign(amz_date, &credential, &canonical),
+        );
+        Request::builder()
+            .uri(format!("/bucket/key?{}&X-Amz-Signature={}", query, signature))
+            .header("host", "localhost")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    fn verify_presigned(request: &Request, now: &str) -> Result<Requester, S3Error> {
+        let query = serde_urlencoded::from_str(request.uri().query().unwrap()).unwrap();
+        verify_query(&store(), request, &query, sigv4::parse_amz_date(now).unwrap())
+    }
+
+    #[test]
+    fn test_presigned_url() {
+        let request = presigned_request("20240101T120000Z", 3600);
+        assert_eq!(
+            verify_presigned(&request, "20240101T123000Z").unwrap(),
+            Requester::Authenticated(Identity::owner())
+        );
+
+        let err = verify_presigned(&request, "20240101T130001Z").unwrap_err();
+        assert_eq!(err.message, "Request has expired");
+        assert!(err.to_xml().contains("<X-Amz-Expires>3600</X-Amz-Expires>"));
+
+        let tampered = request.uri().to_string().replace("X-Amz-Expires=3600", "X-Amz-Expires=7200");
+        let tampered = Request::builder().uri(tampered).header("host", "localhost").body(Body::empty()).unwrap();
+        assert_eq!(verify_presigned(&tampered, "20240101T123000Z").unwrap_err().code, "SignatureDoesNotMatch");
+
+        let too_long = presigned_request("20240101T120000Z", 604801);
+        assert_eq!(verify_presigned(&too_long, "20240101T123000Z").unwrap_err().code, "AuthorizationQueryParametersError");
+
+        let mut disabled = store();
+        disabled.set_status("AKID", KeyStatus::Inactive);
+        let query = serde_urlencoded::from_str(request.uri().query().unwrap()).unwrap();
+        let now = sigv4::parse_amz_date("20240101T123000Z").unwrap();
+        assert_eq!(verify_query(&disabled, &request, &query, now).unwrap_err().code, "InvalidAccessKeyId");
+    }
+
+    // A PUT of `body` to /bucket/key, signed in its Authorization header at the current time
+    fn signed_request(body: &str) -> Request {
+        let amz_date = Utc::now().format(sigv4::AMZ_DATE_FORMAT).to_string();
+        let scope = format!("AKID/{}/us-east-1/s3/aws4_request", &amz_date[..8]);
+        let credential = Credential::parse(&scope).unwrap();
+        let payload_hash = sigv4::sha256_hex(body.as_bytes());
+        let headers = vec![
+            ("host".to_string(), "localhost".to_string()),
+            ("x-amz-content-sha256".to_string(), payload_hash.clone()),
+            ("x-amz-date".to_string(), amz_date.clone()),
+            ("x-amz-meta-color".to_string(), "blue".to_string()),
+        ];
+        let signed_headers: Vec<String> = headers.iter().map(|(name, _)| name.clone()).collect();
+        let canonical = sigv4::canonical_request("PUT", "/bucket/key", "", &headers, &signed_headers, &payload_hash);
+        let signature = sigv4::signature(
+            &sigv4::signing_key(SECRET, &credential),
+            &sigv4::string_to_sign(&amz_date, &credential, &canonical),
+        );
+        let authorization = format!(
+            "{} Credential={}, SignedHeaders={}, Signature={}",
+            sigv4::ALGORITHM,
+            scope,
+            signed_headers.join(";"),
+            signature
+        );
+        let mut builder = Request::builder().method("PUT").uri("/bucket/key").header("authorization", authorization);
+        for (name, value) in &headers {
+            builder = builder.header(name, value);
+        }
+        builder.body(Body::from(body.to_string())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_signed_header_request() {
+        let request = verify(&store(), signed_request("hello")).await.unwrap();
+        assert_eq!(request.extensions().get::<Requester>(), Some(&Requester::Authenticated(Identity::owner())));
+        let body = axum::body::to_bytes(request.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"hello");
+
+        let mut disabled = store();
+        disabled.set_status("AKID", KeyStatus::Inactive);
+        assert_eq!(verify(&disabled, signed_request("hello")).await.unwrap_err().code, "InvalidAccessKeyId");
+    }
+
+    #[tokio::test]
+    async fn test_signed_header_request_tampered() {
+        let mut tampered = signed_request("hello");
+        tampered.headers_mut().insert("x-amz-meta-color", "red".parse().unwrap());
+        let err = verify(&store(), tampered).await.unwrap_err();
+        assert_eq!((err.status, err.code), (StatusCode::FORBIDDEN, "SignatureDoesNotMatch"));
+
+        let (parts, _) = signed_request("hello").into_parts();
+        let tampered_body = Request::from_parts(parts, Body::from("jello"));
+        assert_eq!(verify(&store(), tampered_body).await.unwrap_err().code, "XAmzContentSHA256Mismatch");
+    }
+
+    #[test]
+    fn test_check_skew() {
+        let now = sigv4::parse_amz_date("20240101T120000Z").unwrap();
+        assert!(check_skew("20240101T121000Z", sigv4::parse_amz_date("20240101T121000Z").unwrap(), now).is_ok());
+
+        let err = check_skew("20240101T113000Z", sigv4::parse_amz_date("20240101T113000Z").unwrap(), now).unwrap_err();
+        assert_eq!(err.code, "RequestTimeTooSkewed");
+        assert!(err.to_xml().contains("<MaxAllowedSkewMilliseconds>900000</MaxAllowedSkewMilliseconds>"));
+    }
+
+    #[test]
+    fn test_check_signed_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("host", "localhost".parse().unwrap());
+        headers.insert("x-amz-date", "20240101T120000Z".parse().unwrap());
+        headers.insert("x-amz-meta-color", "blue".parse().unwrap());
+
+        let signed = vec!["host".to_string(), "x-amz-date".to_string()];
+        let err = check_signed_headers(&headers, &signed).unwrap_err();
+        assert!(err.to_xml().contains("<HeadersNotSigned>x-amz-meta-color</HeadersNotSigned>"));
+
+        let signed = vec!["host".to_string(), "x-amz-date".to_string(), "x-amz-meta-color".to_string()];
+        assert!(check_signed_headers(&headers, &signed).is_ok());
+    }
+}
@@ -0,0 +1,235 @@
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use crate::domain::checksum::ChecksumAlgorithm;
+use crate::domain::chunked::ChunkedBody;
+use crate::domain::sigv4::{self, StreamingSigner};
+use super::error::S3Error;
+use super::object::{header_str, insert_header};
+
+const AWS_CHUNKED: &str = "aws-chunked";
+
+/// Middleware decoding `aws-chunked` upload bodies, as the SDKs send them for streaming
+/// uploads. Chunk signatures are verified when the request was authenticated, and
+/// `x-amz-checksum-*` values are checked against the decoded payload, whether they come
+/// as headers or as trailing headers.
+pub async fn decode_aws_chunked(request: Request, next: Next) -> Response {
+    match decode(request).await {
+        Ok((request, checksum)) => {
+            let mut response = next.run(request).await;
+            if let Some((algorithm, value)) = checksum.filter(|_| response.status().is_success()) {
+                insert_header(&mut response, algorithm.header_name(), &value);
+            }
+            response
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn decode(request: Request) -> Result<(Request, Option<(ChecksumAlgorithm, String)>), S3Error> {
+    let content_sha256 = header_str(request.headers(), "x-amz-content-sha256").unwrap_or_default().to_string();
+    if !content_sha256.starts_with("STREAMING-") && !is_aws_chunked(request.headers()) {
+        let checksum = header_checksum(request.headers());
+        return match checksum {
+            Some(_) => {
+                let (parts, body) = request.into_parts();
+                let body = axum::body::to_bytes(body, usize::MAX).await.map_err(S3Error::internal)?;
+                let checksum = verify_checksum(checksum, &body)?;
+                Ok((Request::from_parts(parts, Body::from(body)), checksum))
+            }
+            None => Ok((request, None)),
+        };
+    }
+
+    let (mut parts, body) = request.into_parts();
+    let body = axum::body::to_bytes(body, usize::MAX).await.map_err(S3Error::internal)?;
+    let decoded = ChunkedBody::decode(&body).map_err(incomplete_body)?;
+
+    let signed = content_sha256 == sigv4::STREAMING_SIGNED_PAYLOAD
+        || content_sha256 == sigv4::STREAMING_SIGNED_PAYLOAD_TRAILER;
+    if let Some(signer) = parts.extensions.get::<StreamingSigner>().filter(|_| signed) {
+        verify_chunk_signatures(signer, &decoded, content_sha256 == sigv4::STREAMING_SIGNED_PAYLOAD_TRAILER)?;
+    }
+
+    if let Some(expected) = header_str(&parts.headers, "x-amz-decoded-content-length") {
+        if expected.parse::<usize>().ok() != Some(decoded.payload.len()) {
+            return Err(incomplete_body(format!(
+                "You did not provide the number of bytes specified by the x-amz-decoded-content-length header: expected {}, received {}",
+                expected,
+                decoded.payload.len()
+            )));
+        }
+    }
+
+    // Trailing checksums are announced in x-amz-trailer, and take precedence over headers
+    let declared_trailer = header_str(&parts.headers, "x-amz-trailer").map(str::trim).map(str::to_lowercase);
+    let checksum = match declared_trailer {
+        Some(name) => {
+            let algorithm = ChecksumAlgorithm::from_header_name(&name)
+                .ok_or_else(|| S3Error::invalid_argument(format!("The value specified in the x-amz-trailer header is not supported: {}", name)))?;
+            let value = decoded.trailer(&name).ok_or_else(|| {
+                incomplete_body(format!("The trailing header {} was declared but not sent", name))
+            })?;
+            Some((algorithm, value.to_string()))
+        }
+        None => header_checksum(&parts.headers),
+    };
+    let checksum = verify_checksum(checksum, &decoded.payload)?;
+
+    // Downstream handlers see a plain body of the decoded length
+    parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(decoded.payload.len()));
+    let encodings: Vec<&str> = header_str(&parts.headers, header::CONTENT_ENCODING.as_str())
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|encoding| !encoding.is_empty() && !encoding.eq_ignore_ascii_case(AWS_CHUNKED))
+        .collect();
+    match HeaderValue::from_str(&encodings.join(",")) {
+        Ok(value) if !encodings.is_empty() => {
+            parts.headers.insert(header::CONTENT_ENCODING, value);
+        }
+        _ => {
+            parts.headers.remove(header::CONTENT_ENCODING);
+        }
+    }
+    Ok((Request::from_parts(parts, Body::from(decoded.payload)), checksum))
+}
+
+fn is_aws_chunked(headers: &HeaderMap) -> bool {
+    header_str(headers, header::CONTENT_ENCODING.as_str())
+        .unwrap_or_default()
+        .split(',')
+        .any(|encoding| encoding.trim().eq_ignore_ascii_case(AWS_CHUNKED))
+}
+
+fn header_checksum(headers: &HeaderMap) -> Option<(ChecksumAlgorithm, String)> {
+    headers.iter().find_map(|(name, value)| {
+        let algorithm = ChecksumAlgorithm::from_header_name(name.as_str())?;
+        Some((algorithm, value.to_str().ok()?.to_string()))
+    })
+}
+
+fn verify_checksum(
+    checksum: Option<(ChecksumAlgorithm, String)>,
+    payload: &[u8],
+) -> Result<Option<(ChecksumAlgorithm, String)>, S3Error> {
+    match checksum {
+        Some((algorithm, value)) if algorithm.compute(payload) != value => Err(S3Error::new(
+            StatusCode::BAD_REQUEST,
+            "BadDigest",
+            format!("The {} you specified did not match the calculated checksum.", algorithm.name()),
+        )),
+        checksum => Ok(checksum),
+    }
+}
+
+// Each chunk signs its data and the previous signature, starting from the request's
+fn verify_chunk_signatures(signer: &StreamingSigner, decoded: &ChunkedBody, with_trailer: bool) -> Result<(), S3Error> {
+    let mut previous = signer.seed_signature.clone();
+    for chunk in &decoded.chunks {
+        let provided = chunk
+            .signature
+            .as_deref()
+            .ok_or_else(|| incomplete_body("Chunk is missing its chunk-signature"))?;
+        let expected = signer.chunk_signature(&previous, decoded.data(chunk));
+        if !sigv4::signatures_match(&expected, provided) {
+            return Err(chunk_signature_error(provided));
+        }
+        previous = expected;
+    }
+    if with_trailer {
+        let provided = decoded
+            .trailer_signature
+            .as_deref()
+            .ok_or_else(|| incomplete_body("Trailing headers are missing x-amz-trailer-signature"))?;
+        if !sigv4::signatures_match(&signer.trailer_signature(&previous, &decoded.trailers), provided) {
+            return Err(chunk_signature_error(provided));
+        }
+    }
+    Ok(())
+}
+
+fn chunk_signature_error(provided: &str) -> S3Error {
+    S3Error::new(
+        StatusCode::FORBIDDEN,
+        "SignatureDoesNotMatch",
+        "The request signature we calculated does not match the signature you provided. Check your key and signing method.",
+    )
+    .with_detail("SignatureProvided", provided)
+}
+
+fn incomplete_body(reason: impl std::fmt::Display) -> S3Error {
+    S3Error::new(StatusCode::BAD_REQUEST, "IncompleteBody", reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::sigv4::{signing_key, Credential};
+
+    fn chunked_request(body: &str, headers: &[(&str, &str)]) -> Request {
+        let mut builder = Request::builder().method("PUT").uri("/bucket/key");
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(Body::from(body.to_string())).unwrap()
+    }
+
+    async fn payload(request: Request) -> Vec<u8> {
+        axum::body::to_bytes(request.into_body(), usize::MAX).await.unwrap().to_vec()
+    }
+
+    #[tokio::test]
+    async fn test_decode_unsigned_with_trailer() {
+        let request = chunked_request(
+            "b\r\nhello world\r\n0\r\nx-amz-checksum-crc32:DUoRhQ==\r\n\r\n",
+            &[
+                ("content-encoding", "aws-chunked"),
+                ("x-amz-content-sha256", "STREAMING-UNSIGNED-PAYLOAD-TRAILER"),
+                ("x-amz-decoded-content-length", "11"),
+                ("x-amz-trailer", "x-amz-checksum-crc32"),
+            ],
+        );
+        let (request, checksum) = decode(request).await.unwrap();
+        assert_eq!(checksum, Some((ChecksumAlgorithm::Crc32, "DUoRhQ==".to_string())));
+        assert_eq!(request.headers().get(header::CONTENT_ENCODING), None);
+        assert_eq!(request.headers()[header::CONTENT_LENGTH], "11");
+        assert_eq!(payload(request).await, b"hello world");
+
+        let bad_digest = chunked_request(
+            "b\r\nhello world\r\n0\r\nx-amz-checksum-crc32:AAAAAA==\r\n\r\n",
+            &[("content-encoding", "aws-chunked"), ("x-amz-trailer", "x-amz-checksum-crc32")],
+        );
+        assert_eq!(decode(bad_digest).await.unwrap_err().code, "BadDigest");
+
+        let short = chunked_request(
+            "b\r\nhello world\r\n0\r\n\r\n",
+            &[("content-encoding", "aws-chunked"), ("x-amz-decoded-content-length", "12")],
+        );
+        assert_eq!(decode(short).await.unwrap_err().code, "IncompleteBody");
+
+        let oversized = chunked_request("ffffffffffffffff\r\nhello\r\n0\r\n\r\n", &[("content-encoding", "aws-chunked")]);
+        assert_eq!(decode(oversized).await.unwrap_err().code, "IncompleteBody");
+    }
+
+    #[tokio::test]
+    async fn test_verify_chunk_signatures() {
+        let credential = Credential::parse("AKID/20250101/us-east-1/s3/aws4_request").unwrap();
+        let signer = StreamingSigner::new(signing_key("secret", &credential), "20250101T000000Z", &credential, "seed");
+        let first = signer.chunk_signature("seed", b"hello");
+        let last = signer.chunk_signature(&first, b"");
+        let body = format!("5;chunk-signature={}\r\nhello\r\n0;chunk-signature={}\r\n\r\n", first, last);
+        let headers = [("x-amz-content-sha256", sigv4::STREAMING_SIGNED_PAYLOAD)];
+
+        let mut request = chunked_request(&body, &headers);
+        request.extensions_mut().insert(signer.clone());
+        let (request, _) = decode(request).await.unwrap();
+        assert_eq!(payload(request).await, b"hello");
+
+        let mut tampered = chunked_request(&body.replace("hello", "jello"), &headers);
+        tampered.extensions_mut().insert(signer);
+        assert_eq!(decode(tampered).await.unwrap_err().code, "SignatureDoesNotMatch");
+    }
+}
@@ -0,0 +1,151 @@
+use axum::extract::{Path, Request, State};
+use axum::http::{header, HeaderMap, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use crate::domain::cors::{self, CorsConfiguration, CorsRule};
+use super::api::{require_bucket, AppState};
+use super::error::S3Error;
+use super::object::{header_str, insert_header};
+use super::operation::S3Operation;
+
+// Sent whenever the bucket has a CORS configuration, so caches keep one response per origin
+const VARY: &str = "Origin, Access-Control-Request-Headers, Access-Control-Request-Method";
+
+pub async fn put_bucket_cors(state: &AppState, bucket_name: &str, body: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let config = CorsConfiguration::from_xml(body).map_err(|_| S3Error::malformed_xml())?;
+    config.validate().map_err(S3Error::invalid_argument)?;
+
+    let xml = config.to_xml().map_err(S3Error::internal)?;
+    state
+        .storage
+        .put_bucket_config(bucket_name, cors::CONFIG_NAME, &xml)
+        .await?;
+    Ok(StatusCode::OK.into_response())
+}
+
+pub async fn get_bucket_cors(state: &AppState, bucket_name: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let xml = state
+        .storage
+        .get_bucket_config(bucket_name, cors::CONFIG_NAME)
+        .await?
+        .ok_or_else(|| {
+            S3Error::new(StatusCode::NOT_FOUND, "NoSuchCORSConfiguration", "The CORS configuration does not exist")
+                .with_resource(bucket_name)
+        })?;
+    Ok(([(header::CONTENT_TYPE, "application/xml")], xml).into_response())
+}
+
+pub async fn delete_bucket_cors(state: &AppState, bucket_name: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    state
+        .storage
+        .delete_bucket_config(bucket_name, cors::CONFIG_NAME)
+        .await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+pub async fn preflight_bucket(
+    State(state): State<AppState>,
+    Path(bucket_name): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, S3Error> {
+    preflight(&state, &bucket_name, &headers).await
+}
+
+pub async fn preflight_object(
+    State(state): State<AppState>,
+    Path((bucket_name, _key)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, S3Error> {
+    preflight(&state, &bucket_name, &headers).await
+}
+
+// Answers an OPTIONS request from the bucket's CORS rules. Preflights carry no
+// credentials, so they are never subject to the bucket policy or ACLs.
+async fn preflight(state: &AppState, bucket_name: &str, headers: &HeaderMap) -> Result<Response, S3Error> {
+    let origin = header_str(headers, header::ORIGIN.as_str()).ok_or_else(|| {
+        S3Error::new(StatusCode::BAD_REQUEST, "BadRequest", "Insufficient information. Origin request header needed.")
+    })?;
+    let method = header_str(headers, header::ACCESS_CONTROL_REQUEST_METHOD.as_str()).ok_or_else(|| {
+        S3Error::new(StatusCode::BAD_REQUEST, "BadRequest", "Invalid Access-Control-Request-Method: null")
+    })?;
+    let request_headers: Vec<String> = header_str(headers, header::ACCESS_CONTROL_REQUEST_HEADERS.as_str())
+        .unwrap_or_default()
+        .split(',')
+        .map(|name| name.trim().to_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect();
+
+    if state.storage.get_bucket(bucket_name).await?.is_none() {
+        return Err(cors_forbidden("Bucket not found"));
+    }
+    let config = bucket_cors(state, bucket_name)
+        .await?
+        .ok_or_else(|| cors_forbidden("CORS is not enabled for this bucket."))?;
+    let rule = config.find_rule(origin, method, &request_headers).ok_or_else(|| {
+        cors_forbidden(
+            "This CORS request is not allowed. This is usually because the evalution of Origin, request method / Access-Control-Request-Method or Access-Control-Request-Headers are not whitelisted by the resource's CORS spec.",
+        )
+    })?;
+
+    let mut response = StatusCode::OK.into_response();
+    apply_rule(&mut response, rule, origin);
+    if !request_headers.is_empty() {
+        insert_header(&mut response, header::ACCESS_CONTROL_ALLOW_HEADERS.as_str(), &request_headers.join(", "));
+    }
+    Ok(response)
+}
+
+/// Middleware adding the `Access-Control-*` headers of the matching CORS rule to
+/// responses for cross-origin requests.
+pub async fn add_cors_headers(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let origin = header_str(request.headers(), header::ORIGIN.as_str()).map(str::to_string);
+    let method = request.method().clone();
+    let bucket = S3Operation::classify(&method, request.uri(), request.headers()).bucket;
+
+    let mut response = next.run(request).await;
+    let (Some(origin), Some(bucket_name)) = (origin, bucket) else {
+        return response;
+    };
+    // Preflight responses are built by their handler
+    if method == Method::OPTIONS {
+        return response;
+    }
+    if let Ok(Some(config)) = bucket_cors(&state, &bucket_name).await {
+        insert_header(&mut response, header::VARY.as_str(), VARY);
+        if let Some(rule) = config.find_rule(&origin, method.as_str(), &[]) {
+            apply_rule(&mut response, rule, &origin);
+        }
+    }
+    response
+}
+
+async fn bucket_cors(state: &AppState, bucket_name: &str) -> Result<Option<CorsConfiguration>, S3Error> {
+    match state.storage.get_bucket_config(bucket_name, cors::CONFIG_NAME).await? {
+        Some(xml) => CorsConfiguration::from_xml(&xml).map(Some).map_err(S3Error::internal),
+        None => Ok(None),
+    }
+}
+
+fn apply_rule(response: &mut Response, rule: &CorsRule, origin: &str) {
+    if rule.allows_any_origin() {
+        insert_header(response, header::ACCESS_CONTROL_ALLOW_ORIGIN.as_str(), "*");
+    } else {
+        insert_header(response, header::ACCESS_CONTROL_ALLOW_ORIGIN.as_str(), origin);
+        insert_header(response, header::ACCESS_CONTROL_ALLOW_CREDENTIALS.as_str(), "true");
+    }
+    insert_header(response, header::ACCESS_CONTROL_ALLOW_METHODS.as_str(), &rule.allowed_methods.join(", "));
+    if !rule.expose_headers.is_empty() {
+        insert_header(response, header::ACCESS_CONTROL_EXPOSE_HEADERS.as_str(), &rule.expose_headers.join(", "));
+    }
+    if let Some(max_age) = rule.max_age_seconds {
+        insert_header(response, header::ACCESS_CONTROL_MAX_AGE.as_str(), &max_age.to_string());
+    }
+    insert_header(response, header::VARY.as_str(), VARY);
+}
+
+fn cors_forbidden(reason: &str) -> S3Error {
+    S3Error::new(StatusCode::FORBIDDEN, "AccessForbidden", format!("CORSResponse: {}", reason))
+}
@@ -0,0 +1,217 @@
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use crate::domain::encryption::{self, CustomerKey, ObjectEncryption, ServerSideEncryptionConfiguration, AES256};
+use crate::domain::identity::Requester;
+use crate::domain::object::Object;
+use super::admin::require_owner;
+use super::api::{require_bucket, AppState};
+use super::error::S3Error;
+use super::object::{header_str, insert_header};
+
+pub async fn put_bucket_encryption(state: &AppState, bucket_name: &str, body: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let config = ServerSideEncryptionConfiguration::from_xml(body).map_err(|_| S3Error::malformed_xml())?;
+    config.validate().map_err(S3Error::invalid_argument)?;
+    check_algorithm(config.sse_algorithm().unwrap_or_default())?;
+    let xml = config.to_xml().map_err(S3Error::internal)?;
+    state.storage.put_bucket_config(bucket_name, encryption::CONFIG_NAME, &xml).await?;
+    Ok(StatusCode::OK.into_response())
+}
+
+/// Buckets without a configuration of their own report SSE-S3, as every S3 bucket does.
+pub async fn get_bucket_encryption(state: &AppState, bucket_name: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let xml = match state.storage.get_bucket_config(bucket_name, encryption::CONFIG_NAME).await? {
+        Some(xml) => xml,
+        None => ServerSideEncryptionConfiguration::sse_s3().to_xml().map_err(S3Error::internal)?,
+    };
+    Ok(([(header::CONTENT_TYPE, "application/xml")], xml).into_response())
+}
+
+pub async fn delete_bucket_encryption(state: &AppState, bucket_name: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    state.storage.delete_bucket_config(bucket_name, encryption::CONFIG_NAME).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// Encrypts a new object as the request asks: with the SSE-C key of its
+/// `x-amz-server-side-encryption-customer-*` headers, or as SSE-S3 from its
+/// `x-amz-server-side-encryption` headers or the bucket default. Content is encrypted at
+/// rest with the server keys either way, SSE-C content on top of its own encryption.
+pub async fn encrypt_object(
+    state: &AppState,
+    bucket_name: &str,
+    headers: &HeaderMap,
+    mut object: Object,
+) -> Result<Object, S3Error> {
+    if let Some(key) = customer_key(headers, CUSTOMER_HEADERS)? {
+        if headers.contains_key("x-amz-server-side-encryption") {
+            return Err(S3Error::invalid_argument(
+                "Server Side Encryption with Customer provided key is incompatible with the encryption method specified",
+            ));
+        }
+        object.content = key.encrypt(&object.content);
+        return Ok(object.with_encryption(ObjectEncryption::customer(&key)));
+    }
+
+    let config = match state.storage.get_bucket_config(bucket_name, encryption::CONFIG_NAME).await? {
+        Some(xml) => ServerSideEncryptionConfiguration::from_xml(&xml).map_err(S3Error::internal)?,
+        None => ServerSideEncryptionConfiguration::sse_s3(),
+    };
+    let algorithm = header_str(headers, "x-amz-server-side-encryption").unwrap_or(AES256);
+    check_algorithm(algorithm)?;
+    let bucket_key_enabled = match header_str(headers, "x-amz-server-side-encryption-bucket-key-enabled") {
+        Some(enabled) => enabled.eq_ignore_ascii_case("true"),
+        None => config.bucket_key_enabled(),
+    };
+    let encryption = ObjectEncryption { algorithm: algorithm.to_string(), bucket_key_enabled, customer_key_md5: None };
+    Ok(object.with_encryption(encryption))
+}
+
+/// Decrypts the content of an SSE-C object with the key of the request, which must be the
+/// one it was stored with. `headers` are the ones of the object's key, as
+/// [`CUSTOMER_HEADERS`] or [`COPY_SOURCE_CUSTOMER_HEADERS`].
+pub fn decrypt_object(request: &HeaderMap, headers: CustomerHeaders, object: &mut Object) -> Result<(), S3Error> {
+    let Some(key) = check_customer_key(request, headers, object)? else {
+        return Ok(());
+    };
+    object.content = key.decrypt(&object.content).ok_or_else(wrong_key)?;
+    object.size = object.content.len();
+    Ok(())
+}
+
+/// Checks the request carries the SSE-C key of the object if it has one, and no SSE-C
+/// key otherwise. Returns the key.
+pub fn check_customer_key(
+    request: &HeaderMap,
+    headers: CustomerHeaders,
+    object: &Object,
+) -> Result<Option<CustomerKey>, S3Error> {
+    let key = customer_key(request, headers)?;
+    let stored_md5 = object.encryption.as_ref().and_then(|encryption| encryption.customer_key_md5.as_deref());
+    match (key, stored_md5) {
+        (None, None) => Ok(None),
+        (Some(_), None) => Err(invalid_request("The encryption parameters are not applicable to this object.")),
+        (None, Some(_)) => Err(invalid_request(
+            "The object was stored using a form of Server Side Encryption. The correct parameters must be provided to retrieve the object.",
+        )),
+        (Some(key), Some(stored_md5)) if key.key_md5 != stored_md5 => Err(wrong_key()),
+        (Some(key), Some(_)) => Ok(Some(key)),
+    }
+}
+
+pub fn insert_encryption_headers(response: &mut Response, object: &Object) {
+    let Some(encryption) = &object.encryption else {
+        return;
+    };
+    if let Some(key_md5) = &encryption.customer_key_md5 {
+        insert_header(response, "x-amz-server-side-encryption-customer-algorithm", &encryption.algorithm);
+        insert_header(response, "x-amz-server-side-encryption-customer-key-MD5", key_md5);
+        return;
+    }
+    insert_header(response, "x-amz-server-side-encryption", &encryption.algorithm);
+    if encryption.bucket_key_enabled {
+        insert_header(response, "x-amz-server-side-encryption-bucket-key-enabled", "true");
+    }
+}
+
+/// Names of the algorithm, key and key MD5 headers of an SSE-C key.
+pub type CustomerHeaders = [&'static str; 3];
+
+pub const CUSTOMER_HEADERS: CustomerHeaders = [
+    "x-amz-server-side-encryption-customer-algorithm",
+    "x-amz-server-side-encryption-customer-key",
+    "x-amz-server-side-encryption-customer-key-md5",
+];
+
+// The key of the source object of a copy
+pub const COPY_SOURCE_CUSTOMER_HEADERS: CustomerHeaders = [
+    "x-amz-copy-source-server-side-encryption-customer-algorithm",
+    "x-amz-copy-source-server-side-encryption-customer-key",
+    "x-amz-copy-source-server-side-encryption-customer-key-md5",
+];
+
+fn customer_key(request: &HeaderMap, headers: CustomerHeaders) -> Result<Option<CustomerKey>, S3Error> {
+    let [algorithm_header, key_header, key_md5_header] = headers;
+    let (algorithm, key, key_md5) = (
+        header_str(request, algorithm_header),
+        header_str(request, key_header),
+        header_str(request, key_md5_header),
+    );
+    let Some(algorithm) = algorithm else {
+        if key.is_some() || key_md5.is_some() {
+            return Err(S3Error::invalid_argument(
+                "Requests specifying Server Side Encryption with Customer provided keys must provide a valid encryption algorithm.",
+            ));
+        }
+        return Ok(None);
+    };
+    if algorithm != AES256 {
+        return Err(S3Error::new(
+            StatusCode::BAD_REQUEST,
+            "InvalidEncryptionAlgorithmError",
+            "The encryption request you specified is not valid. The valid value is AES256.",
+        ));
+    }
+    let key = key.ok_or_else(|| {
+        S3Error::invalid_argument(
+            "Requests specifying Server Side Encryption with Customer provided keys must provide an appropriate secret key.",
+        )
+    })?;
+    let key_md5 = key_md5.ok_or_else(|| {
+        S3Error::invalid_argument(
+            "Requests specifying Server Side Encryption with Customer provided keys must provide the client calculated MD5 of the secret key.",
+        )
+    })?;
+    CustomerKey::new(key, key_md5).map(Some).map_err(S3Error::invalid_argument)
+}
+
+fn invalid_request(message: &str) -> S3Error {
+    S3Error::new(StatusCode::BAD_REQUEST, "InvalidRequest", message)
+}
+
+// As S3, which doesn't tell whether the object exists or the key is wrong
+fn wrong_key() -> S3Error {
+    S3Error::new(StatusCode::FORBIDDEN, "AccessDenied", "Access Denied")
+}
+
+// Keys are only managed by this server, there is no KMS to encrypt with
+fn check_algorithm(algorithm: &str) -> Result<(), S3Error> {
+    match algorithm {
+        AES256 => Ok(()),
+        "aws:kms" | "aws:kms:dsse" => Err(S3Error::new(
+            StatusCode::NOT_IMPLEMENTED,
+            "NotImplemented",
+            "Server-side encryption with AWS KMS keys is not implemented, only AES256 (SSE-S3) is.",
+        )),
+        _ => Err(S3Error::invalid_argument("The encryption method specified is not supported")),
+    }
+}
+
+/// Admin endpoint listing the server-managed keys, without their key material.
+pub async fn list_encryption_keys(State(state): State<AppState>, requester: Requester) -> Result<Response, S3Error> {
+    require_owner(&state, &requester)?;
+    let keys = state.storage.encryption_keys().await?;
+    let key_views: Vec<_> = keys
+        .keys
+        .iter()
+        .map(|key| serde_json::json!({ "KeyId": key.id, "CreateDate": key.created, "Active": key.id == keys.active_key_id }))
+        .collect();
+    Ok(Json(serde_json::json!({ "ActiveKeyId": keys.active_key_id, "Keys": key_views })).into_response())
+}
+
+/// Admin endpoint adding a key that encrypts new content from now on.
+pub async fn rotate_encryption_key(State(state): State<AppState>, requester: Requester) -> Result<Response, S3Error> {
+    require_owner(&state, &requester)?;
+    let key_id = state.storage.rotate_encryption_key().await?;
+    Ok((StatusCode::CREATED, Json(serde_json::json!({ "ActiveKeyId": key_id }))).into_response())
+}
+
+/// Admin endpoint re-encrypting the content of every object with the active key.
+pub async fn reencrypt_objects(State(state): State<AppState>, requester: Requester) -> Result<Response, S3Error> {
+    require_owner(&state, &requester)?;
+    let reencrypted = state.storage.reencrypt_objects().await?;
+    Ok(Json(serde_json::json!({ "ReencryptedObjects": reencrypted })).into_response())
+}
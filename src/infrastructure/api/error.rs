@@ -0,0 +1,106 @@
+use std::io;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+
+/// Error a response carries, kept in its extensions for the access log and audit trail.
+#[derive(Debug, Clone)]
+pub struct ResponseError {
+    pub code: &'static str,
+    pub message: String,
+}
+
+/// Error returned to clients in the S3 XML error format.
+#[derive(Debug, Clone)]
+pub struct S3Error {
+    pub status: StatusCode,
+    pub code: &'static str,
+    pub message: String,
+    pub resource: Option<String>,
+    // Extra elements some errors carry, as in <StringToSign> for SignatureDoesNotMatch
+    pub details: Vec<(&'static str, String)>,
+}
+
+impl S3Error {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+            resource: None,
+            details: Vec::new(),
+        }
+    }
+
+    pub fn with_resource(mut self, resource: impl Into<String>) -> Self {
+        self.resource = Some(resource.into());
+        self
+    }
+
+    pub fn with_detail(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.details.push((name, value.into()));
+        self
+    }
+
+    pub fn no_such_bucket(bucket_name: &str) -> Self {
+        Self::new(StatusCode::NOT_FOUND, "NoSuchBucket", "The specified bucket does not exist")
+            .with_resource(bucket_name)
+    }
+
+    pub fn no_such_key(key: &str) -> Self {
+        Self::new(StatusCode::NOT_FOUND, "NoSuchKey", "The specified key does not exist.")
+            .with_resource(key)
+    }
+
+    pub fn malformed_xml() -> Self {
+        Self::new(
+            StatusCode::BAD_REQUEST,
+            "MalformedXML",
+            "The XML you provided was not well-formed or did not validate against our published schema",
+        )
+    }
+
+    pub fn invalid_argument(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "InvalidArgument", message)
+    }
+
+    pub fn internal(err: impl std::fmt::Display) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "InternalError", err.to_string())
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Error>");
+        xml.push_str(&format!("<Code>{}</Code>", self.code));
+        xml.push_str(&format!("<Message>{}</Message>", escape_xml(&self.message)));
+        if let Some(resource) = &self.resource {
+            xml.push_str(&format!("<Resource>{}</Resource>", escape_xml(resource)));
+        }
+        for (name, value) in &self.details {
+            xml.push_str(&format!("<{}>{}</{}>", name, escape_xml(value), name));
+        }
+        xml.push_str("</Error>");
+        xml
+    }
+}
+
+impl From<io::Error> for S3Error {
+    fn from(err: io::Error) -> Self {
+        Self::internal(err)
+    }
+}
+
+impl IntoResponse for S3Error {
+    fn into_response(self) -> Response {
+        let mut response = (self.status, [(header::CONTENT_TYPE, "application/xml")], self.to_xml()).into_response();
+        response.extensions_mut().insert(ResponseError { code: self.code, message: self.message });
+        response
+    }
+}
+
+pub fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,105 @@
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use axum::extract::{Query, Request, State};
+use axum::http::{HeaderMap, Method};
+use axum::middleware::Next;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use chrono::Utc;
+use futures_util::stream;
+use serde::Deserialize;
+use crate::domain::event::{ChangeEvent, EventFilter};
+use crate::domain::identity::Requester;
+use super::access::requester_of;
+use super::admin::require_owner;
+use super::api::AppState;
+use super::error::S3Error;
+use super::notification::principal_id;
+use super::object::header_str;
+use super::operation::S3Operation;
+
+// Recorded along with their event notifications, with the object's size and ETag
+const OBJECT_OPERATIONS: [&str; 4] = ["PutObject", "CopyObject", "PostObject", "DeleteObject"];
+
+#[derive(Deserialize)]
+pub struct StreamQuery {
+    bucket: Option<String>,
+    prefix: Option<String>,
+    // Comma-separated event types, as in "ObjectCreated:*,PutBucketPolicy"
+    types: Option<String>,
+    after: Option<u64>,
+}
+
+/// Server-Sent Events stream of bucket and object changes, with the sequence number of
+/// each change as the event ID. Streams start with the changes after `after`, or after
+/// the `Last-Event-ID` of a reconnecting client, and with new changes only otherwise.
+pub async fn stream_events(
+    State(state): State<AppState>,
+    Query(query): Query<StreamQuery>,
+    requester: Requester,
+    headers: HeaderMap,
+) -> Result<Response, S3Error> {
+    require_owner(&state, &requester)?;
+    let filter = EventFilter {
+        bucket: query.bucket,
+        prefix: query.prefix,
+        types: query
+            .types
+            .map(|types| types.split(',').map(str::trim).filter(|t| !t.is_empty()).map(str::to_string).collect())
+            .unwrap_or_default(),
+    };
+    let after = header_str(&headers, "last-event-id")
+        .and_then(|id| id.parse().ok())
+        .or(query.after)
+        .unwrap_or_else(|| state.events.last_sequence());
+
+    let events = state.events.clone();
+    let stream = stream::unfold((after, VecDeque::<ChangeEvent>::new()), move |(mut last, mut pending)| {
+        let (events, filter) = (events.clone(), filter.clone());
+        async move {
+            loop {
+                if let Some(event) = pending.pop_front() {
+                    let sse = Event::default()
+                        .id(event.sequence.to_string())
+                        .data(serde_json::to_string(&event).unwrap_or_default());
+                    return Some((Ok::<_, Infallible>(sse), (last, pending)));
+                }
+                let arrival = events.arrival();
+                let recorded = events.since(last);
+                if let Some(latest) = recorded.last() {
+                    last = latest.sequence;
+                }
+                pending.extend(recorded.into_iter().filter(|event| filter.matches(event)));
+                if pending.is_empty() {
+                    arrival.await;
+                }
+            }
+        }
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()).into_response())
+}
+
+/// Middleware recording successful bucket changes, and object changes other than
+/// creations and removals, for the event stream.
+pub async fn record_changes(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let operation = S3Operation::classify(request.method(), request.uri(), request.headers());
+    let is_change = matches!(*request.method(), Method::PUT | Method::POST | Method::DELETE)
+        && operation.name != "Unknown"
+        && !OBJECT_OPERATIONS.contains(&operation.name);
+    let principal = principal_id(&requester_of(request.extensions()));
+    let response = next.run(request).await;
+
+    if let Some(bucket) = operation.bucket.filter(|_| is_change && response.status().is_success()) {
+        state.events.record(ChangeEvent {
+            sequence: 0,
+            time: Utc::now(),
+            event_type: operation.name.to_string(),
+            bucket,
+            key: operation.key,
+            size: None,
+            etag: None,
+            principal,
+        });
+    }
+    response
+}
@@ -0,0 +1,138 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+use crate::domain::hook::{self, Hook};
+use crate::domain::identity::{Requester, DEFAULT_ACCOUNT_ID};
+use super::admin::{require_owner, validation_error};
+use super::api::AppState;
+use super::error::S3Error;
+use super::policy;
+
+// A hook, along with the ARN bucket notifications invoke it by
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct HookView<'a> {
+    #[serde(flatten)]
+    hook: &'a Hook,
+    function_arn: String,
+}
+
+impl<'a> HookView<'a> {
+    fn new(state: &AppState, hook: &'a Hook) -> Self {
+        Self {
+            hook,
+            function_arn: hook::function_arn(state.hooks.region(), DEFAULT_ACCOUNT_ID, &hook.name),
+        }
+    }
+}
+
+pub async fn list_hooks(State(state): State<AppState>, requester: Requester) -> Result<Response, S3Error> {
+    require_owner(&state, &requester)?;
+    let store = state.hooks.store().await?;
+    let hooks: Vec<HookView> = store.hooks().map(|hook| HookView::new(&state, hook)).collect();
+    Ok(Json(serde_json::json!({ "Hooks": hooks })).into_response())
+}
+
+pub async fn get_hook(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    requester: Requester,
+) -> Result<Response, S3Error> {
+    require_owner(&state, &requester)?;
+    let store = state.hooks.store().await?;
+    let hook = store.get(&name).ok_or_else(|| not_found(&name))?;
+    Ok(Json(HookView::new(&state, hook)).into_response())
+}
+
+/// Registers an HTTP hook, replacing any of the same name. Its name comes from the path.
+/// Command hooks run programs on the server, so they can only be registered from the CLI.
+pub async fn put_hook(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    requester: Requester,
+    body: String,
+) -> Result<Response, S3Error> {
+    require_owner(&state, &requester)?;
+    let mut hook: Hook =
+        serde_json::from_str(&body).map_err(|e| validation_error(format!("Invalid request body: {}", e)))?;
+    hook.name = name;
+    hook.validate().map_err(validation_error)?;
+    if !hook.command.is_empty() {
+        return Err(policy::access_denied_with(
+            "Command hooks can only be registered from the CLI on the server's host.",
+        ));
+    }
+
+    let mut store = state.hooks.store().await?;
+    store.put(hook.clone());
+    state.hooks.save(&store).await?;
+    Ok(Json(HookView::new(&state, &hook)).into_response())
+}
+
+pub async fn delete_hook(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    requester: Requester,
+) -> Result<Response, S3Error> {
+    require_owner(&state, &requester)?;
+    let mut store = state.hooks.store().await?;
+    store.remove(&name).ok_or_else(|| not_found(&name))?;
+    state.hooks.save(&store).await?;
+    state.hooks.forget(&name);
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// The latest invocations of the hook, oldest first, with their captured output.
+pub async fn list_invocations(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    requester: Requester,
+) -> Result<Response, S3Error> {
+    require_owner(&state, &requester)?;
+    if state.hooks.store().await?.get(&name).is_none() {
+        return Err(not_found(&name));
+    }
+    Ok(Json(serde_json::json!({ "Invocations": state.hooks.invocations(&name) })).into_response())
+}
+
+fn not_found(name: &str) -> S3Error {
+    S3Error::new(
+        StatusCode::NOT_FOUND,
+        "ResourceNotFoundException",
+        format!("Hook not found: {}", name),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::identity::Identity;
+    use super::super::api::tests::test_state;
+
+    async fn put(state: &AppState, requester: Requester, body: &str) -> Result<Response, S3Error> {
+        put_hook(State(state.clone()), Path("resize".to_string()), requester, body.to_string()).await
+    }
+
+    #[tokio::test]
+    async fn test_put_hook_rejects_commands() {
+        let (_data_dir, mut state) = test_state();
+        let command = r#"{"Command": ["touch", "/tmp/pwned"]}"#;
+        let url = r#"{"Url": "http://localhost:9000/resize"}"#;
+
+        let error = put(&state, Requester::Anonymous, url).await.unwrap_err();
+        assert_eq!((error.status, error.code), (StatusCode::FORBIDDEN, "AccessDenied"));
+        let error = put(&state, Requester::Anonymous, command).await.unwrap_err();
+        assert_eq!((error.status, error.code), (StatusCode::FORBIDDEN, "AccessDenied"));
+        // Not even the owner may register commands, nor anyone while authentication is off
+        let owner = Requester::Authenticated(Identity::owner());
+        assert_eq!(put(&state, owner.clone(), command).await.unwrap_err().code, "AccessDenied");
+        state.auth_enabled = false;
+        assert_eq!(put(&state, Requester::Anonymous, command).await.unwrap_err().code, "AccessDenied");
+        assert!(state.hooks.store().await.unwrap().get("resize").is_none());
+
+        put(&state, owner, url).await.unwrap();
+        assert!(state.hooks.store().await.unwrap().get("resize").is_some());
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use axum::extract::{Path, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use chrono::Utc;
+use crate::domain::identity::Requester;
+use crate::domain::inventory::{self, InventoryConfiguration, InventoryConfigurations, ListInventoryConfigurationsResult};
+use super::admin::require_owner;
+use super::api::{require_bucket, AppState};
+use super::error::S3Error;
+
+pub async fn put_bucket_inventory(
+    state: &AppState,
+    bucket_name: &str,
+    params: &HashMap<String, String>,
+    body: &str,
+) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let id = require_id(params)?;
+    let config = InventoryConfiguration::from_xml(body).map_err(|_| S3Error::malformed_xml())?;
+    if config.id != id {
+        return Err(S3Error::invalid_argument("The ID in the configuration does not match the id parameter"));
+    }
+    config.validate().map_err(S3Error::invalid_argument)?;
+
+    let mut configs = configurations(state, bucket_name).await?;
+    configs.put(config).map_err(S3Error::invalid_argument)?;
+    save(state, bucket_name, &configs).await?;
+    Ok(StatusCode::OK.into_response())
+}
+
+/// GetBucketInventoryConfiguration with an `id`, ListBucketInventoryConfigurations without.
+pub async fn get_bucket_inventory(
+    state: &AppState,
+    bucket_name: &str,
+    params: &HashMap<String, String>,
+) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let configs = configurations(state, bucket_name).await?;
+    let xml = match params.get("id") {
+        Some(id) => configs
+            .get(id)
+            .ok_or_else(no_such_configuration)?
+            .to_xml()
+            .map_err(S3Error::internal)?,
+        None => {
+            let result = ListInventoryConfigurationsResult {
+                xmlns: "http://s3.amazonaws.com/doc/2006-03-01/",
+                configurations: configs.configurations,
+                is_truncated: false,
+            };
+            quick_xml::se::to_string(&result).map_err(S3Error::internal)?
+        }
+    };
+    Ok(([(header::CONTENT_TYPE, "application/xml")], xml).into_response())
+}
+
+pub async fn delete_bucket_inventory(
+    state: &AppState,
+    bucket_name: &str,
+    params: &HashMap<String, String>,
+) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let id = require_id(params)?;
+    let mut configs = configurations(state, bucket_name).await?;
+    if !configs.remove(id) {
+        return Err(no_such_configuration());
+    }
+    save(state, bucket_name, &configs).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// Admin endpoint writing a report of the configuration right away, whatever its schedule.
+pub async fn generate_report(
+    State(state): State<AppState>,
+    Path((bucket_name, id)): Path<(String, String)>,
+    requester: Requester,
+) -> Result<Response, S3Error> {
+    require_owner(&state, &requester)?;
+    require_bucket(&state, &bucket_name).await?;
+    let configs = configurations(&state, &bucket_name).await?;
+    let config = configs.get(&id).ok_or_else(no_such_configuration)?;
+    let manifest_key = state
+        .inventory
+        .generate(&bucket_name, config, Utc::now())
+        .await
+        .map_err(|e| S3Error::invalid_argument(format!("The inventory report could not be written: {}", e)))?;
+    let manifest = format!("{}/{}", config.destination_bucket().unwrap_or_default(), manifest_key);
+    Ok(Json(serde_json::json!({ "Manifest": manifest })).into_response())
+}
+
+async fn configurations(state: &AppState, bucket_name: &str) -> Result<InventoryConfigurations, S3Error> {
+    match state.storage.get_bucket_config(bucket_name, inventory::CONFIG_NAME).await? {
+        Some(xml) => InventoryConfigurations::from_xml(&xml).map_err(S3Error::internal),
+        None => Ok(InventoryConfigurations::default()),
+    }
+}
+
+async fn save(state: &AppState, bucket_name: &str, configs: &InventoryConfigurations) -> Result<(), S3Error> {
+    if configs.configurations.is_empty() {
+        state.storage.delete_bucket_config(bucket_name, inventory::CONFIG_NAME).await?;
+        return Ok(());
+    }
+    let xml = configs.to_xml().map_err(S3Error::internal)?;
+    state.storage.put_bucket_config(bucket_name, inventory::CONFIG_NAME, &xml).await?;
+    Ok(())
+}
+
+fn require_id(params: &HashMap<String, String>) -> Result<&str, S3Error> {
+    params
+        .get("id")
+        .map(String::as_str)
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| S3Error::invalid_argument("Missing required parameter id"))
+}
+
+fn no_such_configuration() -> S3Error {
+    S3Error::new(StatusCode::NOT_FOUND, "NoSuchConfiguration", "The specified configuration does not exist.")
+}
@@ -0,0 +1,90 @@
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use crate::domain::lifecycle::{self, LifecycleConfiguration};
+use crate::domain::object::Object;
+use super::api::{require_bucket, AppState};
+use super::error::S3Error;
+
+pub async fn put_bucket_lifecycle(state: &AppState, bucket_name: &str, body: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let config = LifecycleConfiguration::from_xml(body).map_err(|_| S3Error::malformed_xml())?;
+    config.validate().map_err(S3Error::invalid_argument)?;
+    if let Some(action) = config.unsupported_action() {
+        return Err(S3Error::new(
+            StatusCode::NOT_IMPLEMENTED,
+            "NotImplemented",
+            format!("The {} lifecycle action is not implemented, only Expiration by Days or Date is.", action),
+        ));
+    }
+
+    let xml = config.with_generated_ids().to_xml().map_err(S3Error::internal)?;
+    state
+        .storage
+        .put_bucket_config(bucket_name, lifecycle::CONFIG_NAME, &xml)
+        .await?;
+    Ok(StatusCode::OK.into_response())
+}
+
+pub async fn get_bucket_lifecycle(state: &AppState, bucket_name: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let xml = state
+        .storage
+        .get_bucket_config(bucket_name, lifecycle::CONFIG_NAME)
+        .await?
+        .ok_or_else(|| {
+            S3Error::new(
+                StatusCode::NOT_FOUND,
+                "NoSuchLifecycleConfiguration",
+                "The lifecycle configuration does not exist",
+            )
+            .with_resource(bucket_name)
+        })?;
+    Ok(([(header::CONTENT_TYPE, "application/xml")], xml).into_response())
+}
+
+pub async fn delete_bucket_lifecycle(state: &AppState, bucket_name: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    state
+        .storage
+        .delete_bucket_config(bucket_name, lifecycle::CONFIG_NAME)
+        .await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// Value of the `x-amz-expiration` header for the object, if a lifecycle rule applies to it.
+pub async fn expiration_header(state: &AppState, bucket_name: &str, object: &Object) -> Option<String> {
+    let xml = state
+        .storage
+        .get_bucket_config(bucket_name, lifecycle::CONFIG_NAME)
+        .await
+        .ok()??;
+    let config = LifecycleConfiguration::from_xml(&xml).ok()?;
+    config
+        .expiration_for(object)
+        .map(|(date, rule_id)| lifecycle::expiration_header(date, rule_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::bucket::Bucket;
+    use super::super::api::tests::test_state;
+
+    #[tokio::test]
+    async fn test_put_bucket_lifecycle_rejects_unsupported_actions() {
+        let (_data_dir, state) = test_state();
+        state.storage.create_bucket(&Bucket::new("logs".to_string())).await.unwrap();
+        let config = |action: &str| {
+            format!("<LifecycleConfiguration><Rule><ID>r</ID><Status>Enabled</Status>{}</Rule></LifecycleConfiguration>", action)
+        };
+
+        let abort = config("<AbortIncompleteMultipartUpload><DaysAfterInitiation>7</DaysAfterInitiation></AbortIncompleteMultipartUpload>");
+        let error = put_bucket_lifecycle(&state, "logs", &abort).await.unwrap_err();
+        assert_eq!((error.status, error.code), (StatusCode::NOT_IMPLEMENTED, "NotImplemented"));
+        assert!(state.storage.get_bucket_config("logs", lifecycle::CONFIG_NAME).await.unwrap().is_none());
+
+        let expiration = config("<Expiration><Days>30</Days></Expiration>");
+        put_bucket_lifecycle(&state, "logs", &expiration).await.unwrap();
+        assert!(state.storage.get_bucket_config("logs", lifecycle::CONFIG_NAME).await.unwrap().is_some());
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use crate::domain::acl::Owner;
+use super::acl::{bucket_acl, object_ownership};
+use super::api::{require_bucket, AppState};
+use super::error::{escape_xml, S3Error};
+
+const MAX_KEYS: usize = 1000;
+
+// What `encoding-type=url` escapes, leaving the key readable
+const URL_ENCODE: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~').remove(b'/');
+
+// A key or common prefix of the listing
+enum Entry {
+    Key(String),
+    CommonPrefix(String),
+}
+
+/// ListObjects, or ListObjectsV2 with `list-type=2`, of the keys after the marker.
+pub async fn list_objects(state: &AppState, bucket_name: &str, params: &HashMap<String, String>) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let v2 = params.get("list-type").map(String::as_str) == Some("2");
+    let prefix = params.get("prefix").map(String::as_str).unwrap_or("");
+    let delimiter = params.get("delimiter").map(String::as_str).filter(|d| !d.is_empty());
+    let max_keys = match params.get("max-keys") {
+        Some(value) => value
+            .parse::<usize>()
+            .map_err(|_| S3Error::invalid_argument("Provided max-keys not an integer or within integer range"))?
+            .min(MAX_KEYS),
+        None => MAX_KEYS,
+    };
+    let url_encoded = match params.get("encoding-type").map(String::as_str) {
+        None => false,
+        Some("url") => true,
+        Some(_) => return Err(S3Error::invalid_argument("Invalid Encoding Method specified in Request")),
+    };
+    let encode = |value: &str| {
+        if url_encoded {
+            escape_xml(&utf8_percent_encode(value, URL_ENCODE).to_string())
+        } else {
+            escape_xml(value)
+        }
+    };
+
+    let continuation_token = params.get("continuation-token").filter(|_| v2);
+    let start_after = match continuation_token {
+        Some(token) => hex::decode(token)
+            .ok()
+            .and_then(|key| String::from_utf8(key).ok())
+            .ok_or_else(|| S3Error::invalid_argument("The continuation token provided is incorrect"))?,
+        None if v2 => params.get("start-after").cloned().unwrap_or_default(),
+        None => params.get("marker").cloned().unwrap_or_default(),
+    };
+
+    let mut entries = Vec::new();
+    let mut is_truncated = false;
+    for key in state.storage.list_objects(bucket_name).await? {
+        if key <= start_after || !key.starts_with(prefix) {
+            continue;
+        }
+        let common_prefix = delimiter.and_then(|delimiter| {
+            key[prefix.len()..]
+                .find(delimiter)
+                .map(|end| key[..prefix.len() + end + delimiter.len()].to_string())
+        });
+        let entry = match common_prefix {
+            // Keys of a common prefix are rolled up into the first one, including the prefix
+            // already returned before resuming after it
+            Some(common_prefix) if common_prefix == start_after => continue,
+            Some(common_prefix) => {
+                if matches!(entries.last(), Some(Entry::CommonPrefix(last)) if *last == common_prefix) {
+                    continue;
+                }
+                Entry::CommonPrefix(common_prefix)
+            }
+            None => Entry::Key(key),
+        };
+        if entries.len() == max_keys {
+            is_truncated = true;
+            break;
+        }
+        entries.push(entry);
+    }
+
+    let bucket_owner = bucket_acl(state, bucket_name).await?.owner;
+    let acls_enabled = object_ownership(state, bucket_name).await?.acls_enabled();
+    let fetch_owner = !v2 || params.get("fetch-owner").map(String::as_str) == Some("true");
+    let mut contents = String::new();
+    let mut common_prefixes = String::new();
+    for entry in &entries {
+        match entry {
+            Entry::Key(key) => {
+                let object = state.storage.head_object(bucket_name, key).await?;
+                let owner = if fetch_owner {
+                    // As for GetObjectAcl, the bucket owner owns everything with ACLs disabled
+                    let owner = match object.acl {
+                        Some(acl) if acls_enabled => acl.owner,
+                        _ => bucket_owner.clone(),
+                    };
+                    owner_xml(&owner)
+                } else {
+                    String::new()
+                };
+                contents.push_str(&format!(
+                    "<Contents><Key>{}</Key><LastModified>{}</LastModified><ETag>{}</ETag><Size>{}</Size>{}<StorageClass>STANDARD</StorageClass></Contents>",
+                    encode(key),
+                    object.last_modified.format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+                    escape_xml(&object.etag),
+                    object.size,
+                    owner
+                ));
+            }
+            Entry::CommonPrefix(common_prefix) => {
+                common_prefixes.push_str(&format!("<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>", encode(common_prefix)));
+            }
+        }
+    }
+
+    let last = entries.last().map(|entry| match entry {
+        Entry::Key(key) | Entry::CommonPrefix(key) => key.as_str(),
+    });
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<ListBucketResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\"><Name>{}</Name><Prefix>{}</Prefix>",
+        escape_xml(bucket_name),
+        encode(prefix)
+    );
+    if v2 {
+        xml.push_str(&format!("<KeyCount>{}</KeyCount>", entries.len()));
+    } else {
+        xml.push_str(&format!("<Marker>{}</Marker>", encode(&start_after)));
+    }
+    xml.push_str(&format!("<MaxKeys>{}</MaxKeys>", max_keys));
+    if let Some(delimiter) = delimiter {
+        xml.push_str(&format!("<Delimiter>{}</Delimiter>", encode(delimiter)));
+    }
+    if url_encoded {
+        xml.push_str("<EncodingType>url</EncodingType>");
+    }
+    xml.push_str(&format!("<IsTruncated>{}</IsTruncated>", is_truncated));
+    if v2 {
+        if let Some(token) = continuation_token {
+            xml.push_str(&format!("<ContinuationToken>{}</ContinuationToken>", escape_xml(token)));
+        }
+        if let Some(last) = last.filter(|_| is_truncated) {
+            xml.push_str(&format!("<NextContinuationToken>{}</NextContinuationToken>", hex::encode(last)));
+        }
+        if let Some(start_after) = params.get("start-after") {
+            xml.push_str(&format!("<StartAfter>{}</StartAfter>", encode(start_after)));
+        }
+    } else if let Some(last) = last.filter(|_| is_truncated && delimiter.is_some()) {
+        // Without a delimiter, clients continue after the last key
+        xml.push_str(&format!("<NextMarker>{}</NextMarker>", encode(last)));
+    }
+    xml.push_str(&contents);
+    xml.push_str(&common_prefixes);
+    xml.push_str("</ListBucketResult>");
+    Ok(([(header::CONTENT_TYPE, "application/xml")], xml).into_response())
+}
+
+fn owner_xml(owner: &Owner) -> String {
+    format!(
+        "<Owner><ID>{}</ID><DisplayName>{}</DisplayName></Owner>",
+        escape_xml(&owner.id),
+        escape_xml(&owner.display_name)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::bucket::Bucket;
+    use crate::domain::object::Object;
+    use super::super::api::tests::test_state;
+
+    async fn list(state: &AppState, query: &[(&str, &str)]) -> String {
+        let params = query.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let response = list_objects(state, "photos", &params).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    async fn test_bucket() -> (tempfile::TempDir, AppState) {
+        let (data_dir, state) = test_state();
+        state.storage.create_bucket(&Bucket::new("photos".to_string())).await.unwrap();
+        for key in ["2023/a.jpg", "2024/b.jpg", "2024/c.jpg", "index.html", "a b+c"] {
+            let object = Object::new(key.to_string(), b"data".to_vec(), "image/jpeg".to_string());
+            state.storage.put_object("photos", &object).await.unwrap();
+        }
+        (data_dir, state)
+    }
+
+    #[tokio::test]
+    async fn test_list_objects_v1() {
+        let (_data_dir, state) = test_bucket().await;
+
+        let xml = list(&state, &[]).await;
+        assert!(xml.contains("<Name>photos</Name><Prefix></Prefix><Marker></Marker><MaxKeys>1000</MaxKeys><IsTruncated>false</IsTruncated>"));
+        assert_eq!(xml.matches("<Contents>").count(), 5);
+        assert_eq!(xml.matches("<Owner>").count(), 5);
+        assert!(xml.contains("<Key>2023/a.jpg</Key>"));
+        assert!(xml.contains("<ETag>&quot;"));
+
+        let xml = list(&state, &[("delimiter", "/"), ("max-keys", "2")]).await;
+        assert!(xml.contains("<IsTruncated>true</IsTruncated><NextMarker>2024/</NextMarker>"));
+        assert!(xml.contains("<CommonPrefixes><Prefix>2023/</Prefix></CommonPrefixes><CommonPrefixes><Prefix>2024/</Prefix></CommonPrefixes>"));
+        let xml = list(&state, &[("delimiter", "/"), ("marker", "2024/")]).await;
+        assert!(!xml.contains("<CommonPrefixes>"));
+        assert!(xml.contains("<Key>a b+c</Key>") && xml.contains("<Key>index.html</Key>"));
+
+        let xml = list(&state, &[("prefix", "2024/"), ("encoding-type", "url")]).await;
+        assert_eq!(xml.matches("<Contents>").count(), 2);
+        let xml = list(&state, &[("prefix", "a"), ("encoding-type", "url")]).await;
+        assert!(xml.contains("<Key>a%20b%2Bc</Key>"));
+    }
+
+    #[tokio::test]
+    async fn test_list_objects_v2() {
+        let (_data_dir, state) = test_bucket().await;
+
+        let xml = list(&state, &[("list-type", "2"), ("max-keys", "3")]).await;
+        assert!(xml.contains("<KeyCount>3</KeyCount>"));
+        assert!(!xml.contains("<Owner>"));
+        let token = xml.split("<NextContinuationToken>").nth(1).unwrap().split('<').next().unwrap();
+        let xml = list(&state, &[("list-type", "2"), ("continuation-token", token), ("fetch-owner", "true")]).await;
+        assert!(xml.contains("<KeyCount>2</KeyCount>") && xml.contains("<IsTruncated>false</IsTruncated>"));
+        assert!(xml.contains("<Key>a b+c</Key>") && xml.contains("<Key>index.html</Key>"));
+        assert_eq!(xml.matches("<Owner>").count(), 2);
+
+        let xml = list(&state, &[("list-type", "2"), ("start-after", "2024/b.jpg")]).await;
+        assert!(xml.contains("<KeyCount>3</KeyCount>") && xml.contains("<StartAfter>2024/b.jpg</StartAfter>"));
+
+        let params = [("list-type", "2"), ("continuation-token", "nope")].iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        assert_eq!(list_objects(&state, "photos", &params).await.unwrap_err().code, "InvalidArgument");
+    }
+}
@@ -0,0 +1,212 @@
+use std::net::SocketAddr;
+use std::time::Instant;
+use axum::body::HttpBody;
+use axum::extract::{ConnectInfo, OriginalUri, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use chrono::Utc;
+use uuid::Uuid;
+use crate::domain::identity::Requester;
+use crate::domain::logging::{self, AccessLogRecord, BucketLoggingStatus};
+use super::access::requester_of;
+use super::api::{require_bucket, AppState};
+use super::error::{ResponseError, S3Error};
+use super::object::header_str;
+use super::operation::S3Operation;
+use super::acl;
+
+// Query parameters naming a bucket subresource, and how operations on it are logged
+const SUBRESOURCES: [(&str, &str); 14] = [
+    ("acl", "ACL"),
+    ("cors", "CORS"),
+    ("encryption", "ENCRYPTION"),
+    ("inventory", "INVENTORY"),
+    ("lifecycle", "LIFECYCLE"),
+    ("location", "LOCATION"),
+    ("logging", "LOGGING_STATUS"),
+    ("notification", "NOTIFICATION"),
+    ("ownershipControls", "OWNERSHIP_CONTROLS"),
+    ("policy", "BUCKETPOLICY"),
+    ("publicAccessBlock", "PUBLIC_ACCESS_BLOCK"),
+    ("replication", "REPLICATION"),
+    ("tagging", "TAGGING"),
+    ("website", "WEBSITE"),
+];
+
+pub async fn put_bucket_logging(state: &AppState, bucket_name: &str, body: &str) -> Result<Response, S3Error> {
+    let bucket = require_bucket(state, bucket_name).await?;
+    let status = BucketLoggingStatus::from_xml(body).map_err(|_| S3Error::malformed_xml())?;
+    status.validate().map_err(S3Error::invalid_argument)?;
+    let Some(enabled) = &status.logging_enabled else {
+        state.storage.delete_bucket_config(bucket_name, logging::CONFIG_NAME).await?;
+        return Ok(StatusCode::OK.into_response());
+    };
+
+    let invalid_target = |message: &str| {
+        S3Error::new(StatusCode::BAD_REQUEST, "InvalidTargetBucketForLogging", message)
+            .with_detail("TargetBucket", enabled.target_bucket.clone())
+    };
+    let target = state
+        .storage
+        .get_bucket(&enabled.target_bucket)
+        .await?
+        .ok_or_else(|| invalid_target("The target bucket for logging does not exist"))?;
+    if target.region != bucket.region {
+        return Err(invalid_target("Cross S3 location logging not allowed. "));
+    }
+
+    let xml = status.to_xml().map_err(S3Error::internal)?;
+    state
+        .storage
+        .put_bucket_config(bucket_name, logging::CONFIG_NAME, &xml)
+        .await?;
+    Ok(StatusCode::OK.into_response())
+}
+
+/// GetBucketLogging. Buckets that don't log get an empty `BucketLoggingStatus`.
+pub async fn get_bucket_logging(state: &AppState, bucket_name: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let xml = state
+        .storage
+        .get_bucket_config(bucket_name, logging::CONFIG_NAME)
+        .await?
+        .unwrap_or_else(|| "<BucketLoggingStatus xmlns=\"http://doc.s3.amazonaws.com/2006-03-01\"/>".to_string());
+    Ok(([(header::CONTENT_TYPE, "application/xml")], xml).into_response())
+}
+
+/// Middleware recording requests for buckets with logging enabled, in the server access
+/// log format, for delivery to their target bucket.
+pub async fn log_access(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let time = Utc::now();
+    let operation = S3Operation::classify(request.method(), request.uri(), request.headers());
+    let Some(bucket_name) = operation.bucket.clone() else {
+        return next.run(request).await;
+    };
+    // Signed for, and logged as, the path the client sent
+    let uri = request
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|OriginalUri(uri)| uri.clone())
+        .unwrap_or_else(|| request.uri().clone());
+    let request_uri = format!("{} {} {:?}", request.method(), uri, request.version());
+    let label = operation_label(request.method().as_str(), &operation);
+    let requester = requester_name(&requester_of(request.extensions()));
+    let remote_ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string())
+        .unwrap_or_else(|| "127.0.0.1".to_string());
+    let headers = request.headers().clone();
+    let (signature_version, authentication_type) = authentication(&headers, uri.query().unwrap_or_default());
+
+    let response = next.run(request).await;
+    let turn_around_time = started.elapsed().as_millis() as u64;
+
+    let Ok(Some(bucket)) = state.storage.get_bucket(&bucket_name).await else {
+        return response;
+    };
+    let status = match state.storage.get_bucket_config(&bucket_name, logging::CONFIG_NAME).await {
+        Ok(Some(xml)) => BucketLoggingStatus::from_xml(&xml).unwrap_or_default(),
+        _ => return response,
+    };
+    let Some(target) = status.logging_enabled else {
+        return response;
+    };
+    let bucket_owner = match acl::bucket_acl(&state, &bucket_name).await {
+        Ok(bucket_acl) => bucket_acl.owner.id,
+        Err(_) => "-".to_string(),
+    };
+    let bytes_sent = response.body().size_hint().exact();
+    let object_size = match operation.name {
+        _ if !response.status().is_success() => None,
+        "GetObject" | "HeadObject" => header_str(response.headers(), header::CONTENT_LENGTH.as_str())
+            .and_then(|length| length.parse().ok())
+            .or(bytes_sent),
+        "PutObject" | "PostObject" => header_str(&headers, header::CONTENT_LENGTH.as_str()).and_then(|length| length.parse().ok()),
+        _ => None,
+    };
+
+    let record = AccessLogRecord {
+        bucket_owner,
+        bucket: bucket_name,
+        time,
+        remote_ip,
+        requester,
+        request_id: Uuid::new_v4().simple().to_string()[..16].to_uppercase(),
+        operation: label,
+        key: operation.key,
+        request_uri,
+        http_status: response.status().as_u16(),
+        error_code: response.extensions().get::<ResponseError>().map(|error| error.code.to_string()),
+        bytes_sent,
+        object_size,
+        total_time_ms: started.elapsed().as_millis() as u64,
+        turn_around_time_ms: Some(turn_around_time),
+        referer: header_str(&headers, header::REFERER.as_str()).map(str::to_string),
+        user_agent: header_str(&headers, header::USER_AGENT.as_str()).map(str::to_string),
+        signature_version,
+        authentication_type,
+        host_header: header_str(&headers, header::HOST.as_str()).map(str::to_string),
+    };
+    state.access_logs.record(&bucket.region, target, record);
+    response
+}
+
+// As in "REST.GET.OBJECT" or "REST.PUT.BUCKETPOLICY"
+fn operation_label(method: &str, operation: &S3Operation) -> String {
+    let resource = match operation.name {
+        "PostObject" => return "REST.POST.UPLOAD".to_string(),
+        "CopyObject" => return "REST.COPY.OBJECT".to_string(),
+        "PreflightRequest" => return "REST.OPTIONS.PREFLIGHT".to_string(),
+        "SelectObjectContent" => return "REST.POST.SELECT".to_string(),
+        "GetObject" | "HeadObject" | "PutObject" | "DeleteObject" => "OBJECT",
+        _ => SUBRESOURCES
+            .iter()
+            .find(|(param, _)| operation.query.contains_key(*param))
+            .map(|(_, resource)| *resource)
+            .unwrap_or("BUCKET"),
+    };
+    format!("REST.{}.{}", method, resource)
+}
+
+// Root credentials are logged by their canonical user ID, others by their ARN
+fn requester_name(requester: &Requester) -> String {
+    match requester.identity() {
+        Some(identity) if identity.arn.ends_with(":root") => identity.canonical_user_id.clone(),
+        Some(identity) => identity.arn.clone(),
+        None => "-".to_string(),
+    }
+}
+
+/// Signature version and authentication type of a request, as logged.
+pub(crate) fn authentication(headers: &HeaderMap, query: &str) -> (Option<String>, Option<String>) {
+    let signature_version = |version: &str, auth_type: &str| (Some(version.to_string()), Some(auth_type.to_string()));
+    match header_str(headers, header::AUTHORIZATION.as_str()) {
+        Some(authorization) if authorization.starts_with("AWS4-") => signature_version("SigV4", "AuthHeader"),
+        Some(authorization) if authorization.starts_with("AWS ") => signature_version("SigV2", "AuthHeader"),
+        _ if query.contains("X-Amz-Algorithm=") => signature_version("SigV4", "QueryString"),
+        _ if query.contains("Signature=") => signature_version("SigV2", "QueryString"),
+        _ => (None, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Method;
+
+    #[test]
+    fn test_operation_labels() {
+        let label = |method: Method, uri: &str| {
+            let operation = S3Operation::classify(&method, &uri.parse().unwrap(), &HeaderMap::new());
+            operation_label(method.as_str(), &operation)
+        };
+        assert_eq!(label(Method::GET, "/site/a.txt"), "REST.GET.OBJECT");
+        assert_eq!(label(Method::PUT, "/site?policy"), "REST.PUT.BUCKETPOLICY");
+        assert_eq!(label(Method::GET, "/site/a.txt?acl"), "REST.GET.ACL");
+        assert_eq!(label(Method::GET, "/site"), "REST.GET.BUCKET");
+        assert_eq!(label(Method::POST, "/site"), "REST.POST.UPLOAD");
+    }
+}
@@ -1,2 +1,31 @@
+#[allow(clippy::module_inception)]
 mod api;
+mod access;
+mod acl;
+mod admin;
+mod audit;
+mod auth;
+mod chunked;
+mod cors;
+mod encryption;
+mod error;
+mod events;
+mod hooks;
+mod inventory;
+mod lifecycle;
+mod list_objects;
+mod logging;
+mod notification;
+mod object;
+mod operation;
+mod policy;
+mod post_object;
+mod public_access;
+mod region;
+mod replication;
+mod select;
+mod sqs;
+mod sts;
+mod virtual_host;
+mod website;
 pub use api::*;
@@ -0,0 +1,97 @@
+use std::net::SocketAddr;
+use axum::extract::ConnectInfo;
+use axum::http::{header, Extensions, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use uuid::Uuid;
+use crate::domain::identity::Requester;
+use crate::domain::notification::{self, NotificationConfiguration};
+use crate::domain::object::Object;
+use super::api::{require_bucket, AppState};
+use super::error::S3Error;
+use super::object::header_str;
+
+pub async fn put_bucket_notification(
+    state: &AppState,
+    bucket_name: &str,
+    headers: &HeaderMap,
+    body: &str,
+) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let mut config = NotificationConfiguration::from_xml(body).map_err(|_| S3Error::malformed_xml())?;
+    config.validate().map_err(S3Error::invalid_argument)?;
+    let skip_validation = header_str(headers, "x-amz-skip-destination-validation") == Some("true");
+    if !skip_validation {
+        let mut unknown = None;
+        for arn in config.destinations().filter_map(|destination| destination.destination()) {
+            if !state.notifier.knows_destination(arn).await {
+                unknown = Some(arn);
+                break;
+            }
+        }
+        if let Some(arn) = unknown {
+            return Err(S3Error::invalid_argument("Unable to validate the following destination configurations")
+                .with_detail("ArgumentName", arn)
+                .with_detail("ArgumentValue", "Not authorized to invoke the destination"));
+        }
+    }
+    for destination in config.destinations_mut().filter(|destination| destination.id.is_none()) {
+        destination.id = Some(Uuid::new_v4().to_string());
+    }
+
+    let xml = config.to_xml().map_err(S3Error::internal)?;
+    state
+        .storage
+        .put_bucket_config(bucket_name, notification::CONFIG_NAME, &xml)
+        .await?;
+    state.notifier.send_test_events(bucket_name, &config);
+    Ok(StatusCode::OK.into_response())
+}
+
+/// GetBucketNotificationConfiguration. Buckets without one get an empty configuration.
+pub async fn get_bucket_notification(state: &AppState, bucket_name: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let xml = match state
+        .storage
+        .get_bucket_config(bucket_name, notification::CONFIG_NAME)
+        .await?
+    {
+        Some(xml) => xml,
+        None => NotificationConfiguration::default().to_xml().map_err(S3Error::internal)?,
+    };
+    Ok(([(header::CONTENT_TYPE, "application/xml")], xml).into_response())
+}
+
+/// Publishes an event for `key`, naming the requester and its address. `object` is the
+/// created object, or `None` for removals. The request already succeeded, so failures
+/// are only logged.
+pub async fn notify(
+    state: &AppState,
+    extensions: &Extensions,
+    requester: &Requester,
+    event_name: &str,
+    bucket_name: &str,
+    key: &str,
+    object: Option<&Object>,
+) {
+    let principal_id = principal_id(requester);
+    let source_ip = extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string())
+        .unwrap_or_else(|| "127.0.0.1".to_string());
+    match state
+        .notifier
+        .event(event_name, bucket_name, key, object, principal_id, source_ip)
+        .await
+    {
+        Ok(event) => state.notifier.publish(&event).await,
+        Err(e) => eprintln!("Skipping {} notification of {}/{}: {}", event_name, bucket_name, key, e),
+    }
+}
+
+/// The requester as named in event records.
+pub fn principal_id(requester: &Requester) -> String {
+    match requester.identity() {
+        Some(identity) => format!("AWS:{}", identity.account_id),
+        None => "Anonymous".to_string(),
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use axum::body::Bytes;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, Extensions, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use crate::domain::identity::Requester;
+use crate::domain::object::{Object, MAX_KEY_LENGTH};
+use super::api::{require_bucket, AppState};
+use super::error::{escape_xml, S3Error};
+use super::operation::parse_copy_source;
+use super::notification::notify;
+use super::{acl, encryption, lifecycle, replication, website};
+
+const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+pub async fn put_object(
+    State(state): State<AppState>,
+    Path((bucket_name, key)): Path<(String, String)>,
+    Query(params): Query<HashMap<String, String>>,
+    requester: Requester,
+    extensions: Extensions,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, S3Error> {
+    check_key(&key)?;
+    if params.contains_key("acl") {
+        let body = String::from_utf8_lossy(&body);
+        return acl::put_object_acl(&state, &bucket_name, &key, &headers, &body).await;
+    }
+    if let Some(copy_source) = header_str(&headers, "x-amz-copy-source") {
+        return copy_object(&state, &bucket_name, &key, copy_source, &requester, &extensions, &headers).await;
+    }
+    require_bucket(&state, &bucket_name).await?;
+
+    let content_type = header_str(&headers, header::CONTENT_TYPE.as_str()).unwrap_or(DEFAULT_CONTENT_TYPE);
+    let tags = match header_str(&headers, "x-amz-tagging") {
+        Some(tagging) => parse_tagging(tagging)?,
+        None => BTreeMap::new(),
+    };
+    let redirect_location = website::redirect_location(header_str(&headers, "x-amz-website-redirect-location"))?;
+    let object_acl = acl::new_object_acl(&state, &bucket_name, &headers, &requester).await?;
+    let object = Object::new(key, body.to_vec(), content_type.to_string())
+        .with_tags(tags)
+        .with_website_redirect_location(redirect_location)
+        .with_acl(object_acl);
+    let object = encryption::encrypt_object(&state, &bucket_name, &headers, object).await?;
+    let object = replication::store_object(&state, &bucket_name, object).await?;
+    notify(&state, &extensions, &requester, "ObjectCreated:Put", &bucket_name, &object.key, Some(&object)).await;
+
+    let mut response = StatusCode::OK.into_response();
+    insert_header(&mut response, header::ETAG.as_str(), &object.etag);
+    encryption::insert_encryption_headers(&mut response, &object);
+    if let Some(expiration) = lifecycle::expiration_header(&state, &bucket_name, &object).await {
+        insert_header(&mut response, "x-amz-expiration", &expiration);
+    }
+    Ok(response)
+}
+
+async fn copy_object(
+    state: &AppState,
+    bucket_name: &str,
+    key: &str,
+    copy_source: &str,
+    requester: &Requester,
+    extensions: &Extensions,
+    headers: &HeaderMap,
+) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let (source_bucket, source_key) = parse_copy_source(copy_source)
+        .ok_or_else(|| S3Error::invalid_argument("Copy Source must mention the source bucket and key: sourcebucket/sourcekey"))?;
+    check_key(&source_key)?;
+    require_bucket(state, &source_bucket).await?;
+    let mut source = state
+        .storage
+        .get_object(&source_bucket, &source_key)
+        .await
+        .map_err(|e| object_error(e, &source_key))?;
+    encryption::decrypt_object(headers, encryption::COPY_SOURCE_CUSTOMER_HEADERS, &mut source)?;
+
+    let replace_metadata = header_str(headers, "x-amz-metadata-directive") == Some("REPLACE");
+    let replace_tags = header_str(headers, "x-amz-tagging-directive") == Some("REPLACE");
+    if source_bucket == bucket_name && source_key == key && !replace_metadata {
+        return Err(S3Error::new(
+            StatusCode::BAD_REQUEST,
+            "InvalidRequest",
+            "This copy request is illegal because it is trying to copy an object to itself without changing the object's metadata, storage class, website redirect location or encryption attributes.",
+        ));
+    }
+
+    let content_type = if replace_metadata {
+        header_str(headers, header::CONTENT_TYPE.as_str()).unwrap_or(DEFAULT_CONTENT_TYPE).to_string()
+    } else {
+        source.content_type.clone()
+    };
+    let tags = match (replace_tags, header_str(headers, "x-amz-tagging")) {
+        (true, Some(tagging)) => parse_tagging(tagging)?,
+        (true, None) => BTreeMap::new(),
+        (false, _) => source.tags.clone(),
+    };
+    let redirect_location = match header_str(headers, "x-amz-website-redirect-location") {
+        Some(location) => website::redirect_location(Some(location))?,
+        None if replace_metadata => None,
+        None => source.website_redirect_location.clone(),
+    };
+    // ACLs are never copied, the new object gets the one from the request
+    let object_acl = acl::new_object_acl(state, bucket_name, headers, requester).await?;
+    // The copy is encrypted as the request asks, whatever the source's encryption
+    let object = Object::new(key.to_string(), source.content, content_type)
+        .with_tags(tags)
+        .with_website_redirect_location(redirect_location)
+        .with_acl(object_acl);
+    let object = encryption::encrypt_object(state, bucket_name, headers, object).await?;
+    let object = replication::store_object(state, bucket_name, object).await?;
+    notify(state, extensions, requester, "ObjectCreated:Copy", bucket_name, key, Some(&object)).await;
+
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<CopyObjectResult><LastModified>{}</LastModified><ETag>{}</ETag></CopyObjectResult>",
+        object.last_modified.format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+        escape_xml(&object.etag)
+    );
+    let mut response = ([(header::CONTENT_TYPE, "application/xml")], xml).into_response();
+    encryption::insert_encryption_headers(&mut response, &object);
+    if let Some(expiration) = lifecycle::expiration_header(state, bucket_name, &object).await {
+        insert_header(&mut response, "x-amz-expiration", &expiration);
+    }
+    Ok(response)
+}
+
+pub async fn get_object(
+    State(state): State<AppState>,
+    Path((bucket_name, key)): Path<(String, String)>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Result<Response, S3Error> {
+    check_key(&key)?;
+    if params.contains_key("acl") {
+        return acl::get_object_acl(&state, &bucket_name, &key).await;
+    }
+    require_bucket(&state, &bucket_name).await?;
+    let mut object = state
+        .storage
+        .get_object(&bucket_name, &key)
+        .await
+        .map_err(|e| object_error(e, &key))?;
+    encryption::decrypt_object(&headers, encryption::CUSTOMER_HEADERS, &mut object)?;
+
+    let range = header_str(&headers, header::RANGE.as_str()).and_then(|range| parse_range(range, object.size));
+    let mut response = match range {
+        Some(Ok((start, end))) => {
+            let mut response = (StatusCode::PARTIAL_CONTENT, object.content[start..=end].to_vec()).into_response();
+            let content_range = format!("bytes {}-{}/{}", start, end, object.size);
+            insert_header(&mut response, header::CONTENT_RANGE.as_str(), &content_range);
+            response
+        }
+        Some(Err(())) => {
+            return Err(S3Error::new(
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                "InvalidRange",
+                "The requested range is not satisfiable",
+            )
+            .with_detail("RangeRequested", header_str(&headers, header::RANGE.as_str()).unwrap_or_default())
+            .with_detail("ActualObjectSize", object.size.to_string()));
+        }
+        None => object.content.clone().into_response(),
+    };
+    insert_header(&mut response, header::ACCEPT_RANGES.as_str(), "bytes");
+    apply_object_headers(&state, &bucket_name, &object, &mut response).await;
+    Ok(response)
+}
+
+pub async fn head_object(
+    State(state): State<AppState>,
+    Path((bucket_name, key)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, S3Error> {
+    check_key(&key)?;
+    require_bucket(&state, &bucket_name).await?;
+    let object = state
+        .storage
+        .head_object(&bucket_name, &key)
+        .await
+        .map_err(|e| object_error(e, &key))?;
+    encryption::check_customer_key(&headers, encryption::CUSTOMER_HEADERS, &object)?;
+
+    let mut response = StatusCode::OK.into_response();
+    insert_header(&mut response, header::CONTENT_LENGTH.as_str(), &object.size.to_string());
+    apply_object_headers(&state, &bucket_name, &object, &mut response).await;
+    Ok(response)
+}
+
+pub async fn delete_object(
+    State(state): State<AppState>,
+    Path((bucket_name, key)): Path<(String, String)>,
+    requester: Requester,
+    extensions: Extensions,
+) -> Result<Response, S3Error> {
+    check_key(&key)?;
+    require_bucket(&state, &bucket_name).await?;
+    replication::delete_object(&state, &bucket_name, &key).await?;
+    notify(&state, &extensions, &requester, "ObjectRemoved:Delete", &bucket_name, &key, None).await;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+async fn apply_object_headers(state: &AppState, bucket_name: &str, object: &Object, response: &mut Response) {
+    insert_header(response, header::CONTENT_TYPE.as_str(), &object.content_type);
+    insert_header(response, header::ETAG.as_str(), &object.etag);
+    insert_header(
+        response,
+        header::LAST_MODIFIED.as_str(),
+        &object.last_modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
+    );
+    if !object.tags.is_empty() {
+        insert_header(response, "x-amz-tagging-count", &object.tags.len().to_string());
+    }
+    if let Some(expiration) = lifecycle::expiration_header(state, bucket_name, object).await {
+        insert_header(response, "x-amz-expiration", &expiration);
+    }
+    if let Some(location) = &object.website_redirect_location {
+        insert_header(response, "x-amz-website-redirect-location", location);
+    }
+    if let Some(status) = object.replication_status {
+        insert_header(response, "x-amz-replication-status", status.as_str());
+    }
+    encryption::insert_encryption_headers(response, object);
+}
+
+pub(crate) fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+pub(crate) fn insert_header(response: &mut Response, name: &'static str, value: &str) {
+    if let Ok(value) = HeaderValue::from_str(value) {
+        response.headers_mut().insert(name, value);
+    }
+}
+
+/// Rejects keys S3 doesn't accept, before they reach the storage.
+pub(crate) fn check_key(key: &str) -> Result<(), S3Error> {
+    if Object::validate_key(key) {
+        return Ok(());
+    }
+    if key.is_empty() {
+        return Err(S3Error::invalid_argument("User key must have a length greater than 0."));
+    }
+    Err(S3Error::new(StatusCode::BAD_REQUEST, "KeyTooLongError", "Your key is too long")
+        .with_detail("Size", key.len().to_string())
+        .with_detail("MaxSizeAllowed", MAX_KEY_LENGTH.to_string()))
+}
+
+pub(crate) fn object_error(err: io::Error, key: &str) -> S3Error {
+    if err.kind() == io::ErrorKind::NotFound {
+        S3Error::no_such_key(key)
+    } else {
+        S3Error::internal(err)
+    }
+}
+
+/// Byte range, inclusive, of a `Range: bytes=...` header within an object of `size` bytes.
+/// `None` for headers that aren't a single byte range, which S3 ignores to return the whole
+/// object, and `Err` for ranges starting past the end of the object.
+fn parse_range(range: &str, size: usize) -> Option<Result<(usize, usize), ()>> {
+    let (start, end) = range.trim().strip_prefix("bytes=")?.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+    if start.is_empty() {
+        // Suffix range, the last bytes of the object
+        let length: usize = end.parse().ok()?;
+        if length == 0 || size == 0 {
+            return Some(Err(()));
+        }
+        return Some(Ok((size.saturating_sub(length), size - 1)));
+    }
+    let start: usize = start.parse().ok()?;
+    let end = if end.is_empty() { usize::MAX } else { end.parse().ok()? };
+    if end < start {
+        return None;
+    }
+    if start >= size {
+        return Some(Err(()));
+    }
+    Some(Ok((start, end.min(size - 1))))
+}
+
+// x-amz-tagging carries the tag set URL-encoded, as in "key1=value1&key2=value2"
+fn parse_tagging(tagging: &str) -> Result<BTreeMap<String, String>, S3Error> {
+    serde_urlencoded::from_str::<Vec<(String, String)>>(tagging)
+        .map(|pairs| pairs.into_iter().collect())
+        .map_err(|_| S3Error::invalid_argument("The header 'x-amz-tagging' shall be encoded as UTF-8 then URLEncoded URL query parameters without tag name duplicates."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-4", 10), Some(Ok((0, 4))));
+        assert_eq!(parse_range("bytes=5-", 10), Some(Ok((5, 9))));
+        assert_eq!(parse_range("bytes=8-100", 10), Some(Ok((8, 9))));
+        assert_eq!(parse_range("bytes=-3", 10), Some(Ok((7, 9))));
+        assert_eq!(parse_range("bytes=-30", 10), Some(Ok((0, 9))));
+        assert_eq!(parse_range("bytes=10-", 10), Some(Err(())));
+        assert_eq!(parse_range("bytes=5-2", 10), None);
+        assert_eq!(parse_range("bytes=0-1,4-5", 10), None);
+        assert_eq!(parse_range("items=0-4", 10), None);
+    }
+}
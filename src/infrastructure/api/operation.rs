@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use axum::http::{HeaderMap, Method, Uri};
+use percent_encoding::percent_decode_str;
+
+/// S3 operation targeted by an HTTP request, derived from its method, path and query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct S3Operation {
+    // API operation name, as in "GetObject"
+    pub name: &'static str,
+    // IAM action checked by policies, as in "s3:GetObject"
+    pub action: &'static str,
+    pub bucket: Option<String>,
+    pub key: Option<String>,
+    pub query: HashMap<String, String>,
+}
+
+impl S3Operation {
+    pub fn classify(method: &Method, uri: &Uri, headers: &HeaderMap) -> Self {
+        let query: HashMap<String, String> = uri
+            .query()
+            .and_then(|query| serde_urlencoded::from_str(query).ok())
+            .unwrap_or_default();
+        let (bucket, key) = split_path(uri.path());
+        let is_copy = headers.contains_key("x-amz-copy-source");
+        let (name, action) = operation_name(method, bucket.is_some(), key.is_some(), is_copy, &query);
+        Self { name, action, bucket, key, query }
+    }
+
+    /// The object read by a CopyObject request, from its `x-amz-copy-source` header.
+    pub fn copy_source(headers: &HeaderMap) -> Option<Self> {
+        let (bucket, key) = parse_copy_source(headers.get("x-amz-copy-source")?.to_str().ok()?)?;
+        Some(Self {
+            name: "GetObject",
+            action: "s3:GetObject",
+            bucket: Some(bucket),
+            key: Some(key),
+            query: HashMap::new(),
+        })
+    }
+
+    /// ARN of the bucket or object the operation acts on.
+    pub fn resource_arn(&self) -> String {
+        match (&self.bucket, &self.key) {
+            (Some(bucket), Some(key)) => format!("arn:aws:s3:::{}/{}", bucket, key),
+            (Some(bucket), None) => format!("arn:aws:s3:::{}", bucket),
+            _ => "*".to_string(),
+        }
+    }
+}
+
+fn split_path(path: &str) -> (Option<String>, Option<String>) {
+    let path = path.trim_start_matches('/');
+    let (bucket, key) = match path.split_once('/') {
+        Some((bucket, key)) => (bucket, Some(key)),
+        None => (path, None),
+    };
+    let decode = |value: &str| percent_decode_str(value).decode_utf8_lossy().into_owned();
+    let bucket = (!bucket.is_empty()).then(|| decode(bucket));
+    let key = key.filter(|key| !key.is_empty()).map(decode);
+    (bucket, key)
+}
+
+/// Splits an `x-amz-copy-source` value ("/bucket/key" or "bucket/key", URL-encoded) into bucket and key.
+pub fn parse_copy_source(value: &str) -> Option<(String, String)> {
+    // Only the latest version exists, a versionId is ignored
+    let value = value.split('?').next().unwrap_or(value);
+    match split_path(value) {
+        (Some(bucket), Some(key)) => Some((bucket, key)),
+        _ => None,
+    }
+}
+
+fn operation_name(
+    method: &Method,
+    has_bucket: bool,
+    has_key: bool,
+    is_copy: bool,
+    query: &HashMap<String, String>,
+) -> (&'static str, &'static str) {
+    let subresource = |name: &str| query.contains_key(name);
+
+    if *method == Method::OPTIONS {
+        return ("PreflightRequest", "s3:*");
+    }
+    if !has_bucket {
+        return ("ListBuckets", "s3:ListAllMyBuckets");
+    }
+
+    if has_key {
+        if subresource("acl") {
+            return match *method {
+                Method::GET => ("GetObjectAcl", "s3:GetObjectAcl"),
+                Method::PUT => ("PutObjectAcl", "s3:PutObjectAcl"),
+                _ => ("Unknown", "s3:*"),
+            };
+        }
+        return match *method {
+            Method::GET => ("GetObject", "s3:GetObject"),
+            Method::HEAD => ("HeadObject", "s3:GetObject"),
+            Method::PUT if is_copy => ("CopyObject", "s3:PutObject"),
+            Method::PUT => ("PutObject", "s3:PutObject"),
+            Method::POST if subresource("select") => ("SelectObjectContent", "s3:GetObject"),
+            Method::DELETE => ("DeleteObject", "s3:DeleteObject"),
+            _ => ("Unknown", "s3:*"),
+        };
+    }
+
+    if subresource("lifecycle") {
+        return match *method {
+            Method::GET => ("GetBucketLifecycleConfiguration", "s3:GetLifecycleConfiguration"),
+            Method::PUT => ("PutBucketLifecycleConfiguration", "s3:PutLifecycleConfiguration"),
+            Method::DELETE => ("DeleteBucketLifecycle", "s3:PutLifecycleConfiguration"),
+            _ => ("Unknown", "s3:*"),
+        };
+    }
+    if subresource("acl") {
+        return match *method {
+            Method::GET => ("GetBucketAcl", "s3:GetBucketAcl"),
+            Method::PUT => ("PutBucketAcl", "s3:PutBucketAcl"),
+            _ => ("Unknown", "s3:*"),
+        };
+    }
+    if subresource("cors") {
+        return match *method {
+            Method::GET => ("GetBucketCors", "s3:GetBucketCORS"),
+            Method::PUT => ("PutBucketCors", "s3:PutBucketCORS"),
+            Method::DELETE => ("DeleteBucketCors", "s3:PutBucketCORS"),
+            _ => ("Unknown", "s3:*"),
+        };
+    }
+    if subresource("publicAccessBlock") {
+        return match *method {
+            Method::GET => ("GetPublicAccessBlock", "s3:GetBucketPublicAccessBlock"),
+            Method::PUT => ("PutPublicAccessBlock", "s3:PutBucketPublicAccessBlock"),
+            Method::DELETE => ("DeletePublicAccessBlock", "s3:PutBucketPublicAccessBlock"),
+            _ => ("Unknown", "s3:*"),
+        };
+    }
+    if subresource("ownershipControls") {
+        return match *method {
+            Method::GET => ("GetBucketOwnershipControls", "s3:GetBucketOwnershipControls"),
+            Method::PUT => ("PutBucketOwnershipControls", "s3:PutBucketOwnershipControls"),
+            Method::DELETE => ("DeleteBucketOwnershipControls", "s3:PutBucketOwnershipControls"),
+            _ => ("Unknown", "s3:*"),
+        };
+    }
+    if subresource("notification") {
+        return match *method {
+            Method::GET => ("GetBucketNotificationConfiguration", "s3:GetBucketNotification"),
+            Method::PUT => ("PutBucketNotificationConfiguration", "s3:PutBucketNotification"),
+            _ => ("Unknown", "s3:*"),
+        };
+    }
+    if subresource("replication") {
+        return match *method {
+            Method::GET => ("GetBucketReplication", "s3:GetReplicationConfiguration"),
+            Method::PUT => ("PutBucketReplication", "s3:PutReplicationConfiguration"),
+            Method::DELETE => ("DeleteBucketReplication", "s3:PutReplicationConfiguration"),
+            _ => ("Unknown", "s3:*"),
+        };
+    }
+    if subresource("website") {
+        return match *method {
+            Method::GET => ("GetBucketWebsite", "s3:GetBucketWebsite"),
+            Method::PUT => ("PutBucketWebsite", "s3:PutBucketWebsite"),
+            Method::DELETE => ("DeleteBucketWebsite", "s3:DeleteBucketWebsite"),
+            _ => ("Unknown", "s3:*"),
+        };
+    }
+    if subresource("inventory") {
+        return match *method {
+            Method::GET if subresource("id") => ("GetBucketInventoryConfiguration", "s3:GetInventoryConfiguration"),
+            Method::GET => ("ListBucketInventoryConfigurations", "s3:GetInventoryConfiguration"),
+            Method::PUT => ("PutBucketInventoryConfiguration", "s3:PutInventoryConfiguration"),
+            Method::DELETE => ("DeleteBucketInventoryConfiguration", "s3:PutInventoryConfiguration"),
+            _ => ("Unknown", "s3:*"),
+        };
+    }
+    if subresource("encryption") {
+        return match *method {
+            Method::GET => ("GetBucketEncryption", "s3:GetEncryptionConfiguration"),
+            Method::PUT => ("PutBucketEncryption", "s3:PutEncryptionConfiguration"),
+            Method::DELETE => ("DeleteBucketEncryption", "s3:PutEncryptionConfiguration"),
+            _ => ("Unknown", "s3:*"),
+        };
+    }
+    if subresource("logging") {
+        return match *method {
+            Method::GET => ("GetBucketLogging", "s3:GetBucketLogging"),
+            Method::PUT => ("PutBucketLogging", "s3:PutBucketLogging"),
+            _ => ("Unknown", "s3:*"),
+        };
+    }
+    if subresource("location") && *method == Method::GET {
+        return ("GetBucketLocation", "s3:GetBucketLocation");
+    }
+    if subresource("policy") {
+        return match *method {
+            Method::GET => ("GetBucketPolicy", "s3:GetBucketPolicy"),
+            Method::PUT => ("PutBucketPolicy", "s3:PutBucketPolicy"),
+            Method::DELETE => ("DeleteBucketPolicy", "s3:DeleteBucketPolicy"),
+            _ => ("Unknown", "s3:*"),
+        };
+    }
+
+    match *method {
+        Method::GET => ("ListObjects", "s3:ListBucket"),
+        Method::HEAD => ("HeadBucket", "s3:ListBucket"),
+        Method::PUT => ("CreateBucket", "s3:CreateBucket"),
+        Method::DELETE => ("DeleteBucket", "s3:DeleteBucket"),
+        Method::POST => ("PostObject", "s3:PutObject"),
+        _ => ("Unknown", "s3:*"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(method: Method, uri: &str) -> S3Operation {
+        S3Operation::classify(&method, &uri.parse().unwrap(), &HeaderMap::new())
+    }
+
+    #[test]
+    fn test_classify_requests() {
+        let get = classify(Method::GET, "/bucket/dir/my%20file.txt");
+        assert_eq!(get.name, "GetObject");
+        assert_eq!(get.bucket.as_deref(), Some("bucket"));
+        assert_eq!(get.key.as_deref(), Some("dir/my file.txt"));
+        assert_eq!(get.resource_arn(), "arn:aws:s3:::bucket/dir/my file.txt");
+
+        let policy = classify(Method::PUT, "/bucket?policy");
+        assert_eq!(policy.action, "s3:PutBucketPolicy");
+        assert_eq!(policy.resource_arn(), "arn:aws:s3:::bucket");
+
+        assert_eq!(classify(Method::DELETE, "/bucket?lifecycle").action, "s3:PutLifecycleConfiguration");
+        assert_eq!(classify(Method::GET, "/").name, "ListBuckets");
+        assert_eq!(classify(Method::GET, "/bucket/").name, "ListObjects");
+        assert_eq!(classify(Method::PUT, "/bucket/key?acl").action, "s3:PutObjectAcl");
+        assert_eq!(classify(Method::GET, "/bucket?acl").action, "s3:GetBucketAcl");
+        assert_eq!(classify(Method::DELETE, "/bucket?cors").action, "s3:PutBucketCORS");
+        assert_eq!(classify(Method::DELETE, "/bucket?replication").action, "s3:PutReplicationConfiguration");
+        assert_eq!(classify(Method::OPTIONS, "/bucket/key").name, "PreflightRequest");
+        assert_eq!(classify(Method::POST, "/bucket").action, "s3:PutObject");
+        assert_eq!(classify(Method::GET, "/bucket?location").action, "s3:GetBucketLocation");
+        assert_eq!(classify(Method::DELETE, "/bucket?publicAccessBlock").action, "s3:PutBucketPublicAccessBlock");
+        assert_eq!(classify(Method::GET, "/bucket?ownershipControls").name, "GetBucketOwnershipControls");
+        assert_eq!(classify(Method::PUT, "/bucket?notification").action, "s3:PutBucketNotification");
+    }
+
+    #[test]
+    fn test_copy_source() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-amz-copy-source", "/source/dir/a%2Bb.txt?versionId=1".parse().unwrap());
+        let operation = S3Operation::classify(&Method::PUT, &"/bucket/key".parse().unwrap(), &headers);
+        assert_eq!(operation.name, "CopyObject");
+
+        let source = S3Operation::copy_source(&headers).unwrap();
+        assert_eq!(source.bucket.as_deref(), Some("source"));
+        assert_eq!(source.key.as_deref(), Some("dir/a+b.txt"));
+        assert_eq!(parse_copy_source("source"), None);
+    }
+}
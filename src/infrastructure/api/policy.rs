@@ -0,0 +1,123 @@
+use std::net::IpAddr;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use crate::domain::identity::Requester;
+use crate::domain::policy::{self, BucketPolicy, RequestContext};
+use super::api::{require_bucket, AppState};
+use super::error::S3Error;
+use super::object::header_str;
+use super::operation::S3Operation;
+use super::public_access::public_access_block;
+
+pub async fn put_bucket_policy(state: &AppState, bucket_name: &str, body: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let policy = BucketPolicy::from_json(body).map_err(malformed_policy)?;
+    policy.validate(bucket_name).map_err(malformed_policy)?;
+    if policy.is_public() && public_access_block(state, bucket_name).await?.block_public_policy {
+        return Err(access_denied());
+    }
+    state
+        .storage
+        .put_bucket_config(bucket_name, policy::CONFIG_NAME, body)
+        .await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+pub async fn get_bucket_policy(state: &AppState, bucket_name: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let policy = state
+        .storage
+        .get_bucket_config(bucket_name, policy::CONFIG_NAME)
+        .await?
+        .ok_or_else(|| {
+            S3Error::new(StatusCode::NOT_FOUND, "NoSuchBucketPolicy", "The bucket policy does not exist")
+                .with_resource(bucket_name)
+        })?;
+    Ok(([(header::CONTENT_TYPE, "application/json")], policy).into_response())
+}
+
+pub async fn delete_bucket_policy(state: &AppState, bucket_name: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    state
+        .storage
+        .delete_bucket_config(bucket_name, policy::CONFIG_NAME)
+        .await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// Stored policy of the bucket, if it has one.
+pub async fn bucket_policy(state: &AppState, bucket_name: &str) -> Result<Option<BucketPolicy>, S3Error> {
+    match state.storage.get_bucket_config(bucket_name, policy::CONFIG_NAME).await? {
+        Some(json) => BucketPolicy::from_json(&json).map(Some).map_err(S3Error::internal),
+        None => Ok(None),
+    }
+}
+
+pub fn request_context(
+    state: &AppState,
+    requester: Requester,
+    operation: &S3Operation,
+    headers: &HeaderMap,
+    source_ip: Option<IpAddr>,
+) -> RequestContext {
+    let mut context = RequestContext::new(requester, operation.action, operation.resource_arn())
+        .with_condition("aws:SecureTransport", is_secure_transport(state.trust_proxy, headers).to_string());
+
+    if let Some(ip) = source_ip {
+        context.set_condition("aws:SourceIp", ip.to_string());
+    }
+    if let Some(user_agent) = header_str(headers, header::USER_AGENT.as_str()) {
+        context.set_condition("aws:UserAgent", user_agent);
+    }
+    if let Some(referer) = header_str(headers, header::REFERER.as_str()) {
+        context.set_condition("aws:Referer", referer);
+    }
+    for name in ["prefix", "delimiter", "max-keys"] {
+        if let Some(value) = operation.query.get(name) {
+            context.set_condition(&format!("s3:{}", name), value.clone());
+        }
+    }
+    for (name, value) in headers {
+        if name.as_str().starts_with("x-amz-") {
+            if let Ok(value) = value.to_str() {
+                context.set_condition(&format!("s3:{}", name.as_str()), value);
+            }
+        }
+    }
+    context
+}
+
+// The listeners only speak plain HTTP. Requests are secure when a trusted proxy received
+// them over HTTPS, as it tells in X-Forwarded-Proto; clients could set it themselves otherwise.
+fn is_secure_transport(trust_proxy: bool, headers: &HeaderMap) -> bool {
+    trust_proxy
+        && header_str(headers, "x-forwarded-proto").is_some_and(|proto| proto.eq_ignore_ascii_case("https"))
+}
+
+pub fn access_denied() -> S3Error {
+    access_denied_with("Access Denied")
+}
+
+pub fn access_denied_with(message: &str) -> S3Error {
+    S3Error::new(StatusCode::FORBIDDEN, "AccessDenied", message)
+}
+
+fn malformed_policy(message: String) -> S3Error {
+    S3Error::new(StatusCode::BAD_REQUEST, "MalformedPolicy", message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secure_transport_needs_trusted_proxy() {
+        let mut headers = HeaderMap::new();
+        assert!(!is_secure_transport(true, &headers));
+        headers.insert("x-forwarded-proto", "https".parse().unwrap());
+        assert!(!is_secure_transport(false, &headers));
+        assert!(is_secure_transport(true, &headers));
+        headers.insert("x-forwarded-proto", "http".parse().unwrap());
+        assert!(!is_secure_transport(true, &headers));
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use axum::extract::{ConnectInfo, Multipart, Path, State};
+use axum::http::{header, Extensions, HeaderMap, HeaderName, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use chrono::Utc;
+use crate::domain::identity::Requester;
+use crate::domain::object::Object;
+use crate::domain::post_policy::{PolicyViolation, PostPolicy};
+use super::access::{check_access, requester_of};
+use super::api::{require_bucket, AppState};
+use super::error::{escape_xml, S3Error};
+use super::notification::notify;
+use super::object::{check_key, insert_header};
+use super::operation::S3Operation;
+use super::{acl, auth, encryption, replication, virtual_host, website};
+
+const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+struct UploadedFile {
+    filename: String,
+    content_type: Option<String>,
+    content: Vec<u8>,
+}
+
+/// Browser-based upload: a `multipart/form-data` POST to the bucket, whose fields
+/// carry the key, the policy and its signature, followed by the file itself.
+pub async fn post_object(
+    State(state): State<AppState>,
+    Path(bucket_name): Path<String>,
+    extensions: Extensions,
+    headers: HeaderMap,
+    multipart: Multipart,
+) -> Result<Response, S3Error> {
+    require_bucket(&state, &bucket_name).await?;
+    let (mut fields, file) = read_form(multipart).await?;
+    let file = file.ok_or_else(|| S3Error::invalid_argument("POST requires exactly one file upload per request."))?;
+    let key = fields
+        .get("key")
+        .ok_or_else(|| {
+            S3Error::invalid_argument(
+                "Bucket POST must contain a field named 'key'.  If it is specified, please check the order of the fields.",
+            )
+        })?
+        .replace("${filename}", &file.filename);
+    if key.is_empty() {
+        return Err(S3Error::invalid_argument("User key must have a length greater than 0."));
+    }
+    check_key(&key)?;
+    // Conditions apply to the final key and to the bucket posted to
+    fields.insert("key".to_string(), key.clone());
+    fields.insert("bucket".to_string(), bucket_name.clone());
+
+    let requester = match fields.get("policy") {
+        Some(encoded) => {
+            let policy = PostPolicy::from_base64(encoded)
+                .map_err(|message| S3Error::new(StatusCode::BAD_REQUEST, "InvalidPolicyDocument", message))?;
+            let requester = if state.auth_enabled {
+                auth::verify_post_signature(&auth::credential_store(&state).await?, &fields)?
+            } else {
+                requester_of(&extensions)
+            };
+            policy
+                .check(&fields, file.content.len() as u64, Utc::now())
+                .map_err(policy_error)?;
+            requester
+        }
+        None if state.auth_enabled => Requester::Anonymous,
+        None => requester_of(&extensions),
+    };
+
+    let operation = S3Operation {
+        name: "PutObject",
+        action: "s3:PutObject",
+        bucket: Some(bucket_name.clone()),
+        key: Some(key.clone()),
+        query: HashMap::new(),
+    };
+    let source_ip = extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    check_access(&state, &operation, &requester, &headers, source_ip).await?;
+
+    let content_type = fields
+        .get("content-type")
+        .cloned()
+        .or(file.content_type)
+        .unwrap_or_else(|| DEFAULT_CONTENT_TYPE.to_string());
+    let field_headers = field_headers(&fields);
+    let object_acl = acl::new_object_acl(&state, &bucket_name, &field_headers, &requester).await?;
+    let redirect_location =
+        website::redirect_location(fields.get("x-amz-website-redirect-location").map(String::as_str))?;
+    let object = Object::new(key, file.content, content_type)
+        .with_website_redirect_location(redirect_location)
+        .with_acl(object_acl);
+    let object = encryption::encrypt_object(&state, &bucket_name, &field_headers, object).await?;
+    let object = replication::store_object(&state, &bucket_name, object).await?;
+    notify(&state, &extensions, &requester, "ObjectCreated:Post", &bucket_name, &object.key, Some(&object)).await;
+
+    Ok(success_response(&state, &fields, &bucket_name, &object, &headers))
+}
+
+// Fields up to the file part, with lowercased names. S3 ignores anything after the file.
+async fn read_form(mut multipart: Multipart) -> Result<(HashMap<String, String>, Option<UploadedFile>), S3Error> {
+    let malformed = |_| {
+        S3Error::new(
+            StatusCode::BAD_REQUEST,
+            "MalformedPOSTRequest",
+            "The body of your POST request is not well-formed multipart/form-data.",
+        )
+    };
+    let mut fields = HashMap::new();
+    while let Some(field) = multipart.next_field().await.map_err(malformed)? {
+        let name = field.name().unwrap_or_default().to_lowercase();
+        if name == "file" {
+            let filename = field.file_name().unwrap_or_default().to_string();
+            let content_type = field.content_type().map(str::to_string);
+            let content = field.bytes().await.map_err(malformed)?.to_vec();
+            return Ok((fields, Some(UploadedFile { filename, content_type, content })));
+        }
+        let value = field.text().await.map_err(malformed)?;
+        fields.insert(name, value);
+    }
+    Ok((fields, None))
+}
+
+// ACL and encryption form fields, as the headers a PUT would carry them in
+fn field_headers(fields: &HashMap<String, String>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in fields {
+        let header_name = match name.as_str() {
+            "acl" => "x-amz-acl",
+            name if name.starts_with("x-amz-grant-") || name.starts_with("x-amz-server-side-encryption") => name,
+            _ => continue,
+        };
+        if let (Ok(header_name), Ok(value)) = (HeaderName::try_from(header_name), HeaderValue::from_str(value)) {
+            headers.insert(header_name, value);
+        }
+    }
+    headers
+}
+
+fn success_response(
+    state: &AppState,
+    fields: &HashMap<String, String>,
+    bucket_name: &str,
+    object: &Object,
+    headers: &HeaderMap,
+) -> Response {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .unwrap_or("localhost");
+    let location = if virtual_host::bucket_from_host(host, &state.domains).as_deref() == Some(bucket_name) {
+        format!("http://{}/{}", host, object.key)
+    } else {
+        format!("http://{}/{}/{}", host, bucket_name, object.key)
+    };
+
+    let redirect = fields
+        .get("success_action_redirect")
+        .or_else(|| fields.get("redirect"))
+        .filter(|url| url.starts_with("http://") || url.starts_with("https://"));
+    if let Some(redirect) = redirect {
+        let query = serde_urlencoded::to_string([
+            ("bucket", bucket_name),
+            ("key", object.key.as_str()),
+            ("etag", object.etag.as_str()),
+        ])
+        .unwrap_or_default();
+        let separator = if redirect.contains('?') { '&' } else { '?' };
+        let mut response = StatusCode::SEE_OTHER.into_response();
+        insert_header(&mut response, header::LOCATION.as_str(), &format!("{}{}{}", redirect, separator, query));
+        return response;
+    }
+
+    let mut response = match fields.get("success_action_status").map(String::as_str) {
+        Some("200") => StatusCode::OK.into_response(),
+        Some("201") => {
+            let xml = format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<PostResponse><Location>{}</Location><Bucket>{}</Bucket><Key>{}</Key><ETag>{}</ETag></PostResponse>",
+                escape_xml(&location),
+                escape_xml(bucket_name),
+                escape_xml(&object.key),
+                escape_xml(&object.etag)
+            );
+            (StatusCode::CREATED, [(header::CONTENT_TYPE, "application/xml")], xml).into_response()
+        }
+        _ => StatusCode::NO_CONTENT.into_response(),
+    };
+    insert_header(&mut response, header::ETAG.as_str(), &object.etag);
+    insert_header(&mut response, header::LOCATION.as_str(), &location);
+    encryption::insert_encryption_headers(&mut response, object);
+    response
+}
+
+fn policy_error(violation: PolicyViolation) -> S3Error {
+    match violation {
+        PolicyViolation::TooLarge => S3Error::new(StatusCode::BAD_REQUEST, "EntityTooLarge", violation.to_string()),
+        PolicyViolation::TooSmall => S3Error::new(StatusCode::BAD_REQUEST, "EntityTooSmall", violation.to_string()),
+        _ => S3Error::new(StatusCode::FORBIDDEN, "AccessDenied", violation.to_string()),
+    }
+}
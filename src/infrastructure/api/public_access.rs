@@ -0,0 +1,50 @@
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use crate::domain::public_access::{self, PublicAccessBlockConfiguration};
+use super::api::{require_bucket, AppState};
+use super::error::S3Error;
+
+pub async fn put_public_access_block(state: &AppState, bucket_name: &str, body: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let config = PublicAccessBlockConfiguration::from_xml(body).map_err(|_| S3Error::malformed_xml())?;
+    let xml = config.to_xml().map_err(S3Error::internal)?;
+    state
+        .storage
+        .put_bucket_config(bucket_name, public_access::CONFIG_NAME, &xml)
+        .await?;
+    Ok(StatusCode::OK.into_response())
+}
+
+pub async fn get_public_access_block(state: &AppState, bucket_name: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let xml = state
+        .storage
+        .get_bucket_config(bucket_name, public_access::CONFIG_NAME)
+        .await?
+        .ok_or_else(|| {
+            S3Error::new(
+                StatusCode::NOT_FOUND,
+                "NoSuchPublicAccessBlockConfiguration",
+                "The public access block configuration was not found",
+            )
+            .with_detail("BucketName", bucket_name)
+        })?;
+    Ok(([(header::CONTENT_TYPE, "application/xml")], xml).into_response())
+}
+
+pub async fn delete_public_access_block(state: &AppState, bucket_name: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    state
+        .storage
+        .delete_bucket_config(bucket_name, public_access::CONFIG_NAME)
+        .await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// Block Public Access settings of the bucket, blocking nothing when it has none.
+pub async fn public_access_block(state: &AppState, bucket_name: &str) -> Result<PublicAccessBlockConfiguration, S3Error> {
+    match state.storage.get_bucket_config(bucket_name, public_access::CONFIG_NAME).await? {
+        Some(xml) => PublicAccessBlockConfiguration::from_xml(&xml).map_err(S3Error::internal),
+        None => Ok(PublicAccessBlockConfiguration::default()),
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode, Uri};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use crate::domain::region::{self, CreateBucketConfiguration, LocationError, DEFAULT_REGION};
+use crate::domain::sigv4::{Authorization, Credential};
+use super::api::{require_bucket, AppState};
+use super::error::{escape_xml, S3Error};
+use super::operation::S3Operation;
+
+const BUCKET_REGION_HEADER: &str = "x-amz-bucket-region";
+
+/// The region a request was sent to: the one of its SigV4 credential scope, from the
+/// `Authorization` header or a presigned URL, or the configured region otherwise.
+pub fn request_region(state: &AppState, headers: &HeaderMap, uri: &Uri) -> String {
+    let from_header = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| Authorization::parse(value).ok())
+        .map(|authorization| authorization.credential);
+    let from_query = || {
+        let query: HashMap<String, String> = serde_urlencoded::from_str(uri.query()?).ok()?;
+        Credential::parse(query.get("X-Amz-Credential")?).ok()
+    };
+    from_header
+        .or_else(from_query)
+        .map(|credential| credential.region)
+        .unwrap_or_else(|| state.region.clone())
+}
+
+/// Middleware answering requests for a bucket in another region the way S3 does: with a
+/// `301 PermanentRedirect` naming the bucket's region in `x-amz-bucket-region`.
+pub async fn redirect_to_bucket_region(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let operation = S3Operation::classify(request.method(), request.uri(), request.headers());
+    // The location can be looked up from anywhere, and new buckets take the region of the request
+    let exempt = ["CreateBucket", "GetBucketLocation", "PreflightRequest", "PostObject"];
+    let Some(bucket_name) = operation.bucket.filter(|_| !exempt.contains(&operation.name)) else {
+        return next.run(request).await;
+    };
+    let bucket = match state.storage.get_bucket(&bucket_name).await {
+        Ok(Some(bucket)) => bucket,
+        // Missing buckets are reported by the handlers
+        Ok(None) => return next.run(request).await,
+        Err(e) => return S3Error::from(e).into_response(),
+    };
+
+    let mut response = if bucket.region != request_region(&state, request.headers(), request.uri()) {
+        S3Error::new(
+            StatusCode::MOVED_PERMANENTLY,
+            "PermanentRedirect",
+            "The bucket you are attempting to access must be addressed using the specified endpoint. Please send all future requests to this endpoint.",
+        )
+        .with_detail("Endpoint", region::endpoint(&bucket.name, &bucket.region))
+        .with_detail("Bucket", bucket.name.as_str())
+        .into_response()
+    } else {
+        next.run(request).await
+    };
+    // Clients locate buckets through HeadBucket, and learn the region from redirects
+    if operation.name == "HeadBucket" || response.status() == StatusCode::MOVED_PERMANENTLY {
+        if let Ok(value) = HeaderValue::from_str(&bucket.region) {
+            response.headers_mut().insert(BUCKET_REGION_HEADER, value);
+        }
+    }
+    response
+}
+
+/// The region a CreateBucket request puts the bucket in, from its optional
+/// `CreateBucketConfiguration` body.
+pub fn new_bucket_region(state: &AppState, headers: &HeaderMap, uri: &Uri, body: &str) -> Result<String, S3Error> {
+    let config = if body.trim().is_empty() {
+        CreateBucketConfiguration::default()
+    } else {
+        CreateBucketConfiguration::from_xml(body).map_err(|_| S3Error::malformed_xml())?
+    };
+    let constraint = config.location_constraint.as_deref();
+    region::bucket_region(&request_region(state, headers, uri), constraint).map_err(|e| match e {
+        LocationError::Invalid => S3Error::new(
+            StatusCode::BAD_REQUEST,
+            "InvalidLocationConstraint",
+            "The specified location-constraint is not valid",
+        )
+        .with_detail("LocationConstraint", constraint.unwrap_or_default()),
+        LocationError::Illegal(message) => {
+            S3Error::new(StatusCode::BAD_REQUEST, "IllegalLocationConstraintException", message)
+        }
+    })
+}
+
+/// GetBucketLocation. Buckets in us-east-1 have an empty location constraint.
+pub async fn get_bucket_location(state: &AppState, bucket_name: &str) -> Result<Response, S3Error> {
+    let bucket = require_bucket(state, bucket_name).await?;
+    let location = if bucket.region == DEFAULT_REGION { "" } else { bucket.region.as_str() };
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<LocationConstraint xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">{}</LocationConstraint>",
+        escape_xml(location)
+    );
+    Ok(([(header::CONTENT_TYPE, "application/xml")], xml).into_response())
+}
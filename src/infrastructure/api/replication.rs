@@ -0,0 +1,73 @@
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use crate::domain::object::Object;
+use crate::domain::replication::{self, ReplicationConfiguration};
+use super::api::{require_bucket, AppState};
+use super::error::S3Error;
+
+pub async fn put_bucket_replication(state: &AppState, bucket_name: &str, body: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let config = ReplicationConfiguration::from_xml(body).map_err(|_| S3Error::malformed_xml())?;
+    config.validate().map_err(S3Error::invalid_argument)?;
+    for destination in config.rules.iter().filter_map(|rule| rule.destination_bucket()) {
+        if destination == bucket_name {
+            return Err(invalid_request("Destination bucket cannot be the same as the source bucket."));
+        }
+        if state.storage.get_bucket(destination).await?.is_none() {
+            return Err(invalid_request("Destination bucket must exist."));
+        }
+    }
+
+    let xml = config.with_generated_ids().to_xml().map_err(S3Error::internal)?;
+    state
+        .storage
+        .put_bucket_config(bucket_name, replication::CONFIG_NAME, &xml)
+        .await?;
+    Ok(StatusCode::OK.into_response())
+}
+
+pub async fn get_bucket_replication(state: &AppState, bucket_name: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let xml = state
+        .storage
+        .get_bucket_config(bucket_name, replication::CONFIG_NAME)
+        .await?
+        .ok_or_else(|| {
+            S3Error::new(
+                StatusCode::NOT_FOUND,
+                "ReplicationConfigurationNotFoundError",
+                "The replication configuration was not found",
+            )
+            .with_resource(bucket_name)
+        })?;
+    Ok(([(header::CONTENT_TYPE, "application/xml")], xml).into_response())
+}
+
+pub async fn delete_bucket_replication(state: &AppState, bucket_name: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    state
+        .storage
+        .delete_bucket_config(bucket_name, replication::CONFIG_NAME)
+        .await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// Stores a new object, marked `PENDING` and queued for replication when a rule of the
+/// bucket applies to it.
+pub async fn store_object(state: &AppState, bucket_name: &str, mut object: Object) -> Result<Object, S3Error> {
+    state.replicator.mark_pending(bucket_name, &mut object).await?;
+    state.storage.put_object(bucket_name, &object).await?;
+    state.replicator.object_stored(bucket_name, &object);
+    Ok(object)
+}
+
+/// Deletes an object, and its replica when a rule replicates delete markers.
+pub async fn delete_object(state: &AppState, bucket_name: &str, key: &str) -> Result<(), S3Error> {
+    state.storage.delete_object(bucket_name, key).await?;
+    state.replicator.object_deleted(bucket_name, key).await?;
+    Ok(())
+}
+
+fn invalid_request(message: &str) -> S3Error {
+    S3Error::new(StatusCode::BAD_REQUEST, "InvalidRequest", message)
+}
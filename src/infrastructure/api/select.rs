@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use crate::domain::select::SelectRequest;
+use crate::domain::sql::{self, SqlError};
+use super::api::{require_bucket, AppState};
+use super::encryption;
+use super::error::S3Error;
+use super::object::{check_key, object_error};
+
+/// SelectObjectContent: runs a SQL expression over a CSV or JSON object and streams the
+/// result back as event-stream messages.
+///
+/// The whole object is queried before the response starts, so that every error is
+/// returned as an XML error rather than in the middle of the stream.
+pub async fn select_object_content(
+    State(state): State<AppState>,
+    Path((bucket_name, key)): Path<(String, String)>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<Response, S3Error> {
+    if !params.contains_key("select") {
+        return Err(S3Error::new(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "MethodNotAllowed",
+            "The specified method is not allowed against this resource.",
+        )
+        .with_detail("Method", "POST")
+        .with_detail("ResourceType", "OBJECT"));
+    }
+    if params.get("select-type").is_some_and(|select_type| select_type != "2") {
+        return Err(S3Error::invalid_argument("Unsupported select-type, only 2 is supported"));
+    }
+    check_key(&key)?;
+    let request = SelectRequest::from_xml(&body).map_err(|_| S3Error::malformed_xml())?;
+    request.validate().map_err(select_error)?;
+    let query = sql::Query::parse(&request.expression).map_err(select_error)?;
+
+    require_bucket(&state, &bucket_name).await?;
+    let mut object = state
+        .storage
+        .get_object(&bucket_name, &key)
+        .await
+        .map_err(|e| object_error(e, &key))?;
+    encryption::decrypt_object(&headers, encryption::CUSTOMER_HEADERS, &mut object)?;
+    let selection = request.select(&query, &object.content).map_err(select_error)?;
+    let stream = selection.to_event_stream(&request, object.content.len());
+    Ok(([(header::CONTENT_TYPE, "application/octet-stream")], stream).into_response())
+}
+
+fn select_error(error: SqlError) -> S3Error {
+    let status = if error.code == "NotImplemented" { StatusCode::NOT_IMPLEMENTED } else { StatusCode::BAD_REQUEST };
+    S3Error::new(status, error.code, error.message)
+}
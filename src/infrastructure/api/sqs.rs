@@ -0,0 +1,500 @@
+use std::collections::BTreeMap;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
+use crate::domain::queue::QueueError;
+use crate::domain::sigv4::Authorization;
+use super::access::requester_of;
+use super::api::AppState;
+use super::error::{escape_xml, S3Error};
+use super::object::header_str;
+
+const NAMESPACE: &str = "http://queue.amazonaws.com/doc/2012-11-05/";
+const JSON_CONTENT_TYPE: &str = "application/x-amz-json-1.0";
+const TARGET_PREFIX: &str = "AmazonSQS.";
+
+/// SQS clients either POST a form with an `Action` (query protocol), or JSON naming the
+/// action in `X-Amz-Target` (JSON protocol). Responses and errors follow the request's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Query,
+    Json,
+}
+
+impl Protocol {
+    pub fn of(headers: &HeaderMap) -> Self {
+        if header_str(headers, "x-amz-target").is_some() {
+            Protocol::Json
+        } else {
+            Protocol::Query
+        }
+    }
+}
+
+/// SQS shares the endpoint with S3. Its requests are POSTs to the root, signed for the
+/// `sqs` service or naming an `AmazonSQS` target, or query API calls to a queue URL.
+pub fn is_sqs_request(request: &Request) -> bool {
+    if request.method() != Method::POST {
+        return false;
+    }
+    let headers = request.headers();
+    if header_str(headers, "x-amz-target").is_some_and(|target| target.starts_with(TARGET_PREFIX)) {
+        return true;
+    }
+    let path = request.uri().path();
+    if path == "/" {
+        // STS query calls go to the root as well, the credential scope tells them apart
+        return header_str(headers, header::AUTHORIZATION.as_str())
+            .and_then(|value| Authorization::parse(value).ok())
+            .is_some_and(|authorization| authorization.credential.service == "sqs");
+    }
+    let is_form = header_str(headers, header::CONTENT_TYPE.as_str())
+        .is_some_and(|value| value.starts_with("application/x-www-form-urlencoded"));
+    is_form && queue_of_path(path).is_some()
+}
+
+/// Middleware answering SQS requests, which S3 routing, bucket policies and ACLs don't apply to.
+pub async fn handle_sqs_requests(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if !is_sqs_request(&request) {
+        return next.run(request).await;
+    }
+    let protocol = Protocol::of(request.headers());
+    handle(&state, protocol, request)
+        .await
+        .unwrap_or_else(|e| error_response(e, protocol))
+}
+
+async fn handle(state: &AppState, protocol: Protocol, request: Request) -> Result<Response, S3Error> {
+    let requester = requester_of(request.extensions());
+    let sender_id = match requester.identity() {
+        Some(identity) => identity.account_id.clone(),
+        None => {
+            return Err(S3Error::new(
+                StatusCode::FORBIDDEN,
+                "MissingAuthenticationToken",
+                "Request is missing Authentication Token",
+            ))
+        }
+    };
+    let host = header_str(request.headers(), header::HOST.as_str()).unwrap_or("localhost").to_string();
+    let path_queue = queue_of_path(request.uri().path()).map(str::to_string);
+    let (action, params) = match protocol {
+        Protocol::Json => {
+            let action = header_str(request.headers(), "x-amz-target")
+                .and_then(|target| target.strip_prefix(TARGET_PREFIX))
+                .unwrap_or_default()
+                .to_string();
+            let body = read_body(request).await?;
+            let params = match serde_json::from_slice::<Value>(&body) {
+                Ok(Value::Object(params)) => params,
+                _ if body.is_empty() => Map::new(),
+                _ => return Err(S3Error::new(StatusCode::BAD_REQUEST, "SerializationException", "Malformed JSON request body")),
+            };
+            (action, params)
+        }
+        Protocol::Query => {
+            let mut pairs: Vec<(String, String)> = request
+                .uri()
+                .query()
+                .and_then(|query| serde_urlencoded::from_str(query).ok())
+                .unwrap_or_default();
+            let body = read_body(request).await?;
+            pairs.extend(serde_urlencoded::from_bytes::<Vec<(String, String)>>(&body).unwrap_or_default());
+            let mut params = query_params(pairs);
+            let action = match params.remove("Action") {
+                Some(Value::String(action)) => action,
+                _ => return Err(S3Error::new(StatusCode::BAD_REQUEST, "MissingAction", "Missing Action")),
+            };
+            (action, params)
+        }
+    };
+
+    let request = SqsRequest {
+        params,
+        host,
+        path_queue,
+        sender_id,
+    };
+    let result = dispatch(state, &action, &request).await?;
+    Ok(match protocol {
+        Protocol::Json => (
+            [(header::CONTENT_TYPE, JSON_CONTENT_TYPE)],
+            Value::Object(result).to_string(),
+        )
+            .into_response(),
+        Protocol::Query => {
+            let xml = format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<{action}Response xmlns=\"{}\"><{action}Result>{}</{action}Result><ResponseMetadata><RequestId>{}</RequestId></ResponseMetadata></{action}Response>",
+                NAMESPACE,
+                xml_elements(&result),
+                Uuid::new_v4(),
+                action = action
+            );
+            ([(header::CONTENT_TYPE, "text/xml")], xml).into_response()
+        }
+    })
+}
+
+struct SqsRequest {
+    params: Map<String, Value>,
+    host: String,
+    // Queue of the URL a query API call was sent to
+    path_queue: Option<String>,
+    sender_id: String,
+}
+
+impl SqsRequest {
+    fn str(&self, name: &str) -> Option<&str> {
+        self.params.get(name).and_then(Value::as_str)
+    }
+
+    fn required(&self, name: &str) -> Result<&str, S3Error> {
+        self.str(name).ok_or_else(|| missing_parameter(name))
+    }
+
+    // Numbers are strings in the query protocol
+    fn number(&self, name: &str) -> Result<Option<u32>, S3Error> {
+        let value = match self.params.get(name) {
+            None | Some(Value::Null) => return Ok(None),
+            Some(Value::String(value)) => value.parse().ok(),
+            Some(value) => value.as_u64().and_then(|value| u32::try_from(value).ok()),
+        };
+        value.map(Some).ok_or_else(|| {
+            S3Error::new(
+                StatusCode::BAD_REQUEST,
+                "InvalidParameterValue",
+                format!("Value for parameter {} is invalid. Reason: Must be a non-negative integer.", name),
+            )
+        })
+    }
+
+    fn map(&self, name: &str) -> BTreeMap<String, String> {
+        self.params
+            .get(name)
+            .and_then(Value::as_object)
+            .map(|map| {
+                map.iter()
+                    .map(|(key, value)| (key.clone(), value.as_str().map(str::to_string).unwrap_or_else(|| value.to_string())))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn list(&self, name: &str) -> Vec<String> {
+        self.params
+            .get(name)
+            .and_then(Value::as_array)
+            .map(|items| items.iter().filter_map(Value::as_str).map(str::to_string).collect())
+            .unwrap_or_default()
+    }
+
+    // From the QueueUrl parameter, or the URL the request was sent to
+    fn queue_name(&self) -> Result<String, S3Error> {
+        match self.str("QueueUrl") {
+            Some(url) => Ok(url.trim_end_matches('/').rsplit('/').next().unwrap_or_default().to_string()),
+            None => self.path_queue.clone().ok_or_else(|| missing_parameter("QueueUrl")),
+        }
+    }
+
+    fn queue_url(&self, account_id: &str, queue_name: &str) -> String {
+        format!("http://{}/{}/{}", self.host, account_id, queue_name)
+    }
+}
+
+async fn dispatch(state: &AppState, action: &str, request: &SqsRequest) -> Result<Map<String, Value>, S3Error> {
+    let queues = &state.queues;
+    let result = match action {
+        "CreateQueue" => {
+            let name = request.required("QueueName")?;
+            queues.create_queue(name, &request.map("Attributes")).map_err(queue_error)?;
+            json!({ "QueueUrl": request.queue_url(queues.account_id(), name) })
+        }
+        "GetQueueUrl" => {
+            let name = request.required("QueueName")?;
+            queues.require_queue(name).map_err(queue_error)?;
+            json!({ "QueueUrl": request.queue_url(queues.account_id(), name) })
+        }
+        "ListQueues" => {
+            let urls: Vec<String> = queues
+                .list_queues(request.str("QueueNamePrefix").unwrap_or_default())
+                .iter()
+                .map(|name| request.queue_url(queues.account_id(), name))
+                .collect();
+            json!({ "QueueUrls": urls })
+        }
+        "DeleteQueue" => {
+            queues.delete_queue(&request.queue_name()?).map_err(queue_error)?;
+            json!({})
+        }
+        "PurgeQueue" => {
+            queues.purge_queue(&request.queue_name()?).map_err(queue_error)?;
+            json!({})
+        }
+        "GetQueueAttributes" => {
+            let names = request.list("AttributeNames");
+            let mut attributes = queues.queue_attributes(&request.queue_name()?).map_err(queue_error)?;
+            if !names.iter().any(|name| name == "All") {
+                attributes.retain(|name, _| names.contains(name));
+            }
+            json!({ "Attributes": attributes })
+        }
+        "SendMessage" => {
+            let body = request.required("MessageBody")?;
+            let delay = request.number("DelaySeconds")?;
+            let message = queues
+                .send_message(&request.queue_name()?, body, delay, &request.sender_id)
+                .map_err(queue_error)?;
+            json!({ "MessageId": message.message_id, "MD5OfMessageBody": message.md5_of_body })
+        }
+        "ReceiveMessage" => {
+            let max = request.number("MaxNumberOfMessages")?.unwrap_or(1);
+            let visibility_timeout = request.number("VisibilityTimeout")?;
+            let wait_seconds = request.number("WaitTimeSeconds")?;
+            let mut names = request.list("AttributeNames");
+            names.extend(request.list("MessageSystemAttributeNames"));
+            let messages = queues
+                .receive_messages(&request.queue_name()?, max, visibility_timeout, wait_seconds)
+                .await
+                .map_err(queue_error)?;
+            let messages: Vec<Value> = messages
+                .iter()
+                .map(|message| {
+                    let mut entry = json!({
+                        "MessageId": message.message_id,
+                        "ReceiptHandle": message.receipt_handle,
+                        "MD5OfBody": message.md5_of_body,
+                        "Body": message.body,
+                    });
+                    let mut attributes = message.attributes();
+                    if !names.iter().any(|name| name == "All") {
+                        attributes.retain(|name, _| names.contains(name));
+                    }
+                    if !attributes.is_empty() {
+                        entry["Attributes"] = json!(attributes);
+                    }
+                    entry
+                })
+                .collect();
+            json!({ "Messages": messages })
+        }
+        "DeleteMessage" => {
+            let receipt_handle = request.required("ReceiptHandle")?;
+            queues
+                .delete_message(&request.queue_name()?, receipt_handle)
+                .map_err(queue_error)?;
+            json!({})
+        }
+        "ChangeMessageVisibility" => {
+            let receipt_handle = request.required("ReceiptHandle")?;
+            let timeout = request
+                .number("VisibilityTimeout")?
+                .ok_or_else(|| missing_parameter("VisibilityTimeout"))?;
+            queues
+                .change_message_visibility(&request.queue_name()?, receipt_handle, timeout)
+                .map_err(queue_error)?;
+            json!({})
+        }
+        _ => {
+            return Err(S3Error::new(
+                StatusCode::BAD_REQUEST,
+                "InvalidAction",
+                format!("The action {} is not valid for this endpoint.", action),
+            ))
+        }
+    };
+    match result {
+        Value::Object(result) => Ok(result),
+        _ => Ok(Map::new()),
+    }
+}
+
+fn missing_parameter(name: &str) -> S3Error {
+    S3Error::new(
+        StatusCode::BAD_REQUEST,
+        "MissingParameter",
+        format!("The request must contain the parameter {}.", name),
+    )
+}
+
+// "/000000000000/jobs" names the queue "jobs"
+fn queue_of_path(path: &str) -> Option<&str> {
+    let (account_id, name) = path.strip_prefix('/')?.split_once('/')?;
+    let is_account = account_id.len() == 12 && account_id.chars().all(|c| c.is_ascii_digit());
+    (is_account && !name.is_empty() && !name.contains('/')).then_some(name)
+}
+
+// Flattened query parameters, as in "Attribute.1.Name=VisibilityTimeout&Attribute.1.Value=60",
+// turned into the shape of the JSON protocol
+fn query_params(pairs: Vec<(String, String)>) -> Map<String, Value> {
+    let mut params = Map::new();
+    let mut attributes: BTreeMap<String, (Option<String>, Option<String>)> = BTreeMap::new();
+    let mut lists: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for (name, value) in pairs {
+        if let Some(entry) = name.strip_prefix("Attribute.") {
+            match entry.split_once('.') {
+                Some((index, "Name")) => attributes.entry(index.to_string()).or_default().0 = Some(value),
+                Some((index, "Value")) => attributes.entry(index.to_string()).or_default().1 = Some(value),
+                _ => {}
+            }
+        } else if name.starts_with("AttributeName.") {
+            lists.entry("AttributeNames").or_default().push(value);
+        } else if name.starts_with("MessageSystemAttributeName.") {
+            lists.entry("MessageSystemAttributeNames").or_default().push(value);
+        } else if !name.contains('.') {
+            params.insert(name, Value::String(value));
+        }
+    }
+    let attributes: Map<String, Value> = attributes
+        .into_values()
+        .filter_map(|(name, value)| Some((name?, Value::String(value.unwrap_or_default()))))
+        .collect();
+    if !attributes.is_empty() {
+        params.insert("Attributes".to_string(), Value::Object(attributes));
+    }
+    for (name, values) in lists {
+        params.insert(name.to_string(), json!(values));
+    }
+    params
+}
+
+// The query protocol result matching a JSON protocol one: lists are flattened into
+// repeated elements, and attribute maps become Name/Value pairs
+fn xml_elements(fields: &Map<String, Value>) -> String {
+    let scalar = |value: &Value| match value {
+        Value::String(value) => escape_xml(value),
+        value => value.to_string(),
+    };
+    let mut xml = String::new();
+    for (name, value) in fields {
+        match value {
+            Value::Object(map) if name == "Attributes" => {
+                for (key, value) in map {
+                    xml.push_str(&format!(
+                        "<Attribute><Name>{}</Name><Value>{}</Value></Attribute>",
+                        escape_xml(key),
+                        scalar(value)
+                    ));
+                }
+            }
+            Value::Array(items) => {
+                let element = name.strip_suffix('s').unwrap_or(name);
+                for item in items {
+                    let content = match item {
+                        Value::Object(map) => xml_elements(map),
+                        item => scalar(item),
+                    };
+                    xml.push_str(&format!("<{element}>{}</{element}>", content, element = element));
+                }
+            }
+            Value::Null => {}
+            value => xml.push_str(&format!("<{name}>{}</{name}>", scalar(value), name = name)),
+        }
+    }
+    xml
+}
+
+fn queue_error(error: QueueError) -> S3Error {
+    let invalid = |code: &'static str, message: String| S3Error::new(StatusCode::BAD_REQUEST, code, message);
+    match error {
+        QueueError::NonExistentQueue => invalid(
+            "AWS.SimpleQueueService.NonExistentQueue",
+            "The specified queue does not exist.".to_string(),
+        ),
+        QueueError::QueueNameExists(message) => invalid("QueueAlreadyExists", message),
+        QueueError::InvalidAttributeName(message) => invalid("InvalidAttributeName", message),
+        QueueError::InvalidAttributeValue(message) => invalid("InvalidAttributeValue", message),
+        QueueError::InvalidParameterValue(message) => invalid("InvalidParameterValue", message),
+        QueueError::ReceiptHandleIsInvalid(message) => invalid("ReceiptHandleIsInvalid", message),
+        QueueError::MessageNotInflight => invalid(
+            "AWS.SimpleQueueService.MessageNotInflight",
+            "Message does not exist or is not available for visibility timeout change.".to_string(),
+        ),
+    }
+}
+
+/// Renders an error for the protocol of the request. JSON errors carry the query protocol
+/// code in `x-amzn-query-error`, which SDKs report instead of the error type.
+pub fn error_response(error: S3Error, protocol: Protocol) -> Response {
+    let kind = if error.status.is_server_error() { "Receiver" } else { "Sender" };
+    match protocol {
+        Protocol::Json => {
+            let error_type = match error.code {
+                "AWS.SimpleQueueService.NonExistentQueue" => "QueueDoesNotExist",
+                "QueueAlreadyExists" => "QueueNameExists",
+                "AWS.SimpleQueueService.MessageNotInflight" => "MessageNotInflight",
+                code => code,
+            };
+            let body = json!({ "__type": format!("com.amazonaws.sqs#{}", error_type), "message": error.message });
+            (
+                error.status,
+                [
+                    (header::CONTENT_TYPE.as_str(), JSON_CONTENT_TYPE.to_string()),
+                    ("x-amzn-query-error", format!("{};{}", error.code, kind)),
+                ],
+                body.to_string(),
+            )
+                .into_response()
+        }
+        Protocol::Query => {
+            let xml = format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<ErrorResponse xmlns=\"{}\"><Error><Type>{}</Type><Code>{}</Code><Message>{}</Message></Error><RequestId>{}</RequestId></ErrorResponse>",
+                NAMESPACE,
+                kind,
+                error.code,
+                escape_xml(&error.message),
+                Uuid::new_v4()
+            );
+            (error.status, [(header::CONTENT_TYPE, "text/xml")], xml).into_response()
+        }
+    }
+}
+
+async fn read_body(request: Request) -> Result<axum::body::Bytes, S3Error> {
+    axum::body::to_bytes(request.into_body(), usize::MAX)
+        .await
+        .map_err(S3Error::internal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_params() {
+        let pairs = [
+            ("Action", "CreateQueue"),
+            ("QueueName", "jobs"),
+            ("Attribute.1.Name", "VisibilityTimeout"),
+            ("Attribute.1.Value", "60"),
+            ("AttributeName.1", "All"),
+            ("MessageAttribute.1.Name", "ignored"),
+        ];
+        let params = query_params(pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect());
+        assert_eq!(
+            Value::Object(params),
+            json!({
+                "Action": "CreateQueue",
+                "QueueName": "jobs",
+                "Attributes": { "VisibilityTimeout": "60" },
+                "AttributeNames": ["All"],
+            })
+        );
+        assert_eq!(queue_of_path("/000000000000/jobs"), Some("jobs"));
+        assert_eq!(queue_of_path("/bucket/key"), None);
+    }
+
+    #[test]
+    fn test_xml_result() {
+        let result = json!({
+            "Messages": [{ "Body": "a<b", "Attributes": { "SentTimestamp": "1" } }],
+            "QueueUrl": "http://localhost/000000000000/jobs",
+        });
+        assert_eq!(
+            xml_elements(result.as_object().unwrap()),
+            "<Message><Attribute><Name>SentTimestamp</Name><Value>1</Value></Attribute><Body>a&lt;b</Body></Message><QueueUrl>http://localhost/000000000000/jobs</QueueUrl>"
+        );
+    }
+}
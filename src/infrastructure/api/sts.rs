@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use axum::extract::{Request, State};
+use axum::http::{header, Method, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use crate::domain::credentials::{AccessKey, CredentialStore};
+use crate::domain::identity::{Identity, Requester};
+use crate::domain::sts::{self, RoleArn, ASSUME_ROLE_DURATION, SESSION_TOKEN_DURATION};
+use super::api::AppState;
+use super::auth::{credential_store, save_credential_store};
+use super::error::{escape_xml, S3Error};
+
+const NAMESPACE: &str = "https://sts.amazonaws.com/doc/2011-06-15/";
+
+/// STS shares the endpoint with S3: its query API is a form POSTed to the root.
+pub fn is_sts_request(request: &Request) -> bool {
+    request.method() == Method::POST && request.uri().path() == "/"
+}
+
+/// Minimal STS query API, issuing temporary credentials that S3 requests can then be
+/// signed with, along with `x-amz-security-token`.
+pub async fn handle(State(state): State<AppState>, uri: Uri, requester: Requester, body: String) -> Response {
+    let mut params: HashMap<String, String> = uri
+        .query()
+        .and_then(|query| serde_urlencoded::from_str(query).ok())
+        .unwrap_or_default();
+    params.extend(serde_urlencoded::from_str::<Vec<(String, String)>>(&body).unwrap_or_default());
+
+    let result = match params.get("Action").map(String::as_str) {
+        Some("AssumeRole") => assume_role(&state, &requester, &params).await,
+        Some("AssumeRoleWithWebIdentity") => assume_role_with_web_identity(&state, &params).await,
+        Some("GetSessionToken") => get_session_token(&state, &requester, &params).await,
+        Some(action) => Err(S3Error::new(
+            StatusCode::BAD_REQUEST,
+            "InvalidAction",
+            format!("Could not find operation {} for version 2011-06-15", action),
+        )),
+        None => Err(S3Error::new(StatusCode::BAD_REQUEST, "MissingAction", "Missing Action")),
+    };
+    result.unwrap_or_else(error_response)
+}
+
+async fn assume_role(
+    state: &AppState,
+    requester: &Requester,
+    params: &HashMap<String, String>,
+) -> Result<Response, S3Error> {
+    caller(requester)?;
+    let (role, session_name) = role_session(params)?;
+    let duration = ASSUME_ROLE_DURATION.parse(params.get("DurationSeconds").map(String::as_str)).map_err(validation_error)?;
+
+    let store = credential_store(state).await?;
+    let identity = role.session_identity(&store.account_identity(&role.account_id), &session_name);
+    let key = issue(state, store, identity, duration).await?;
+    Ok(result_response(
+        "AssumeRole",
+        format!("{}{}", credentials_xml(&key), assumed_role_user_xml(&role, &session_name, &key.identity)),
+    ))
+}
+
+async fn assume_role_with_web_identity(state: &AppState, params: &HashMap<String, String>) -> Result<Response, S3Error> {
+    let (role, session_name) = role_session(params)?;
+    let token = required(params, "WebIdentityToken", "webIdentityToken")?;
+    let (subject, provider) = sts::web_identity_claims(token)
+        .map_err(|message| S3Error::new(StatusCode::BAD_REQUEST, "InvalidIdentityToken", message))?;
+    let duration = ASSUME_ROLE_DURATION.parse(params.get("DurationSeconds").map(String::as_str)).map_err(validation_error)?;
+
+    let store = credential_store(state).await?;
+    let identity = role.session_identity(&store.account_identity(&role.account_id), &session_name);
+    let key = issue(state, store, identity, duration).await?;
+    Ok(result_response(
+        "AssumeRoleWithWebIdentity",
+        format!(
+            "{}<SubjectFromWebIdentityToken>{}</SubjectFromWebIdentityToken>{}<Provider>{}</Provider>",
+            credentials_xml(&key),
+            escape_xml(&subject),
+            assumed_role_user_xml(&role, &session_name, &key.identity),
+            escape_xml(&provider)
+        ),
+    ))
+}
+
+// Session credentials act as the caller itself
+async fn get_session_token(
+    state: &AppState,
+    requester: &Requester,
+    params: &HashMap<String, String>,
+) -> Result<Response, S3Error> {
+    let identity = caller(requester)?.clone();
+    let duration = SESSION_TOKEN_DURATION.parse(params.get("DurationSeconds").map(String::as_str)).map_err(validation_error)?;
+    let store = credential_store(state).await?;
+    let key = issue(state, store, identity, duration).await?;
+    Ok(result_response("GetSessionToken", credentials_xml(&key)))
+}
+
+async fn issue(state: &AppState, mut store: CredentialStore, identity: Identity, duration: Duration) -> Result<AccessKey, S3Error> {
+    let now = Utc::now();
+    store.prune_expired(now);
+    let key = AccessKey::temporary(identity, now + duration);
+    store.add(key.clone()).map_err(S3Error::internal)?;
+    save_credential_store(state, &store).await?;
+    Ok(key)
+}
+
+fn caller(requester: &Requester) -> Result<&Identity, S3Error> {
+    requester.identity().ok_or_else(|| {
+        S3Error::new(StatusCode::FORBIDDEN, "MissingAuthenticationToken", "Request is missing Authentication Token")
+    })
+}
+
+fn role_session(params: &HashMap<String, String>) -> Result<(RoleArn, String), S3Error> {
+    let role = RoleArn::parse(required(params, "RoleArn", "roleArn")?).map_err(validation_error)?;
+    let session_name = required(params, "RoleSessionName", "roleSessionName")?;
+    sts::validate_session_name(session_name).map_err(validation_error)?;
+    Ok((role, session_name.to_string()))
+}
+
+fn required<'a>(params: &'a HashMap<String, String>, name: &str, member: &str) -> Result<&'a str, S3Error> {
+    params.get(name).map(String::as_str).ok_or_else(|| {
+        validation_error(format!(
+            "1 validation error detected: Value null at '{}' failed to satisfy constraint: Member must not be null",
+            member
+        ))
+    })
+}
+
+fn credentials_xml(key: &AccessKey) -> String {
+    format!(
+        "<Credentials><AccessKeyId>{}</AccessKeyId><SecretAccessKey>{}</SecretAccessKey><SessionToken>{}</SessionToken><Expiration>{}</Expiration></Credentials>",
+        key.access_key_id,
+        key.secret_access_key,
+        escape_xml(key.session_token.as_deref().unwrap_or_default()),
+        key.expiration.unwrap_or_default().format("%Y-%m-%dT%H:%M:%SZ")
+    )
+}
+
+fn assumed_role_user_xml(role: &RoleArn, session_name: &str, identity: &Identity) -> String {
+    format!(
+        "<AssumedRoleUser><AssumedRoleId>{}:{}</AssumedRoleId><Arn>{}</Arn></AssumedRoleUser>",
+        role.role_id(),
+        escape_xml(session_name),
+        escape_xml(&identity.arn)
+    )
+}
+
+fn result_response(action: &str, result: String) -> Response {
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<{action}Response xmlns=\"{}\"><{action}Result>{}</{action}Result><ResponseMetadata><RequestId>{}</RequestId></ResponseMetadata></{action}Response>",
+        NAMESPACE,
+        result,
+        Uuid::new_v4(),
+        action = action
+    );
+    ([(header::CONTENT_TYPE, "text/xml")], xml).into_response()
+}
+
+/// Renders an error in the `ErrorResponse` format of the STS query protocol.
+pub fn error_response(error: S3Error) -> Response {
+    let kind = if error.status.is_server_error() { "Receiver" } else { "Sender" };
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<ErrorResponse xmlns=\"{}\"><Error><Type>{}</Type><Code>{}</Code><Message>{}</Message></Error><RequestId>{}</RequestId></ErrorResponse>",
+        NAMESPACE,
+        kind,
+        error.code,
+        escape_xml(&error.message),
+        Uuid::new_v4()
+    );
+    (error.status, [(header::CONTENT_TYPE, "text/xml")], xml).into_response()
+}
+
+fn validation_error(message: impl Into<String>) -> S3Error {
+    S3Error::new(StatusCode::BAD_REQUEST, "ValidationError", message)
+}
@@ -0,0 +1,87 @@
+use axum::extract::{OriginalUri, Request, State};
+use axum::http::{header, Uri};
+use axum::middleware::Next;
+use axum::response::Response;
+use super::api::AppState;
+
+/// Middleware mapping virtual-hosted-style requests (`Host: bucket.<domain>`) onto the
+/// path-style routes, so `/key` on that host is served as `/bucket/key`. Requests for
+/// any other host are left alone. Runs before routing; signatures are checked against
+/// the `OriginalUri` the client signed.
+pub async fn route_virtual_host(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    let host = request
+        .headers()
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .or_else(|| request.uri().host())
+        .unwrap_or_default();
+    if let Some(bucket_name) = bucket_from_host(host, &state.domains) {
+        let original = request.uri().clone();
+        if let Some(uri) = path_style_uri(&original, &bucket_name) {
+            request.extensions_mut().insert(OriginalUri(original));
+            *request.uri_mut() = uri;
+        }
+    }
+    next.run(request).await
+}
+
+/// The bucket a host addresses, as in "my.bucket" for "my.bucket.s3.localhost:3000"
+/// with the "s3.localhost" domain.
+pub fn bucket_from_host(host: &str, domains: &[String]) -> Option<String> {
+    let host = strip_port(host).trim_end_matches('.').to_lowercase();
+    domains.iter().find_map(|domain| {
+        let bucket_name = host.strip_suffix(&domain.to_lowercase())?.strip_suffix('.')?;
+        (!bucket_name.is_empty()).then(|| bucket_name.to_string())
+    })
+}
+
+/// The host name without its port, if any.
+pub fn strip_port(host: &str) -> &str {
+    // IPv6 literals have colons of their own, and end at their bracket
+    if host.starts_with('[') {
+        return host.find(']').map_or(host, |end| &host[..=end]);
+    }
+    match host.rsplit_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    }
+}
+
+fn path_style_uri(uri: &Uri, bucket_name: &str) -> Option<Uri> {
+    let path = match uri.path() {
+        "/" | "" => format!("/{}", bucket_name),
+        path => format!("/{}{}", bucket_name, path),
+    };
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path,
+    };
+    path_and_query.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_from_host() {
+        let domains = vec!["s3.localhost".to_string(), "s3.example.test".to_string()];
+        assert_eq!(bucket_from_host("photos.s3.localhost:3000", &domains).as_deref(), Some("photos"));
+        assert_eq!(bucket_from_host("my.dotted.bucket.s3.localhost", &domains).as_deref(), Some("my.dotted.bucket"));
+        assert_eq!(bucket_from_host("Logs.S3.Example.Test.", &domains).as_deref(), Some("logs"));
+        assert_eq!(bucket_from_host("s3.localhost:3000", &domains), None);
+        assert_eq!(bucket_from_host("localhost:3000", &domains), None);
+        assert_eq!(bucket_from_host("photos.s3.localhost.evil:3000", &domains), None);
+        assert_eq!(bucket_from_host("[::1]:3000", &domains), None);
+        assert_eq!(strip_port("[::1]:3000"), "[::1]");
+        assert_eq!(strip_port("www.example.com"), "www.example.com");
+    }
+
+    #[test]
+    fn test_path_style_uri() {
+        let uri: Uri = "/dir/key.txt?acl".parse().unwrap();
+        assert_eq!(path_style_uri(&uri, "photos").unwrap(), "/photos/dir/key.txt?acl");
+        let root: Uri = "/?cors".parse().unwrap();
+        assert_eq!(path_style_uri(&root, "my.bucket").unwrap(), "/my.bucket?cors");
+    }
+}
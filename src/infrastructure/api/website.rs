@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::request::Parts;
+use axum::http::{header, HeaderMap, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use percent_encoding::percent_decode_str;
+use crate::domain::identity::{Identity, Requester};
+use crate::domain::object::Object;
+use crate::domain::website::{self, WebsiteConfiguration};
+use super::access::check_access;
+use super::api::{require_bucket, AppState};
+use super::encryption;
+use super::error::{escape_xml, S3Error};
+use super::object::{check_key, header_str, insert_header};
+use super::operation::S3Operation;
+use super::virtual_host::{bucket_from_host, strip_port};
+
+pub async fn put_bucket_website(state: &AppState, bucket_name: &str, body: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let config = WebsiteConfiguration::from_xml(body).map_err(|_| S3Error::malformed_xml())?;
+    config.validate().map_err(S3Error::invalid_argument)?;
+
+    let xml = config.to_xml().map_err(S3Error::internal)?;
+    state
+        .storage
+        .put_bucket_config(bucket_name, website::CONFIG_NAME, &xml)
+        .await?;
+    Ok(StatusCode::OK.into_response())
+}
+
+pub async fn get_bucket_website(state: &AppState, bucket_name: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    let xml = state
+        .storage
+        .get_bucket_config(bucket_name, website::CONFIG_NAME)
+        .await?
+        .ok_or_else(|| no_such_website_configuration(bucket_name))?;
+    Ok(([(header::CONTENT_TYPE, "application/xml")], xml).into_response())
+}
+
+pub async fn delete_bucket_website(state: &AppState, bucket_name: &str) -> Result<Response, S3Error> {
+    require_bucket(state, bucket_name).await?;
+    state
+        .storage
+        .delete_bucket_config(bucket_name, website::CONFIG_NAME)
+        .await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// The `x-amz-website-redirect-location` of a new object, checked for a valid prefix.
+pub(crate) fn redirect_location(value: Option<&str>) -> Result<Option<String>, S3Error> {
+    let Some(location) = value else {
+        return Ok(None);
+    };
+    website::validate_redirect_location(location)
+        .map_err(|message| S3Error::new(StatusCode::BAD_REQUEST, "InvalidRedirectLocation", message))?;
+    Ok(Some(location.to_string()))
+}
+
+/// Middleware serving requests for website endpoint hosts (`Host: bucket.<website domain>`)
+/// as a static website, outside of S3 routing and authentication.
+pub async fn route_website(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    match bucket_from_host(host_of(&request), &state.website_domains) {
+        Some(bucket_name) => serve(&state, &bucket_name, request).await,
+        None => next.run(request).await,
+    }
+}
+
+/// Handler of the separate website listener. Hosts name the bucket like website endpoint
+/// hosts do, or are the bucket name, as for buckets named after a custom domain.
+pub async fn serve_any_host(State(state): State<AppState>, request: Request) -> Response {
+    let host = host_of(&request);
+    let bucket_name = bucket_from_host(host, &state.website_domains)
+        .unwrap_or_else(|| strip_port(host).trim_end_matches('.').to_lowercase());
+    serve(&state, &bucket_name, request).await
+}
+
+fn host_of(request: &Request) -> &str {
+    header_str(request.headers(), header::HOST.as_str())
+        .or_else(|| request.uri().host())
+        .unwrap_or_default()
+}
+
+async fn serve(state: &AppState, bucket_name: &str, request: Request) -> Response {
+    // Website requests have no use for a body
+    let (parts, _) = request.into_parts();
+    match website_response(state, bucket_name, &parts).await {
+        Ok(response) => response,
+        Err(error) => error_page(&error),
+    }
+}
+
+async fn website_response(state: &AppState, bucket_name: &str, request: &Parts) -> Result<Response, S3Error> {
+    let method = &request.method;
+    if method != Method::GET && method != Method::HEAD {
+        return Err(S3Error::new(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "MethodNotAllowed",
+            "The specified method is not allowed against this resource.",
+        )
+        .with_detail("Method", method.as_str())
+        .with_detail("ResourceType", "OBJECT"));
+    }
+    if state.storage.get_bucket(bucket_name).await?.is_none() {
+        return Err(S3Error::new(StatusCode::NOT_FOUND, "NoSuchBucket", "The specified bucket does not exist")
+            .with_detail("BucketName", bucket_name));
+    }
+    let config = match state.storage.get_bucket_config(bucket_name, website::CONFIG_NAME).await? {
+        Some(xml) => WebsiteConfiguration::from_xml(&xml).map_err(S3Error::internal)?,
+        None => return Err(no_such_website_configuration(bucket_name)),
+    };
+
+    let host = header_str(&request.headers, header::HOST.as_str())
+        .or_else(|| request.uri.host())
+        .unwrap_or_default();
+    if let Some(target) = &config.redirect_all_requests_to {
+        let path = request.uri.path_and_query().map(|path| path.as_str()).unwrap_or("/");
+        let location = format!("{}://{}{}", target.protocol.as_deref().unwrap_or("http"), target.host_name, path);
+        return Ok(redirect(StatusCode::MOVED_PERMANENTLY, &location));
+    }
+    let key = percent_decode_str(request.uri.path().trim_start_matches('/'))
+        .decode_utf8_lossy()
+        .into_owned();
+    if let Some(rule) = config.rule_for(&key, None) {
+        return Ok(rule_redirect(rule.location(&key, host, "http")));
+    }
+
+    let reader = WebsiteReader { state, bucket_name, request };
+    let error = match reader.fetch(&config.resolve(&key)).await {
+        Ok(object) => return Ok(object_response(method, object, StatusCode::OK)),
+        Err(error) => error,
+    };
+    // Folders requested without their trailing slash are redirected to it
+    if error.status == StatusCode::NOT_FOUND && !key.is_empty() && !key.ends_with('/') {
+        let folder = format!("{}/", key);
+        if reader.fetch(&config.resolve(&folder)).await.is_ok() {
+            return Ok(redirect(StatusCode::FOUND, &format!("/{}", folder)));
+        }
+    }
+    if let Some(rule) = config.rule_for(&key, Some(error.status.as_u16())) {
+        return Ok(rule_redirect(rule.location(&key, host, "http")));
+    }
+    if let Some(document) = &config.error_document {
+        if let Ok(object) = reader.fetch(&document.key).await {
+            return Ok(object_response(method, object, error.status));
+        }
+    }
+    Err(error)
+}
+
+// Reads objects the way a website visitor may: anonymously when authentication is on
+struct WebsiteReader<'a> {
+    state: &'a AppState,
+    bucket_name: &'a str,
+    request: &'a Parts,
+}
+
+impl WebsiteReader<'_> {
+    async fn fetch(&self, key: &str) -> Result<Object, S3Error> {
+        check_key(key)?;
+        let requester = if self.state.auth_enabled {
+            Requester::Anonymous
+        } else {
+            Requester::Authenticated(Identity::owner())
+        };
+        let operation = S3Operation {
+            name: "GetObject",
+            action: "s3:GetObject",
+            bucket: Some(self.bucket_name.to_string()),
+            key: Some(key.to_string()),
+            query: HashMap::new(),
+        };
+        let source_ip = self
+            .request
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        check_access(self.state, &operation, &requester, &self.request.headers, source_ip)
+            .await
+            .map_err(|error| error.with_detail("Key", key))?;
+        let mut object = self
+            .state
+            .storage
+            .get_object(self.bucket_name, key)
+            .await
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => {
+                    S3Error::new(StatusCode::NOT_FOUND, "NoSuchKey", "The specified key does not exist.")
+                        .with_detail("Key", key)
+                }
+                _ => S3Error::internal(e),
+            })?;
+        encryption::decrypt_object(&self.request.headers, encryption::CUSTOMER_HEADERS, &mut object)?;
+        Ok(object)
+    }
+}
+
+fn object_response(method: &Method, object: Object, status: StatusCode) -> Response {
+    if let Some(location) = &object.website_redirect_location {
+        return redirect(StatusCode::MOVED_PERMANENTLY, location);
+    }
+    let size = object.size.to_string();
+    let mut response = if method == Method::HEAD {
+        status.into_response()
+    } else {
+        (status, object.content).into_response()
+    };
+    insert_header(&mut response, header::CONTENT_TYPE.as_str(), &object.content_type);
+    insert_header(&mut response, header::CONTENT_LENGTH.as_str(), &size);
+    insert_header(&mut response, header::ETAG.as_str(), &object.etag);
+    insert_header(
+        &mut response,
+        header::LAST_MODIFIED.as_str(),
+        &object.last_modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
+    );
+    response
+}
+
+fn rule_redirect((code, location): (u16, String)) -> Response {
+    redirect(StatusCode::from_u16(code).unwrap_or(StatusCode::MOVED_PERMANENTLY), &location)
+}
+
+fn redirect(status: StatusCode, location: &str) -> Response {
+    let mut response = status.into_response();
+    insert_header(&mut response, header::LOCATION.as_str(), location);
+    response
+}
+
+// Website endpoints report errors as an HTML page rather than XML
+fn error_page(error: &S3Error) -> Response {
+    let title = format!(
+        "{} {}",
+        error.status.as_u16(),
+        error.status.canonical_reason().unwrap_or_default()
+    );
+    let mut items = format!(
+        "<li>Code: {}</li>\n<li>Message: {}</li>\n",
+        error.code,
+        escape_xml(&error.message)
+    );
+    for (name, value) in &error.details {
+        items.push_str(&format!("<li>{}: {}</li>\n", name, escape_xml(value)));
+    }
+    let html = format!(
+        "<html>\n<head><title>{title}</title></head>\n<body>\n<h1>{title}</h1>\n<ul>\n{items}</ul>\n<hr/>\n</body>\n</html>\n"
+    );
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, "text/html; charset=utf-8".parse().unwrap());
+    (error.status, headers, html).into_response()
+}
+
+fn no_such_website_configuration(bucket_name: &str) -> S3Error {
+    S3Error::new(
+        StatusCode::NOT_FOUND,
+        "NoSuchWebsiteConfiguration",
+        "The specified bucket does not have a website configuration",
+    )
+    .with_detail("BucketName", bucket_name)
+}
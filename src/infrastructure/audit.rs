@@ -0,0 +1,137 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+use crate::domain::audit::AuditRecord;
+use crate::domain::identity::DEFAULT_ACCOUNT_ID;
+use crate::domain::object::Object;
+use crate::infrastructure::storage::Storage;
+
+/// Where audit trail files are written.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuditDestination {
+    Directory(PathBuf),
+    Bucket(String),
+}
+
+/// Buffers CloudTrail records of S3 API calls and writes them periodically as gzipped
+/// JSON Lines files, one per region, to a local directory or a bucket.
+pub struct AuditTrail {
+    storage: Arc<dyn Storage>,
+    destination: AuditDestination,
+    interval: Duration,
+    pending: Mutex<Vec<AuditRecord>>,
+}
+
+impl AuditTrail {
+    pub fn new(storage: Arc<dyn Storage>, destination: AuditDestination, interval: Duration) -> Self {
+        Self { storage, destination, interval, pending: Mutex::new(Vec::new()) }
+    }
+
+    pub fn spawn(self: &Arc<Self>) -> JoinHandle<()> {
+        let trail = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(trail.interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = trail.flush(Utc::now()).await {
+                    eprintln!("Audit trail delivery failed: {}", e);
+                }
+            }
+        })
+    }
+
+    pub fn record(&self, record: AuditRecord) {
+        self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(record);
+    }
+
+    /// Writes the buffered records as of `now`, under CloudTrail's key layout. Returns the
+    /// number of files written.
+    pub async fn flush(&self, now: DateTime<Utc>) -> io::Result<usize> {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+        let mut regions: Vec<(String, Vec<AuditRecord>)> = Vec::new();
+        for record in pending {
+            match regions.iter_mut().find(|(region, _)| *region == record.aws_region) {
+                Some((_, records)) => records.push(record),
+                None => regions.push((record.aws_region.clone(), vec![record])),
+            }
+        }
+
+        for (region, records) in &regions {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            for record in records {
+                writeln!(encoder, "{}", record.to_json())?;
+            }
+            let content = encoder.finish()?;
+            let key = trail_key(region, now, &Uuid::new_v4().simple().to_string()[..16]);
+            match &self.destination {
+                AuditDestination::Directory(dir) => {
+                    let path = dir.join(&key);
+                    if let Some(parent) = path.parent() {
+                        tokio::fs::create_dir_all(parent).await?;
+                    }
+                    tokio::fs::write(path, content).await?;
+                }
+                AuditDestination::Bucket(bucket) => {
+                    let object = Object::new(key, content, "application/gzip".to_string());
+                    self.storage.put_object(bucket, &object).await?;
+                }
+            }
+        }
+        Ok(regions.len())
+    }
+}
+
+// As in "AWSLogs/000000000000/CloudTrail/us-east-1/2024/03/31/000000000000_CloudTrail_us-east-1_20240331T2359Z_<unique>.jsonl.gz"
+fn trail_key(region: &str, delivered: DateTime<Utc>, unique: &str) -> String {
+    format!(
+        "AWSLogs/{account}/CloudTrail/{region}/{}/{account}_CloudTrail_{region}_{}_{}.jsonl.gz",
+        delivered.format("%Y/%m/%d"),
+        delivered.format("%Y%m%dT%H%MZ"),
+        unique,
+        account = DEFAULT_ACCOUNT_ID,
+        region = region,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use flate2::read::GzDecoder;
+    use crate::domain::audit::UserIdentity;
+    use crate::domain::identity::Requester;
+    use crate::infrastructure::storage::FileStorage;
+
+    #[tokio::test]
+    async fn test_flush_writes_gzipped_json_lines() {
+        let base_path = std::env::temp_dir().join(format!("s3-mocker-audit-{}", Uuid::new_v4()));
+        let storage: Arc<dyn Storage> = Arc::new(FileStorage::new(base_path.join("data")));
+        let trail = AuditTrail::new(storage, AuditDestination::Directory(base_path.join("trail")), Duration::from_secs(60));
+        for (name, region) in [("GetObject", "us-east-1"), ("PutObject", "us-east-1"), ("GetObject", "eu-west-1")] {
+            let identity = UserIdentity::from_requester(&Requester::Anonymous, None);
+            trail.record(AuditRecord::new(name, identity, region, DEFAULT_ACCOUNT_ID));
+        }
+        let now = Utc::now();
+        assert_eq!(trail.flush(now).await.unwrap(), 2);
+        assert_eq!(trail.flush(now).await.unwrap(), 0);
+
+        let dir = base_path.join(format!("trail/AWSLogs/000000000000/CloudTrail/us-east-1/{}", now.format("%Y/%m/%d")));
+        let files: Vec<_> = std::fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+        assert_eq!(files.len(), 1);
+        let mut lines = String::new();
+        GzDecoder::new(std::fs::File::open(&files[0]).unwrap()).read_to_string(&mut lines).unwrap();
+        let names: Vec<String> = lines
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["eventName"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(names, ["GetObject", "PutObject"]);
+
+        std::fs::remove_dir_all(base_path).unwrap();
+    }
+}
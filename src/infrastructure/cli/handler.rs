@@ -1,5 +1,7 @@
 use std::path::PathBuf;
 use crate::domain::bucket::Bucket;
+use crate::domain::credentials::{self, AccessKey, CredentialStore, KeyStatus};
+use crate::domain::hook::{self, Hook, HookStore};
 use crate::domain::object::Object;
 use crate::infrastructure::storage::{FileStorage, Storage};
 
@@ -8,10 +10,13 @@ pub struct CliHandler {
 }
 
 impl CliHandler {
-    pub fn new() -> Self {
-        let base_path = PathBuf::from("./s3-data");
+    pub fn new(base_path: PathBuf) -> Self {
+        let storage = FileStorage::new(base_path);
+        if let Err(e) = storage.migrate_legacy_layout() {
+            eprintln!("Could not move objects to encoded file names: {}", e);
+        }
         Self {
-            storage: Box::new(FileStorage::new(base_path)),
+            storage: Box::new(storage),
         }
     }
 
@@ -75,4 +80,136 @@ impl CliHandler {
         }
         Ok(())
     }
-} 
\ No newline at end of file
+
+    pub async fn create_access_key(&self, user_name: &str, canonical_user_id: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+        let mut store = self.credential_store().await?;
+        let key = AccessKey::generate(store.identity_for(user_name, canonical_user_id));
+        store.add(key.clone())?;
+        self.save_credential_store(&store).await?;
+        println!("Created access key for {} ({})", key.identity.display_name, key.identity.canonical_user_id);
+        println!("Access key ID: {}", key.access_key_id);
+        println!("Secret access key: {}", key.secret_access_key);
+        Ok(())
+    }
+
+    pub async fn list_access_keys(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let store = self.credential_store().await?;
+        if store.keys().next().is_none() {
+            println!("No access keys found");
+            return Ok(());
+        }
+        println!("Access keys:");
+        for key in store.keys() {
+            println!(
+                "- {} [{}] {} ({})",
+                key.access_key_id,
+                key.status.as_str(),
+                key.identity.display_name,
+                key.identity.canonical_user_id
+            );
+        }
+        Ok(())
+    }
+
+    pub async fn set_access_key_status(&self, access_key_id: &str, status: KeyStatus) -> Result<(), Box<dyn std::error::Error>> {
+        let mut store = self.credential_store().await?;
+        store
+            .set_status(access_key_id, status)
+            .ok_or_else(|| format!("Access key not found: {}", access_key_id))?;
+        self.save_credential_store(&store).await?;
+        println!("Access key {} is now {}", access_key_id, status.as_str());
+        Ok(())
+    }
+
+    pub async fn delete_access_key(&self, access_key_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut store = self.credential_store().await?;
+        store
+            .remove(access_key_id)
+            .ok_or_else(|| format!("Access key not found: {}", access_key_id))?;
+        self.save_credential_store(&store).await?;
+        println!("Deleted access key: {}", access_key_id);
+        Ok(())
+    }
+
+    pub async fn list_encryption_keys(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let keys = self.storage.encryption_keys().await?;
+        println!("Encryption keys:");
+        for key in &keys.keys {
+            let active = if key.id == keys.active_key_id { " (active)" } else { "" };
+            println!("- {} created {}{}", key.id, key.created.to_rfc3339(), active);
+        }
+        Ok(())
+    }
+
+    pub async fn rotate_encryption_key(&self, reencrypt: bool) -> Result<(), Box<dyn std::error::Error>> {
+        let key_id = self.storage.rotate_encryption_key().await?;
+        println!("Active encryption key: {}", key_id);
+        if reencrypt {
+            let reencrypted = self.storage.reencrypt_objects().await?;
+            println!("Re-encrypted objects: {}", reencrypted);
+        }
+        Ok(())
+    }
+
+    /// Registers the hook described by `json`, as accepted by the admin API. Only here may
+    /// hooks run commands, as the CLI runs on the server's host.
+    pub async fn put_hook(&self, name: &str, json: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut hook: Hook = serde_json::from_str(json)?;
+        hook.name = name.to_string();
+        hook.validate()?;
+        let mut store = self.hook_store().await?;
+        store.put(hook);
+        self.save_hook_store(&store).await?;
+        println!("Registered hook: {}", name);
+        Ok(())
+    }
+
+    pub async fn list_hooks(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let store = self.hook_store().await?;
+        if store.hooks().next().is_none() {
+            println!("No hooks found");
+            return Ok(());
+        }
+        println!("Hooks:");
+        for hook in store.hooks() {
+            let target = match &hook.url {
+                Some(url) => url.clone(),
+                None => hook.command.join(" "),
+            };
+            println!("- {}: {}", hook.name, target);
+        }
+        Ok(())
+    }
+
+    pub async fn delete_hook(&self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut store = self.hook_store().await?;
+        store.remove(name).ok_or_else(|| format!("Hook not found: {}", name))?;
+        self.save_hook_store(&store).await?;
+        println!("Deleted hook: {}", name);
+        Ok(())
+    }
+
+    async fn credential_store(&self) -> Result<CredentialStore, Box<dyn std::error::Error>> {
+        match self.storage.get_server_config(credentials::CONFIG_NAME).await? {
+            Some(json) => Ok(CredentialStore::from_json(&json)?),
+            None => Ok(CredentialStore::default()),
+        }
+    }
+
+    async fn save_credential_store(&self, store: &CredentialStore) -> Result<(), Box<dyn std::error::Error>> {
+        self.storage.put_server_config(credentials::CONFIG_NAME, &store.to_json()?).await?;
+        Ok(())
+    }
+
+    async fn hook_store(&self) -> Result<HookStore, Box<dyn std::error::Error>> {
+        match self.storage.get_server_config(hook::CONFIG_NAME).await? {
+            Some(json) => Ok(HookStore::from_json(&json)?),
+            None => Ok(HookStore::default()),
+        }
+    }
+
+    async fn save_hook_store(&self, store: &HookStore) -> Result<(), Box<dyn std::error::Error>> {
+        self.storage.put_server_config(hook::CONFIG_NAME, &store.to_json()?).await?;
+        Ok(())
+    }
+}
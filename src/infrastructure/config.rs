@@ -0,0 +1,149 @@
+use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
+use crate::domain::region::DEFAULT_REGION;
+
+const DEFAULT_PORT: u16 = 3000;
+const DEFAULT_DATA_DIR: &str = "./s3-data";
+const DEFAULT_LIFECYCLE_INTERVAL_SECS: u64 = 60;
+const DEFAULT_DOMAIN: &str = "s3.localhost";
+const DEFAULT_WEBSITE_DOMAIN: &str = "s3-website.localhost";
+const DEFAULT_WEBHOOK_RETRIES: u32 = 3;
+const DEFAULT_REPLICATION_DELAY_MS: u64 = 0;
+const DEFAULT_ACCESS_LOG_INTERVAL_SECS: u64 = 60;
+const DEFAULT_AUDIT_INTERVAL_SECS: u64 = 60;
+const DEFAULT_INVENTORY_INTERVAL_SECS: u64 = 3600;
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub port: u16,
+    pub data_dir: PathBuf,
+    pub lifecycle_interval: Duration,
+    pub auth_enabled: bool,
+    // Whether X-Forwarded-Proto is trusted to tell requests a TLS proxy received over HTTPS
+    pub trust_proxy: bool,
+    // Access key ID and secret pairs accepted when authentication is enabled
+    pub access_keys: Vec<(String, String)>,
+    // Base domains for virtual-hosted-style requests, as in "bucket.s3.localhost"
+    pub domains: Vec<String>,
+    // Base domains of website endpoints, as in "bucket.s3-website.localhost"
+    pub website_domains: Vec<String>,
+    // Port of a separate listener serving every request as a website request
+    pub website_port: Option<u16>,
+    // Region of requests that don't name one in a SigV4 credential scope
+    pub region: String,
+    // Event notification destination ARNs and the HTTP endpoints they deliver to
+    pub webhooks: Vec<(String, String)>,
+    pub webhook_retries: u32,
+    // Time between storing an object and replicating it, to simulate replication lag
+    pub replication_delay: Duration,
+    // How often buffered server access logs are delivered to their target buckets
+    pub access_log_interval: Duration,
+    // Directory or bucket the audit trail of S3 API calls is written to, if any
+    pub audit_dir: Option<PathBuf>,
+    pub audit_bucket: Option<String>,
+    pub audit_interval: Duration,
+    // How often inventory configurations are checked for a report that is due
+    pub inventory_interval: Duration,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            port: DEFAULT_PORT,
+            data_dir: PathBuf::from(DEFAULT_DATA_DIR),
+            lifecycle_interval: Duration::from_secs(DEFAULT_LIFECYCLE_INTERVAL_SECS),
+            auth_enabled: false,
+            trust_proxy: false,
+            access_keys: Vec::new(),
+            domains: vec![DEFAULT_DOMAIN.to_string()],
+            website_domains: vec![DEFAULT_WEBSITE_DOMAIN.to_string()],
+            website_port: None,
+            region: DEFAULT_REGION.to_string(),
+            webhooks: Vec::new(),
+            webhook_retries: DEFAULT_WEBHOOK_RETRIES,
+            replication_delay: Duration::from_millis(DEFAULT_REPLICATION_DELAY_MS),
+            access_log_interval: Duration::from_secs(DEFAULT_ACCESS_LOG_INTERVAL_SECS),
+            audit_dir: None,
+            audit_bucket: None,
+            audit_interval: Duration::from_secs(DEFAULT_AUDIT_INTERVAL_SECS),
+            inventory_interval: Duration::from_secs(DEFAULT_INVENTORY_INTERVAL_SECS),
+        }
+    }
+}
+
+impl ServerConfig {
+    // Every setting can be overridden with an S3_MOCKER_* environment variable
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            port: parse_var("S3_MOCKER_PORT").unwrap_or(defaults.port),
+            data_dir: env::var("S3_MOCKER_DATA_DIR").map(PathBuf::from).unwrap_or(defaults.data_dir),
+            lifecycle_interval: parse_var("S3_MOCKER_LIFECYCLE_INTERVAL_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.lifecycle_interval),
+            auth_enabled: parse_var("S3_MOCKER_AUTH").unwrap_or(defaults.auth_enabled),
+            trust_proxy: parse_var("S3_MOCKER_TRUST_PROXY").unwrap_or(defaults.trust_proxy),
+            access_keys: env::var("S3_MOCKER_ACCESS_KEYS")
+                .map(|keys| parse_access_keys(&keys))
+                .unwrap_or(defaults.access_keys),
+            domains: env::var("S3_MOCKER_DOMAIN")
+                .map(|domains| parse_domains(&domains))
+                .unwrap_or(defaults.domains),
+            website_domains: env::var("S3_MOCKER_WEBSITE_DOMAIN")
+                .map(|domains| parse_domains(&domains))
+                .unwrap_or(defaults.website_domains),
+            website_port: parse_var("S3_MOCKER_WEBSITE_PORT").or(defaults.website_port),
+            region: env::var("S3_MOCKER_REGION").unwrap_or(defaults.region),
+            webhooks: env::var("S3_MOCKER_WEBHOOKS")
+                .map(|webhooks| parse_webhooks(&webhooks))
+                .unwrap_or(defaults.webhooks),
+            webhook_retries: parse_var("S3_MOCKER_WEBHOOK_RETRIES").unwrap_or(defaults.webhook_retries),
+            replication_delay: parse_var("S3_MOCKER_REPLICATION_DELAY_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.replication_delay),
+            access_log_interval: parse_var("S3_MOCKER_ACCESS_LOG_INTERVAL_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.access_log_interval),
+            audit_dir: env::var("S3_MOCKER_AUDIT_DIR").map(PathBuf::from).ok().or(defaults.audit_dir),
+            audit_bucket: env::var("S3_MOCKER_AUDIT_BUCKET").ok().or(defaults.audit_bucket),
+            audit_interval: parse_var("S3_MOCKER_AUDIT_INTERVAL_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.audit_interval),
+            inventory_interval: parse_var("S3_MOCKER_INVENTORY_INTERVAL_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.inventory_interval),
+        }
+    }
+}
+
+// "AKID1:secret1,AKID2:secret2"
+fn parse_access_keys(value: &str) -> Vec<(String, String)> {
+    value
+        .split(',')
+        .filter_map(|pair| pair.trim().split_once(':'))
+        .map(|(id, secret)| (id.to_string(), secret.to_string()))
+        .collect()
+}
+
+// "s3.localhost,s3.example.test"
+fn parse_domains(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|domain| domain.trim().trim_matches('.').to_string())
+        .filter(|domain| !domain.is_empty())
+        .collect()
+}
+
+// "arn:aws:sqs:us-east-1:123456789012:jobs=http://localhost:8080/events,..."
+fn parse_webhooks(value: &str) -> Vec<(String, String)> {
+    value
+        .split(',')
+        .filter_map(|pair| pair.trim().split_once('='))
+        .map(|(arn, url)| (arn.trim().to_string(), url.trim().to_string()))
+        .collect()
+}
+
+fn parse_var<T: std::str::FromStr>(name: &str) -> Option<T> {
+    env::var(name).ok().and_then(|value| value.parse().ok())
+}
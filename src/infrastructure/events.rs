@@ -0,0 +1,98 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use tokio::sync::futures::Notified;
+use tokio::sync::Notify;
+use crate::domain::event::ChangeEvent;
+
+// Events kept for subscribers resuming after a reconnect
+const CAPACITY: usize = 1000;
+
+/// The latest bucket and object changes, numbered in order, for event stream subscribers.
+pub struct EventLog {
+    inner: Mutex<Inner>,
+    // Wakes up subscribers when events are recorded
+    arrivals: Notify,
+}
+
+struct Inner {
+    last_sequence: u64,
+    events: VecDeque<ChangeEvent>,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                last_sequence: 0,
+                events: VecDeque::new(),
+            }),
+            arrivals: Notify::new(),
+        }
+    }
+}
+
+impl EventLog {
+    /// Numbers the event and keeps it, dropping the oldest one when full.
+    pub fn record(&self, mut event: ChangeEvent) {
+        {
+            let mut inner = self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            inner.last_sequence += 1;
+            event.sequence = inner.last_sequence;
+            if inner.events.len() == CAPACITY {
+                inner.events.pop_front();
+            }
+            inner.events.push_back(event);
+        }
+        self.arrivals.notify_waiters();
+    }
+
+    /// Kept events numbered after `after`, oldest first.
+    pub fn since(&self, after: u64) -> Vec<ChangeEvent> {
+        let inner = self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        inner.events.iter().filter(|event| event.sequence > after).cloned().collect()
+    }
+
+    pub fn last_sequence(&self) -> u64 {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).last_sequence
+    }
+
+    /// Completes once an event is recorded. Take it before looking at `since`, so that
+    /// no event recorded in between goes unnoticed.
+    pub fn arrival(&self) -> Notified<'_> {
+        self.arrivals.notified()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn event(key: &str) -> ChangeEvent {
+        ChangeEvent {
+            sequence: 0,
+            time: Utc::now(),
+            event_type: "ObjectCreated:Put".to_string(),
+            bucket: "bucket".to_string(),
+            key: Some(key.to_string()),
+            size: Some(1),
+            etag: None,
+            principal: "AWS:000000000000".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_sequence_and_capacity() {
+        let log = EventLog::default();
+        for i in 0..CAPACITY + 5 {
+            log.record(event(&i.to_string()));
+        }
+        assert_eq!(log.last_sequence(), CAPACITY as u64 + 5);
+        let kept = log.since(0);
+        assert_eq!(kept.len(), CAPACITY);
+        assert_eq!(kept[0].sequence, 6);
+        assert_eq!(kept[0].key.as_deref(), Some("5"));
+        let latest = log.since(CAPACITY as u64 + 3);
+        assert_eq!(latest.iter().map(|event| event.sequence).collect::<Vec<_>>(), vec![1004, 1005]);
+    }
+}
@@ -1,6 +1,19 @@
-use std::sync::{Arc, Mutex};
-use crate::infrastructure::api::{create_router, AppState};
+use std::sync::Arc;
+use crate::domain::credentials::{self, AccessKey, CredentialStore, KeyStatus};
+use crate::domain::identity::{Identity, DEFAULT_ACCOUNT_ID};
+use crate::infrastructure::access_logs::AccessLogger;
+use crate::infrastructure::api::{create_router, create_website_router, AppState};
+use crate::infrastructure::audit::{AuditDestination, AuditTrail};
 use crate::infrastructure::cli::CliHandler;
+use crate::infrastructure::config::ServerConfig;
+use crate::infrastructure::events::EventLog;
+use crate::infrastructure::hooks::HookRunner;
+use crate::infrastructure::inventory::InventoryGenerator;
+use crate::infrastructure::lifecycle::LifecycleWorker;
+use crate::infrastructure::notifications::Notifier;
+use crate::infrastructure::queues::QueueService;
+use crate::infrastructure::replication::Replicator;
+use crate::infrastructure::storage::{FileStorage, Storage};
 
 #[async_trait::async_trait]
 pub trait ApplicationFactory {
@@ -8,7 +21,7 @@ pub trait ApplicationFactory {
 }
 
 pub struct ApiFactory {
-    port: u16,
+    config: ServerConfig,
 }
 
 pub struct CliFactory {
@@ -17,8 +30,35 @@ pub struct CliFactory {
 }
 
 impl ApiFactory {
-    pub fn new(port: u16) -> Self {
-        Self { port }
+    pub fn new(config: ServerConfig) -> Self {
+        Self { config }
+    }
+
+    // Keys from the configuration sign as the default owner. They are (re)added to the
+    // persisted store on every start, next to the keys managed through the admin API.
+    async fn seed_access_keys(&self, storage: &dyn Storage) -> Result<(), Box<dyn std::error::Error>> {
+        if self.config.access_keys.is_empty() {
+            return Ok(());
+        }
+        let mut store = match storage.get_server_config(credentials::CONFIG_NAME).await? {
+            Some(json) => CredentialStore::from_json(&json)?,
+            None => CredentialStore::default(),
+        };
+        for (access_key_id, secret_access_key) in &self.config.access_keys {
+            store.remove(access_key_id);
+            store.add(AccessKey::new(access_key_id.clone(), secret_access_key.clone(), Identity::owner()))?;
+        }
+        storage.put_server_config(credentials::CONFIG_NAME, &store.to_json()?).await?;
+        Ok(())
+    }
+
+    // A local directory takes precedence over a bucket
+    fn audit_destination(&self) -> Option<AuditDestination> {
+        match (&self.config.audit_dir, &self.config.audit_bucket) {
+            (Some(dir), _) => Some(AuditDestination::Directory(dir.clone())),
+            (None, Some(bucket)) => Some(AuditDestination::Bucket(bucket.clone())),
+            (None, None) => None,
+        }
     }
 }
 
@@ -31,19 +71,96 @@ impl CliFactory {
 #[async_trait::async_trait]
 impl ApplicationFactory for ApiFactory {
     async fn run(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let storage = FileStorage::new(self.config.data_dir.clone());
+        let migrated = storage.migrate_legacy_layout()?;
+        if migrated > 0 {
+            println!("Moved {} objects to encoded file names", migrated);
+        }
+        let storage: Arc<dyn Storage> = Arc::new(storage);
+        let queues = Arc::new(QueueService::new(DEFAULT_ACCOUNT_ID, &self.config.region));
+        let events = Arc::new(EventLog::default());
+        let hooks = Arc::new(HookRunner::new(storage.clone(), &self.config.region));
+        let notifier = Arc::new(Notifier::new(
+            storage.clone(),
+            queues.clone(),
+            hooks.clone(),
+            events.clone(),
+            self.config.webhooks.clone(),
+            self.config.webhook_retries,
+        ));
+        let (replicator, replication) = Replicator::spawn(storage.clone(), self.config.replication_delay);
+        let access_logs = Arc::new(AccessLogger::new(storage.clone(), self.config.access_log_interval));
+        let inventory = Arc::new(InventoryGenerator::new(storage.clone(), self.config.inventory_interval));
+        let audit = self
+            .audit_destination()
+            .map(|destination| Arc::new(AuditTrail::new(storage.clone(), destination, self.config.audit_interval)));
         let state = AppState {
-            buckets: Arc::new(Mutex::new(vec![])),
+            storage,
+            auth_enabled: self.config.auth_enabled,
+            trust_proxy: self.config.trust_proxy,
+            domains: self.config.domains.clone(),
+            website_domains: self.config.website_domains.clone(),
+            region: self.config.region.clone(),
+            notifier: notifier.clone(),
+            queues,
+            events,
+            hooks,
+            replicator,
+            access_logs: access_logs.clone(),
+            audit: audit.clone(),
+            inventory: inventory.clone(),
+        };
+        self.seed_access_keys(state.storage.as_ref()).await?;
+
+        let lifecycle = LifecycleWorker::new(state.storage.clone(), self.config.lifecycle_interval)
+            .with_notifier(notifier)
+            .spawn();
+        let access_log_delivery = access_logs.spawn();
+        let inventory_reports = inventory.spawn();
+        let audit_delivery = audit.as_ref().map(|audit| audit.spawn());
+
+        let website = match self.config.website_port {
+            Some(port) => {
+                let addr = std::net::SocketAddr::from(([0, 0, 0, 0], port));
+                let listener = tokio::net::TcpListener::bind(addr).await?;
+                let app = create_website_router(state.clone());
+                println!("Website endpoint running on {}", addr);
+                Some(tokio::spawn(async move {
+                    let service = app.into_make_service_with_connect_info::<std::net::SocketAddr>();
+                    if let Err(e) = axum::serve(listener, service).await {
+                        eprintln!("Website endpoint failed: {}", e);
+                    }
+                }))
+            }
+            None => None,
         };
 
         let app = create_router(state);
-        let addr = std::net::SocketAddr::from(([0, 0, 0, 0], self.port));
+        let addr = std::net::SocketAddr::from(([0, 0, 0, 0], self.config.port));
         let listener = tokio::net::TcpListener::bind(addr).await?;
 
         println!("Server running on {}", addr);
-        axum::serve(listener, app)
+        axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>())
             .with_graceful_shutdown(shutdown_signal())
             .await?;
 
+        lifecycle.abort();
+        replication.abort();
+        inventory_reports.abort();
+        if let Some(website) = website {
+            website.abort();
+        }
+        // Deliver what was logged since the last delivery
+        access_log_delivery.abort();
+        if let Err(e) = access_logs.flush(chrono::Utc::now()).await {
+            eprintln!("Access log delivery failed: {}", e);
+        }
+        if let (Some(audit), Some(delivery)) = (audit, audit_delivery) {
+            delivery.abort();
+            if let Err(e) = audit.flush(chrono::Utc::now()).await {
+                eprintln!("Audit trail delivery failed: {}", e);
+            }
+        }
         Ok(())
     }
 }
@@ -51,7 +168,7 @@ impl ApplicationFactory for ApiFactory {
 #[async_trait::async_trait]
 impl ApplicationFactory for CliFactory {
     async fn run(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let cli = CliHandler::new();
+        let cli = CliHandler::new(ServerConfig::from_env().data_dir);
         
         match self.command.as_str() {
             // Bucket operations
@@ -105,8 +222,55 @@ impl ApplicationFactory for CliFactory {
                 let bucket_name = &self.args[0];
                 cli.list_objects(bucket_name).await?;
             }
+            // Access key operations
+            "create-access-key" => {
+                if self.args.is_empty() {
+                    return Err("Usage: create-access-key <user-name> [canonical-user-id]".into());
+                }
+                cli.create_access_key(&self.args[0], self.args.get(1).map(String::as_str)).await?;
+            }
+            "list-access-keys" => {
+                cli.list_access_keys().await?;
+            }
+            "disable-access-key" | "enable-access-key" => {
+                if self.args.is_empty() {
+                    return Err(format!("Usage: {} <access-key-id>", self.command).into());
+                }
+                let status = if self.command == "disable-access-key" { KeyStatus::Inactive } else { KeyStatus::Active };
+                cli.set_access_key_status(&self.args[0], status).await?;
+            }
+            "delete-access-key" => {
+                if self.args.is_empty() {
+                    return Err("Usage: delete-access-key <access-key-id>".into());
+                }
+                cli.delete_access_key(&self.args[0]).await?;
+            }
+            // Encryption key operations
+            "list-encryption-keys" => {
+                cli.list_encryption_keys().await?;
+            }
+            "rotate-encryption-key" => {
+                let reencrypt = self.args.iter().any(|arg| arg == "--reencrypt");
+                cli.rotate_encryption_key(reencrypt).await?;
+            }
+            // Hook operations
+            "put-hook" => {
+                if self.args.len() < 2 {
+                    return Err("Usage: put-hook <name> <json>".into());
+                }
+                cli.put_hook(&self.args[0], &self.args[1]).await?;
+            }
+            "list-hooks" => {
+                cli.list_hooks().await?;
+            }
+            "delete-hook" => {
+                if self.args.is_empty() {
+                    return Err("Usage: delete-hook <name>".into());
+                }
+                cli.delete_hook(&self.args[0]).await?;
+            }
             _ => {
-                return Err(format!("Unknown command: {}. Available commands: create, list, delete, put-object, get-object, delete-object, list-objects", self.command).into());
+                return Err(format!("Unknown command: {}. Available commands: create, list, delete, put-object, get-object, delete-object, list-objects, create-access-key, list-access-keys, disable-access-key, enable-access-key, delete-access-key, list-encryption-keys, rotate-encryption-key, put-hook, list-hooks, delete-hook", self.command).into());
             }
         }
         Ok(())
@@ -0,0 +1,273 @@
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use chrono::Utc;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+use uuid::Uuid;
+use crate::domain::hook::{self, Hook, HookStore, Invocation, InvocationStatus};
+use crate::infrastructure::storage::Storage;
+
+// Output beyond this is cut off in the invocation log
+const MAX_OUTPUT_BYTES: usize = 64 * 1024;
+// Invocations kept per hook
+const KEPT_INVOCATIONS: usize = 100;
+
+/// Runs hooks, at most `Concurrency` at a time each, and keeps a log of their latest
+/// invocations in memory.
+pub struct HookRunner {
+    storage: Arc<dyn Storage>,
+    region: String,
+    client: reqwest::Client,
+    // Hook name to its concurrency and the permits left
+    limits: Mutex<HashMap<String, (u32, Arc<Semaphore>)>>,
+    invocations: Mutex<HashMap<String, VecDeque<Invocation>>>,
+}
+
+impl HookRunner {
+    pub fn new(storage: Arc<dyn Storage>, region: &str) -> Self {
+        Self {
+            storage,
+            region: region.to_string(),
+            client: reqwest::Client::new(),
+            limits: Mutex::new(HashMap::new()),
+            invocations: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn region(&self) -> &str {
+        &self.region
+    }
+
+    /// The registered hooks, read on every use so that changes apply right away.
+    pub async fn store(&self) -> io::Result<HookStore> {
+        match self.storage.get_server_config(hook::CONFIG_NAME).await? {
+            Some(json) => HookStore::from_json(&json).map_err(io::Error::other),
+            None => Ok(HookStore::default()),
+        }
+    }
+
+    pub async fn save(&self, store: &HookStore) -> io::Result<()> {
+        let json = store.to_json().map_err(io::Error::other)?;
+        self.storage.put_server_config(hook::CONFIG_NAME, &json).await
+    }
+
+    /// Whether `arn` names a registered hook.
+    pub async fn has_function_arn(&self, arn: &str) -> bool {
+        let Some(name) = hook::function_name_of_arn(arn) else {
+            return false;
+        };
+        self.store().await.is_ok_and(|store| store.get(name).is_some())
+    }
+
+    /// Runs the hook named `name` with `payload`, once one of its concurrency slots is free.
+    /// Returns `None` when there is no such hook.
+    pub async fn invoke(&self, name: &str, payload: &str) -> io::Result<Option<Invocation>> {
+        let Some(hook) = self.store().await?.get(name).cloned() else {
+            return Ok(None);
+        };
+        let limit = self.limit(&hook);
+        let _permit = limit.acquire().await.map_err(io::Error::other)?;
+
+        let request_id = Uuid::new_v4().to_string();
+        let start_time = Utc::now();
+        let started = Instant::now();
+        let timeout = Duration::from_secs(hook.timeout_seconds);
+        let mut invocation = match &hook.url {
+            Some(url) => self.call_url(url, payload, timeout).await,
+            None => self.run_command(&hook, payload, &request_id, timeout).await,
+        };
+        invocation.request_id = request_id;
+        invocation.start_time = start_time;
+        invocation.duration_ms = started.elapsed().as_millis() as u64;
+        println!(
+            "Hook {} invocation {}: {:?} in {} ms",
+            hook.name, invocation.request_id, invocation.status, invocation.duration_ms
+        );
+
+        let mut invocations = self.invocations.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let log = invocations.entry(hook.name.clone()).or_default();
+        if log.len() == KEPT_INVOCATIONS {
+            log.pop_front();
+        }
+        log.push_back(invocation.clone());
+        Ok(Some(invocation))
+    }
+
+    /// The latest invocations of a hook, oldest first.
+    pub fn invocations(&self, name: &str) -> Vec<Invocation> {
+        let invocations = self.invocations.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        invocations.get(name).map(|log| log.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    /// Drops the invocation log of a removed hook.
+    pub fn forget(&self, name: &str) {
+        self.invocations.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).remove(name);
+        self.limits.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).remove(name);
+    }
+
+    // A changed concurrency only applies to invocations starting afterwards
+    fn limit(&self, hook: &Hook) -> Arc<Semaphore> {
+        let mut limits = self.limits.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match limits.get(&hook.name) {
+            Some((concurrency, semaphore)) if *concurrency == hook.concurrency => semaphore.clone(),
+            _ => {
+                let semaphore = Arc::new(Semaphore::new(hook.concurrency as usize));
+                limits.insert(hook.name.clone(), (hook.concurrency, semaphore.clone()));
+                semaphore
+            }
+        }
+    }
+
+    async fn run_command(&self, hook: &Hook, payload: &str, request_id: &str, timeout: Duration) -> Invocation {
+        let (program, arguments) = hook.command.split_first().expect("validated hooks have a command");
+        let spawned = Command::new(program)
+            .args(arguments)
+            .envs(&hook.environment)
+            .env("AWS_LAMBDA_FUNCTION_NAME", &hook.name)
+            .env("AWS_REGION", &self.region)
+            .env("AWS_DEFAULT_REGION", &self.region)
+            .env("AWS_LAMBDA_REQUEST_ID", request_id)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // Timed out commands are killed when their output is given up on
+            .kill_on_drop(true)
+            .spawn();
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(e) => return invocation(InvocationStatus::Failed, Some(format!("Could not start {}: {}", program, e))),
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            let payload = payload.to_string();
+            // Written alongside, as commands that don't read it would block a large payload
+            tokio::spawn(async move {
+                let _ = stdin.write_all(payload.as_bytes()).await;
+            });
+        }
+        match tokio::time::timeout(timeout, child.wait_with_output()).await {
+            Ok(Ok(output)) => {
+                let status = if output.status.success() { InvocationStatus::Succeeded } else { InvocationStatus::Failed };
+                Invocation {
+                    exit_code: output.status.code(),
+                    stdout: truncate(&output.stdout),
+                    stderr: truncate(&output.stderr),
+                    ..invocation(status, None)
+                }
+            }
+            Ok(Err(e)) => invocation(InvocationStatus::Failed, Some(e.to_string())),
+            Err(_) => invocation(
+                InvocationStatus::TimedOut,
+                Some(format!("Task timed out after {} seconds", timeout.as_secs())),
+            ),
+        }
+    }
+
+    async fn call_url(&self, url: &str, payload: &str, timeout: Duration) -> Invocation {
+        let result = self
+            .client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(payload.to_string())
+            .timeout(timeout)
+            .send()
+            .await;
+        let response = match result {
+            Ok(response) => response,
+            Err(e) if e.is_timeout() => {
+                return invocation(
+                    InvocationStatus::TimedOut,
+                    Some(format!("Task timed out after {} seconds", timeout.as_secs())),
+                )
+            }
+            Err(e) => return invocation(InvocationStatus::Failed, Some(e.to_string())),
+        };
+        let status_code = response.status();
+        let body = response.bytes().await.unwrap_or_default();
+        let status = if status_code.is_success() { InvocationStatus::Succeeded } else { InvocationStatus::Failed };
+        Invocation {
+            status_code: Some(status_code.as_u16()),
+            stdout: truncate(&body),
+            ..invocation(status, None)
+        }
+    }
+}
+
+// Timing and the request ID are filled in by the caller
+fn invocation(status: InvocationStatus, error: Option<String>) -> Invocation {
+    Invocation {
+        request_id: String::new(),
+        start_time: Utc::now(),
+        duration_ms: 0,
+        status,
+        exit_code: None,
+        status_code: None,
+        stdout: String::new(),
+        stderr: String::new(),
+        error,
+    }
+}
+
+fn truncate(output: &[u8]) -> String {
+    let output = String::from_utf8_lossy(&output[..output.len().min(MAX_OUTPUT_BYTES)]);
+    output.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::storage::FileStorage;
+
+    async fn runner(base_path: &std::path::Path, hooks: Vec<Hook>) -> HookRunner {
+        let runner = HookRunner::new(Arc::new(FileStorage::new(base_path.to_path_buf())), "us-east-1");
+        let mut store = HookStore::default();
+        for hook in hooks {
+            store.put(hook);
+        }
+        runner.save(&store).await.unwrap();
+        runner
+    }
+
+    fn hook(name: &str, script: &str, timeout_seconds: u64) -> Hook {
+        Hook {
+            name: name.to_string(),
+            command: vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+            url: None,
+            timeout_seconds,
+            concurrency: 1,
+            environment: [("GREETING".to_string(), "hi".to_string())].into_iter().collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_commands() {
+        let base_path = std::env::temp_dir().join(format!("s3-mocker-hooks-{}", Uuid::new_v4()));
+        let runner = runner(&base_path, vec![
+            hook("echo", "echo $GREETING $AWS_LAMBDA_FUNCTION_NAME; cat; echo oops >&2", 5),
+            hook("fail", "exit 3", 5),
+            hook("slow", "sleep 5", 1),
+        ])
+        .await;
+        assert!(runner.has_function_arn("arn:aws:lambda:us-east-1:000000000000:function:echo").await);
+
+        let echoed = runner.invoke("echo", "{\"Records\":[]}").await.unwrap().unwrap();
+        assert_eq!(echoed.status, InvocationStatus::Succeeded);
+        assert_eq!(echoed.stdout, "hi echo\n{\"Records\":[]}");
+        assert_eq!(echoed.stderr, "oops\n");
+
+        let failed = runner.invoke("fail", "{}").await.unwrap().unwrap();
+        assert_eq!((failed.status, failed.exit_code), (InvocationStatus::Failed, Some(3)));
+        let slow = runner.invoke("slow", "{}").await.unwrap().unwrap();
+        assert_eq!(slow.status, InvocationStatus::TimedOut);
+        assert!(slow.duration_ms < 3000);
+
+        assert!(runner.invoke("missing", "{}").await.unwrap().is_none());
+        assert_eq!(runner.invocations("echo").len(), 1);
+        runner.forget("echo");
+        assert!(runner.invocations("echo").is_empty());
+        std::fs::remove_dir_all(base_path).unwrap();
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use md5::{Digest, Md5};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+use crate::domain::inventory::{self, InventoryConfiguration, InventoryConfigurations, Manifest, ManifestFile};
+use crate::domain::object::Object;
+use crate::infrastructure::storage::Storage;
+
+/// Writes inventory reports of buckets to their destination buckets, when their daily or
+/// weekly schedule is due or on demand.
+///
+/// When reports were last written is only kept in memory, so every enabled configuration
+/// is reported on again once the server restarts.
+pub struct InventoryGenerator {
+    storage: Arc<dyn Storage>,
+    interval: Duration,
+    // Last report of each bucket and configuration ID
+    last_reports: Mutex<HashMap<(String, String), DateTime<Utc>>>,
+}
+
+impl InventoryGenerator {
+    pub fn new(storage: Arc<dyn Storage>, interval: Duration) -> Self {
+        Self { storage, interval, last_reports: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn spawn(self: &Arc<Self>) -> JoinHandle<()> {
+        let generator = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(generator.interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = generator.run_once(Utc::now()).await {
+                    eprintln!("Inventory run failed: {}", e);
+                }
+            }
+        })
+    }
+
+    /// Reports on every enabled configuration that is due at `now`. Returns the number of
+    /// reports written.
+    pub async fn run_once(&self, now: DateTime<Utc>) -> io::Result<usize> {
+        let mut written = 0;
+        for bucket_name in self.storage.list_buckets().await? {
+            for config in self.configurations(&bucket_name).await? {
+                let last = self.last_report(&bucket_name, &config.id);
+                if !config.is_enabled || last.is_some_and(|last| now - last < config.period()) {
+                    continue;
+                }
+                match self.generate(&bucket_name, &config, now).await {
+                    Ok(manifest_key) => {
+                        println!("Inventory report: {} of bucket: {}", manifest_key, bucket_name);
+                        written += 1;
+                    }
+                    Err(e) => eprintln!("Inventory report {} of bucket {} failed: {}", config.id, bucket_name, e),
+                }
+            }
+        }
+        Ok(written)
+    }
+
+    pub async fn configurations(&self, bucket_name: &str) -> io::Result<Vec<InventoryConfiguration>> {
+        let Some(xml) = self.storage.get_bucket_config(bucket_name, inventory::CONFIG_NAME).await? else {
+            return Ok(Vec::new());
+        };
+        InventoryConfigurations::from_xml(&xml)
+            .map(|configs| configs.configurations)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Writes a report of `bucket_name` as of `now`: a data file, then the manifest and its
+    /// checksum, and the Hive symlink file. Returns the key of the manifest.
+    pub async fn generate(&self, bucket_name: &str, config: &InventoryConfiguration, now: DateTime<Utc>) -> io::Result<String> {
+        let destination = config.destination_bucket().unwrap_or_default();
+        if self.storage.get_bucket(destination).await?.is_none() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("no destination bucket {}", destination)));
+        }
+
+        let mut keys = self.storage.list_objects(bucket_name).await?;
+        keys.sort();
+        let is_json = config.destination.s3_bucket_destination.format == "JSON";
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        for key in keys.iter().filter(|key| config.includes(key)) {
+            let object = self.storage.head_object(bucket_name, key).await?;
+            let line = if is_json {
+                config.json_line(bucket_name, &object)
+            } else {
+                config.csv_line(bucket_name, &object)
+            };
+            writeln!(encoder, "{}", line)?;
+        }
+        let data = encoder.finish()?;
+
+        let prefix = config.report_prefix(bucket_name);
+        let extension = if is_json { "json" } else { "csv" };
+        let data_key = format!("{}/data/{}.{}.gz", prefix, Uuid::new_v4(), extension);
+        let file = ManifestFile { key: data_key.clone(), size: data.len(), md5_checksum: hex::encode(Md5::digest(&data)) };
+        self.put(destination, &data_key, data, "application/gzip").await?;
+
+        let manifest = Manifest::new(config, bucket_name, now, vec![file]);
+        let manifest_json = serde_json::to_string_pretty(&manifest).map_err(io::Error::other)?;
+        let report_dir = format!("{}/{}", prefix, now.format("%Y-%m-%dT%H-%MZ"));
+        let manifest_key = format!("{}/manifest.json", report_dir);
+        let checksum = hex::encode(Md5::digest(manifest_json.as_bytes()));
+        self.put(destination, &manifest_key, manifest_json.into_bytes(), "application/json").await?;
+        self.put(destination, &format!("{}/manifest.checksum", report_dir), checksum.into_bytes(), "text/plain").await?;
+
+        let symlink = format!("s3://{}/{}\n", destination, data_key);
+        let symlink_key = format!("{}/hive/dt={}/symlink.txt", prefix, now.format("%Y-%m-%d-%H-%M"));
+        self.put(destination, &symlink_key, symlink.into_bytes(), "text/plain").await?;
+
+        self.last_reports
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert((bucket_name.to_string(), config.id.clone()), now);
+        Ok(manifest_key)
+    }
+
+    fn last_report(&self, bucket_name: &str, id: &str) -> Option<DateTime<Utc>> {
+        let last_reports = self.last_reports.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        last_reports.get(&(bucket_name.to_string(), id.to_string())).copied()
+    }
+
+    async fn put(&self, bucket_name: &str, key: &str, content: Vec<u8>, content_type: &str) -> io::Result<()> {
+        let object = Object::new(key.to_string(), content, content_type.to_string());
+        self.storage.put_object(bucket_name, &object).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use flate2::read::GzDecoder;
+    use crate::domain::bucket::Bucket;
+    use crate::infrastructure::storage::FileStorage;
+
+    #[tokio::test]
+    async fn test_daily_reports() {
+        let base_path = std::env::temp_dir().join(format!("s3-mocker-inventory-{}", Uuid::new_v4()));
+        let storage: Arc<dyn Storage> = Arc::new(FileStorage::new(base_path.clone()));
+        for name in ["site", "inventory"] {
+            storage.create_bucket(&Bucket::new(name.to_string())).await.unwrap();
+        }
+        for key in ["docs/b.txt", "docs/a.txt", "other.txt"] {
+            let object = Object::new(key.to_string(), b"hello".to_vec(), "text/plain".to_string());
+            storage.put_object("site", &object).await.unwrap();
+        }
+        let config = InventoryConfiguration::from_xml(
+            "<InventoryConfiguration><Id>daily</Id><IsEnabled>true</IsEnabled><Filter><Prefix>docs/</Prefix></Filter>\
+             <Destination><S3BucketDestination><Bucket>arn:aws:s3:::inventory</Bucket><Format>CSV</Format></S3BucketDestination></Destination>\
+             <Schedule><Frequency>Daily</Frequency></Schedule><IncludedObjectVersions>Current</IncludedObjectVersions>\
+             <OptionalFields><Field>Size</Field></OptionalFields></InventoryConfiguration>",
+        )
+        .unwrap();
+        let configs = InventoryConfigurations { configurations: vec![config] };
+        storage.put_bucket_config("site", inventory::CONFIG_NAME, &configs.to_xml().unwrap()).await.unwrap();
+
+        let generator = InventoryGenerator::new(storage.clone(), Duration::from_secs(60));
+        let now = Utc::now();
+        assert_eq!(generator.run_once(now).await.unwrap(), 1);
+        assert_eq!(generator.run_once(now + chrono::Duration::hours(1)).await.unwrap(), 0);
+        assert_eq!(generator.run_once(now + chrono::Duration::days(1)).await.unwrap(), 1);
+
+        let manifest_key = format!("site/daily/{}/manifest.json", now.format("%Y-%m-%dT%H-%MZ"));
+        let manifest = storage.get_object("inventory", &manifest_key).await.unwrap();
+        let manifest: serde_json::Value = serde_json::from_slice(&manifest.content).unwrap();
+        assert_eq!(manifest["fileSchema"], "Bucket, Key, Size");
+        let data_key = manifest["files"][0]["key"].as_str().unwrap();
+        let data = storage.get_object("inventory", data_key).await.unwrap();
+        let mut rows = String::new();
+        GzDecoder::new(data.content.as_slice()).read_to_string(&mut rows).unwrap();
+        assert_eq!(rows, "\"site\",\"docs/a.txt\",\"5\"\n\"site\",\"docs/b.txt\",\"5\"\n");
+
+        std::fs::remove_dir_all(base_path).unwrap();
+    }
+}
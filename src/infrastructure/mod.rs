@@ -1,6 +1,17 @@
+pub mod access_logs;
 pub mod api;
+pub mod audit;
+pub mod config;
 pub mod factory;
 pub mod cli;
+pub mod events;
+pub mod hooks;
+pub mod inventory;
+pub mod lifecycle;
+pub mod notifications;
+pub mod queues;
+pub mod replication;
 pub mod storage;
 
-pub use factory::{ApplicationFactory, ApiFactory, CliFactory};
\ No newline at end of file
+pub use config::ServerConfig;
+pub use factory::{ApplicationFactory, ApiFactory, CliFactory};
@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::Utc;
+use serde_json::Value;
+use uuid::Uuid;
+use crate::domain::acl::{self, AccessControlPolicy};
+use crate::domain::event::ChangeEvent;
+use crate::domain::hook;
+use crate::domain::notification::{self, NotificationConfiguration, ObjectEvent};
+use crate::domain::object::Object;
+use crate::domain::queue;
+use crate::domain::region::DEFAULT_REGION;
+use crate::infrastructure::events::EventLog;
+use crate::infrastructure::hooks::HookRunner;
+use crate::infrastructure::queues::QueueService;
+use crate::infrastructure::storage::Storage;
+
+// Delay before the first retry of a failed delivery, doubled for every further attempt
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Delivers bucket event notifications. Destination ARNs are mapped to local HTTP
+/// endpoints, which get the event message POSTed as JSON, or name an embedded queue or
+/// a hook.
+pub struct Notifier {
+    storage: Arc<dyn Storage>,
+    queues: Arc<QueueService>,
+    hooks: Arc<HookRunner>,
+    // Every event also goes to the admin event stream
+    events: Arc<EventLog>,
+    // Destination ARN to webhook URL
+    webhooks: HashMap<String, String>,
+    retries: u32,
+    client: reqwest::Client,
+}
+
+impl Notifier {
+    pub fn new(
+        storage: Arc<dyn Storage>,
+        queues: Arc<QueueService>,
+        hooks: Arc<HookRunner>,
+        events: Arc<EventLog>,
+        webhooks: Vec<(String, String)>,
+        retries: u32,
+    ) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Self {
+            storage,
+            queues,
+            hooks,
+            events,
+            webhooks: webhooks.into_iter().collect(),
+            retries,
+            client,
+        }
+    }
+
+    /// Whether events for `arn` can be delivered anywhere.
+    pub async fn knows_destination(&self, arn: &str) -> bool {
+        self.webhooks.contains_key(arn) || self.queues.has_queue_arn(arn) || self.hooks.has_function_arn(arn).await
+    }
+
+    /// An event for `key` in the bucket, carrying the bucket's region and owner.
+    /// `object` is the created object, or `None` for removals.
+    pub async fn event(
+        &self,
+        event_name: &str,
+        bucket_name: &str,
+        key: &str,
+        object: Option<&Object>,
+        principal_id: String,
+        source_ip: String,
+    ) -> io::Result<ObjectEvent> {
+        let region = self
+            .storage
+            .get_bucket(bucket_name)
+            .await?
+            .map(|bucket| bucket.region)
+            .unwrap_or_else(|| DEFAULT_REGION.to_string());
+        let bucket_owner_id = match self.storage.get_bucket_config(bucket_name, acl::CONFIG_NAME).await? {
+            Some(xml) => AccessControlPolicy::from_xml(&xml).map(|acl| acl.owner.id).unwrap_or_default(),
+            None => String::new(),
+        };
+        Ok(ObjectEvent {
+            event_name: event_name.to_string(),
+            bucket_name: bucket_name.to_string(),
+            key: key.to_string(),
+            size: object.map(|object| object.size),
+            etag: object.map(|object| object.etag.clone()),
+            time: Utc::now(),
+            region,
+            principal_id,
+            bucket_owner_id,
+            source_ip,
+            request_id: request_id(),
+        })
+    }
+
+    /// Sends the event to every destination of the bucket subscribed to it. Deliveries
+    /// run in the background, so a slow or failing endpoint never holds up requests.
+    pub async fn publish(&self, event: &ObjectEvent) {
+        self.events.record(ChangeEvent::from(event));
+        let config = match self.configuration(&event.bucket_name).await {
+            Ok(Some(config)) => config,
+            Ok(None) => return,
+            Err(e) => {
+                eprintln!("Skipping notifications of bucket {}: {}", event.bucket_name, e);
+                return;
+            }
+        };
+        for destination in config.destinations() {
+            if !destination.matches(&event.event_name, &event.key) {
+                continue;
+            }
+            let (Some(arn), Some(id)) = (destination.destination(), destination.id.as_deref()) else {
+                continue;
+            };
+            self.deliver(arn, event.message(id));
+        }
+    }
+
+    /// Sends the `s3:TestEvent` message to the topics and queues of a new configuration.
+    /// As in S3, Lambda functions don't get one.
+    pub fn send_test_events(&self, bucket_name: &str, config: &NotificationConfiguration) {
+        let message = notification::test_event(bucket_name, Utc::now(), &request_id());
+        let destinations = config.topic_configurations.iter().chain(&config.queue_configurations);
+        for arn in destinations.filter_map(|destination| destination.destination()) {
+            self.deliver(arn, message.clone());
+        }
+    }
+
+    async fn configuration(&self, bucket_name: &str) -> io::Result<Option<NotificationConfiguration>> {
+        match self.storage.get_bucket_config(bucket_name, notification::CONFIG_NAME).await? {
+            Some(xml) => NotificationConfiguration::from_xml(&xml).map(Some).map_err(io::Error::other),
+            None => Ok(None),
+        }
+    }
+
+    // Webhooks take precedence over embedded queues and hooks of the same ARN
+    fn deliver(&self, arn: &str, message: Value) {
+        let Some(url) = self.webhooks.get(arn).cloned() else {
+            if let Some(name) = hook::function_name_of_arn(arn) {
+                self.invoke_hook(name, message);
+            } else {
+                self.enqueue(arn, &message);
+            }
+            return;
+        };
+        let client = self.client.clone();
+        let retries = self.retries;
+        tokio::spawn(async move {
+            let body = message.to_string();
+            let mut delay = RETRY_DELAY;
+            for attempt in 0..=retries {
+                if attempt > 0 {
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                let result = client
+                    .post(&url)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(body.clone())
+                    .send()
+                    .await;
+                match result {
+                    Ok(response) if response.status().is_success() => return,
+                    Ok(response) => eprintln!("Notification to {} failed with status {}", url, response.status()),
+                    Err(e) => eprintln!("Notification to {} failed: {}", url, e),
+                }
+            }
+            eprintln!("Giving up on notification to {} after {} attempts", url, retries + 1);
+        });
+    }
+
+    // Invoked asynchronously, like S3 invokes Lambda functions
+    fn invoke_hook(&self, name: &str, message: Value) {
+        let hooks = self.hooks.clone();
+        let name = name.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = hooks.invoke(&name, &message.to_string()).await {
+                eprintln!("Invoking hook {} failed: {}", name, e);
+            }
+        });
+    }
+
+    fn enqueue(&self, arn: &str, message: &Value) {
+        let Some(queue_name) = queue::queue_name_of_arn(arn) else {
+            return;
+        };
+        let sender_id = self.queues.account_id().to_string();
+        if let Err(e) = self.queues.send_message(queue_name, &message.to_string(), None, &sender_id) {
+            eprintln!("Notification to queue {} failed: {:?}", queue_name, e);
+        }
+    }
+}
+
+// Shaped like the 16 character request IDs of S3
+fn request_id() -> String {
+    Uuid::new_v4().simple().to_string()[..16].to_uppercase()
+}
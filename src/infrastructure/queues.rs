@@ -0,0 +1,174 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+use chrono::Utc;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+use crate::domain::queue::{self, Message, Queue, QueueAttributes, QueueError};
+
+// Long polls also look again this often, for delayed and timed out messages
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// In-memory SQS queues, owned by a single account in a single region. Queues and their
+/// messages are lost when the server stops.
+pub struct QueueService {
+    queues: Mutex<HashMap<String, Queue>>,
+    // Wakes up long polls when messages arrive
+    arrivals: Notify,
+    account_id: String,
+    region: String,
+}
+
+impl QueueService {
+    pub fn new(account_id: &str, region: &str) -> Self {
+        Self {
+            queues: Mutex::new(HashMap::new()),
+            arrivals: Notify::new(),
+            account_id: account_id.to_string(),
+            region: region.to_string(),
+        }
+    }
+
+    pub fn account_id(&self) -> &str {
+        &self.account_id
+    }
+
+    pub fn arn(&self, queue_name: &str) -> String {
+        format!("arn:aws:sqs:{}:{}:{}", self.region, self.account_id, queue_name)
+    }
+
+    /// Whether `arn` names an existing queue.
+    pub fn has_queue_arn(&self, arn: &str) -> bool {
+        queue::queue_name_of_arn(arn).is_some_and(|name| self.lock().contains_key(name))
+    }
+
+    /// Creates the queue, or does nothing when it already exists with the same attributes.
+    pub fn create_queue(&self, name: &str, attributes: &BTreeMap<String, String>) -> Result<(), QueueError> {
+        let attributes = QueueAttributes::from_map(attributes)?;
+        let mut queues = self.lock();
+        match queues.get(name) {
+            Some(existing) if existing.attributes == attributes => Ok(()),
+            Some(_) => Err(QueueError::QueueNameExists(format!(
+                "A queue already exists with the same name and a different value for attribute(s) of {}",
+                name
+            ))),
+            None => {
+                queues.insert(name.to_string(), Queue::new(name, attributes, Utc::now())?);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn delete_queue(&self, name: &str) -> Result<(), QueueError> {
+        self.lock().remove(name).map(|_| ()).ok_or(QueueError::NonExistentQueue)
+    }
+
+    pub fn require_queue(&self, name: &str) -> Result<(), QueueError> {
+        self.with_queue(name, |_| Ok(()))
+    }
+
+    pub fn list_queues(&self, prefix: &str) -> Vec<String> {
+        let mut names: Vec<String> = self.lock().keys().filter(|name| name.starts_with(prefix)).cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub fn send_message(&self, name: &str, body: &str, delay_seconds: Option<u32>, sender_id: &str) -> Result<Message, QueueError> {
+        let message = self.with_queue(name, |queue| queue.send(body, delay_seconds, sender_id, Utc::now()))?;
+        self.arrivals.notify_waiters();
+        Ok(message)
+    }
+
+    /// Receives up to `max` messages, waiting up to `wait_seconds` (or the queue's
+    /// `ReceiveMessageWaitTimeSeconds`) for one to become available.
+    pub async fn receive_messages(
+        &self,
+        name: &str,
+        max: u32,
+        visibility_timeout: Option<u32>,
+        wait_seconds: Option<u32>,
+    ) -> Result<Vec<Message>, QueueError> {
+        let wait = match wait_seconds {
+            Some(seconds) if seconds > queue::MAX_WAIT_SECONDS => {
+                return Err(QueueError::InvalidParameterValue(format!(
+                    "Value {} for parameter WaitTimeSeconds is invalid. Reason: Must be >= 0 and <= 20, if provided.",
+                    seconds
+                )))
+            }
+            Some(seconds) => seconds,
+            None => self.with_queue(name, |queue| Ok(queue.attributes.receive_message_wait_time_seconds))?,
+        };
+        let deadline = Instant::now() + Duration::from_secs(wait.into());
+        loop {
+            // Registered before looking, so that no arrival goes unnoticed
+            let arrival = self.arrivals.notified();
+            let messages = self.with_queue(name, |queue| queue.receive(max, visibility_timeout, Utc::now()))?;
+            let now = Instant::now();
+            if !messages.is_empty() || now >= deadline {
+                return Ok(messages);
+            }
+            let _ = tokio::time::timeout((deadline - now).min(POLL_INTERVAL), arrival).await;
+        }
+    }
+
+    pub fn delete_message(&self, name: &str, receipt_handle: &str) -> Result<(), QueueError> {
+        self.with_queue(name, |queue| queue.delete(receipt_handle))
+    }
+
+    pub fn change_message_visibility(&self, name: &str, receipt_handle: &str, timeout: u32) -> Result<(), QueueError> {
+        self.with_queue(name, |queue| queue.change_visibility(receipt_handle, timeout, Utc::now()))
+    }
+
+    pub fn purge_queue(&self, name: &str) -> Result<(), QueueError> {
+        self.with_queue(name, |queue| {
+            queue.purge();
+            Ok(())
+        })
+    }
+
+    pub fn queue_attributes(&self, name: &str) -> Result<BTreeMap<String, String>, QueueError> {
+        let arn = self.arn(name);
+        self.with_queue(name, |queue| Ok(queue.attributes(&arn, Utc::now())))
+    }
+
+    fn with_queue<T>(&self, name: &str, f: impl FnOnce(&mut Queue) -> Result<T, QueueError>) -> Result<T, QueueError> {
+        let mut queues = self.lock();
+        let queue = queues.get_mut(name).ok_or(QueueError::NonExistentQueue)?;
+        f(queue)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Queue>> {
+        self.queues.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_long_polling() {
+        let service = Arc::new(QueueService::new("000000000000", "us-east-1"));
+        service.create_queue("jobs", &BTreeMap::new()).unwrap();
+        assert!(service.has_queue_arn("arn:aws:sqs:us-east-1:000000000000:jobs"));
+        assert!(service.create_queue("jobs", &BTreeMap::new()).is_ok());
+        let mut attributes = BTreeMap::new();
+        attributes.insert("VisibilityTimeout".to_string(), "5".to_string());
+        assert!(matches!(service.create_queue("jobs", &attributes), Err(QueueError::QueueNameExists(_))));
+
+        let sender = service.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            sender.send_message("jobs", "hello", None, "000000000000").unwrap();
+        });
+        let started = Instant::now();
+        let messages = service.receive_messages("jobs", 1, None, Some(5)).await.unwrap();
+        assert_eq!(messages[0].body, "hello");
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        assert!(service.receive_messages("jobs", 1, None, Some(0)).await.unwrap().is_empty());
+        assert!(service.receive_messages("jobs", 1, None, Some(21)).await.is_err());
+        assert_eq!(service.receive_messages("missing", 1, None, None).await, Err(QueueError::NonExistentQueue));
+    }
+}
@@ -0,0 +1,204 @@
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use crate::domain::object::Object;
+use crate::domain::replication::{self, ReplicationConfiguration, ReplicationStatus};
+use crate::infrastructure::storage::Storage;
+
+#[derive(Debug)]
+enum Task {
+    // Copy the object, if it is still the one with this ID
+    Object { bucket: String, key: String, id: String },
+    // Remove the replica from the destination bucket
+    DeleteMarker { bucket: String, key: String, destination: String },
+}
+
+/// Background worker replicating objects to the destination buckets of their bucket's
+/// replication rules, in the order they were queued and each `delay` after it was.
+pub struct Replicator {
+    storage: Arc<dyn Storage>,
+    delay: Duration,
+    sender: mpsc::UnboundedSender<(Instant, Task)>,
+}
+
+impl Replicator {
+    /// Starts the worker, which runs until its handle is aborted.
+    pub fn spawn(storage: Arc<dyn Storage>, delay: Duration) -> (Arc<Self>, JoinHandle<()>) {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let replicator = Arc::new(Self { storage, delay, sender });
+        let worker = replicator.clone();
+        let handle = tokio::spawn(async move {
+            while let Some((due, task)) = receiver.recv().await {
+                tokio::time::sleep_until(due).await;
+                if let Err(e) = worker.perform(&task).await {
+                    eprintln!("Replication of {:?} failed: {}", task, e);
+                }
+            }
+        });
+        (replicator, handle)
+    }
+
+    /// The bucket's replication configuration, if it has a valid one.
+    pub async fn configuration(&self, bucket_name: &str) -> io::Result<Option<ReplicationConfiguration>> {
+        let Some(xml) = self.storage.get_bucket_config(bucket_name, replication::CONFIG_NAME).await? else {
+            return Ok(None);
+        };
+        Ok(ReplicationConfiguration::from_xml(&xml).ok())
+    }
+
+    /// Marks a new object `PENDING` when a rule of its bucket replicates it. It is queued
+    /// for replication by `object_stored` once stored.
+    pub async fn mark_pending(&self, bucket_name: &str, object: &mut Object) -> io::Result<()> {
+        if let Some(config) = self.configuration(bucket_name).await? {
+            if config.rule_for(object).is_some() {
+                object.replication_status = Some(ReplicationStatus::Pending);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn object_stored(&self, bucket_name: &str, object: &Object) {
+        if object.replication_status == Some(ReplicationStatus::Pending) {
+            self.queue(Task::Object {
+                bucket: bucket_name.to_string(),
+                key: object.key.clone(),
+                id: object.id.clone(),
+            });
+        }
+    }
+
+    /// Queues the removal of the replica of `key`, when a rule replicates delete markers.
+    pub async fn object_deleted(&self, bucket_name: &str, key: &str) -> io::Result<()> {
+        let Some(config) = self.configuration(bucket_name).await? else {
+            return Ok(());
+        };
+        if let Some(destination) = config.delete_rule_for(key).and_then(|rule| rule.destination_bucket()) {
+            self.queue(Task::DeleteMarker {
+                bucket: bucket_name.to_string(),
+                key: key.to_string(),
+                destination: destination.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn queue(&self, task: Task) {
+        // The worker only stops with the server
+        let _ = self.sender.send((Instant::now() + self.delay, task));
+    }
+
+    async fn perform(&self, task: &Task) -> io::Result<()> {
+        match task {
+            Task::Object { bucket, key, id } => self.replicate_object(bucket, key, id).await,
+            Task::DeleteMarker { bucket, key, destination } => {
+                // The object may have been created again in the meantime
+                if self.storage.head_object(bucket, key).await.is_ok() {
+                    return Ok(());
+                }
+                match self.storage.delete_object(destination, key).await {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+
+    async fn replicate_object(&self, bucket_name: &str, key: &str, id: &str) -> io::Result<()> {
+        let source = match self.storage.get_object(bucket_name, key).await {
+            Ok(source) if source.id == id => source,
+            // Replaced or removed since, which queued its own task
+            Ok(_) => return Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        // Rules may have changed since the object was stored
+        let destination = self
+            .configuration(bucket_name)
+            .await?
+            .and_then(|config| config.rule_for(&source).and_then(|rule| rule.destination_bucket()).map(str::to_string));
+        let copied = match &destination {
+            Some(destination) => self.copy(destination, &source).await,
+            None => Err(io::Error::other("no replication rule applies anymore")),
+        };
+        let status = match &copied {
+            Ok(()) => ReplicationStatus::Completed,
+            Err(e) => {
+                eprintln!("Could not replicate {} in bucket {}: {}", key, bucket_name, e);
+                ReplicationStatus::Failed
+            }
+        };
+
+        let mut current = self.storage.head_object(bucket_name, key).await?;
+        if current.id == id {
+            current.replication_status = Some(status);
+            self.storage.put_object_metadata(bucket_name, &current).await?;
+        }
+        Ok(())
+    }
+
+    async fn copy(&self, destination: &str, source: &Object) -> io::Result<()> {
+        if self.storage.get_bucket(destination).await?.is_none() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("destination bucket {} does not exist", destination)));
+        }
+        let replica = Object { replication_status: Some(ReplicationStatus::Replica), ..source.clone() };
+        self.storage.put_object(destination, &replica).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::bucket::Bucket;
+    use crate::infrastructure::storage::FileStorage;
+
+    #[tokio::test]
+    async fn test_replicate_objects_and_delete_markers() {
+        let base_path = std::env::temp_dir().join(format!("s3-mocker-replication-{}", uuid::Uuid::new_v4()));
+        let storage: Arc<dyn Storage> = Arc::new(FileStorage::new(base_path.clone()));
+        for name in ["source", "replica"] {
+            storage.create_bucket(&Bucket::new(name.to_string())).await.unwrap();
+        }
+        let config = r#"<ReplicationConfiguration><Role></Role><Rule><ID>dr</ID><Filter><Prefix>dr/</Prefix></Filter><Status>Enabled</Status><Destination><Bucket>arn:aws:s3:::replica</Bucket></Destination><DeleteMarkerReplication><Status>Enabled</Status></DeleteMarkerReplication></Rule></ReplicationConfiguration>"#;
+        storage.put_bucket_config("source", replication::CONFIG_NAME, config).await.unwrap();
+        let (replicator, worker) = Replicator::spawn(storage.clone(), Duration::ZERO);
+
+        let mut skipped = Object::new("other.txt".to_string(), b"data".to_vec(), "text/plain".to_string());
+        replicator.mark_pending("source", &mut skipped).await.unwrap();
+        assert_eq!(skipped.replication_status, None);
+
+        let mut object = Object::new("dr/a.txt".to_string(), b"data".to_vec(), "text/plain".to_string());
+        replicator.mark_pending("source", &mut object).await.unwrap();
+        assert_eq!(object.replication_status, Some(ReplicationStatus::Pending));
+        storage.put_object("source", &object).await.unwrap();
+        replicator.replicate_object("source", "dr/a.txt", &object.id).await.unwrap();
+
+        let source = storage.head_object("source", "dr/a.txt").await.unwrap();
+        assert_eq!(source.replication_status, Some(ReplicationStatus::Completed));
+        let replica = storage.get_object("replica", "dr/a.txt").await.unwrap();
+        assert_eq!((replica.content, replica.etag), (b"data".to_vec(), object.etag.clone()));
+        assert_eq!(replica.replication_status, Some(ReplicationStatus::Replica));
+
+        storage.delete_object("source", "dr/a.txt").await.unwrap();
+        replicator.object_deleted("source", "dr/a.txt").await.unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while storage.head_object("replica", "dr/a.txt").await.is_ok() {
+            assert!(Instant::now() < deadline, "the replica was not removed");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        storage.delete_bucket("replica").await.unwrap();
+        let mut failing = Object::new("dr/b.txt".to_string(), b"data".to_vec(), "text/plain".to_string());
+        replicator.mark_pending("source", &mut failing).await.unwrap();
+        storage.put_object("source", &failing).await.unwrap();
+        replicator.replicate_object("source", "dr/b.txt", &failing.id).await.unwrap();
+        let failed = storage.head_object("source", "dr/b.txt").await.unwrap();
+        assert_eq!(failed.replication_status, Some(ReplicationStatus::Failed));
+
+        worker.abort();
+        std::fs::remove_dir_all(base_path).unwrap();
+    }
+}
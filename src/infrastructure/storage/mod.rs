@@ -1,19 +1,263 @@
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
 use std::io::{self, Write, Read};
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use sha2::{Digest, Sha256};
 use crate::domain::bucket::Bucket;
+use crate::domain::encryption::{self, EncryptionKeys};
 use crate::domain::object::Object;
 mod traits;
-pub use traits::{Storage, BucketStorage, ObjectStorage};
+pub use traits::{Storage, BucketStorage, ObjectStorage, ServerStorage};
 
+// Metadata lives outside of the bucket directories. Bucket names must start
+// with a letter or a digit, so the dot prefix can never clash with a bucket.
+const META_DIR: &str = ".meta";
+// Server-wide documents, which can't clash with a bucket's metadata for the same reason
+const SERVER_DIR: &str = ".server";
+
+// Objects are files named after their percent-encoded key, directly in the bucket directory,
+// so that keys like "dir/" or both "a" and "a/b" can be stored. A leading dot is encoded too,
+// so no file name is "." or "..".
+const FILE_NAME_ENCODE: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+// Longer names are replaced with a hash of the key, leaving room for the ".json" of the
+// metadata file within the usual 255 byte limit
+const MAX_FILE_NAME_LENGTH: usize = 250;
+// Starts the hashed names, and is always encoded in the others
+const HASHED_NAME_PREFIX: char = '#';
+
+/// Stores buckets and objects as files under `base_path`. Object content is encrypted at
+/// rest with server-managed keys, generated on the first write.
 pub struct FileStorage {
     base_path: PathBuf,
+    // Serializes the creation and rotation of the encryption keys
+    keys_lock: Mutex<()>,
 }
 
 impl FileStorage {
     pub fn new(base_path: PathBuf) -> Self {
-        Self { base_path }
+        Self { base_path, keys_lock: Mutex::new(()) }
+    }
+
+    fn bucket_meta_path(&self, bucket_name: &str) -> PathBuf {
+        self.base_path.join(META_DIR).join(bucket_name)
+    }
+
+    fn config_path(&self, bucket_name: &str, name: &str) -> PathBuf {
+        self.bucket_meta_path(bucket_name).join("config").join(name)
+    }
+
+    fn server_config_path(&self, name: &str) -> PathBuf {
+        self.base_path.join(META_DIR).join(SERVER_DIR).join(name)
+    }
+
+    fn bucket_path(&self, bucket_name: &str) -> io::Result<PathBuf> {
+        let components = Path::new(bucket_name).components().collect::<Vec<_>>();
+        if !matches!(components.as_slice(), [Component::Normal(_)]) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid bucket name: {}", bucket_name)));
+        }
+        Ok(self.base_path.join(bucket_name))
+    }
+
+    fn object_path(&self, bucket_name: &str, key: &str) -> io::Result<PathBuf> {
+        if key.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Object keys must not be empty"));
+        }
+        Ok(self.bucket_path(bucket_name)?.join(file_name(key)))
+    }
+
+    fn object_meta_path(&self, bucket_name: &str, key: &str) -> PathBuf {
+        self.bucket_meta_path(bucket_name)
+            .join("objects")
+            .join(format!("{}.json", file_name(key)))
+    }
+
+    // The key of an object file, which only hashed names need the metadata for
+    fn key_of_file_name(&self, bucket_name: &str, name: &str) -> io::Result<Option<String>> {
+        if !is_hashed_name(name) {
+            return Ok(key_of_file_name(name));
+        }
+        let meta_path = self.bucket_meta_path(bucket_name).join("objects").join(format!("{}.json", name));
+        match fs::read_to_string(meta_path) {
+            Ok(json) => Ok(Some(serde_json::from_str::<Object>(&json).map_err(io::Error::other)?.key)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Moves objects stored before keys were encoded, as nested files named after the key
+    /// itself, to their encoded names. Returns how many were moved.
+    pub fn migrate_legacy_layout(&self) -> io::Result<usize> {
+        let mut migrated = 0;
+        if !self.base_path.exists() {
+            return Ok(migrated);
+        }
+        for entry in fs::read_dir(&self.base_path)? {
+            let entry = entry?;
+            let Some(bucket_name) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            if bucket_name.starts_with('.') || !entry.file_type()?.is_dir() {
+                continue;
+            }
+            migrated += self.migrate_legacy_bucket(&bucket_name)?;
+        }
+        Ok(migrated)
+    }
+
+    fn migrate_legacy_bucket(&self, bucket_name: &str) -> io::Result<usize> {
+        let bucket_path = self.bucket_path(bucket_name)?;
+        let legacy_meta_path = self.bucket_meta_path(bucket_name).join("objects");
+        let mut files = Vec::new();
+        collect_files(&bucket_path, &bucket_path, &mut files)?;
+
+        let mut migrated = 0;
+        for key in files {
+            // Files of the current layout are all at the top, named so that they decode to a
+            // key encoding back to the same name. Legacy keys that needed no encoding are
+            // stored in the same place either way.
+            let is_current = !key.contains('/') && (is_hashed_name(&key) || key_of_file_name(&key).is_some());
+            let path = self.object_path(bucket_name, &key)?;
+            if is_current || path.exists() {
+                continue;
+            }
+            fs::rename(bucket_path.join(&key), &path)?;
+            let legacy_meta = legacy_meta_path.join(format!("{}.json", key));
+            if legacy_meta.is_file() {
+                fs::rename(&legacy_meta, self.object_meta_path(bucket_name, &key))?;
+            }
+            migrated += 1;
+        }
+        remove_empty_dirs(&bucket_path)?;
+        if legacy_meta_path.is_dir() {
+            remove_empty_dirs(&legacy_meta_path)?;
+        }
+        Ok(migrated)
+    }
+
+    fn read_object_meta(&self, bucket_name: &str, key: &str, object_path: &Path) -> io::Result<Object> {
+        let meta_path = self.object_meta_path(bucket_name, key);
+        let file_meta = fs::metadata(object_path)?;
+        let object = match fs::read_to_string(&meta_path) {
+            Ok(json) => serde_json::from_str(&json).map_err(io::Error::other)?,
+            // Objects written before metadata was tracked
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let mut object = Object::new(key.to_string(), Vec::new(), "application/octet-stream".to_string());
+                if let Ok(modified) = file_meta.modified() {
+                    object.last_modified = DateTime::<Utc>::from(modified);
+                }
+                object.size = file_meta.len() as usize;
+                object.encryption = None;
+                object
+            }
+            Err(e) => return Err(e),
+        };
+        Ok(object)
+    }
+
+    // Keys are read on every use, so that rotations from the CLI are seen by a running server
+    fn load_encryption_keys(&self) -> io::Result<EncryptionKeys> {
+        let path = self.server_config_path(encryption::KEYS_CONFIG_NAME);
+        if !path.exists() {
+            let _guard = self.keys_lock.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            // Unless another request generated them meanwhile
+            if !path.exists() {
+                let keys = EncryptionKeys::generate();
+                self.save_encryption_keys(&keys)?;
+                return Ok(keys);
+            }
+        }
+        let json = fs::read_to_string(&path)?;
+        EncryptionKeys::from_json(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
     }
+
+    fn save_encryption_keys(&self, keys: &EncryptionKeys) -> io::Result<()> {
+        let json = keys.to_json().map_err(io::Error::other)?;
+        write_file(&self.server_config_path(encryption::KEYS_CONFIG_NAME), json.as_bytes())
+    }
+
+    fn read_content(&self, object_path: &Path) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        fs::File::open(object_path)?.read_to_end(&mut data)?;
+        self.decrypt_content(data)
+    }
+
+    fn decrypt_content(&self, data: Vec<u8>) -> io::Result<Vec<u8>> {
+        // Content written before encryption at rest is stored in plain
+        if encryption::key_id(&data).is_none() {
+            return Ok(data);
+        }
+        self.load_encryption_keys()?
+            .decrypt(&data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn write_content(&self, object_path: &Path, content: &[u8], keys: &EncryptionKeys) -> io::Result<()> {
+        let data = keys.encrypt(content).map_err(io::Error::other)?;
+        write_file(object_path, &data)
+    }
+}
+
+fn write_file(path: &Path, content: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = fs::File::create(path)?;
+    file.write_all(content)
+}
+
+// The percent-encoded key, or a hash of it when that gets too long
+fn file_name(key: &str) -> String {
+    let mut name = utf8_percent_encode(key, FILE_NAME_ENCODE).to_string();
+    if name.starts_with('.') {
+        name.replace_range(..1, "%2E");
+    }
+    if name.len() > MAX_FILE_NAME_LENGTH {
+        return format!("{}{}", HASHED_NAME_PREFIX, hex::encode(Sha256::digest(key.as_bytes())));
+    }
+    name
+}
+
+fn is_hashed_name(name: &str) -> bool {
+    name.strip_prefix(HASHED_NAME_PREFIX)
+        .is_some_and(|hash| hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+// The key of an encoded file name, unless it isn't one
+fn key_of_file_name(name: &str) -> Option<String> {
+    let key = percent_decode_str(name).decode_utf8().ok()?.into_owned();
+    (!key.is_empty() && file_name(&key) == name).then_some(key)
+}
+
+// Removes the directories under `dir` left without any file
+fn remove_empty_dirs(dir: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            let path = entry.path();
+            remove_empty_dirs(&path)?;
+            if fs::read_dir(&path)?.next().is_none() {
+                fs::remove_dir(&path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            collect_files(root, &path, files)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            if let Some(key) = relative.to_str() {
+                files.push(key.replace(std::path::MAIN_SEPARATOR, "/"));
+            }
+        }
+    }
+    Ok(())
 }
 
 #[async_trait::async_trait]
@@ -21,16 +265,37 @@ impl BucketStorage for FileStorage {
     async fn create_bucket(&self, bucket: &Bucket) -> io::Result<()> {
         let bucket_path = self.base_path.join(&bucket.name);
         fs::create_dir_all(&bucket_path)?;
+        let json = serde_json::to_vec_pretty(bucket).map_err(io::Error::other)?;
+        write_file(&self.bucket_meta_path(&bucket.name).join("bucket.json"), &json)?;
         Ok(())
     }
 
+    async fn get_bucket(&self, bucket_name: &str) -> io::Result<Option<Bucket>> {
+        if !self.base_path.join(bucket_name).is_dir() {
+            return Ok(None);
+        }
+        match fs::read_to_string(self.bucket_meta_path(bucket_name).join("bucket.json")) {
+            Ok(json) => Ok(Some(serde_json::from_str(&json).map_err(io::Error::other)?)),
+            // Buckets created before metadata was tracked
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                Ok(Bucket::validate_name(bucket_name).then(|| Bucket::new(bucket_name.to_string())))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
     async fn list_buckets(&self) -> io::Result<Vec<String>> {
         let mut buckets = Vec::new();
+        if !self.base_path.exists() {
+            return Ok(buckets);
+        }
         for entry in fs::read_dir(&self.base_path)? {
             let entry = entry?;
             if entry.file_type()?.is_dir() {
                 if let Some(name) = entry.file_name().to_str() {
-                    buckets.push(name.to_string());
+                    if !name.starts_with('.') {
+                        buckets.push(name.to_string());
+                    }
                 }
             }
         }
@@ -42,6 +307,30 @@ impl BucketStorage for FileStorage {
         if bucket_path.exists() {
             fs::remove_dir_all(bucket_path)?;
         }
+        let meta_path = self.bucket_meta_path(bucket_name);
+        if meta_path.exists() {
+            fs::remove_dir_all(meta_path)?;
+        }
+        Ok(())
+    }
+
+    async fn get_bucket_config(&self, bucket_name: &str, name: &str) -> io::Result<Option<String>> {
+        match fs::read_to_string(self.config_path(bucket_name, name)) {
+            Ok(config) => Ok(Some(config)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn put_bucket_config(&self, bucket_name: &str, name: &str, config: &str) -> io::Result<()> {
+        write_file(&self.config_path(bucket_name, name), config.as_bytes())
+    }
+
+    async fn delete_bucket_config(&self, bucket_name: &str, name: &str) -> io::Result<()> {
+        let config_path = self.config_path(bucket_name, name);
+        if config_path.exists() {
+            fs::remove_file(config_path)?;
+        }
         Ok(())
     }
 }
@@ -49,51 +338,174 @@ impl BucketStorage for FileStorage {
 #[async_trait::async_trait]
 impl ObjectStorage for FileStorage {
     async fn put_object(&self, bucket_name: &str, object: &Object) -> io::Result<()> {
-        let object_path = self.base_path.join(bucket_name).join(&object.key);
-        if let Some(parent) = object_path.parent() {
-            fs::create_dir_all(parent)?;
-        }
-        let mut file = fs::File::create(object_path)?;
-        file.write_all(&object.content)?;
+        let object_path = self.object_path(bucket_name, &object.key)?;
+        self.write_content(&object_path, &object.content, &self.load_encryption_keys()?)?;
+        let json = serde_json::to_vec_pretty(object).map_err(io::Error::other)?;
+        write_file(&self.object_meta_path(bucket_name, &object.key), &json)?;
         Ok(())
     }
 
+    async fn put_object_metadata(&self, bucket_name: &str, object: &Object) -> io::Result<()> {
+        if !self.object_path(bucket_name, &object.key)?.is_file() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("Object not found: {}", object.key)));
+        }
+        let json = serde_json::to_vec_pretty(object).map_err(io::Error::other)?;
+        write_file(&self.object_meta_path(bucket_name, &object.key), &json)
+    }
+
     async fn get_object(&self, bucket_name: &str, key: &str) -> io::Result<Object> {
-        let object_path = self.base_path.join(bucket_name).join(key);
-        let mut content = Vec::new();
-        let mut file = fs::File::open(object_path)?;
-        file.read_to_end(&mut content)?;
-        
-        // In a real implementation, we would store and retrieve metadata
-        Ok(Object::new(
-            key.to_string(),
-            content,
-            "application/octet-stream".to_string(),
-        ))
+        let object_path = self.object_path(bucket_name, key)?;
+        // Keys that are a prefix of other keys are directories on disk
+        if !object_path.is_file() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("Object not found: {}", key)));
+        }
+        let content = self.read_content(&object_path)?;
+        let mut object = self.read_object_meta(bucket_name, key, &object_path)?;
+        object.content = content;
+        Ok(object)
+    }
+
+    async fn head_object(&self, bucket_name: &str, key: &str) -> io::Result<Object> {
+        let object_path = self.object_path(bucket_name, key)?;
+        if !object_path.is_file() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("Object not found: {}", key)));
+        }
+        self.read_object_meta(bucket_name, key, &object_path)
     }
 
     async fn delete_object(&self, bucket_name: &str, key: &str) -> io::Result<()> {
-        let object_path = self.base_path.join(bucket_name).join(key);
+        let object_path = self.object_path(bucket_name, key)?;
         if object_path.exists() {
             fs::remove_file(object_path)?;
         }
+        let meta_path = self.object_meta_path(bucket_name, key);
+        if meta_path.exists() {
+            fs::remove_file(meta_path)?;
+        }
         Ok(())
     }
 
     async fn list_objects(&self, bucket_name: &str) -> io::Result<Vec<String>> {
-        let bucket_path = self.base_path.join(bucket_name);
         let mut objects = Vec::new();
-        for entry in fs::read_dir(bucket_path)? {
+        for entry in fs::read_dir(self.bucket_path(bucket_name)?)? {
             let entry = entry?;
-            if entry.file_type()?.is_file() {
-                if let Some(name) = entry.file_name().to_str() {
-                    objects.push(name.to_string());
-                }
+            if !entry.file_type()?.is_file() {
+                continue;
             }
+            let key = match entry.file_name().to_str() {
+                Some(name) => self.key_of_file_name(bucket_name, name)?,
+                None => None,
+            };
+            objects.extend(key);
         }
+        objects.sort();
         Ok(objects)
     }
 }
 
 #[async_trait::async_trait]
-impl Storage for FileStorage {} 
\ No newline at end of file
+impl ServerStorage for FileStorage {
+    async fn get_server_config(&self, name: &str) -> io::Result<Option<String>> {
+        match fs::read_to_string(self.server_config_path(name)) {
+            Ok(config) => Ok(Some(config)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn put_server_config(&self, name: &str, config: &str) -> io::Result<()> {
+        write_file(&self.server_config_path(name), config.as_bytes())
+    }
+
+    async fn encryption_keys(&self) -> io::Result<EncryptionKeys> {
+        self.load_encryption_keys()
+    }
+
+    async fn rotate_encryption_key(&self) -> io::Result<String> {
+        // Generated first if need be, as that takes the lock too
+        self.load_encryption_keys()?;
+        let _guard = self.keys_lock.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut keys = self.load_encryption_keys()?;
+        let key_id = keys.rotate().to_string();
+        self.save_encryption_keys(&keys)?;
+        Ok(key_id)
+    }
+
+    async fn reencrypt_objects(&self) -> io::Result<usize> {
+        let keys = self.load_encryption_keys()?;
+        let mut reencrypted = 0;
+        for bucket_name in self.list_buckets().await? {
+            for key in self.list_objects(&bucket_name).await? {
+                let object_path = self.object_path(&bucket_name, &key)?;
+                let data = fs::read(&object_path)?;
+                if encryption::key_id(&data) == Some(keys.active_key_id.as_str()) {
+                    continue;
+                }
+                let content = self.decrypt_content(data)?;
+                self.write_content(&object_path, &content, &keys)?;
+                reencrypted += 1;
+            }
+        }
+        Ok(reencrypted)
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for FileStorage {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    async fn test_storage() -> (TempDir, FileStorage) {
+        let dir = TempDir::new().unwrap();
+        let storage = FileStorage::new(dir.path().to_path_buf());
+        storage.create_bucket(&Bucket::new("bucket".to_string())).await.unwrap();
+        (dir, storage)
+    }
+
+    async fn put(storage: &FileStorage, key: &str, content: &[u8]) {
+        let object = Object::new(key.to_string(), content.to_vec(), "text/plain".to_string());
+        storage.put_object("bucket", &object).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_keys_like_paths_coexist() {
+        let (_dir, storage) = test_storage().await;
+        let long_key = format!("{}/end", "x".repeat(1000));
+        for key in ["a", "a/b", "dir/", "dir", "..", ".meta", "/etc/passwd", long_key.as_str()] {
+            put(&storage, key, key.as_bytes()).await;
+        }
+
+        for key in ["a", "a/b", "dir/", "dir", "..", ".meta", "/etc/passwd", long_key.as_str()] {
+            let object = storage.get_object("bucket", key).await.unwrap();
+            assert_eq!(object.key, key);
+            assert_eq!(object.content, key.as_bytes());
+        }
+        let mut expected = vec!["a", "a/b", "dir/", "dir", "..", ".meta", "/etc/passwd", long_key.as_str()];
+        expected.sort();
+        assert_eq!(storage.list_objects("bucket").await.unwrap(), expected);
+
+        storage.delete_object("bucket", "a").await.unwrap();
+        assert_eq!(storage.get_object("bucket", "a/b").await.unwrap().content, b"a/b");
+    }
+
+    #[tokio::test]
+    async fn test_migrate_legacy_layout() {
+        let (dir, storage) = test_storage().await;
+        let bucket_path = dir.path().join("bucket");
+        write_file(&bucket_path.join("photos/2024/a.jpg"), b"nested").unwrap();
+        write_file(&bucket_path.join("plain.txt"), b"plain").unwrap();
+        write_file(&bucket_path.join("with space"), b"space").unwrap();
+
+        assert_eq!(storage.migrate_legacy_layout().unwrap(), 2);
+        assert_eq!(storage.migrate_legacy_layout().unwrap(), 0);
+        assert!(!bucket_path.join("photos").exists());
+        assert_eq!(
+            storage.list_objects("bucket").await.unwrap(),
+            vec!["photos/2024/a.jpg", "plain.txt", "with space"]
+        );
+        assert_eq!(storage.get_object("bucket", "photos/2024/a.jpg").await.unwrap().content, b"nested");
+    }
+}
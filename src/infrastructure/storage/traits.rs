@@ -1,22 +1,47 @@
 use std::io;
 use crate::domain::bucket::Bucket;
+use crate::domain::encryption::EncryptionKeys;
 use crate::domain::object::Object;
 
 #[async_trait::async_trait]
 pub trait BucketStorage: Send + Sync {
     async fn create_bucket(&self, bucket: &Bucket) -> io::Result<()>;
+    async fn get_bucket(&self, bucket_name: &str) -> io::Result<Option<Bucket>>;
     async fn list_buckets(&self) -> io::Result<Vec<String>>;
     async fn delete_bucket(&self, bucket_name: &str) -> io::Result<()>;
+
+    // Bucket subresources (lifecycle, policy, ...) are stored as opaque documents by name
+    async fn get_bucket_config(&self, bucket_name: &str, name: &str) -> io::Result<Option<String>>;
+    async fn put_bucket_config(&self, bucket_name: &str, name: &str, config: &str) -> io::Result<()>;
+    async fn delete_bucket_config(&self, bucket_name: &str, name: &str) -> io::Result<()>;
 }
 
 #[async_trait::async_trait]
 pub trait ObjectStorage: Send + Sync {
     async fn put_object(&self, bucket_name: &str, object: &Object) -> io::Result<()>;
+    // Updates the stored metadata (ACL, tags, ...) of an existing object, leaving its content alone
+    async fn put_object_metadata(&self, bucket_name: &str, object: &Object) -> io::Result<()>;
     async fn get_object(&self, bucket_name: &str, key: &str) -> io::Result<Object>;
+    // Same as get_object, without reading the content
+    async fn head_object(&self, bucket_name: &str, key: &str) -> io::Result<Object>;
     async fn delete_object(&self, bucket_name: &str, key: &str) -> io::Result<()>;
     async fn list_objects(&self, bucket_name: &str) -> io::Result<Vec<String>>;
 }
 
-// Combined trait for implementations that support both
 #[async_trait::async_trait]
-pub trait Storage: BucketStorage + ObjectStorage {} 
\ No newline at end of file
+pub trait ServerStorage: Send + Sync {
+    // Server-wide documents (credentials, ...), stored by name like bucket subresources
+    async fn get_server_config(&self, name: &str) -> io::Result<Option<String>>;
+    async fn put_server_config(&self, name: &str, config: &str) -> io::Result<()>;
+
+    // Server-managed keys object content is encrypted with at rest
+    async fn encryption_keys(&self) -> io::Result<EncryptionKeys>;
+    // Adds a key that encrypts from now on and returns its ID; content keeps its key until re-encrypted
+    async fn rotate_encryption_key(&self) -> io::Result<String>;
+    // Re-encrypts the content of every object not encrypted with the active key, returning how many
+    async fn reencrypt_objects(&self) -> io::Result<usize>;
+}
+
+// Combined trait for implementations that support all of them
+#[async_trait::async_trait]
+pub trait Storage: BucketStorage + ObjectStorage + ServerStorage {}
@@ -1,7 +1,7 @@
 mod domain;
 mod infrastructure;
 
-use infrastructure::{ApplicationFactory, ApiFactory, CliFactory};
+use infrastructure::{ApplicationFactory, ApiFactory, CliFactory, ServerConfig};
 use std::env;
 
 #[tokio::main]
@@ -15,7 +15,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Box::new(CliFactory::new(command, command_args))
     } else {
         // API mode
-        Box::new(ApiFactory::new(3000))
+        Box::new(ApiFactory::new(ServerConfig::from_env()))
     };
 
     factory.run().await